@@ -7,32 +7,39 @@ use std::pin::Pin;
 use std::task::Poll;
 
 use anyhow::Error;
-use futures_util::future::{Either, ErrInto};
-use futures_util::{Sink, SinkExt, Stream, StreamExt, TryFutureExt};
+use futures_util::future::{Either, Map, TryJoinAll};
+use futures_util::{FutureExt, Sink, SinkExt, Stream, StreamExt};
 use serde_json::Value;
 use tokio::net::TcpStream;
 #[cfg(unix)]
 use tokio::net::UnixStream;
 use tokio::sync::{mpsc, oneshot, watch};
-use tokio::task::JoinHandle;
 use tokio_util::sync::PollSender;
-use tower::Service;
-use tracing::error;
+use tower::{BoxError, Service};
+use tracing::instrument::Instrumented;
+use tracing::{error, Instrument, Span};
 
-use super::codec::{self, Exception, Request};
+use super::codec::{self, Exception, Packet, Request};
+use super::keepalive::{KeepAlive, Pinger};
+use crate::shutdown::Task;
+
+/// The outcome of a request as seen by the caller: either the remote result or a transport failure.
+type Reply = Result<Result<Value, Exception>, Error>;
 
 #[derive(Clone)]
 pub struct MakeClient {
     running: watch::Receiver<bool>,
-    handler_tx: mpsc::Sender<JoinHandle<()>>,
+    handler_tx: mpsc::Sender<Task>,
+    keepalive: Option<KeepAlive>,
 }
 
 impl MakeClient {
     pub fn new(
         running: watch::Receiver<bool>,
-        handler_tx: mpsc::Sender<JoinHandle<()>>,
+        handler_tx: mpsc::Sender<Task>,
+        keepalive: Option<KeepAlive>,
     ) -> MakeClient {
-        MakeClient { running, handler_tx }
+        MakeClient { running, handler_tx, keepalive }
     }
 }
 
@@ -51,7 +58,13 @@ impl Service<PathBuf> for MakeClient {
         Box::pin(async move {
             let connection = UnixStream::connect(&path).await?;
 
-            Ok(Client::from_stream(this.running.clone(), this.handler_tx.clone(), connection).await)
+            Ok(Client::from_stream(
+                this.running.clone(),
+                this.handler_tx.clone(),
+                this.keepalive,
+                connection,
+            )
+            .await)
         })
     }
 }
@@ -70,25 +83,40 @@ impl Service<u16> for MakeClient {
         Box::pin(async move {
             let connection = TcpStream::connect(&(Ipv6Addr::LOCALHOST, port)).await?;
 
-            Ok(Client::from_stream(this.running.clone(), this.handler_tx.clone(), connection).await)
+            Ok(Client::from_stream(
+                this.running.clone(),
+                this.handler_tx.clone(),
+                this.keepalive,
+                connection,
+            )
+            .await)
         })
     }
 }
 
 pub struct Client {
-    channel: PollSender<(Request, oneshot::Sender<Result<Value, Exception>>)>,
+    channel: PollSender<(Request, oneshot::Sender<Reply>, Span)>,
 }
 
 impl Client {
     async fn from_stream<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>(
         running: watch::Receiver<bool>,
-        handler_tx: mpsc::Sender<JoinHandle<()>>,
+        handler_tx: mpsc::Sender<Task>,
+        keepalive: Option<KeepAlive>,
         stream: S,
     ) -> Client {
         let (tx, rx) = mpsc::channel(16);
 
         let _ = handler_tx
-            .send(tokio::spawn(Client::dispatch(running, rx, codec::client(stream))))
+            .send((
+                "rpc client".into(),
+                tokio::spawn(Client::dispatch(
+                    running,
+                    rx,
+                    Pinger::new(keepalive),
+                    codec::client(stream),
+                )),
+            ))
             .await;
 
         Client { channel: PollSender::new(tx) }
@@ -96,51 +124,83 @@ impl Client {
 
     async fn dispatch<T>(
         mut running: watch::Receiver<bool>,
-        mut channel: mpsc::Receiver<(Request, oneshot::Sender<Result<Value, Exception>>)>,
+        mut channel: mpsc::Receiver<(Request, oneshot::Sender<Reply>, Span)>,
+        mut pinger: Pinger,
         stream: T,
     ) where
-        T: Sink<(u64, Request), Error = Error>
-            + Stream<Item = Result<(u64, Result<Value, Exception>), Error>>,
+        T: Sink<Packet<Request>, Error = Error>
+            + Stream<Item = Result<Packet<Result<Value, Exception>>, Error>>,
     {
         let (mut sink, mut stream) = stream.split();
 
-        let mut pending = HashMap::<u64, oneshot::Sender<Result<Value, Exception>>>::new();
+        let mut pending = HashMap::<u64, oneshot::Sender<Reply>>::new();
         let mut next_request_id = 0;
 
-        while *running.borrow() || !pending.is_empty() {
+        let closed = loop {
+            if !*running.borrow() && pending.is_empty() {
+                return;
+            }
+
             tokio::select! {
                 _ = running.changed() => continue,
                 new_request = channel.recv() => {
                     match new_request {
-                        Some((request, channel)) => {
+                        Some((request, channel, span)) => {
                             let request_id = next_request_id;
                             next_request_id += 1;
+                            span.record("rpc.id", request_id);
 
                             pending.insert(request_id, channel);
 
-                            if let Err(error) = sink.send((request_id, request)).await {
+                            let request = Packet::Message(request_id, request);
+                            if let Err(error) = sink.send(request).await {
                                 error!(?error, "Failed to send the request");
-                                return;
+                                break "failed to send the request";
                             };
                         },
-                        None => return,
+                        None => break "client dropped before response",
                     }
                 },
                 new_response = stream.next() => {
                     match new_response {
-                        Some(Ok((request_id, response))) => {
+                        Some(Ok(Packet::Message(request_id, response))) => {
                             if let Some(channel) = pending.remove(&request_id) {
-                                let _ = channel.send(response);
+                                let _ = channel.send(Ok(response));
+                            }
+                        },
+                        Some(Ok(Packet::Ping(id))) => {
+                            if let Err(error) = sink.send(Packet::Pong(id)).await {
+                                error!(?error, "Failed to send a pong");
+                                break "failed to send a pong";
                             }
                         },
+                        Some(Ok(Packet::Pong(id))) => pinger.pong(id),
                         Some(Err(error)) => {
                             error!(?error, "Failed to read a response");
-                            return;
+                            break "failed to read the response";
+                        },
+                        None => break "connection closed before response",
+                    }
+                },
+                ping = pinger.tick() => {
+                    match ping {
+                        Ok(id) => {
+                            if let Err(error) = sink.send(Packet::Ping(id)).await {
+                                error!(?error, "Failed to send a ping");
+                                break "failed to send a ping";
+                            }
+                        },
+                        Err(_) => {
+                            error!("The server stopped answering pings");
+                            break "connection timed out";
                         },
-                        None => return,
                     }
                 },
             }
+        };
+
+        for (_, channel) in pending.drain() {
+            let _ = channel.send(Err(Error::msg(closed)));
         }
     }
 }
@@ -148,9 +208,14 @@ impl Client {
 impl Service<Request> for Client {
     type Response = Result<Value, Exception>;
     type Error = Error;
-    type Future = Either<
-        Ready<Result<Self::Response, Self::Error>>,
-        ErrInto<oneshot::Receiver<Self::Response>, Self::Error>,
+    type Future = Instrumented<
+        Either<
+            Ready<Result<Self::Response, Self::Error>>,
+            Map<
+                oneshot::Receiver<Reply>,
+                fn(Result<Reply, oneshot::error::RecvError>) -> Result<Self::Response, Self::Error>,
+            >,
+        >,
     >;
 
     fn poll_ready(&mut self, cx: &mut std::task::Context<'_>) -> Poll<Result<(), Self::Error>> {
@@ -160,29 +225,62 @@ impl Service<Request> for Client {
     }
 
     fn call(&mut self, req: Request) -> Self::Future {
+        let span = tracing::info_span!(
+            "rpc_call",
+            rpc.method = req.0.as_str(),
+            rpc.id = tracing::field::Empty,
+        );
         let (tx, rx) = oneshot::channel();
 
-        if let Err(error) = self.channel.send_item((req, tx)) {
+        if let Err(error) = self.channel.send_item((req, tx, span.clone())) {
             return Either::Left(ready(Err(
                 Error::from(error).context("failed to queue the request")
-            )));
+            )))
+            .instrument(span);
         };
 
-        Either::Right(rx.err_into())
+        let flatten: fn(_) -> _ = |reply| reply?;
+        Either::Right(rx.map(flatten)).instrument(span)
+    }
+}
+
+/// Queue all the `requests` on `service` before waiting for any of the responses.
+///
+/// The returned future resolves to the results in the same order as the requests. It doesn't
+/// borrow `service`, so the service can be released while the responses come in.
+pub async fn call_batch<S>(
+    service: &mut S,
+    requests: Vec<Request>,
+) -> Result<TryJoinAll<S::Future>, Error>
+where
+    S: Service<Request, Response = Result<Value, Exception>>,
+    S::Error: Into<BoxError>,
+{
+    let mut replies = Vec::with_capacity(requests.len());
+    for request in requests {
+        std::future::poll_fn(|cx| service.poll_ready(cx)).await.map_err(|error| {
+            anyhow::anyhow!(error.into()).context("failed to wait for the service to be ready")
+        })?;
+        replies.push(service.call(request));
     }
+    Ok(futures_util::future::try_join_all(replies))
 }
 
 // TODO: #[cfg(not(unix))]
 #[cfg(all(test, unix))]
 mod tests {
     use std::collections::HashMap;
+    use std::time::Duration;
 
     use serde_json::Value;
     use tokio::io::{AsyncReadExt, AsyncWriteExt};
     use tokio::net::UnixStream;
     use tower::Service;
 
-    use super::Client;
+    use super::{call_batch, Client};
+    use crate::aiomas::codec::Exception;
+    use crate::aiomas::keepalive::KeepAlive;
+    use crate::testing::TraceCapture;
 
     #[tokio::test]
     async fn smoke_test() {
@@ -195,7 +293,7 @@ mod tests {
         let (_running_tx, running_rx) = tokio::sync::watch::channel(true);
         let (handles_tx, _handles_rx) = tokio::sync::mpsc::channel(8);
 
-        let mut client = Client::from_stream(running_rx, handles_tx, read).await;
+        let mut client = Client::from_stream(running_rx, handles_tx, None, read).await;
 
         std::future::poll_fn(|cx| client.poll_ready(cx)).await.unwrap();
         let first = client.call((String::from("test"), vec![], HashMap::new()));
@@ -211,4 +309,139 @@ mod tests {
         assert_eq!(first.await.expect("first"), Ok(Value::Number(0.into())));
         assert_eq!(second.await.expect("second"), Ok(Value::Number(1.into())));
     }
+
+    #[tokio::test]
+    async fn call_spans() {
+        const REQUEST: &[u8] =
+            b"\x00\x00\x00\x14[0,0,[\"test\",[],{}]]\x00\x00\x00\x14[0,1,[\"next\",[],{}]]";
+
+        let capture = TraceCapture::default();
+        let _guard = capture.set_default();
+
+        let (read, mut write) = UnixStream::pair().expect("failed to create a socket pair");
+
+        let (_running_tx, running_rx) = tokio::sync::watch::channel(true);
+        let (handles_tx, _handles_rx) = tokio::sync::mpsc::channel(8);
+
+        let mut client = Client::from_stream(running_rx, handles_tx, None, read).await;
+
+        std::future::poll_fn(|cx| client.poll_ready(cx)).await.unwrap();
+        let _first = client.call((String::from("test"), vec![], HashMap::new()));
+        std::future::poll_fn(|cx| client.poll_ready(cx)).await.unwrap();
+        let _second = client.call((String::from("next"), vec![], HashMap::new()));
+
+        let mut buf = [0; REQUEST.len()];
+        write.read_exact(&mut buf[..]).await.expect("failed to read request");
+        assert_eq!(&buf[..], REQUEST);
+
+        let span = |method: &str, id: &str| {
+            ("rpc_call", vec![("rpc.method", String::from(method)), ("rpc.id", String::from(id))])
+        };
+        assert_eq!(capture.spans(), vec![span("test", "0"), span("next", "1")]);
+    }
+
+    #[tokio::test]
+    async fn batch() {
+        const REQUEST: &[u8] = concat!(
+            "\x00\x00\x00\x14[0,0,[\"test\",[],{}]]",
+            "\x00\x00\x00\x14[0,1,[\"test\",[],{}]]",
+            "\x00\x00\x00\x14[0,2,[\"test\",[],{}]]",
+            "\x00\x00\x00\x14[0,3,[\"test\",[],{}]]",
+        )
+        .as_bytes();
+        const RESPONSE: &[u8] = concat!(
+            "\x00\x00\x00\x09[1, 2, 2]",
+            "\x00\x00\x00\x09[1, 0, 0]",
+            "\x00\x00\x00\x1b[2, 3, [\"handler\", \"nope\"]]",
+            "\x00\x00\x00\x09[1, 1, 1]",
+        )
+        .as_bytes();
+
+        let (read, mut write) = UnixStream::pair().expect("failed to create a socket pair");
+
+        let (_running_tx, running_rx) = tokio::sync::watch::channel(true);
+        let (handles_tx, _handles_rx) = tokio::sync::mpsc::channel(8);
+
+        let mut client = Client::from_stream(running_rx, handles_tx, None, read).await;
+
+        let requests = (0..4).map(|_| (String::from("test"), vec![], HashMap::new())).collect();
+        let replies = call_batch(&mut client, requests).await.unwrap();
+
+        // Every request is sent before any of the responses arrive.
+        let mut buf = [0; REQUEST.len()];
+        write.read_exact(&mut buf[..]).await.expect("failed to read requests");
+        assert_eq!(&buf[..], REQUEST);
+        write.write_all(RESPONSE).await.expect("failed to write responses");
+
+        assert_eq!(
+            replies.await.expect("batch"),
+            vec![
+                Ok(Value::Number(0.into())),
+                Ok(Value::Number(1.into())),
+                Ok(Value::Number(2.into())),
+                Err(Exception::Handler(String::from("nope"))),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn connection_closed() {
+        const REQUEST: &[u8] = b"\x00\x00\x00\x14[0,0,[\"test\",[],{}]]";
+
+        let (read, mut write) = UnixStream::pair().expect("failed to create a socket pair");
+
+        let (_running_tx, running_rx) = tokio::sync::watch::channel(true);
+        let (handles_tx, _handles_rx) = tokio::sync::mpsc::channel(8);
+
+        let mut client = Client::from_stream(running_rx, handles_tx, None, read).await;
+
+        std::future::poll_fn(|cx| client.poll_ready(cx)).await.unwrap();
+        let request = client.call((String::from("test"), vec![], HashMap::new()));
+
+        let mut buf = [0; REQUEST.len()];
+        write.read_exact(&mut buf[..]).await.expect("failed to read request");
+        assert_eq!(&buf[..], REQUEST);
+        drop(write);
+
+        let error = request.await.expect_err("request succeeded");
+        assert_eq!(error.to_string(), "connection closed before response");
+    }
+
+    #[tokio::test]
+    async fn keepalive() {
+        const PING: &[u8] = b"\x00\x00\x00\x0a[3,0,null]";
+        const PONG: &[u8] = b"\x00\x00\x00\x0a[4,0,null]";
+        const SERVER_PING: &[u8] = b"\x00\x00\x00\x0a[3,7,null]";
+        const CLIENT_PONG: &[u8] = b"\x00\x00\x00\x0a[4,7,null]";
+
+        let (read, mut write) = UnixStream::pair().expect("failed to create a socket pair");
+
+        let (_running_tx, running_rx) = tokio::sync::watch::channel(true);
+        let (handles_tx, _handles_rx) = tokio::sync::mpsc::channel(8);
+
+        let keepalive =
+            KeepAlive { interval: Duration::from_millis(10), timeout: Duration::from_millis(50) };
+        let mut client = Client::from_stream(running_rx, handles_tx, Some(keepalive), read).await;
+
+        // Pings from the server get answered.
+        write.write_all(SERVER_PING).await.expect("failed to write ping");
+        let mut buf = [0; PING.len()];
+        write.read_exact(&mut buf[..]).await.expect("failed to read pong");
+        if buf == PING {
+            write.write_all(PONG).await.expect("failed to write pong");
+            write.read_exact(&mut buf[..]).await.expect("failed to read pong");
+        }
+        assert_eq!(&buf[..], CLIENT_PONG);
+
+        std::future::poll_fn(|cx| client.poll_ready(cx)).await.unwrap();
+        let request = client.call((String::from("test"), vec![], HashMap::new()));
+
+        // The server stops responding but keeps the connection open.
+        let error = tokio::time::timeout(Duration::from_secs(5), request)
+            .await
+            .expect("the connection wasn't declared dead")
+            .expect_err("request succeeded");
+        assert_eq!(error.to_string(), "connection timed out");
+        drop(write);
+    }
 }
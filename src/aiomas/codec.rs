@@ -1,10 +1,10 @@
 use std::collections::HashMap;
-use std::fmt::{Formatter, Result as FmtResult};
+use std::fmt::{Display, Formatter, Result as FmtResult};
 
 use anyhow::Error;
 use bytes::{Bytes, BytesMut};
 use futures_util::{Sink, SinkExt, Stream, TryStreamExt};
-use serde::de::{Error as DeserializationError, Visitor};
+use serde::de::{Error as DeserializationError, IgnoredAny, Visitor};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde_json::{self, Value};
 use tokio::io::{AsyncRead, AsyncWrite};
@@ -15,6 +15,8 @@ enum FrameType {
     Request = 0,
     Result = 1,
     Exception = 2,
+    Ping = 3,
+    Pong = 4,
 }
 
 impl Serialize for FrameType {
@@ -39,6 +41,8 @@ impl<'de> Deserialize<'de> for FrameType {
                     0 => Ok(FrameType::Request),
                     1 => Ok(FrameType::Result),
                     2 => Ok(FrameType::Exception),
+                    3 => Ok(FrameType::Ping),
+                    4 => Ok(FrameType::Pong),
                     n => Err(DeserializationError::custom(format!("unknown frame type {n}"))),
                 }
             }
@@ -50,45 +54,205 @@ impl<'de> Deserialize<'de> for FrameType {
 
 type Frame<T> = (FrameType, u64, T);
 
+/// What's sent over a connection: requests or responses with their request ID, and the keep-alive
+/// frames with their own ID.
+///
+/// Keep-alive frames are `[3, id, null]` for a ping and `[4, id, null]` for the pong answering it.
+/// The Python side doesn't know them, so they're only sent if keep-alive is enabled.
+#[derive(Debug, PartialEq)]
+pub enum Packet<T> {
+    Message(u64, T),
+    Ping(u64),
+    Pong(u64),
+}
+
+fn encode_keepalive(ty: FrameType, id: u64) -> Result<Bytes, Error> {
+    Ok(serde_json::to_vec(&(ty, id, ()))?.into())
+}
+
+fn decode_keepalive<T>(buf: &[u8]) -> Option<Packet<T>> {
+    match serde_json::from_slice::<Frame<IgnoredAny>>(buf) {
+        Ok((FrameType::Ping, id, _)) => Some(Packet::Ping(id)),
+        Ok((FrameType::Pong, id, _)) => Some(Packet::Pong(id)),
+        _ => None,
+    }
+}
+
 pub type Request = (String, Vec<Value>, HashMap<String, Value>);
-pub type Exception = String;
 
-async fn encode_request((request_id, payload): (u64, Request)) -> Result<Bytes, Error> {
-    Ok(serde_json::to_vec(&(FrameType::Request, request_id, payload))?.into())
+/// An error in the exception position of a response.
+///
+/// Serialized as a `[code, message]` array. Both that, a `{"code": ..., "message": ...}` object,
+/// and a bare string (a Python traceback) are accepted when deserializing.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Exception {
+    /// The requested method isn't registered.
+    NoSuchMethod(String),
+    /// Wrong number of arguments or an argument failed to deserialize.
+    InvalidArguments(String),
+    /// The caller isn't allowed to call the method.
+    Unauthorized,
+    /// The connection already has as many requests in flight as the server allows.
+    TooManyRequests,
+    /// The return value failed to serialize.
+    Serialization(String),
+    /// The method itself returned an error.
+    Handler(String),
+    /// Anything else, including exceptions from the Python side.
+    Other(String),
+}
+
+impl Exception {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Exception::NoSuchMethod(_) => "no_such_method",
+            Exception::InvalidArguments(_) => "invalid_arguments",
+            Exception::Unauthorized => "unauthorized",
+            Exception::TooManyRequests => "too_many_requests",
+            Exception::Serialization(_) => "serialization",
+            Exception::Handler(_) => "handler",
+            Exception::Other(_) => "other",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            Exception::NoSuchMethod(message)
+            | Exception::InvalidArguments(message)
+            | Exception::Serialization(message)
+            | Exception::Handler(message)
+            | Exception::Other(message) => message,
+            Exception::Unauthorized => "unauthorized",
+            Exception::TooManyRequests => "too many requests",
+        }
+    }
+
+    fn from_parts(code: &str, message: String) -> Exception {
+        match code {
+            "no_such_method" => Exception::NoSuchMethod(message),
+            "invalid_arguments" => Exception::InvalidArguments(message),
+            "unauthorized" => Exception::Unauthorized,
+            "too_many_requests" => Exception::TooManyRequests,
+            "serialization" => Exception::Serialization(message),
+            "handler" => Exception::Handler(message),
+            _ => Exception::Other(message),
+        }
+    }
+}
+
+impl Display for Exception {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            Exception::NoSuchMethod(method) => write!(f, "no such method: {method}"),
+            Exception::Unauthorized => f.write_str("unauthorized"),
+            _ => f.write_str(self.message()),
+        }
+    }
 }
 
-async fn decode_response(buf: BytesMut) -> Result<(u64, Result<Value, Exception>), Error> {
+impl std::error::Error for Exception {}
+
+impl Serialize for Exception {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (self.code(), self.message()).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Exception {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Exception, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Message(String),
+            Array(String, String),
+            Object { code: String, message: String },
+        }
+
+        Ok(match Repr::deserialize(deserializer)? {
+            Repr::Message(message) => Exception::Other(message),
+            Repr::Array(code, message) | Repr::Object { code, message } => {
+                Exception::from_parts(&code, message)
+            }
+        })
+    }
+}
+
+async fn encode_request(packet: Packet<Request>) -> Result<Bytes, Error> {
+    match packet {
+        Packet::Message(request_id, payload) => {
+            Ok(serde_json::to_vec(&(FrameType::Request, request_id, payload))?.into())
+        }
+        Packet::Ping(id) => encode_keepalive(FrameType::Ping, id),
+        Packet::Pong(id) => encode_keepalive(FrameType::Pong, id),
+    }
+}
+
+async fn decode_response(buf: BytesMut) -> Result<Packet<Result<Value, Exception>>, Error> {
     match serde_json::from_slice::<Frame<Value>>(&buf)? {
-        (FrameType::Result, request_id, payload) => Ok((request_id, Ok(payload))),
+        (FrameType::Result, request_id, payload) => Ok(Packet::Message(request_id, Ok(payload))),
         (FrameType::Exception, request_id, payload) => {
-            Ok((request_id, Err(serde_json::from_value(payload)?)))
+            Ok(Packet::Message(request_id, Err(serde_json::from_value(payload)?)))
         }
+        (FrameType::Ping, id, _) => Ok(Packet::Ping(id)),
+        (FrameType::Pong, id, _) => Ok(Packet::Pong(id)),
         (ty, _, _) => anyhow::bail!("response type {:?} invalid", ty),
     }
 }
 
-async fn encode_response(
-    (request_id, payload): (u64, Result<Value, Exception>),
-) -> Result<Bytes, Error> {
-    Ok(serde_json::to_vec(&(
-        if payload.is_ok() { FrameType::Result } else { FrameType::Exception },
-        request_id,
-        payload.unwrap_or_else(Value::String),
-    ))?
-    .into())
+async fn encode_response(packet: Packet<Result<Value, Exception>>) -> Result<Bytes, Error> {
+    let frame = match packet {
+        Packet::Message(request_id, Ok(value)) => (FrameType::Result, request_id, value),
+        Packet::Message(request_id, Err(exception)) => {
+            (FrameType::Exception, request_id, serde_json::to_value(exception)?)
+        }
+        Packet::Ping(id) => return encode_keepalive(FrameType::Ping, id),
+        Packet::Pong(id) => return encode_keepalive(FrameType::Pong, id),
+    };
+    Ok(serde_json::to_vec(&frame)?.into())
 }
 
-async fn decode_request(buf: BytesMut) -> Result<(u64, Request), Error> {
-    match serde_json::from_slice::<Frame<Request>>(&buf)? {
-        (FrameType::Request, request_id, payload) => Ok((request_id, payload)),
-        (ty, _, _) => anyhow::bail!("request type {:?} invalid", ty),
+/// A request frame that was received in full but couldn't be decoded.
+///
+/// Unlike I/O and framing errors the stream is still in sync, so the connection can keep going.
+#[derive(Debug)]
+pub struct MalformedRequest {
+    /// The request ID, if the frame was well-formed enough to find it.
+    pub request_id: Option<u64>,
+    pub error: Error,
+}
+
+impl Display for MalformedRequest {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "malformed request: {:#}", self.error)
+    }
+}
+
+impl std::error::Error for MalformedRequest {}
+
+async fn decode_request(buf: BytesMut) -> Result<Packet<Request>, Error> {
+    if let Some(keepalive) = decode_keepalive(&buf) {
+        return Ok(keepalive);
+    }
+
+    let malformed = |error: Error| {
+        // Salvage the ID from `[type, id, ...]` so the error can be sent to the caller.
+        let request_id = serde_json::from_slice::<Vec<Value>>(&buf)
+            .ok()
+            .and_then(|frame| frame.get(1).and_then(Value::as_u64));
+        Error::new(MalformedRequest { request_id, error })
+    };
+
+    match serde_json::from_slice::<Frame<Request>>(&buf) {
+        Ok((FrameType::Request, request_id, payload)) => Ok(Packet::Message(request_id, payload)),
+        Ok((ty, _, _)) => Err(malformed(anyhow::anyhow!("request type {:?} invalid", ty))),
+        Err(error) => Err(malformed(error.into())),
     }
 }
 
 pub fn client<T: AsyncRead + AsyncWrite>(
     io: T,
-) -> impl Stream<Item = Result<(u64, Result<Value, Exception>), Error>>
-       + Sink<(u64, Request), Error = Error> {
+) -> impl Stream<Item = Result<Packet<Result<Value, Exception>>, Error>>
+       + Sink<Packet<Request>, Error = Error> {
     LengthDelimitedCodec::builder()
         .big_endian()
         .length_field_length(4)
@@ -100,8 +264,8 @@ pub fn client<T: AsyncRead + AsyncWrite>(
 
 pub fn server<T: AsyncRead + AsyncWrite>(
     io: T,
-) -> impl Stream<Item = Result<(u64, Request), Error>>
-       + Sink<(u64, Result<Value, Exception>), Error = Error> {
+) -> impl Stream<Item = Result<Packet<Request>, Error>>
+       + Sink<Packet<Result<Value, Exception>>, Error = Error> {
     LengthDelimitedCodec::builder()
         .big_endian()
         .length_field_length(4)
@@ -110,3 +274,70 @@ pub fn server<T: AsyncRead + AsyncWrite>(
         .and_then(decode_request)
         .with(encode_response)
 }
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+    use serde_json::json;
+
+    use super::{decode_request, Exception, MalformedRequest};
+
+    #[test]
+    fn exception_serialization() {
+        let cases = [
+            (Exception::NoSuchMethod(String::from("foo")), json!(["no_such_method", "foo"])),
+            (
+                Exception::InvalidArguments(String::from("function takes no arguments (1 given)")),
+                json!(["invalid_arguments", "function takes no arguments (1 given)"]),
+            ),
+            (Exception::Unauthorized, json!(["unauthorized", "unauthorized"])),
+            (Exception::TooManyRequests, json!(["too_many_requests", "too many requests"])),
+            (
+                Exception::Serialization(String::from("key must be a string")),
+                json!(["serialization", "key must be a string"]),
+            ),
+            (Exception::Handler(String::from("oops")), json!(["handler", "oops"])),
+            (Exception::Other(String::from("Traceback")), json!(["other", "Traceback"])),
+        ];
+
+        for (exception, value) in cases {
+            assert_eq!(serde_json::to_value(&exception).unwrap(), value);
+            assert_eq!(serde_json::from_value::<Exception>(value).unwrap(), exception);
+        }
+    }
+
+    #[test]
+    fn exception_deserialization() {
+        assert_eq!(
+            serde_json::from_value::<Exception>(json!("Traceback (most recent call last): ..."))
+                .unwrap(),
+            Exception::Other(String::from("Traceback (most recent call last): ..."))
+        );
+        assert_eq!(
+            serde_json::from_value::<Exception>(
+                json!({"code": "no_such_method", "message": "foo"})
+            )
+            .unwrap(),
+            Exception::NoSuchMethod(String::from("foo"))
+        );
+        assert_eq!(
+            serde_json::from_value::<Exception>(json!(["teapot", "short and stout"])).unwrap(),
+            Exception::Other(String::from("short and stout"))
+        );
+        assert!(serde_json::from_value::<Exception>(json!(42)).is_err());
+    }
+
+    async fn malformed_request_id(frame: &[u8]) -> Option<u64> {
+        let error = decode_request(BytesMut::from(frame)).await.unwrap_err();
+        error.downcast_ref::<MalformedRequest>().unwrap().request_id
+    }
+
+    #[tokio::test]
+    async fn malformed_requests() {
+        assert_eq!(malformed_request_id(b"[0, 1, [\"ping\", [], {}]").await, None);
+        assert_eq!(malformed_request_id(b"[0, 2, [\"ping\", {}, {}]]").await, Some(2));
+        assert_eq!(malformed_request_id(b"[1, 3, null]").await, Some(3));
+        assert_eq!(malformed_request_id(b"[0, 4, [\"\xff\", [], {}]]").await, None);
+        assert_eq!(malformed_request_id(b"{}").await, None);
+    }
+}
@@ -0,0 +1,58 @@
+use std::future::pending;
+use std::time::Duration;
+
+use tokio::time::{sleep_until, Instant};
+
+/// How often to ping the peer and how long to wait for the pong before giving up on it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct KeepAlive {
+    pub interval: Duration,
+    pub timeout: Duration,
+}
+
+/// The peer didn't answer a ping in time.
+#[derive(Debug)]
+pub struct Dead;
+
+/// The keep-alive state of one connection.
+pub struct Pinger {
+    keepalive: Option<KeepAlive>,
+    next_ping: Instant,
+    next_id: u64,
+    /// The ID of the unanswered ping and when the pong has to arrive by.
+    waiting: Option<(u64, Instant)>,
+}
+
+impl Pinger {
+    pub fn new(keepalive: Option<KeepAlive>) -> Pinger {
+        let next_ping = Instant::now() + keepalive.map_or(Duration::ZERO, |k| k.interval);
+        Pinger { keepalive, next_ping, next_id: 0, waiting: None }
+    }
+
+    /// Wait until it's time to send the next ping and return its ID, or fail if the previous ping
+    /// wasn't answered in time. Never finishes if keep-alive is disabled.
+    ///
+    /// Cancel-safe, so it can be polled in a `select!` loop.
+    pub async fn tick(&mut self) -> Result<u64, Dead> {
+        let Some(keepalive) = self.keepalive else { return pending().await };
+
+        if let Some((_, deadline)) = self.waiting {
+            sleep_until(deadline).await;
+            return Err(Dead);
+        }
+
+        sleep_until(self.next_ping).await;
+        let now = Instant::now();
+        let id = self.next_id;
+        self.next_id += 1;
+        self.next_ping = now + keepalive.interval;
+        self.waiting = Some((id, now + keepalive.timeout));
+        Ok(id)
+    }
+
+    pub fn pong(&mut self, id: u64) {
+        if self.waiting.is_some_and(|(waiting, _)| waiting == id) {
+            self.waiting = None;
+        }
+    }
+}
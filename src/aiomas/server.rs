@@ -2,6 +2,7 @@ use std::collections::HashMap;
 use std::fmt::Debug;
 use std::future::Future;
 use std::marker::PhantomData;
+use std::net::SocketAddr;
 #[cfg(unix)]
 use std::path::Path;
 use std::pin::Pin;
@@ -15,11 +16,12 @@ use serde_json::Value;
 use tokio::net::TcpListener;
 #[cfg(unix)]
 use tokio::net::UnixListener;
-use tokio::sync::{mpsc, watch};
-use tokio::task::JoinHandle;
-use tracing::error;
+use tokio::sync::{mpsc, watch, Semaphore};
+use tracing::{error, warn, Instrument};
 
-use crate::aiomas::codec::{self, Exception, Request};
+use crate::aiomas::codec::{self, Exception, MalformedRequest, Packet, Request};
+use crate::aiomas::keepalive::{KeepAlive, Pinger};
+use crate::shutdown::Task;
 
 // Need to have the `Args` parameter on the trait otherwise the argument types are "unconstrained".
 // But then we need a second trait and a struct to erase it...
@@ -45,23 +47,31 @@ where
         kwargs: HashMap<String, Value>,
     ) -> Pin<Box<dyn Future<Output = Result<Value, Exception>> + Send + 'static>> {
         if !kwargs.is_empty() {
-            return future::ready(Err(String::from("function takes no keyword arguments"))).boxed();
+            return future::ready(Err(Exception::InvalidArguments(String::from(
+                "function takes no keyword arguments",
+            ))))
+            .boxed();
         }
 
         if args.len() != 0 {
-            return future::ready(Err(format!(
+            return future::ready(Err(Exception::InvalidArguments(format!(
                 "function takes no arguments ({} given)",
                 args.len()
-            )))
+            ))))
             .boxed();
         }
 
         self()
             .then(|res| async move {
                 match res {
-                    Ok(val) => serde_json::to_value(val)
-                        .map_err(|err| format!("failed to serialize the return value: {err:?}")),
-                    Err(err) => Err(format!("function returned an error: {err:?}")),
+                    Ok(val) => serde_json::to_value(val).map_err(|err| {
+                        Exception::Serialization(format!(
+                            "failed to serialize the return value: {err:?}"
+                        ))
+                    }),
+                    Err(err) => {
+                        Err(Exception::Handler(format!("function returned an error: {err:?}")))
+                    }
                 }
             })
             .boxed()
@@ -82,14 +92,17 @@ where
         kwargs: HashMap<String, Value>,
     ) -> Pin<Box<dyn Future<Output = Result<Value, Exception>> + Send + 'static>> {
         if !kwargs.is_empty() {
-            return future::ready(Err(String::from("function takes no keyword arguments"))).boxed();
+            return future::ready(Err(Exception::InvalidArguments(String::from(
+                "function takes no keyword arguments",
+            ))))
+            .boxed();
         }
 
         if args.len() != 1 {
-            return future::ready(Err(format!(
+            return future::ready(Err(Exception::InvalidArguments(format!(
                 "function only takes a single argument ({} given)",
                 args.len()
-            )))
+            ))))
             .boxed();
         }
 
@@ -97,17 +110,24 @@ where
         let arg0 = match serde_json::from_value(iter.next().unwrap()) {
             Ok(arg) => arg,
             Err(err) => {
-                return future::ready(Err(format!("failed to deserialize argument 0: {err:?}")))
-                    .boxed()
+                return future::ready(Err(Exception::InvalidArguments(format!(
+                    "failed to deserialize argument 0: {err:?}"
+                ))))
+                .boxed()
             }
         };
 
         self(arg0)
             .then(|res| async move {
                 match res {
-                    Ok(val) => serde_json::to_value(val)
-                        .map_err(|err| format!("failed to serialize the return value: {err:?}")),
-                    Err(err) => Err(format!("function returned an error: {err:?}")),
+                    Ok(val) => serde_json::to_value(val).map_err(|err| {
+                        Exception::Serialization(format!(
+                            "failed to serialize the return value: {err:?}"
+                        ))
+                    }),
+                    Err(err) => {
+                        Err(Exception::Handler(format!("function returned an error: {err:?}")))
+                    }
                 }
             })
             .boxed()
@@ -140,8 +160,85 @@ where
     }
 }
 
+/// The keyword argument that carries the caller's token for [`Server::register_with_auth`].
+///
+/// It is stripped from the request before the route sees it.
+pub const AUTH_TOKEN_KWARG: &str = "_auth_token";
+
+/// Metadata about the caller of a method, for authorizing the call.
+// None of the current routes need authorization.
+#[allow(dead_code)]
+pub struct Caller<'a> {
+    /// The address of the peer. Only known for TCP connections.
+    pub peer_addr: Option<&'a SocketAddr>,
+    /// The value of the [`AUTH_TOKEN_KWARG`] keyword argument.
+    pub token: Option<&'a str>,
+}
+
+type Predicate = Box<dyn Fn(&Caller) -> bool + Send + Sync + 'static>;
+
+struct Method {
+    handler: Box<dyn Handler + Send + Sync + 'static>,
+    predicate: Option<Predicate>,
+}
+
+impl Method {
+    fn call(
+        &self,
+        peer_addr: Option<&SocketAddr>,
+        args: Vec<Value>,
+        mut kwargs: HashMap<String, Value>,
+    ) -> Pin<Box<dyn Future<Output = Result<Value, Exception>> + Send + 'static>> {
+        let token = kwargs.remove(AUTH_TOKEN_KWARG);
+
+        if let Some(ref predicate) = self.predicate {
+            let caller = Caller { peer_addr, token: token.as_ref().and_then(Value::as_str) };
+            if !predicate(&caller) {
+                return future::ready(Err(Exception::Unauthorized)).boxed();
+            }
+        }
+
+        self.handler.handle(args, kwargs)
+    }
+}
+
+type Fallback = Box<
+    dyn Fn(
+            String,
+            Vec<Value>,
+            HashMap<String, Value>,
+        ) -> Pin<Box<dyn Future<Output = Result<Value, Exception>> + Send + 'static>>
+        + Send
+        + Sync
+        + 'static,
+>;
+
+#[derive(Default)]
+struct Routes {
+    methods: HashMap<String, Method>,
+    fallback: Option<Fallback>,
+}
+
+impl Routes {
+    fn call(
+        &self,
+        peer_addr: Option<&SocketAddr>,
+        method: String,
+        args: Vec<Value>,
+        kwargs: HashMap<String, Value>,
+    ) -> Pin<Box<dyn Future<Output = Result<Value, Exception>> + Send + 'static>> {
+        match (self.methods.get(&method), &self.fallback) {
+            (Some(method), _) => method.call(peer_addr, args, kwargs),
+            (None, Some(fallback)) => fallback(method, args, kwargs),
+            (None, None) => future::ready(Err(Exception::NoSuchMethod(method))).boxed(),
+        }
+    }
+}
+
 pub struct Server {
-    methods: HashMap<String, Box<dyn Handler + Send + Sync + 'static>>,
+    routes: Routes,
+    keepalive: Option<KeepAlive>,
+    max_in_flight: Option<usize>,
 
     #[cfg(unix)]
     listener: UnixListener,
@@ -155,7 +252,7 @@ impl Server {
     pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
         let listener = UnixListener::bind(path).context("failed to create a listening socket")?;
 
-        Ok(Server { listener, methods: HashMap::new() })
+        Ok(Server { listener, routes: Routes::default(), keepalive: None, max_in_flight: None })
     }
 
     #[cfg(not(unix))]
@@ -166,32 +263,97 @@ impl Server {
         let listener =
             TcpListener::bind(&addr).await.context("failed to create a listening socket")?;
 
-        Ok(Server { listener, methods: HashMap::new() })
+        Ok(Server { listener, routes: Routes::default(), keepalive: None, max_in_flight: None })
     }
 
+    /// Register a method that any client can call. Only for trusted local sockets.
     pub fn register<Args: 'static>(
         &mut self,
         method: impl Into<String>,
         route: impl Route<Args> + Send + Sync + 'static,
     ) {
-        self.methods.insert(method.into(), Box::new(RouteHandler { route, _marker: PhantomData }));
+        self.routes.methods.insert(
+            method.into(),
+            Method {
+                handler: Box::new(RouteHandler { route, _marker: PhantomData }),
+                predicate: None,
+            },
+        );
     }
 
-    pub async fn serve(
-        self,
-        mut running: watch::Receiver<bool>,
-        handler_tx: mpsc::Sender<JoinHandle<()>>,
+    /// Register a method that can only be called if `predicate` accepts the caller.
+    ///
+    /// Rejected calls get an `"unauthorized"` exception.
+    #[allow(dead_code)]
+    pub fn register_with_auth<Args: 'static>(
+        &mut self,
+        method: impl Into<String>,
+        route: impl Route<Args> + Send + Sync + 'static,
+        predicate: impl Fn(&Caller) -> bool + Send + Sync + 'static,
     ) {
-        let Server { methods, listener } = self;
+        self.routes.methods.insert(
+            method.into(),
+            Method {
+                handler: Box::new(RouteHandler { route, _marker: PhantomData }),
+                predicate: Some(Box::new(predicate)),
+            },
+        );
+    }
+
+    /// Set the handler for calls to methods that haven't been registered, for example to forward
+    /// them elsewhere. It gets the method name and the arguments as they are.
+    ///
+    /// Without a fallback those calls get a `"no such method"` exception.
+    pub fn set_fallback<Fun, Fut>(&mut self, fallback: Fun)
+    where
+        Fun: Fn(String, Vec<Value>, HashMap<String, Value>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Value, Exception>> + Send + 'static,
+    {
+        self.routes.fallback =
+            Some(Box::new(move |method, args, kwargs| fallback(method, args, kwargs).boxed()));
+    }
+
+    /// Ping the clients and drop the ones that stop answering. Off by default.
+    pub fn set_keepalive(&mut self, keepalive: Option<KeepAlive>) {
+        self.keepalive = keepalive;
+    }
+
+    /// Handle at most `max_in_flight` requests at a time on each connection. Requests over the
+    /// limit get a `"too_many_requests"` exception. Unlimited by default.
+    pub fn set_max_in_flight(&mut self, max_in_flight: Option<usize>) {
+        self.max_in_flight = max_in_flight;
+    }
+
+    pub async fn serve(self, mut running: watch::Receiver<bool>, handler_tx: mpsc::Sender<Task>) {
+        let Server { routes, keepalive, max_in_flight, listener } = self;
 
-        let methods = Arc::new(methods);
+        let routes = Arc::new(routes);
 
         loop {
             tokio::select! {
                 _ = running.changed() => break,
                 res = listener.accept() => match res {
-                    Ok((socket, _remote_addr)) => {
-                        let _ = handler_tx.send(tokio::spawn(Server::process(running.clone(), handler_tx.clone(), methods.clone(), codec::server(socket)))).await;
+                    Ok((socket, remote_addr)) => {
+                        #[cfg(unix)]
+                        let peer_addr = {
+                            let _ = remote_addr;
+                            None
+                        };
+                        #[cfg(not(unix))]
+                        let peer_addr = Some(remote_addr);
+
+                        let connection = Server::process(
+                            running.clone(),
+                            handler_tx.clone(),
+                            routes.clone(),
+                            Pinger::new(keepalive),
+                            max_in_flight,
+                            peer_addr,
+                            codec::server(socket),
+                        );
+                        let _ = handler_tx
+                            .send(("rpc connection".into(), tokio::spawn(connection)))
+                            .await;
                     }
                     Err(error) => error!(?error, "Failed to accept an incoming connection"),
                 },
@@ -201,27 +363,34 @@ impl Server {
 
     async fn process<T>(
         mut running: watch::Receiver<bool>,
-        handler_tx: mpsc::Sender<JoinHandle<()>>,
-        methods: Arc<HashMap<String, Box<dyn Handler + Send + Sync + 'static>>>,
+        handler_tx: mpsc::Sender<Task>,
+        routes: Arc<Routes>,
+        mut pinger: Pinger,
+        max_in_flight: Option<usize>,
+        peer_addr: Option<SocketAddr>,
         transport: T,
     ) where
-        T: Sink<(u64, Result<Value, Exception>), Error = Error>
-            + Stream<Item = Result<(u64, Request), Error>>
+        T: Sink<Packet<Result<Value, Exception>>, Error = Error>
+            + Stream<Item = Result<Packet<Request>, Error>>
             + Send
             + Sync
             + 'static,
     {
+        let in_flight = Arc::new(Semaphore::new(max_in_flight.unwrap_or(Semaphore::MAX_PERMITS)));
         let (mut sink, mut stream) = transport.split();
         let (tx, mut rx) = mpsc::channel(16);
         let _ = handler_tx
-            .send(tokio::spawn(async move {
-                while let Some(response) = rx.recv().await {
-                    if let Err(error) = sink.send(response).await {
-                        error!(?error, "Failed to send a response");
-                        break;
+            .send((
+                "rpc responses".into(),
+                tokio::spawn(async move {
+                    while let Some(response) = rx.recv().await {
+                        if let Err(error) = sink.send(response).await {
+                            error!(?error, "Failed to send a response");
+                            break;
+                        }
                     }
-                }
-            }))
+                }),
+            ))
             .await;
 
         loop {
@@ -229,26 +398,407 @@ impl Server {
                 _ = running.changed() => break,
                 // Probably not cancel-safe but we're not continuing anyway.
                 req = stream.try_next() => match req {
-                    Ok(Some((id, (method, args, kwargs)))) => {
-                        let tx = tx.clone();
-                        let future = match methods.get(&method) {
-                            Some(handler) => handler.handle(args, kwargs),
-                            None => async move { Err(format!("no such method: {method}")) }.boxed(),
+                    Ok(Some(Packet::Message(id, (method, args, kwargs)))) => {
+                        let Ok(permit) = in_flight.clone().try_acquire_owned() else {
+                            warn!(
+                                rpc.method = method.as_str(),
+                                rpc.id = id,
+                                "Too many requests in flight",
+                            );
+                            let _ = tx
+                                .send(Packet::Message(id, Err(Exception::TooManyRequests)))
+                                .await;
+                            continue;
                         };
+                        let tx = tx.clone();
+                        let span = tracing::info_span!(
+                            "rpc_request",
+                            rpc.method = method.as_str(),
+                            rpc.id = id,
+                        );
+                        let label = format!("rpc request {method}");
+                        let future = routes.call(peer_addr.as_ref(), method, args, kwargs);
 
                         let _ = handler_tx
-                            .send(tokio::spawn(async move {
-                                let _ = tx.send((id, future.await)).await;
-                            }))
+                            .send((
+                                label.into(),
+                                tokio::spawn(
+                                    async move {
+                                        let result = future.await;
+                                        drop(permit);
+                                        let _ = tx.send(Packet::Message(id, result)).await;
+                                    }
+                                    .instrument(span),
+                                ),
+                            ))
                             .await;
                     }
+                    Ok(Some(Packet::Ping(id))) => {
+                        let _ = tx.send(Packet::Pong(id)).await;
+                    }
+                    Ok(Some(Packet::Pong(id))) => pinger.pong(id),
                     Ok(None) => break,
-                    Err(error) => {
-                        error!(?error, "Failed to read a request");
+                    Err(error) => match error.downcast::<MalformedRequest>() {
+                        // Only the one frame is bad, keep serving the rest.
+                        Ok(malformed) => {
+                            error!(error = ?malformed.error, "Received a malformed request");
+                            if let Some(id) = malformed.request_id {
+                                let _ = tx
+                                    .send(Packet::Message(
+                                        id,
+                                        Err(Exception::Other(malformed.to_string())),
+                                    ))
+                                    .await;
+                            }
+                        }
+                        Err(error) => {
+                            error!(?error, "Failed to read a request");
+                            break;
+                        }
+                    },
+                },
+                ping = pinger.tick() => match ping {
+                    Ok(id) => {
+                        let _ = tx.send(Packet::Ping(id)).await;
+                    }
+                    Err(_) => {
+                        error!("The client stopped answering pings");
                         break;
                     }
-                }
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::net::SocketAddr;
+    #[cfg(unix)]
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use bytes::Bytes;
+    use futures_util::{SinkExt, TryStreamExt};
+    use serde_json::{json, Value};
+    use tokio::io::DuplexStream;
+    use tokio::sync::{mpsc, watch, Notify};
+    use tokio_util::codec::{Framed, LengthDelimitedCodec};
+
+    use super::{Server, AUTH_TOKEN_KWARG};
+    use crate::aiomas::codec::{self, Exception, Packet};
+    use crate::aiomas::keepalive::{KeepAlive, Pinger};
+    use crate::shutdown::Task;
+    use crate::testing::TraceCapture;
+
+    async fn ping() -> Result<&'static str, ()> {
+        Ok("pong")
+    }
+
+    #[cfg(unix)]
+    async fn server() -> Server {
+        // The tests run in parallel so each one needs a socket of its own.
+        static SOCKETS: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "eris-aiomas-test-{}-{}.sock",
+            std::process::id(),
+            SOCKETS.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_file(&path);
+        let server = Server::new(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        server
+    }
+
+    #[cfg(not(unix))]
+    async fn server() -> Server {
+        Server::new(0).await.unwrap()
+    }
+
+    /// A server that runs until it's dropped.
+    struct Running {
+        running_tx: watch::Sender<bool>,
+        _handler_rx: mpsc::Receiver<Task>,
+    }
+
+    impl Drop for Running {
+        fn drop(&mut self) {
+            let _ = self.running_tx.send(false);
+        }
+    }
+
+    /// Serve the routes of `server` over an in-memory connection and return its client end.
+    fn connect(
+        server: Server,
+        pinger: Pinger,
+        max_in_flight: Option<usize>,
+    ) -> (Running, DuplexStream) {
+        let Server { routes, .. } = server;
+
+        let (running_tx, running_rx) = watch::channel(true);
+        let (handler_tx, handler_rx) = mpsc::channel(16);
+        let (client, server) = tokio::io::duplex(1024);
+        tokio::spawn(Server::process(
+            running_rx,
+            handler_tx,
+            Arc::new(routes),
+            pinger,
+            max_in_flight,
+            None,
+            codec::server(server),
+        ));
+
+        (Running { running_tx, _handler_rx: handler_rx }, client)
+    }
+
+    #[tokio::test]
+    async fn unauthorized() {
+        let mut server = server().await;
+        server.register("open", ping);
+        server.register_with_auth("secret", ping, |caller| caller.token == Some("hunter2"));
+        let (_server, client) = connect(server, Pinger::new(None), None);
+
+        let mut client = Box::pin(codec::client(client));
+        let token =
+            |token: &str| HashMap::from([(String::from(AUTH_TOKEN_KWARG), Value::from(token))]);
+
+        client
+            .send(Packet::Message(0, (String::from("open"), vec![], HashMap::new())))
+            .await
+            .unwrap();
+        assert_eq!(
+            client.try_next().await.unwrap(),
+            Some(Packet::Message(0, Ok(Value::from("pong"))))
+        );
+
+        client
+            .send(Packet::Message(1, (String::from("secret"), vec![], HashMap::new())))
+            .await
+            .unwrap();
+        assert_eq!(
+            client.try_next().await.unwrap(),
+            Some(Packet::Message(1, Err(Exception::Unauthorized)))
+        );
+
+        client
+            .send(Packet::Message(2, (String::from("secret"), vec![], token("hunter1"))))
+            .await
+            .unwrap();
+        assert_eq!(
+            client.try_next().await.unwrap(),
+            Some(Packet::Message(2, Err(Exception::Unauthorized)))
+        );
+
+        client
+            .send(Packet::Message(3, (String::from("secret"), vec![], token("hunter2"))))
+            .await
+            .unwrap();
+        assert_eq!(
+            client.try_next().await.unwrap(),
+            Some(Packet::Message(3, Ok(Value::from("pong"))))
+        );
+    }
+
+    #[tokio::test]
+    async fn unauthorized_peer() {
+        let mut server = server().await;
+        server.register_with_auth("local", ping, |caller| {
+            caller.peer_addr.is_some_and(|addr| addr.ip().is_loopback())
+        });
+
+        let call = |peer_addr: Option<SocketAddr>| {
+            server.routes.call(peer_addr.as_ref(), String::from("local"), vec![], HashMap::new())
+        };
+        assert_eq!(call(Some(SocketAddr::from(([127, 0, 0, 1], 4000)))).await, Ok(json!("pong")));
+        assert_eq!(
+            call(Some(SocketAddr::from(([192, 0, 2, 1], 4000)))).await,
+            Err(Exception::Unauthorized)
+        );
+        assert_eq!(call(None).await, Err(Exception::Unauthorized));
+    }
+
+    #[tokio::test]
+    async fn fallback() {
+        let mut server = server().await;
+        server.register("ping", ping);
+        server.set_fallback(|method, args, _| async move {
+            Ok(json!({ "method": method, "args": args }))
+        });
+        let (_server, client) = connect(server, Pinger::new(None), None);
+
+        let mut client = Box::pin(codec::client(client));
+
+        client
+            .send(Packet::Message(0, (String::from("ping"), vec![], HashMap::new())))
+            .await
+            .unwrap();
+        assert_eq!(
+            client.try_next().await.unwrap(),
+            Some(Packet::Message(0, Ok(Value::from("pong"))))
+        );
+
+        client
+            .send(Packet::Message(1, (String::from("echo"), vec![Value::from(1)], HashMap::new())))
+            .await
+            .unwrap();
+        assert_eq!(
+            client.try_next().await.unwrap(),
+            Some(Packet::Message(1, Ok(json!({ "method": "echo", "args": [1] }))))
+        );
+    }
+
+    type RawClient = Framed<DuplexStream, LengthDelimitedCodec>;
+
+    async fn roundtrip(client: &mut RawClient, frame: &'static [u8]) -> Value {
+        client.send(Bytes::from_static(frame)).await.unwrap();
+        let response = client.try_next().await.unwrap().unwrap();
+        serde_json::from_slice(&response).unwrap()
+    }
+
+    #[tokio::test]
+    async fn malformed_request() {
+        let mut server = server().await;
+        server.register("ping", ping);
+        let (_server, client) = connect(server, Pinger::new(None), None);
+
+        let mut client =
+            LengthDelimitedCodec::builder().big_endian().length_field_length(4).new_framed(client);
+
+        // Malformed frames without an ID are skipped.
+        client.send(Bytes::from_static(b"[0, 0, [\"ping\", [],")).await.unwrap();
+        assert_eq!(
+            roundtrip(&mut client, b"[0, 1, [\"ping\", [], {}]]").await,
+            json!([1, 1, "pong"])
+        );
+
+        // Malformed frames with an ID get an exception.
+        assert_eq!(
+            roundtrip(&mut client, b"[0, 2, [\"ping\", {}, {}]]").await,
+            json!([
+                2,
+                2,
+                [
+                    "other",
+                    "malformed request: invalid type: map, expected a sequence at line 1 column 16"
+                ]
+            ])
+        );
+        assert_eq!(
+            roundtrip(&mut client, b"[0, 3, [\"ping\", [], {}]]").await,
+            json!([1, 3, "pong"])
+        );
+    }
+
+    #[tokio::test]
+    async fn keepalive() {
+        let mut server = server().await;
+        server.register("ping", ping);
+        let keepalive =
+            KeepAlive { interval: Duration::from_millis(10), timeout: Duration::from_millis(50) };
+        let (_server, client) = connect(server, Pinger::new(Some(keepalive)), None);
+
+        let mut client = Box::pin(codec::client(client));
+
+        // Pings from the client get answered.
+        client.send(Packet::Ping(7)).await.unwrap();
+        loop {
+            match client.try_next().await.unwrap().unwrap() {
+                Packet::Ping(id) => client.send(Packet::Pong(id)).await.unwrap(),
+                packet => break assert_eq!(packet, Packet::Pong(7)),
             }
         }
+
+        // The connection stays up as long as the pings are answered.
+        for _ in 0..3 {
+            let Some(Packet::Ping(id)) = client.try_next().await.unwrap() else { panic!() };
+            client.send(Packet::Pong(id)).await.unwrap();
+        }
+
+        // Then the client stops responding but keeps the connection open.
+        assert!(matches!(client.try_next().await.unwrap(), Some(Packet::Ping(_))));
+        let closed = tokio::time::timeout(Duration::from_secs(5), client.try_next())
+            .await
+            .expect("the connection wasn't declared dead");
+        assert_eq!(closed.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn max_in_flight() {
+        let release = Arc::new(Notify::new());
+        let mut server = server().await;
+        server.register("ping", ping);
+        server.register("wait", {
+            let release = release.clone();
+            move || {
+                let release = release.clone();
+                async move {
+                    release.notified().await;
+                    Ok::<_, ()>("done")
+                }
+            }
+        });
+        let (_server, client) = connect(server, Pinger::new(None), Some(1));
+
+        let mut client = Box::pin(codec::client(client));
+        let request =
+            |id, method: &str| Packet::Message(id, (String::from(method), vec![], HashMap::new()));
+
+        // The first request takes up the only slot, so the second one is turned away.
+        client.send(request(0, "wait")).await.unwrap();
+        client.send(request(1, "ping")).await.unwrap();
+        assert_eq!(
+            client.try_next().await.unwrap(),
+            Some(Packet::Message(1, Err(Exception::TooManyRequests)))
+        );
+
+        // Once the first one is done there's room again.
+        release.notify_one();
+        assert_eq!(
+            client.try_next().await.unwrap(),
+            Some(Packet::Message(0, Ok(Value::from("done"))))
+        );
+        client.send(request(2, "ping")).await.unwrap();
+        assert_eq!(
+            client.try_next().await.unwrap(),
+            Some(Packet::Message(2, Ok(Value::from("pong"))))
+        );
+    }
+
+    #[tokio::test]
+    async fn request_spans() {
+        let capture = TraceCapture::default();
+        let _guard = capture.set_default();
+
+        let mut server = server().await;
+        server.register("ping", ping);
+        let (_server, client) = connect(server, Pinger::new(None), None);
+
+        let mut client = Box::pin(codec::client(client));
+        client
+            .send(Packet::Message(0, (String::from("ping"), vec![], HashMap::new())))
+            .await
+            .unwrap();
+        assert_eq!(
+            client.try_next().await.unwrap(),
+            Some(Packet::Message(0, Ok(Value::from("pong"))))
+        );
+        client
+            .send(Packet::Message(1, (String::from("pong"), vec![], HashMap::new())))
+            .await
+            .unwrap();
+        assert_eq!(
+            client.try_next().await.unwrap(),
+            Some(Packet::Message(1, Err(Exception::NoSuchMethod(String::from("pong")))))
+        );
+
+        let span = |method: &str, id: &str| {
+            (
+                "rpc_request",
+                vec![("rpc.method", String::from(method)), ("rpc.id", String::from(id))],
+            )
+        };
+        assert_eq!(capture.spans(), vec![span("ping", "0"), span("pong", "1")]);
     }
 }
@@ -0,0 +1,335 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::time::Duration;
+
+use anyhow::{Context, Error};
+use chrono::{DateTime, Utc};
+use sea_orm::DatabaseConnection;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tokio::sync::watch::Receiver;
+use tokio::time::Instant;
+use tracing::{error, info};
+use twilight_http::Client as DiscordClient;
+use twilight_model::id::marker::ChannelMarker;
+use twilight_model::id::Id;
+
+use crate::models::state;
+
+/// The longest the driver waits between polls after they keep failing.
+const MAX_BACKOFF: Duration = Duration::from_secs(15 * 60);
+
+/// A source of social media posts that get announced to Discord channels.
+///
+/// Implementors only need to know how to fetch, filter, and format posts. The polling loop, the
+/// state bookkeeping, and the posting are handled by [`run`].
+pub trait Announcer: Send + Sync {
+    /// A watched account.
+    type Source: Send + Sync;
+    /// A single post.
+    type Item: Send + Sync;
+    /// The cursor that is persisted in the `state` table between polls.
+    type State: Serialize + DeserializeOwned + Send + Sync;
+
+    /// The watched accounts and the channels they get announced to.
+    fn sources(&self) -> Vec<(Self::Source, Vec<Id<ChannelMarker>>)>;
+
+    /// The `state` table key for the cursor of `source`.
+    fn state_key(&self, source: &Self::Source) -> String;
+
+    /// Fetch the posts made after `state`.
+    fn fetch<'a>(
+        &'a self,
+        source: &'a Self::Source,
+        state: Option<&'a Self::State>,
+    ) -> impl Future<Output = Result<Vec<Self::Item>, Error>> + Send + 'a;
+
+    /// Posts are announced in the order of this key.
+    fn sort_key(&self, item: &Self::Item) -> DateTime<Utc>;
+
+    /// The cursor after `item` has been announced.
+    fn state_after(&self, item: &Self::Item) -> Self::State;
+
+    /// The cursor to start from when the source is polled for the first time. `items` is sorted.
+    fn initial_state(&self, items: &[Self::Item]) -> Self::State;
+
+    /// Should `item` be announced at all?
+    fn should_announce(&self, item: &Self::Item) -> bool;
+
+    /// Should `item` be announced in `channel_id`?
+    fn should_announce_to(&self, _item: &Self::Item, _channel_id: Id<ChannelMarker>) -> bool {
+        true
+    }
+
+    fn format(&self, item: &Self::Item) -> String;
+
+    /// Only log the posts instead of announcing them. Checked before every poll, after
+    /// [`Announcer::refresh`].
+    fn dry_run(&self) -> bool {
+        false
+    }
+
+    /// Called before every poll, for example to pick up a reloaded config.
+    fn refresh(&mut self) -> impl Future<Output = Result<(), Error>> + Send {
+        async { Ok(()) }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Action<S> {
+    Post { channel_id: Id<ChannelMarker>, content: String },
+    SetState(S),
+}
+
+/// Turn freshly fetched posts into the actions to perform, in order.
+///
+/// The cursor is advanced after every post so that a failure halfway through doesn't cause
+/// duplicate announcements on the next poll.
+fn plan<A: Announcer>(
+    announcer: &A,
+    state: Option<&A::State>,
+    mut items: Vec<A::Item>,
+    channels: &[Id<ChannelMarker>],
+) -> Vec<Action<A::State>> {
+    items.sort_by_key(|item| announcer.sort_key(item));
+
+    // Don't send an avalanche of posts when first activated.
+    if state.is_none() {
+        return vec![Action::SetState(announcer.initial_state(&items))];
+    }
+
+    let mut actions = vec![];
+    for item in &items {
+        if announcer.should_announce(item) {
+            let content = announcer.format(item);
+            for &channel_id in channels {
+                if announcer.should_announce_to(item, channel_id) {
+                    actions.push(Action::Post { channel_id, content: content.clone() });
+                }
+            }
+        }
+        actions.push(Action::SetState(announcer.state_after(item)));
+    }
+    actions
+}
+
+/// Fetch and announce the new posts. A dry run only logs the posts and keeps the cursors in
+/// `dry_run` instead of the `state` table, so that the real announcements still go out but the same
+/// posts aren't logged on every poll.
+async fn poll<A: Announcer>(
+    announcer: &A,
+    db: &DatabaseConnection,
+    discord: &DiscordClient,
+    mut dry_run: Option<&mut HashMap<String, A::State>>,
+) -> Result<(), Error> {
+    for (source, channels) in announcer.sources() {
+        let state_key = announcer.state_key(&source);
+        let stored;
+        let state = match dry_run.as_deref().and_then(|states| states.get(&state_key)) {
+            Some(state) => Some(state),
+            None => {
+                stored = state::get::<A::State>(&state_key, db)
+                    .await
+                    .context("failed to get the announcer state")?;
+                stored.as_ref()
+            }
+        };
+
+        let items = announcer
+            .fetch(&source, state)
+            .await
+            .with_context(|| format!("failed to fetch new posts for {state_key:?}"))?;
+        let actions = plan(announcer, state, items, &channels);
+
+        if let Some(ref mut states) = dry_run {
+            for action in actions {
+                match action {
+                    Action::Post { channel_id, content } => {
+                        info!(
+                            channel.id = channel_id.get(),
+                            content, "Dry run: not announcing a post"
+                        );
+                    }
+                    Action::SetState(new_state) => {
+                        states.insert(state_key.clone(), new_state);
+                    }
+                }
+            }
+            continue;
+        }
+
+        for action in actions {
+            match action {
+                Action::Post { channel_id, content } => {
+                    let message = discord
+                        .create_message(channel_id)
+                        .content(&content)
+                        .await
+                        .context("failed to send the announcement message")?
+                        .model()
+                        .await
+                        .context("failed to parse the announcement message")?;
+                    if let Err(error) = discord.crosspost_message(channel_id, message.id).await {
+                        error!(?error, "failed to crosspost the announcement message");
+                    }
+                }
+                Action::SetState(new_state) => {
+                    state::set(state_key.clone(), new_state, db)
+                        .await
+                        .context("failed to update the announcer state")?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The time between polls after `failures` failed polls in a row. Doubled for each failure, up to
+/// `MAX_BACKOFF` unless `interval` itself is longer.
+fn poll_delay(interval: Duration, failures: u32) -> Duration {
+    interval.saturating_mul(2u32.saturating_pow(failures)).min(MAX_BACKOFF.max(interval))
+}
+
+pub async fn run<A: Announcer>(
+    mut running: Receiver<bool>,
+    mut announcer: A,
+    db: DatabaseConnection,
+    discord: &DiscordClient,
+    interval: Duration,
+) {
+    let mut dry_run_states = None;
+    let mut failures = 0;
+    let mut next_poll = Instant::now();
+
+    loop {
+        tokio::select! {
+            _ = running.changed() => break,
+            _ = tokio::time::sleep_until(next_poll) => {
+                if let Err(error) = announcer.refresh().await {
+                    error!(?error, "Failed to refresh the announcer");
+                }
+                if announcer.dry_run() != dry_run_states.is_some() {
+                    dry_run_states = announcer.dry_run().then(HashMap::new);
+                }
+                match poll(&announcer, &db, discord, dry_run_states.as_mut()).await {
+                    Ok(()) => failures = 0,
+                    Err(error) => {
+                        failures += 1;
+                        error!(?error, failures, "Failed to announce new posts");
+                    }
+                }
+                next_poll = Instant::now() + poll_delay(interval, failures);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use anyhow::Error;
+    use chrono::{DateTime, TimeZone, Utc};
+    use twilight_model::id::marker::ChannelMarker;
+    use twilight_model::id::Id;
+
+    use super::{plan, poll_delay, Action, Announcer, MAX_BACKOFF};
+
+    struct Post {
+        id: u32,
+        is_reply: bool,
+    }
+
+    struct FakeAnnouncer;
+
+    impl Announcer for FakeAnnouncer {
+        type Source = ();
+        type Item = Post;
+        type State = u32;
+
+        fn sources(&self) -> Vec<(Self::Source, Vec<Id<ChannelMarker>>)> {
+            vec![]
+        }
+
+        fn state_key(&self, _: &Self::Source) -> String {
+            String::from("fake")
+        }
+
+        async fn fetch(
+            &self,
+            _: &Self::Source,
+            _: Option<&Self::State>,
+        ) -> Result<Vec<Self::Item>, Error> {
+            Ok(vec![])
+        }
+
+        fn sort_key(&self, item: &Self::Item) -> DateTime<Utc> {
+            Utc.timestamp_opt(i64::from(item.id), 0).unwrap()
+        }
+
+        fn state_after(&self, item: &Self::Item) -> Self::State {
+            item.id
+        }
+
+        fn initial_state(&self, items: &[Self::Item]) -> Self::State {
+            items.last().map_or(0, |item| item.id)
+        }
+
+        fn should_announce(&self, item: &Self::Item) -> bool {
+            !item.is_reply
+        }
+
+        fn should_announce_to(&self, item: &Self::Item, channel_id: Id<ChannelMarker>) -> bool {
+            item.id != 3 || channel_id != Id::new(2)
+        }
+
+        fn format(&self, item: &Self::Item) -> String {
+            format!("post {}", item.id)
+        }
+    }
+
+    fn posts() -> Vec<Post> {
+        vec![
+            Post { id: 3, is_reply: false },
+            Post { id: 1, is_reply: false },
+            Post { id: 2, is_reply: true },
+        ]
+    }
+
+    #[test]
+    fn first_run() {
+        assert_eq!(plan(&FakeAnnouncer, None, posts(), &[Id::new(1)]), vec![Action::SetState(3)]);
+        assert_eq!(plan(&FakeAnnouncer, None, vec![], &[Id::new(1)]), vec![Action::SetState(0)]);
+    }
+
+    #[test]
+    fn announces_in_order() {
+        assert_eq!(
+            plan(&FakeAnnouncer, Some(&0), posts(), &[Id::new(1), Id::new(2)]),
+            vec![
+                Action::Post { channel_id: Id::new(1), content: String::from("post 1") },
+                Action::Post { channel_id: Id::new(2), content: String::from("post 1") },
+                Action::SetState(1),
+                Action::SetState(2),
+                Action::Post { channel_id: Id::new(1), content: String::from("post 3") },
+                Action::SetState(3),
+            ]
+        );
+    }
+
+    #[test]
+    fn backoff() {
+        let interval = Duration::from_secs(10);
+        assert_eq!(poll_delay(interval, 0), interval);
+        assert_eq!(poll_delay(interval, 1), Duration::from_secs(20));
+        assert_eq!(poll_delay(interval, 3), Duration::from_secs(80));
+        assert_eq!(poll_delay(interval, 10), MAX_BACKOFF);
+        assert_eq!(poll_delay(interval, u32::MAX), MAX_BACKOFF);
+
+        // An interval that's already longer than the cap isn't shortened.
+        let interval = Duration::from_secs(3600);
+        assert_eq!(poll_delay(interval, 0), interval);
+        assert_eq!(poll_delay(interval, 2), interval);
+    }
+}
@@ -3,6 +3,7 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use anyhow::{Context, Error};
+use chrono::{DateTime, Utc};
 use reqwest::Client as HttpClient;
 use sea_orm::DatabaseConnection;
 use tokio::sync::watch::Receiver;
@@ -12,8 +13,8 @@ use twilight_model::id::marker::ChannelMarker;
 use twilight_model::id::Id;
 use url::Url;
 
+use crate::announcements::announcer::Announcer;
 use crate::config::Config;
-use crate::models::state;
 
 mod mastodon_api {
     use chrono::{DateTime, FixedOffset};
@@ -53,27 +54,66 @@ mod mastodon_api {
     }
 }
 
-struct TootAnnouncer {
+pub struct TootAnnouncer {
     config: Arc<Config>,
-    db: DatabaseConnection,
-    discord: Arc<DiscordClient>,
+    config_updates: Option<Receiver<Arc<Config>>>,
     http_client: HttpClient,
 
     users: HashMap<String, Vec<Id<ChannelMarker>>>,
+    usernames: HashMap<String, String>,
 }
 
 impl TootAnnouncer {
-    async fn new(
-        config: Arc<Config>,
-        db: DatabaseConnection,
-        discord: Arc<DiscordClient>,
-        http_client: HttpClient,
-    ) -> Result<Self, Error> {
-        let mut this = Self { config, db, discord, http_client, users: HashMap::new() };
+    pub async fn new(config: Arc<Config>, http_client: HttpClient) -> Result<Self, Error> {
+        let mut this = Self {
+            config,
+            config_updates: None,
+            http_client,
+            users: HashMap::new(),
+            usernames: HashMap::new(),
+        };
         this.populate_users().await?;
         Ok(this)
     }
 
+    /// Pick up the watched accounts from reloaded configs.
+    pub fn watch_config(mut self, config_updates: Receiver<Arc<Config>>) -> Self {
+        self.config_updates = Some(config_updates);
+        self
+    }
+
+    /// Is `username` one of the configured accounts?
+    pub fn is_watching(&self, username: &str) -> bool {
+        self.user_id(username).is_some()
+    }
+
+    /// The most recent toot by `username` that would have been announced.
+    ///
+    /// Returns `None` if `username` isn't a watched account or hasn't tooted.
+    pub async fn latest_toot(&self, username: &str) -> Result<Option<String>, Error> {
+        let Some(user_id) = self.user_id(username) else {
+            return Ok(None);
+        };
+
+        let toots = self.fetch(user_id, None).await?;
+
+        Ok(self.latest(toots).map(|toot| self.format(&toot)))
+    }
+
+    fn user_id(&self, username: &str) -> Option<&String> {
+        self.usernames
+            .iter()
+            .find(|(name, _)| caseless::canonical_caseless_match_str(name, username))
+            .map(|(_, user_id)| user_id)
+    }
+
+    fn latest(&self, toots: Vec<self::mastodon_api::Status>) -> Option<self::mastodon_api::Status> {
+        toots
+            .into_iter()
+            .filter(|toot| self.should_announce(toot))
+            .max_by_key(|toot| self.sort_key(toot))
+    }
+
     fn url(&self, path: &str) -> Result<Url, Error> {
         self.config.mastodon_server.join(path).with_context(|| {
             format!("failed to join {path:?} to {:?}", self.config.mastodon_server)
@@ -83,163 +123,288 @@ impl TootAnnouncer {
     async fn populate_users(&mut self) -> Result<(), Error> {
         let search_url = self.url("api/v2/search").context("failed to construct the search URL")?;
 
+        let mut users = HashMap::new();
+        let mut usernames = HashMap::new();
         for (username, channels) in &self.config.mastodon_users {
-            let res = self
-                .http_client
-                .get(search_url.clone())
-                .query(&[("q", username.as_str()), ("type", "accounts")])
-                .send()
-                .await
-                .with_context(|| format!("failed to send a search request for {username:?}"))?
-                .error_for_status()
-                .with_context(|| format!("failed to search for {username:?}"))?
-                .json::<self::mastodon_api::SearchResponse>()
-                .await
-                .with_context(|| format!("failed to parse the search response for {username:?}"))?;
-
-            let account = res
-                .accounts
-                .iter()
-                .find(|account| caseless::canonical_caseless_match_str(&account.acct, username))
-                .ok_or_else(|| {
-                    let accounts = res
-                        .accounts
-                        .iter()
-                        .map(|account| account.acct.as_str())
-                        .collect::<Vec<_>>();
-                    Error::msg(format!(
-                        "failed to find {username:?}: server returned: {accounts:?}"
-                    ))
-                })?;
-
-            self.users.insert(account.id.clone(), channels.clone());
+            // Accounts that were already looked up on the same server don't need another search.
+            let user_id = match self.usernames.get(username) {
+                Some(user_id) => user_id.clone(),
+                None => self.search(&search_url, username).await?,
+            };
+
+            users.insert(user_id.clone(), channels.clone());
+            usernames.insert(username.clone(), user_id);
         }
 
+        self.users = users;
+        self.usernames = usernames;
+
         Ok(())
     }
 
-    async fn post_toots(&self) -> Result<(), Error> {
-        for (user_id, channels) in &self.users {
-            let state_key = format!("eris.announcements.mastodon.{user_id}.last_toot_id");
-            let last_toot_id = state::get::<String>(&state_key, &self.db)
-                .await
-                .context("failed to get the last toot ID")?;
-
-            let mut toots = self
-                .http_client
-                .get(
-                    self.url(&format!("api/v1/accounts/{user_id}/statuses"))
-                        .context("failed to construct the toots URL")?,
-                )
-                .query(&[("min_id", last_toot_id.as_deref())])
-                .send()
-                .await
-                .with_context(|| format!("failed to request new toots from {user_id}"))?
-                .error_for_status()
-                .with_context(|| format!("failed to get new toots from {user_id}"))?
-                .json::<Vec<self::mastodon_api::Status>>()
-                .await
-                .with_context(|| format!("failed to parse the new toots from {user_id}"))?;
-
-            toots.sort_by_key(|toot| toot.created_at);
-
-            // Don't send an avalanche of toots when first activated.
-            if last_toot_id.is_some() {
-                for toot in &toots {
-                    // Non-reply toot or a reply to an account we're watching
-                    if toot
-                        .in_reply_to_account_id
-                        .as_deref()
-                        .map_or(true, |user_id| self.users.contains_key(user_id))
-                    {
-                        let message = if let Some(ref boosted_toot) = toot.reblog {
-                            format!(
-                                "{} boosted a toot: {}",
-                                toot.account.display_name,
-                                boosted_toot.url.as_ref().unwrap_or(&toot.uri)
-                            )
-                        } else {
-                            format!(
-                                "New toot from {}: {}",
-                                toot.account.display_name,
-                                toot.url.as_ref().unwrap_or(&toot.uri)
-                            )
-                        };
-
-                        for channel in channels.iter().copied() {
-                            if let Some(boosted_user_id) =
-                                toot.reblog.as_deref().map(|toot| toot.account.id.as_str())
-                            {
-                                if let Some(channels) = self.users.get(boosted_user_id) {
-                                    if channels.contains(&channel) {
-                                        info!(
-                                            ?channel,
-                                            msg = message.as_str(),
-                                            "Skipping posting a boost because the target already gets posted to this channel"
-                                        );
-                                        continue;
-                                    }
-                                }
-                            }
-
-                            let message = self
-                                .discord
-                                .create_message(channel)
-                                .content(&message)
-                                .await
-                                .context("failed to send the announcement message")?
-                                .model()
-                                .await
-                                .context("failed to parse the announcement message")?;
-                            if let Err(error) =
-                                self.discord.crosspost_message(channel, message.id).await
-                            {
-                                error!(?error, "failed to crosspost the announcement message");
-                            }
-                        }
-                    }
-
-                    state::set(state_key.clone(), &toot.id, &self.db)
-                        .await
-                        .context("failed to set the new last toot ID")?;
+    async fn search(&self, search_url: &Url, username: &str) -> Result<String, Error> {
+        let res = self
+            .http_client
+            .get(search_url.clone())
+            .query(&[("q", username), ("type", "accounts")])
+            .send()
+            .await
+            .with_context(|| format!("failed to send a search request for {username:?}"))?
+            .error_for_status()
+            .with_context(|| format!("failed to search for {username:?}"))?
+            .json::<self::mastodon_api::SearchResponse>()
+            .await
+            .with_context(|| format!("failed to parse the search response for {username:?}"))?;
+
+        let account = res
+            .accounts
+            .iter()
+            .find(|account| caseless::canonical_caseless_match_str(&account.acct, username))
+            .ok_or_else(|| {
+                let accounts =
+                    res.accounts.iter().map(|account| account.acct.as_str()).collect::<Vec<_>>();
+                Error::msg(format!("failed to find {username:?}: server returned: {accounts:?}"))
+            })?;
+
+        Ok(account.id.clone())
+    }
+}
+
+impl Announcer for TootAnnouncer {
+    type Source = String;
+    type Item = self::mastodon_api::Status;
+    type State = String;
+
+    fn sources(&self) -> Vec<(Self::Source, Vec<Id<ChannelMarker>>)> {
+        self.users.iter().map(|(user_id, channels)| (user_id.clone(), channels.clone())).collect()
+    }
+
+    fn state_key(&self, user_id: &Self::Source) -> String {
+        format!("eris.announcements.mastodon.{user_id}.last_toot_id")
+    }
+
+    async fn fetch(
+        &self,
+        user_id: &Self::Source,
+        last_toot_id: Option<&Self::State>,
+    ) -> Result<Vec<Self::Item>, Error> {
+        self.http_client
+            .get(
+                self.url(&format!("api/v1/accounts/{user_id}/statuses"))
+                    .context("failed to construct the toots URL")?,
+            )
+            .query(&[("min_id", last_toot_id)])
+            .send()
+            .await
+            .with_context(|| format!("failed to request new toots from {user_id}"))?
+            .error_for_status()
+            .with_context(|| format!("failed to get new toots from {user_id}"))?
+            .json::<Vec<self::mastodon_api::Status>>()
+            .await
+            .with_context(|| format!("failed to parse the new toots from {user_id}"))
+    }
+
+    fn sort_key(&self, toot: &Self::Item) -> DateTime<Utc> {
+        toot.created_at.with_timezone(&Utc)
+    }
+
+    fn state_after(&self, toot: &Self::Item) -> Self::State {
+        toot.id.clone()
+    }
+
+    fn initial_state(&self, toots: &[Self::Item]) -> Self::State {
+        toots.last().map_or("0", |toot| toot.id.as_str()).into()
+    }
+
+    fn should_announce(&self, toot: &Self::Item) -> bool {
+        // Non-reply toot or a reply to an account we're watching
+        toot.in_reply_to_account_id
+            .as_deref()
+            .map_or(true, |user_id| self.users.contains_key(user_id))
+    }
+
+    fn should_announce_to(&self, toot: &Self::Item, channel: Id<ChannelMarker>) -> bool {
+        if let Some(boosted_user_id) = toot.reblog.as_deref().map(|toot| toot.account.id.as_str()) {
+            if let Some(channels) = self.users.get(boosted_user_id) {
+                if channels.contains(&channel) {
+                    info!(
+                        ?channel,
+                        toot.id,
+                        "Skipping posting a boost because the target already gets posted to this channel"
+                    );
+                    return false;
                 }
-            } else {
-                let last_toot_id = toots.last().map_or("0", |toot| toot.id.as_str());
-                state::set(state_key, last_toot_id, &self.db)
-                    .await
-                    .context("failed to set the new last toot ID")?;
             }
         }
 
+        true
+    }
+
+    fn dry_run(&self) -> bool {
+        self.config.dry_run
+    }
+
+    async fn refresh(&mut self) -> Result<(), Error> {
+        let Some(ref config_updates) = self.config_updates else { return Ok(()) };
+        if !config_updates.has_changed().unwrap_or(false) {
+            return Ok(());
+        }
+        let config = config_updates.borrow().clone();
+
+        if config.mastodon_server != self.config.mastodon_server {
+            self.usernames.clear();
+        }
+        self.config = config;
+        self.populate_users().await.context("failed to update the watched accounts")?;
+
+        // Only now is the config up to date, so a failed lookup is retried on the next refresh.
+        if let Some(ref mut config_updates) = self.config_updates {
+            if Arc::ptr_eq(&config_updates.borrow(), &self.config) {
+                config_updates.mark_unchanged();
+            }
+        }
         Ok(())
     }
+
+    fn format(&self, toot: &Self::Item) -> String {
+        if let Some(ref boosted_toot) = toot.reblog {
+            format!(
+                "{} boosted a toot: {}",
+                toot.account.display_name,
+                boosted_toot.url.as_ref().unwrap_or(&toot.uri)
+            )
+        } else {
+            format!(
+                "New toot from {}: {}",
+                toot.account.display_name,
+                toot.url.as_ref().unwrap_or(&toot.uri)
+            )
+        }
+    }
 }
 
 pub async fn post_toots(
-    mut running: Receiver<bool>,
-    config: Arc<Config>,
+    running: Receiver<bool>,
+    config_updates: Receiver<Arc<Config>>,
     db: DatabaseConnection,
     discord: Arc<DiscordClient>,
     http_client: HttpClient,
 ) {
-    let annoucer = match TootAnnouncer::new(config, db, discord, http_client).await {
-        Ok(res) => res,
+    let config = config_updates.borrow().clone();
+    let annoucer = match TootAnnouncer::new(config, http_client).await {
+        Ok(res) => res.watch_config(config_updates),
         Err(error) => {
             error!(?error, "failed to initialize the toot announcer");
             return;
         }
     };
 
-    let mut timer = tokio::time::interval(Duration::from_secs(10));
+    crate::announcements::announcer::run(running, annoucer, db, &discord, Duration::from_secs(10))
+        .await;
+}
 
-    loop {
-        tokio::select! {
-            _ = running.changed() => break,
-            _ = timer.tick() => {
-                if let Err(error) = annoucer.post_toots().await {
-                    error!(?error, "Failed to announce new toots");
-                }
-            }
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use twilight_model::id::Id;
+
+    use super::TootAnnouncer;
+    use crate::announcements::announcer::Announcer;
+    use crate::config::Config;
+    use crate::testing::{json, MockServer};
+
+    const STATUSES: &str = r#"[
+        {
+            "id": "3",
+            "url": "https://example.com/@other/3",
+            "uri": "https://example.com/users/other/statuses/3",
+            "account": { "id": "2", "acct": "other", "display_name": "Other" },
+            "reblog": null,
+            "in_reply_to_account_id": "3",
+            "created_at": "2024-01-03T00:00:00Z"
+        },
+        {
+            "id": "2",
+            "url": null,
+            "uri": "https://example.com/users/lrr/statuses/2",
+            "account": { "id": "1", "acct": "lrr", "display_name": "LoadingReadyRun" },
+            "reblog": {
+                "id": "1",
+                "url": "https://example.com/@other/1",
+                "uri": "https://example.com/users/other/statuses/1",
+                "account": { "id": "2", "acct": "other", "display_name": "Other" },
+                "reblog": null,
+                "in_reply_to_account_id": null,
+                "created_at": "2024-01-01T00:00:00Z"
+            },
+            "in_reply_to_account_id": null,
+            "created_at": "2024-01-02T00:00:00Z"
+        }
+    ]"#;
+
+    #[tokio::test]
+    async fn latest_toot() {
+        let server = MockServer::bind().await.serve(|_| json(STATUSES));
+        let config = Config::for_tests(&format!("mastodon_server={server}\n"));
+        let announcer = TootAnnouncer {
+            config: Arc::new(config),
+            config_updates: None,
+            http_client: reqwest::Client::new(),
+            users: HashMap::from([(String::from("1"), vec![Id::new(1)])]),
+            usernames: HashMap::from([(String::from("lrr"), String::from("1"))]),
+        };
+
+        assert!(announcer.is_watching("LRR"));
+        assert!(!announcer.is_watching("other"));
+        assert_eq!(announcer.latest_toot("other").await.unwrap(), None);
+        assert_eq!(
+            announcer.latest_toot("LRR").await.unwrap().as_deref(),
+            Some("LoadingReadyRun boosted a toot: https://example.com/@other/1"),
+        );
+    }
+
+    #[tokio::test]
+    async fn reloaded_config() {
+        const SEARCH: &str = r#"{
+            "accounts": [{ "id": "4", "acct": "new", "display_name": "New" }]
+        }"#;
+
+        // Only the new account is looked up, so a single response is enough.
+        let server = MockServer::bind().await.serve(|_| json(SEARCH));
+        let config = |users: &str| {
+            Arc::new(Config::for_tests(&format!(
+                "mastodon_server={server}\n[eris.mastodon]\n{users}"
+            )))
+        };
+
+        let (config_tx, config_rx) = tokio::sync::watch::channel(config("lrr=1\n"));
+        let initial_config = config_rx.borrow().clone();
+        let mut announcer = TootAnnouncer {
+            config: initial_config,
+            config_updates: None,
+            http_client: reqwest::Client::new(),
+            users: HashMap::from([(String::from("1"), vec![Id::new(1)])]),
+            usernames: HashMap::from([(String::from("lrr"), String::from("1"))]),
         }
+        .watch_config(config_rx);
+
+        announcer.refresh().await.unwrap();
+        assert!(!announcer.is_watching("new"));
+
+        config_tx.send_replace(config("lrr=1\nnew=2,3\n"));
+        announcer.refresh().await.unwrap();
+
+        assert!(announcer.is_watching("new"));
+        let mut sources = announcer.sources();
+        sources.sort();
+        assert_eq!(
+            sources,
+            vec![
+                (String::from("1"), vec![Id::new(1)]),
+                (String::from("4"), vec![Id::new(2), Id::new(3)]),
+            ]
+        );
     }
 }
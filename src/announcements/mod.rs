@@ -1,7 +1,8 @@
+pub mod announcer;
 pub mod mastodon;
 pub mod stream_up;
 pub mod youtube;
 
-pub use self::mastodon::post_toots;
+pub use self::mastodon::{post_toots, TootAnnouncer};
 pub use self::stream_up::stream_up;
 pub use self::youtube::post_videos;
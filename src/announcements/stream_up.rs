@@ -3,7 +3,7 @@ use std::sync::Arc;
 use anyhow::{Context as _, Error};
 use sea_orm::{DatabaseConnection, EntityTrait};
 use tokio::sync::RwLock;
-use tracing::error;
+use tracing::{error, info};
 use twilight_http::Client as DiscordClient;
 use twilight_model::channel::message::MessageFlags;
 use twitch_api::twitch_oauth2::AppAccessToken;
@@ -22,8 +22,8 @@ async fn stream_up_inner(
     helix_token: &RwLock<AppAccessToken>,
     lrrbot: &LRRbot,
 ) -> Result<(), Error> {
-    let game_id = lrrbot.get_game_id().await.context("failed to get the game ID")?;
-    let show_id = lrrbot.get_show_id().await.context("failed to get the show ID")?;
+    let (game_id, show_id) =
+        lrrbot.get_game_and_show_id().await.context("failed to get the game and show IDs")?;
 
     let (game, show, game_entry) = {
         let (game, game_entry) = if let Some(game_id) = game_id {
@@ -58,9 +58,7 @@ async fn stream_up_inner(
     {
         let game = game.as_ref();
         let game_entry = game_entry.as_ref();
-        let game_display_name = game.map(|game| {
-            game_entry.and_then(|entry| entry.display_name.as_deref()).unwrap_or(&game.name)
-        });
+        let game_display_name = game.map(|game| game.effective_name(game_entry));
 
         match (game_display_name, show.as_ref()) {
             (Some(game), Some(show)) => {
@@ -82,6 +80,14 @@ async fn stream_up_inner(
     message.push_str(&channel.broadcaster_login.as_str());
     message.push('>');
 
+    if config.dry_run {
+        info!(
+            channel.id = config.announcements.get(),
+            message, "Dry run: not announcing the stream"
+        );
+        return Ok(());
+    }
+
     let message = discord
         .create_message(config.announcements)
         .flags(MessageFlags::SUPPRESS_EMBEDS)
@@ -8,6 +8,9 @@ use chrono::{DateTime, Utc};
 use google_youtube3::hyper_rustls::HttpsConnector;
 use google_youtube3::hyper_util::client::legacy::connect::HttpConnector;
 use google_youtube3::YouTube;
+use http_body_util::{BodyExt, Empty};
+use hyper::header::LOCATION;
+use hyper::{Request, StatusCode};
 use regex::Regex;
 use sea_orm::DatabaseConnection;
 use tokio::sync::watch::Receiver;
@@ -15,52 +18,86 @@ use tracing::{error, info, warn};
 use twilight_http::Client as DiscordClient;
 use twilight_model::channel::forum::ForumTag;
 use twilight_model::channel::{Channel, ChannelType, Message};
-use twilight_model::id::marker::{ChannelMarker, GuildMarker, TagMarker};
+use twilight_model::id::marker::{ChannelMarker, TagMarker};
 use twilight_model::id::Id;
 use twilight_validate::channel::CHANNEL_NAME_LENGTH_MAX;
 
 use crate::cache::Cache;
-use crate::config::Config;
+use crate::config::{Config, ShortsDetection};
 use crate::models::state;
 
 const MAX_RESULTS: u32 = 10;
 const MAX_STATE_ENTRIES: u32 = MAX_RESULTS * 2;
 const MAX_THREADS_TO_CHECK: usize = MAX_STATE_ENTRIES as usize * 2;
+const SHORTS_URL: &str = "https://www.youtube.com/shorts/";
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+/// The most videos that are announced from a single playlist at once. Also the most video IDs a
+/// single `videos.list` request accepts.
+pub const MAX_PLAYLIST_VIDEOS: usize = 50;
+
+/// The IDs of the first `limit` videos in a playlist and whether the playlist has more videos.
+pub async fn playlist_video_ids(
+    youtube: &YouTube<HttpsConnector<HttpConnector>>,
+    playlist_id: &str,
+    limit: usize,
+) -> Result<(Vec<String>, bool), Error> {
+    let mut video_ids = vec![];
+    let mut page_token: Option<String> = None;
+
+    loop {
+        let mut req = youtube
+            .playlist_items()
+            .list(&vec!["contentDetails".into()])
+            .playlist_id(playlist_id)
+            .max_results(MAX_PLAYLIST_VIDEOS as u32);
+        if let Some(ref page_token) = page_token {
+            req = req.page_token(page_token);
+        }
+        let (_, page) = req.doit().await.context("failed to list the playlist items")?;
+
+        video_ids.extend(
+            page.items
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|item| item.content_details?.video_id),
+        );
+
+        if video_ids.len() > limit {
+            video_ids.truncate(limit);
+            return Ok((video_ids, true));
+        }
+        match page.next_page_token {
+            Some(_) if video_ids.len() == limit => return Ok((video_ids, true)),
+            Some(next_page_token) => page_token = Some(next_page_token),
+            None => return Ok((video_ids, false)),
+        }
+    }
+}
 
 pub async fn post_videos(
     mut running: Receiver<bool>,
+    mut config_updates: Receiver<Arc<Config>>,
     db: DatabaseConnection,
     cache: Arc<Cache>,
-    config: Arc<Config>,
     discord: Arc<DiscordClient>,
     youtube: YouTube<HttpsConnector<HttpConnector>>,
 ) {
-    let Some(channel_id) = config.lrr_videos_channel else {
-        info!("video discussion forum is not set");
-        return;
-    };
-
-    if config.youtube_channels.is_empty() {
-        info!("Youtube channels are not set");
-        return;
-    }
-
-    let mut poster = match VideoPoster::new(db, cache, channel_id, &config, discord, youtube).await
-    {
-        Ok(poster) => poster,
-        Err(error) => {
-            error!(?error, "failed to construct the video poster");
-            return;
-        }
-    };
+    let config = config_updates.borrow_and_update().clone();
+    let mut poster = VideoPoster::from_config(&db, &cache, &config, &discord, &youtube).await;
     let mut interval = tokio::time::interval(Duration::from_secs(300));
 
     loop {
         tokio::select! {
             _ = running.changed() => break,
+            Ok(()) = config_updates.changed() => {
+                let config = config_updates.borrow_and_update().clone();
+                poster = VideoPoster::from_config(&db, &cache, &config, &discord, &youtube).await;
+            },
             _ = interval.tick() => {
-                if let Err(error) = poster.run().await {
-                    error!(?error, "failed to post videos");
+                if let Some(ref mut poster) = poster {
+                    if let Err(error) = poster.run().await {
+                        error!(?error, "failed to post videos");
+                    }
                 }
             },
         }
@@ -74,9 +111,137 @@ struct VideoPoster {
     playlists: Vec<(String, String)>,
     discord: Arc<DiscordClient>,
     youtube: YouTube<HttpsConnector<HttpConnector>>,
+    /// Only log the videos instead of announcing them, and only remember the logged videos here
+    /// instead of recording them as announced.
+    dry_run: Option<HashSet<String>>,
+    digest_threshold: Option<usize>,
+    shorts_detection: ShortsDetection,
+}
+
+/// How a batch of new videos gets announced.
+enum Announcement {
+    /// A thread for every video.
+    Individual(Vec<Video>),
+    /// A single thread listing all of them, so that a bulk upload doesn't flood the channel.
+    Digest(Vec<Video>),
+}
+
+impl Announcement {
+    fn plan(videos: Vec<Video>, digest_threshold: Option<usize>) -> Self {
+        match digest_threshold {
+            Some(threshold) if videos.len() > threshold => Announcement::Digest(videos),
+            _ => Announcement::Individual(videos),
+        }
+    }
+}
+
+/// The digest message: a line per video, for as many as fit.
+fn digest_content(videos: &[Video]) -> String {
+    // Room for the line about the videos that didn't fit.
+    const RESERVED: usize = 32;
+
+    let mut content = String::from("New videos:\n");
+    for (i, video) in videos.iter().enumerate() {
+        let line = format!(
+            "- {}: <https://youtu.be/{}>\n",
+            crate::markdown::escape(&video.title),
+            video.id
+        );
+        if content.chars().count() + line.chars().count() + RESERVED
+            > twilight_validate::message::MESSAGE_CONTENT_LENGTH_MAX
+        {
+            let _ = write!(content, "…and {} more.", videos.len() - i);
+            break;
+        }
+        content.push_str(&line);
+    }
+    content.truncate(content.trim_end().len());
+    content
+}
+
+/// Start a thread in `channel_id`: a post if it's a forum, otherwise a thread on a new message.
+async fn post_thread(
+    discord: &DiscordClient,
+    channel_id: Id<ChannelMarker>,
+    channel_type: ChannelType,
+    name: &str,
+    content: &str,
+    tags: &[Id<TagMarker>],
+) -> Result<Channel, Error> {
+    let name = crate::shorten::shorten(name, CHANNEL_NAME_LENGTH_MAX);
+
+    if channel_type == ChannelType::GuildForum {
+        let thread = discord
+            .create_forum_thread(channel_id, &name)
+            .applied_tags(tags)
+            .message()
+            .content(content)
+            .await
+            .context("failed to create the video thread")?
+            .model()
+            .await
+            .context("failed to deserialize the thread")?
+            .channel;
+
+        Ok(thread)
+    } else {
+        let message = discord
+            .create_message(channel_id)
+            .content(content)
+            .await
+            .context("failed to send video announcement")?
+            .model()
+            .await
+            .context("failed to deserialize the message")?;
+
+        let thread = discord
+            .create_thread_from_message(channel_id, message.id, &name)
+            .await
+            .context("failed to create the thread")?
+            .model()
+            .await
+            .context("failed to deserialize the thread")?;
+
+        Ok(thread)
+    }
 }
 
 impl VideoPoster {
+    async fn from_config(
+        db: &DatabaseConnection,
+        cache: &Arc<Cache>,
+        config: &Config,
+        discord: &Arc<DiscordClient>,
+        youtube: &YouTube<HttpsConnector<HttpConnector>>,
+    ) -> Option<Self> {
+        let Some(channel_id) = config.lrr_videos_channel else {
+            info!("video discussion forum is not set");
+            return None;
+        };
+
+        if config.youtube_channels.is_empty() {
+            info!("Youtube channels are not set");
+            return None;
+        }
+
+        let poster = VideoPoster::new(
+            db.clone(),
+            cache.clone(),
+            channel_id,
+            config,
+            discord.clone(),
+            youtube.clone(),
+        )
+        .await;
+        match poster {
+            Ok(poster) => Some(poster),
+            Err(error) => {
+                error!(?error, "failed to construct the video poster");
+                None
+            }
+        }
+    }
+
     async fn new(
         db: DatabaseConnection,
         cache: Arc<Cache>,
@@ -104,24 +269,49 @@ impl VideoPoster {
             ));
         }
 
-        Ok(Self { db, cache, channel_id, playlists, discord, youtube })
+        Ok(Self {
+            db,
+            cache,
+            channel_id,
+            playlists,
+            discord,
+            youtube,
+            dry_run: config.dry_run.then(HashSet::new),
+            digest_threshold: config.youtube_digest_threshold,
+            shorts_detection: config.youtube_shorts_detection,
+        })
     }
 
     fn state_key(&self, channel_id: &str) -> String {
         format!("eris.announcements.youtube.{channel_id}.announced_videos")
     }
 
+    async fn mark_announced(&mut self, video: &Video) -> Result<(), Error> {
+        if let Some(ref mut logged) = self.dry_run {
+            logged.insert(video.id.clone());
+            return Ok(());
+        }
+
+        state::insert_fifo_cache(
+            self.state_key(&video.channel_id),
+            &video.id,
+            MAX_STATE_ENTRIES,
+            &self.db,
+        )
+        .await
+        .context("failed to append video ID to state")
+    }
+
     async fn run(&mut self) -> Result<(), Error> {
         self.cache.wait_until_ready().await;
 
-        let (channel_type, guild_id, available_tags) = self
+        let (channel_type, available_tags) = self
             .cache
             .with(|cache| {
                 let channel = cache.channel(self.channel_id)?;
-                Some((channel.kind, channel.guild_id, channel.available_tags.clone()))
+                Some((channel.kind, channel.available_tags.clone()))
             })
             .context("video announcements channel not in cache")?;
-        let guild_id = guild_id.context("video announcements channel not in a guild")?;
 
         let mut video_ids = vec![];
 
@@ -152,7 +342,12 @@ impl VideoPoster {
                             items
                                 .into_iter()
                                 .filter_map(|item| item.content_details.and_then(|cd| cd.video_id))
-                                .filter(|video_id| !announced.contains(video_id)),
+                                .filter(|video_id| !announced.contains(video_id))
+                                .filter(|video_id| {
+                                    self.dry_run
+                                        .as_ref()
+                                        .map_or(true, |logged| !logged.contains(video_id))
+                                }),
                         );
                     }
                 }
@@ -160,14 +355,16 @@ impl VideoPoster {
             }
         }
 
-        let mut videos =
-            Video::fetch(&self.youtube, &video_ids).await.context("failed to fetch videos")?;
+        let mut videos = Video::fetch(&self.youtube, &video_ids, self.shorts_detection)
+            .await
+            .context("failed to fetch videos")?;
 
         videos.sort_by(|a, b| a.published_at.cmp(&b.published_at));
 
+        let mut new_videos = vec![];
         for video in videos {
             let is_announced =
-                video.is_already_announced(self.channel_id, guild_id, &self.cache, &self.discord).await
+                video.is_already_announced(self.channel_id, &self.cache, &self.discord).await
                     .unwrap_or_else(|error| {
                         error!(
                             ?error,
@@ -179,25 +376,65 @@ impl VideoPoster {
                     });
 
             if !is_announced && video.should_announce() {
-                video
-                    .announce(
-                        self.channel_id,
-                        channel_type,
-                        available_tags.as_deref(),
-                        &self.discord,
-                    )
-                    .await
-                    .context("failed to announce video")?;
+                new_videos.push(video);
+            } else {
+                self.mark_announced(&video).await?;
             }
+        }
 
-            state::insert_fifo_cache(
-                self.state_key(&video.channel_id),
-                &video.id,
-                MAX_STATE_ENTRIES,
-                &self.db,
-            )
-            .await
-            .context("failed to append video ID to state")?;
+        match Announcement::plan(new_videos, self.digest_threshold) {
+            Announcement::Individual(videos) => {
+                for video in videos {
+                    if self.dry_run.is_some() {
+                        info!(video.id, video.title, "Dry run: not announcing the video");
+                        self.mark_announced(&video).await?;
+                        continue;
+                    }
+
+                    video
+                        .announce(
+                            self.channel_id,
+                            channel_type,
+                            available_tags.as_deref(),
+                            &self.discord,
+                        )
+                        .await
+                        .context("failed to announce video")?;
+                    self.mark_announced(&video).await?;
+                }
+            }
+            Announcement::Digest(videos) => {
+                if self.dry_run.is_some() {
+                    info!(videos = videos.len(), "Dry run: not announcing the video digest");
+                    for video in &videos {
+                        self.mark_announced(video).await?;
+                    }
+                    return Ok(());
+                }
+
+                let mut tags = available_tags
+                    .as_deref()
+                    .map(|available_tags| {
+                        videos.iter().flat_map(|video| video.tags(available_tags)).collect()
+                    })
+                    .unwrap_or_else(Vec::new);
+                tags.sort();
+                tags.dedup();
+                post_thread(
+                    &self.discord,
+                    self.channel_id,
+                    channel_type,
+                    &format!("{} new videos", videos.len()),
+                    &digest_content(&videos),
+                    &tags,
+                )
+                .await
+                .context("failed to announce the video digest")?;
+
+                for video in &videos {
+                    self.mark_announced(video).await?;
+                }
+            }
         }
 
         Ok(())
@@ -222,26 +459,39 @@ pub struct Video {
 
     // player
     player_size: Option<(i64, i64)>,
+
+    /// Whether `youtube.com/shorts/<ID>` serves the video, if it was checked.
+    shorts_page: Option<bool>,
 }
 
 impl Video {
     pub async fn fetch(
         youtube: &YouTube<HttpsConnector<HttpConnector>>,
         ids: &[impl AsRef<str>],
+        shorts_detection: ShortsDetection,
+    ) -> Result<Vec<Self>, Error> {
+        Self::fetch_with_shorts_url(youtube, ids, shorts_detection, SHORTS_URL).await
+    }
+
+    async fn fetch_with_shorts_url(
+        youtube: &YouTube<HttpsConnector<HttpConnector>>,
+        ids: &[impl AsRef<str>],
+        shorts_detection: ShortsDetection,
+        shorts_url: &str,
     ) -> Result<Vec<Self>, Error> {
         if ids.is_empty() {
             return Ok(vec![]);
         }
 
-        let mut req = youtube
-            .videos()
-            .list(&vec![
-                "snippet".into(),
-                "contentDetails".into(),
-                "liveStreamingDetails".into(),
-                "player".into(),
-            ])
-            .max_height(720); // need to specify something to get the player size
+        let mut parts =
+            vec!["snippet".into(), "contentDetails".into(), "liveStreamingDetails".into()];
+        if shorts_detection == ShortsDetection::Player {
+            parts.push("player".into());
+        }
+        let mut req = youtube.videos().list(&parts);
+        if shorts_detection == ShortsDetection::Player {
+            req = req.max_height(720); // need to specify something to get the player size
+        }
 
         for id in ids {
             req = req.add_id(id.as_ref());
@@ -249,7 +499,28 @@ impl Video {
 
         let (_, list) = req.doit().await.context("failed to fetch video details")?;
 
-        list.items.unwrap_or_default().into_iter().map(Self::try_from).collect()
+        let mut videos = list
+            .items
+            .unwrap_or_default()
+            .into_iter()
+            .map(Self::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if shorts_detection == ShortsDetection::Probe {
+            let probes = videos.iter_mut().filter(|video| video.has_short_duration()).map(
+                |video| async move {
+                    match probe_shorts_page(youtube, shorts_url, &video.id).await {
+                        Ok(shorts_page) => video.shorts_page = Some(shorts_page),
+                        Err(error) => {
+                            warn!(?error, video.id = video.id, "failed to probe the Shorts page")
+                        }
+                    }
+                },
+            );
+            futures_util::future::join_all(probes).await;
+        }
+
+        Ok(videos)
     }
 
     pub fn video_id_from_message(message: &str) -> Option<&str> {
@@ -260,38 +531,37 @@ impl Video {
         Some(re_video_id.captures(message)?.get(1)?.as_str())
     }
 
-    async fn is_already_announced(
+    /// The video ID in a command argument, which is either the ID itself or a link to the video.
+    ///
+    /// Links can be `youtu.be` links or `youtube.com` watch, Shorts, live, or embed links, with or
+    /// without the scheme and extra query parameters. Discord's `<...>` embed suppression is
+    /// ignored.
+    pub fn video_id_from_input(input: &str) -> Option<&str> {
+        static RE_VIDEO_LINK: OnceLock<Regex> = OnceLock::new();
+        let re_video_link = RE_VIDEO_LINK.get_or_init(|| {
+            Regex::new(concat!(
+                r"^(?:https?://)?(?:(?:www|m|music)\.)?",
+                r"(?:youtu\.be/|youtube\.com/(?:watch\?(?:\S*&)?v=|shorts/|live/|embed/))?",
+                r"([A-Za-z0-9_-]{11})(?:[?&#/]\S*)?$",
+            ))
+            .unwrap()
+        });
+
+        let input = input.strip_prefix('<').and_then(|s| s.strip_suffix('>')).unwrap_or(input);
+        Some(re_video_link.captures(input)?.get(1)?.as_str())
+    }
+
+    pub fn published_at(&self) -> DateTime<Utc> {
+        self.published_at
+    }
+
+    pub async fn is_already_announced(
         &self,
         channel_id: Id<ChannelMarker>,
-        guild_id: Id<GuildMarker>,
         cache: &Cache,
         discord: &DiscordClient,
     ) -> Result<bool, Error> {
-        let mut threads = cache
-            .with(|cache| {
-                Some(
-                    cache
-                        .guild_channels(guild_id)?
-                        .iter()
-                        .copied()
-                        .filter_map(|id| {
-                            let channel = cache.channel(id);
-                            if channel.is_none() {
-                                error!(
-                                    channel.id = id.get(),
-                                    "channel referenced by cache but not itself in cache"
-                                );
-                            }
-                            channel
-                        })
-                        .filter(|thread| thread.parent_id == Some(channel_id))
-                        .map(|thread| thread.id)
-                        .collect::<Vec<_>>(),
-                )
-            })
-            .context("guild channels not in cache")?;
-
-        threads.sort_by(|a, b| a.cmp(b).reverse());
+        let mut threads = cache.threads_in(channel_id).context("forum threads not in cache")?;
         threads.truncate(MAX_THREADS_TO_CHECK);
 
         for thread_id in threads {
@@ -329,7 +599,7 @@ impl Video {
         Ok(false)
     }
 
-    fn should_announce(&self) -> bool {
+    pub fn should_announce(&self) -> bool {
         // Don't announce livestreams.
         if self.is_livestream() {
             info!(video.id = self.id, video.title = self.title, "video is a livestream");
@@ -358,13 +628,30 @@ impl Video {
         //  * up to 3 minutes long
         //  * with a square or vertical aspect ratio.
         // The API also doesn't tell you the aspect ratio or the video size so divine it from the embed player size.
+        // Without the player, go by whether YouTube is willing to show it on the Shorts page, and
+        // if that couldn't be checked either, by the `#shorts` hashtag.
 
+        let vertical = match (self.player_size, self.shorts_page) {
+            (Some((width, height)), _) => width <= height,
+            (None, Some(shorts_page)) => shorts_page,
+            (None, None) => self.has_shorts_hashtag(),
+        };
+        self.has_short_duration() && vertical
+    }
+
+    fn has_short_duration(&self) -> bool {
         self.duration.map(|duration| duration <= Duration::from_secs(3 * 60)).unwrap_or(false)
-            && self.player_size.map(|(width, height)| width <= height).unwrap_or(false)
+    }
+
+    fn has_shorts_hashtag(&self) -> bool {
+        static RE_SHORTS: OnceLock<Regex> = OnceLock::new();
+        let re_shorts = RE_SHORTS.get_or_init(|| Regex::new(r"(?i)#shorts\b").unwrap());
+
+        re_shorts.is_match(&self.title) || re_shorts.is_match(&self.description)
     }
 
     fn message_content(&self) -> String {
-        let description = crate::shorten::shorten(
+        let description = crate::shorten::shorten_words(
             self.description.split("Support LRR:").next().unwrap_or("").trim(),
             twilight_validate::message::MESSAGE_CONTENT_LENGTH_MAX / 2,
         );
@@ -406,47 +693,15 @@ impl Video {
         available_tags: Option<&[ForumTag]>,
         discord: &DiscordClient,
     ) -> Result<Channel, Error> {
-        if channel_type == ChannelType::GuildForum {
-            let thread = discord
-                .create_forum_thread(
-                    channel_id,
-                    &crate::shorten::shorten(&self.title, CHANNEL_NAME_LENGTH_MAX),
-                )
-                .applied_tags(&available_tags.map(|tags| self.tags(tags)).unwrap_or_default())
-                .message()
-                .content(&self.message_content())
-                .await
-                .context("failed to create the video thread")?
-                .model()
-                .await
-                .context("failed to deserialize the thread")?
-                .channel;
-
-            Ok(thread)
-        } else {
-            let message = discord
-                .create_message(channel_id)
-                .content(&self.message_content())
-                .await
-                .context("failed to send video announcement")?
-                .model()
-                .await
-                .context("failed to deserialize the message")?;
-
-            let thread = discord
-                .create_thread_from_message(
-                    channel_id,
-                    message.id,
-                    &crate::shorten::shorten(&self.title, CHANNEL_NAME_LENGTH_MAX),
-                )
-                .await
-                .context("failed to create the thread")?
-                .model()
-                .await
-                .context("failed to deserialize the thread")?;
-
-            Ok(thread)
-        }
+        post_thread(
+            discord,
+            channel_id,
+            channel_type,
+            &self.title,
+            &self.message_content(),
+            &available_tags.map(|tags| self.tags(tags)).unwrap_or_default(),
+        )
+        .await
     }
 
     pub async fn edit(
@@ -506,6 +761,301 @@ impl TryFrom<google_youtube3::api::Video> for Video {
             player_size: video
                 .player
                 .and_then(|player| Some((player.embed_width?, player.embed_height?))),
+
+            shorts_page: None,
         })
     }
 }
+
+/// Check whether `youtube.com/shorts/<ID>` serves the video. Regular videos are redirected to the
+/// watch page instead. Any other response, like a redirect to a consent page, is an error since
+/// it says nothing about the video.
+async fn probe_shorts_page(
+    youtube: &YouTube<HttpsConnector<HttpConnector>>,
+    shorts_url: &str,
+    video_id: &str,
+) -> Result<bool, Error> {
+    let req = Request::head(format!("{shorts_url}{video_id}"))
+        .body(Empty::new().map_err(|never| match never {}).boxed())
+        .context("failed to build the request")?;
+    let res = tokio::time::timeout(PROBE_TIMEOUT, youtube.client.request(req))
+        .await
+        .context("request timed out")?
+        .context("request failed")?;
+
+    if res.status() == StatusCode::OK {
+        return Ok(true);
+    }
+
+    let location = res.headers().get(LOCATION).and_then(|location| location.to_str().ok());
+    match location {
+        Some(location)
+            if res.status().is_redirection()
+                && (location.starts_with("/watch")
+                    || location.starts_with("https://www.youtube.com/watch")) =>
+        {
+            Ok(false)
+        }
+        _ => anyhow::bail!("unexpected response {} redirecting to {location:?}", res.status()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::{TimeZone, Utc};
+    use google_youtube3::hyper_rustls::HttpsConnectorBuilder;
+    use google_youtube3::hyper_util::client::legacy::Builder as HyperClientBuilder;
+    use google_youtube3::hyper_util::rt::TokioExecutor;
+    use google_youtube3::YouTube;
+
+    use super::{
+        digest_content, playlist_video_ids, Announcement, HttpConnector, HttpsConnector, Video,
+    };
+    use crate::config::ShortsDetection;
+    use crate::testing::{json, response, MockServer};
+
+    fn page(video_ids: &[&str], next_page_token: Option<&str>) -> String {
+        serde_json::json!({
+            "items": video_ids
+                .iter()
+                .map(|id| serde_json::json!({ "contentDetails": { "videoId": id } }))
+                .collect::<Vec<_>>(),
+            "nextPageToken": next_page_token,
+        })
+        .to_string()
+    }
+
+    /// Serve a playlist split into pages, picking the page by the `pageToken` query parameter.
+    async fn serve_playlist(pages: Vec<String>) -> String {
+        MockServer::bind().await.serve(move |request| {
+            let page = match request.request_line().split("pageToken=").nth(1) {
+                Some(token) => token.split(['&', ' ']).next().unwrap().parse().unwrap(),
+                None => 0,
+            };
+            json(&pages[page])
+        })
+    }
+
+    async fn youtube(base_url: String) -> YouTube<HttpsConnector<HttpConnector>> {
+        let connector = HttpsConnectorBuilder::new()
+            .with_native_roots()
+            .unwrap()
+            .https_or_http()
+            .enable_http1()
+            .build();
+        let client = HyperClientBuilder::new(TokioExecutor::new()).build(connector);
+        let mut youtube = YouTube::new(client, String::from("token"));
+        youtube.base_url(base_url);
+        youtube
+    }
+
+    #[tokio::test]
+    async fn multi_page_playlist() {
+        let pages =
+            vec![page(&["a", "b"], Some("1")), page(&["c", "d"], Some("2")), page(&["e"], None)];
+        let youtube = youtube(serve_playlist(pages).await).await;
+
+        assert_eq!(
+            playlist_video_ids(&youtube, "PL", 10).await.unwrap(),
+            (vec!["a", "b", "c", "d", "e"].into_iter().map(String::from).collect(), false)
+        );
+        assert_eq!(
+            playlist_video_ids(&youtube, "PL", 3).await.unwrap(),
+            (vec!["a", "b", "c"].into_iter().map(String::from).collect(), true)
+        );
+        assert_eq!(
+            playlist_video_ids(&youtube, "PL", 4).await.unwrap(),
+            (vec!["a", "b", "c", "d"].into_iter().map(String::from).collect(), true)
+        );
+        assert_eq!(
+            playlist_video_ids(&youtube, "PL", 5).await.unwrap(),
+            (vec!["a", "b", "c", "d", "e"].into_iter().map(String::from).collect(), false)
+        );
+    }
+
+    #[test]
+    fn video_id_from_input() {
+        for input in [
+            "dQw4w9WgXcQ",
+            "https://youtu.be/dQw4w9WgXcQ",
+            "https://youtu.be/dQw4w9WgXcQ?si=abcdef&t=10",
+            "youtu.be/dQw4w9WgXcQ",
+            "<https://youtu.be/dQw4w9WgXcQ>",
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ&t=10",
+            "https://www.youtube.com/watch?feature=shared&v=dQw4w9WgXcQ",
+            "http://m.youtube.com/watch?v=dQw4w9WgXcQ",
+            "https://music.youtube.com/watch?v=dQw4w9WgXcQ&list=RDdQw4w9WgXcQ",
+            "https://youtube.com/shorts/dQw4w9WgXcQ",
+            "https://www.youtube.com/live/dQw4w9WgXcQ?feature=share",
+            "https://www.youtube.com/embed/dQw4w9WgXcQ",
+        ] {
+            assert_eq!(Video::video_id_from_input(input), Some("dQw4w9WgXcQ"), "{input}");
+        }
+
+        for input in [
+            "dQw4w9WgXc",
+            "dQw4w9WgXcQQ",
+            "dQw4w9WgXc!",
+            "https://example.com/watch?v=dQw4w9WgXcQ",
+            "https://www.youtube.com/watch?list=PL1234567890",
+            "https://www.youtube.com/playlist?list=dQw4w9WgXcQ",
+        ] {
+            assert_eq!(Video::video_id_from_input(input), None, "{input}");
+        }
+    }
+
+    fn video(id: usize) -> Video {
+        Video {
+            channel_title: String::from("LoadingReadyRun"),
+            channel_id: String::from("UCwjN2uVdL9A0i3gaIHKFzuA"),
+            id: format!("video{id:06}"),
+            title: format!("Convention panel #{id}"),
+            description: String::new(),
+            published_at: Utc.timestamp_opt(1_700_000_000 + id as i64, 0).unwrap(),
+            duration: Some(Duration::from_secs(3600)),
+            has_live_streaming_details: false,
+            scheduled_start_time: None,
+            player_size: Some((480, 270)),
+            shorts_page: None,
+        }
+    }
+
+    fn videos(count: usize) -> Vec<Video> {
+        (0..count).map(video).collect()
+    }
+
+    #[test]
+    fn digest() {
+        let ids =
+            |videos: &[Video]| videos.iter().map(|video| video.id.clone()).collect::<Vec<_>>();
+
+        match Announcement::plan(videos(5), Some(5)) {
+            Announcement::Individual(announced) => assert_eq!(ids(&announced), ids(&videos(5))),
+            Announcement::Digest(_) => panic!("5 videos were announced as a digest"),
+        }
+        match Announcement::plan(videos(6), Some(5)) {
+            Announcement::Digest(announced) => assert_eq!(ids(&announced), ids(&videos(6))),
+            Announcement::Individual(_) => panic!("6 videos were announced individually"),
+        }
+        assert!(matches!(Announcement::plan(videos(50), None), Announcement::Individual(_)));
+
+        assert_eq!(
+            digest_content(&videos(2)),
+            concat!(
+                "New videos:\n",
+                "- Convention panel #0: <https://youtu.be/video000000>\n",
+                "- Convention panel #1: <https://youtu.be/video000001>",
+            )
+        );
+
+        let content = digest_content(&videos(50));
+        assert!(content.chars().count() <= twilight_validate::message::MESSAGE_CONTENT_LENGTH_MAX);
+        assert!(content.ends_with(" more."), "{content}");
+    }
+
+    #[test]
+    fn shorts_detection_by_player() {
+        // The title, the length in seconds, the embed player size, and whether it's a short.
+        let samples = [
+            ("Ian's Cat Has Opinions #shorts", 45, (405, 720), true),
+            ("Desert Bus Highlight", 58, (720, 720), true),
+            ("Trailer", 120, (1280, 720), false),
+            ("Road Quest Recap", 600, (405, 720), false),
+            ("LRR Tabletop Promo #Shorts", 90, (1280, 720), false),
+        ];
+
+        for (title, length, player_size, short) in samples {
+            let mut player = video(0);
+            player.title = String::from(title);
+            player.duration = Some(Duration::from_secs(length));
+            player.player_size = Some(player_size);
+            assert_eq!(player.is_short(), short, "{title}");
+        }
+    }
+
+    /// How `youtube.com/shorts/<ID>` responds.
+    #[derive(Clone, Copy)]
+    enum ShortsPage {
+        Served,
+        Watch,
+        Consent,
+    }
+
+    #[tokio::test]
+    async fn shorts_detection_by_probe() {
+        use ShortsPage::*;
+
+        // The title, the length in seconds, how the Shorts page responds, and whether it's a short.
+        let samples = [
+            ("Ian's Cat Has Opinions #shorts", 45, Served, true),
+            ("Desert Bus Highlight", 58, Served, true),
+            ("Trailer", 120, Watch, false),
+            ("Road Quest Recap", 600, Served, false),
+            // Hashtags are a hint, not a guarantee.
+            ("LRR Tabletop Promo #Shorts", 90, Watch, false),
+            // But they're all there is to go by when the probe doesn't say.
+            ("Behind the Scenes #shorts", 50, Consent, true),
+            ("Sketch #shortstack", 30, Consent, false),
+        ];
+
+        let id = |i: usize| format!("video{i:06}");
+        let list = serde_json::json!({
+            "items": samples
+                .iter()
+                .enumerate()
+                .map(|(i, (title, length, _, _))| serde_json::json!({
+                    "id": id(i),
+                    "snippet": {
+                        "channelTitle": "LoadingReadyRun",
+                        "channelId": "UCwjN2uVdL9A0i3gaIHKFzuA",
+                        "title": title,
+                        "description": "",
+                        "publishedAt": "2024-01-01T00:00:00Z",
+                    },
+                    "contentDetails": { "duration": format!("PT{length}S") },
+                }))
+                .collect::<Vec<_>>(),
+        })
+        .to_string();
+        let pages = samples.iter().map(|&(_, _, page, _)| page).collect::<Vec<_>>();
+
+        let base_url = MockServer::bind().await.serve(move |request| {
+            let Some(video_id) = request.request_line().strip_prefix("HEAD /shorts/") else {
+                return json(&list);
+            };
+            let video_id = video_id.split(' ').next().unwrap();
+            let i = (0..pages.len()).find(|&i| id(i) == video_id).unwrap();
+            match pages[i] {
+                Served => response("200 OK", &[], ""),
+                Watch => response(
+                    "303 See Other",
+                    &[("Location", &format!("https://www.youtube.com/watch?v={video_id}"))],
+                    "",
+                ),
+                Consent => {
+                    response("302 Found", &[("Location", "https://consent.youtube.com/m")], "")
+                }
+            }
+        });
+        let youtube = youtube(base_url.clone()).await;
+
+        let ids = (0..samples.len()).map(id).collect::<Vec<_>>();
+        let videos = Video::fetch_with_shorts_url(
+            &youtube,
+            &ids,
+            ShortsDetection::Probe,
+            &format!("{base_url}shorts/"),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(videos.len(), samples.len());
+        for (video, (title, _, _, short)) in videos.iter().zip(samples) {
+            assert_eq!(video.title, title);
+            assert_eq!(video.is_short(), short, "{title}");
+        }
+    }
+}
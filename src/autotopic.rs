@@ -1,15 +1,18 @@
+use std::borrow::Cow;
 use std::fmt;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use anyhow::{Context, Error};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, TimeDelta, Utc};
 use sea_orm::{DatabaseConnection, EntityTrait};
-use separator::FixedPlaceSeparatable;
 use tokio::sync::watch::Receiver;
 use tokio::sync::RwLock;
-use tracing::error;
+use tracing::{debug, error, info};
 use twilight_http::Client as DiscordClient;
+use twilight_model::channel::ChannelType;
+use twilight_model::id::marker::ChannelMarker;
+use twilight_model::id::Id;
 use twitch_api::helix::streams::GetStreamsRequest;
 use twitch_api::twitch_oauth2::AppAccessToken;
 use twitch_api::types::UserNameRef;
@@ -27,6 +30,9 @@ use crate::shorten::shorten;
 const TOPIC_MAX_LEN: usize = 1024;
 // Hopefully normal messages don't contain this sequence.
 const DYNAMIC_TAIL_SEPARATOR: &str = " \u{2009}\u{200A}\u{200B}";
+// The tail after `DYNAMIC_TAIL_SEPARATOR` can take up to `1 / TAIL_MAX_SHARE` of the topic. A longer
+// tail is dropped instead of cut.
+const TAIL_MAX_SHARE: usize = 2;
 // Don't update the topic if the old and new topics have a Levenshtein distance below `SIMILARITY_THRESHOLD`.
 const SIMILARITY_THRESHOLD: usize = 5;
 // But even then update the topic every `SIMILAR_MIN_UPDATE_INTERVAL`.
@@ -67,8 +73,68 @@ impl<'a> fmt::Display for EventDisplay<'a> {
     }
 }
 
+/// The topic lines for the upcoming scheduled streams, followed by the next fan stream if it's
+/// within `fan_stream_horizon` of `now`.
+fn schedule_messages(
+    events: &[Event],
+    fan_streams: &[Event],
+    now: DateTime<Utc>,
+    fan_stream_horizon: Option<Duration>,
+) -> Vec<String> {
+    let mut messages =
+        events.iter().map(|event| EventDisplay { event }.to_string()).collect::<Vec<_>>();
+
+    let horizon = fan_stream_horizon.and_then(|horizon| TimeDelta::from_std(horizon).ok());
+    if let (Some(event), Some(horizon)) = (fan_streams.first(), horizon) {
+        if event.start <= now + horizon {
+            messages.push(format!("Next fan stream: {}", EventDisplay { event }));
+        }
+    }
+
+    messages
+}
+
+/// A manual topic set by the mods that pauses the automatic updates until it expires.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pause {
+    pub until: DateTime<Utc>,
+    /// The topic to enforce while paused. If `None` the topic is just left alone.
+    pub topic: Option<String>,
+}
+
+/// The pause state shared between the autotopic task and the `!autotopic` commands.
+#[derive(Clone, Default)]
+pub struct TopicOverride {
+    pause: Arc<Mutex<Option<Pause>>>,
+}
+
+impl TopicOverride {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn pause(&self, pause: Pause) {
+        *self.pause.lock().unwrap() = Some(pause);
+    }
+
+    /// Resume the automatic updates. Returns whether the updates were paused.
+    pub fn resume(&self) -> bool {
+        self.pause.lock().unwrap().take().is_some()
+    }
+
+    /// The pause in effect at `now`, clearing it if it has expired.
+    fn current(&self, now: DateTime<Utc>) -> Option<Pause> {
+        let mut pause = self.pause.lock().unwrap();
+        if pause.as_ref().is_some_and(|pause| pause.until <= now) {
+            *pause = None;
+        }
+        pause.clone()
+    }
+}
+
 pub async fn autotopic(
     mut running: Receiver<bool>,
+    topic_override: TopicOverride,
     cache: Arc<Cache>,
     calendar: CalendarHub,
     config: Arc<Config>,
@@ -80,8 +146,18 @@ pub async fn autotopic(
     lrrbot: Arc<LRRbot>,
 ) {
     let mut timer = tokio::time::interval(Duration::from_secs(60));
-    let mut autotopic =
-        Autotopic::new(cache, calendar, config, db, desertbus, discord, helix, helix_token, lrrbot);
+    let mut autotopic = Autotopic::new(
+        topic_override,
+        cache,
+        calendar,
+        config,
+        db,
+        desertbus,
+        discord,
+        helix,
+        helix_token,
+        lrrbot,
+    );
 
     loop {
         tokio::select! {
@@ -95,8 +171,194 @@ pub async fn autotopic(
     }
 }
 
-struct Autotopic {
+/// The live status with hysteresis, so that a single offline reading in the middle of a stream
+/// (like during a reconnect) doesn't flip the topic to the schedule and back.
+struct LiveStatus {
+    is_live: bool,
+    offline_readings: usize,
+    threshold: usize,
+}
+
+impl LiveStatus {
+    fn new(threshold: usize) -> Self {
+        Self { is_live: false, offline_readings: 0, threshold: threshold.max(1) }
+    }
+
+    /// Record a new reading and return the debounced status.
+    fn update(&mut self, is_live: bool) -> bool {
+        if is_live {
+            self.is_live = true;
+            self.offline_readings = 0;
+        } else if self.is_live {
+            self.offline_readings += 1;
+            if self.offline_readings >= self.threshold {
+                self.is_live = false;
+                self.offline_readings = 0;
+            }
+        }
+
+        self.is_live
+    }
+}
+
+/// What `Target::topic_update` decided to do with the topic.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Decision {
+    /// The static prefix changed enough.
+    Updated,
+    /// The static prefix is the same, or so similar that the update can wait.
+    Skipped,
+    /// The static prefix is only a little different but the topic is due an update anyway.
+    Forced,
+}
+
+/// A channel the topic is set in.
+struct Target {
+    channel_id: Id<ChannelMarker>,
+    max_len: usize,
     last_updated: Option<DateTime<Utc>>,
+    /// The topic a dry run would have set, since the channel keeps its old topic.
+    dry_run_topic: Option<String>,
+}
+
+impl Target {
+    fn new(channel_id: Id<ChannelMarker>, max_len: usize) -> Self {
+        Self { channel_id, max_len, last_updated: None, dry_run_topic: None }
+    }
+
+    /// The topic to change `old_topic` to at `now`, or `None` if it should be left alone.
+    fn topic_update<'a>(
+        &self,
+        old_topic: &str,
+        new_topic: &'a str,
+        is_dynamic: bool,
+        now: DateTime<Utc>,
+    ) -> Option<Cow<'a, str>> {
+        let new_topic = shorten_topic(new_topic, self.max_len);
+
+        let new_topic_static_prefix = new_topic
+            .rsplit_once(DYNAMIC_TAIL_SEPARATOR)
+            .map_or(&new_topic[..], |(prefix, _)| prefix);
+        let old_topic_static_prefix =
+            old_topic.rsplit_once(DYNAMIC_TAIL_SEPARATOR).unwrap_or((old_topic, "")).0;
+
+        let (decision, distance) =
+            self.decide(old_topic_static_prefix, new_topic_static_prefix, is_dynamic, now);
+        debug!(
+            channel.id = self.channel_id.get(),
+            old_topic_static_prefix,
+            new_topic_static_prefix,
+            distance,
+            ?decision,
+            "Topic update decision"
+        );
+
+        match decision {
+            Decision::Updated | Decision::Forced => Some(new_topic),
+            Decision::Skipped => None,
+        }
+    }
+
+    /// Whether to replace the static prefix `old` with `new`, and the Levenshtein distance
+    /// between them if it mattered.
+    fn decide(
+        &self,
+        old: &str,
+        new: &str,
+        is_dynamic: bool,
+        now: DateTime<Utc>,
+    ) -> (Decision, Option<usize>) {
+        if !is_dynamic {
+            let decision = if old == new { Decision::Skipped } else { Decision::Updated };
+            return (decision, None);
+        }
+
+        let distance = levenshtein::levenshtein(old, new);
+        let decision = if distance == 0 {
+            Decision::Skipped
+        } else if distance >= SIMILARITY_THRESHOLD {
+            Decision::Updated
+        } else if self.last_updated.is_some_and(|t| (now - t) < SIMILAR_MIN_UPDATE_INTERVAL) {
+            Decision::Skipped
+        } else {
+            Decision::Forced
+        };
+        (decision, Some(distance))
+    }
+
+    /// The topic to compare new topics against, given the topic the channel has.
+    fn old_topic<'a>(&'a self, channel_topic: &'a str) -> &'a str {
+        self.dry_run_topic.as_deref().unwrap_or(channel_topic)
+    }
+
+    /// Change the topic of the channel to `topic`, or only log it in a dry run.
+    async fn set_topic(
+        &mut self,
+        discord: &DiscordClient,
+        dry_run: bool,
+        topic: &str,
+        now: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        if dry_run {
+            info!(channel.id = self.channel_id.get(), topic, "Dry run: not updating the topic");
+        } else {
+            discord
+                .update_channel(self.channel_id)
+                .topic(topic)
+                .await
+                .context("failed to update the topic")?;
+        }
+        self.updated(dry_run, topic, now);
+        Ok(())
+    }
+
+    /// Record that the topic was changed to `topic` at `now`.
+    fn updated(&mut self, dry_run: bool, topic: &str, now: DateTime<Utc>) {
+        if dry_run {
+            self.dry_run_topic = Some(String::from(topic));
+        }
+        self.last_updated = Some(now);
+    }
+}
+
+/// Whether channels of type `kind` have a topic. Voice and stage channels only have a status,
+/// which the Discord client can't set, so they can't be targets.
+fn has_topic(kind: ChannelType) -> bool {
+    matches!(
+        kind,
+        ChannelType::GuildText
+            | ChannelType::GuildAnnouncement
+            | ChannelType::GuildForum
+            | ChannelType::GuildMedia
+    )
+}
+
+/// Shorten `topic` to `max_len` code points. The prefix is cut to make room for the tail after
+/// `DYNAMIC_TAIL_SEPARATOR` so that the tail is either kept whole or left out entirely.
+fn shorten_topic(topic: &str, max_len: usize) -> Cow<'_, str> {
+    let Some((prefix, tail)) = topic.rsplit_once(DYNAMIC_TAIL_SEPARATOR) else {
+        return shorten(topic, max_len);
+    };
+    if topic.chars().count() <= max_len {
+        return Cow::Borrowed(topic);
+    }
+
+    let tail_len = DYNAMIC_TAIL_SEPARATOR.chars().count() + tail.chars().count();
+    if tail_len > max_len / TAIL_MAX_SHARE {
+        return shorten(prefix, max_len);
+    }
+
+    let mut shortened = shorten(prefix, max_len - tail_len).into_owned();
+    shortened.push_str(DYNAMIC_TAIL_SEPARATOR);
+    shortened.push_str(tail);
+    Cow::Owned(shortened)
+}
+
+struct Autotopic {
+    /// The general channel followed by the mirrors.
+    targets: Vec<Target>,
+    live_status: LiveStatus,
+    topic_override: TopicOverride,
 
     cache: Arc<Cache>,
     calendar: CalendarHub,
@@ -111,6 +373,7 @@ struct Autotopic {
 
 impl Autotopic {
     fn new(
+        topic_override: TopicOverride,
         cache: Arc<Cache>,
         calendar: CalendarHub,
         config: Arc<Config>,
@@ -121,8 +384,15 @@ impl Autotopic {
         helix_token: Arc<RwLock<AppAccessToken>>,
         lrrbot: Arc<LRRbot>,
     ) -> Self {
+        let targets = std::iter::once(Target::new(config.general_channel, TOPIC_MAX_LEN))
+            .chain(config.topic_mirrors.iter().map(|&(channel_id, max_len)| {
+                Target::new(channel_id, max_len.unwrap_or(TOPIC_MAX_LEN))
+            }))
+            .collect();
         Self {
-            last_updated: None,
+            targets,
+            live_status: LiveStatus::new(config.autotopic_offline_threshold),
+            topic_override,
             cache,
             calendar,
             config,
@@ -135,53 +405,53 @@ impl Autotopic {
         }
     }
 
+    /// Set the topic of the general channel and the mirrors. A failure to update a mirror is only
+    /// logged.
     async fn set_topic(&mut self, new_topic: &str, is_dynamic: bool) -> Result<(), Error> {
         self.cache.wait_until_ready().await;
 
-        let new_topic = shorten(new_topic, TOPIC_MAX_LEN);
-        let new_topic = new_topic.as_ref();
-
-        let old_topic = self
-            .cache
-            .with(|cache| {
-                cache.channel(self.config.general_channel).map(|channel| channel.topic.clone())
-            })
-            .context("announcement channel not in cache")?
-            .unwrap_or_default();
-
-        let new_topic_static_prefix =
-            new_topic.rsplit_once(DYNAMIC_TAIL_SEPARATOR).unwrap_or((new_topic, "")).0;
-        let old_topic_static_prefix =
-            old_topic.rsplit_once(DYNAMIC_TAIL_SEPARATOR).unwrap_or((&old_topic, "")).0;
-
         let now = Utc::now();
+        let mut result = Ok(());
+        for (i, target) in self.targets.iter_mut().enumerate() {
+            let target_result = async {
+                let (kind, channel_topic) = self
+                    .cache
+                    .with(|cache| {
+                        cache
+                            .channel(target.channel_id)
+                            .map(|channel| (channel.kind, channel.topic.clone()))
+                    })
+                    .context("channel not in cache")?;
+                anyhow::ensure!(has_topic(kind), "{kind:?} channels don't have a topic");
+                let channel_topic = channel_topic.unwrap_or_default();
+                let old_topic = target.old_topic(&channel_topic);
+
+                if let Some(topic) = target.topic_update(old_topic, new_topic, is_dynamic, now) {
+                    target.set_topic(&self.discord, self.config.dry_run, &topic, now).await?;
+                }
 
-        if !is_dynamic {
-            if old_topic_static_prefix == new_topic_static_prefix {
-                return Ok(());
+                Ok::<_, Error>(())
             }
-        } else {
-            let distance =
-                levenshtein::levenshtein(old_topic_static_prefix, new_topic_static_prefix);
-            if distance == 0
-                || distance < SIMILARITY_THRESHOLD
-                    && self.last_updated.is_some_and(|t| (now - t) < SIMILAR_MIN_UPDATE_INTERVAL)
-            {
-                return Ok(());
+            .await;
+
+            if i == 0 {
+                result = target_result;
+            } else if let Err(error) = target_result {
+                error!(?error, channel.id = target.channel_id.get(), "failed to mirror the topic");
             }
         }
 
-        self.discord
-            .update_channel(self.config.general_channel)
-            .topic(new_topic)
-            .await
-            .context("failed to update the topic")?;
-        self.last_updated = Some(now);
-
-        Ok(())
+        result
     }
 
     async fn update_topic(&mut self) -> Result<(), Error> {
+        if let Some(pause) = self.topic_override.current(Utc::now()) {
+            if let Some(topic) = pause.topic {
+                self.set_topic(&topic, false).await.context("failed to enforce the topic")?;
+            }
+            return Ok(());
+        }
+
         let header = self.lrrbot.get_header_info().await.unwrap_or_else(|error| {
             error!(?error, "failed to fetch header info");
 
@@ -225,26 +495,14 @@ impl Autotopic {
                 None
             };
 
-        if header.is_live {
-            match (game, show) {
-                (Some(game), Some(show)) => {
-                    messages.push(format!(
-                        "Now live: {} on {}.",
-                        game_entry.and_then(|entry| entry.display_name).unwrap_or(game.name),
-                        show.name
-                    ));
-                }
-                (Some(game), None) => {
-                    messages.push(format!(
-                        "Now live: {}.",
-                        game_entry.and_then(|entry| entry.display_name).unwrap_or(game.name)
-                    ));
-                }
-                (None, Some(show)) => {
-                    messages.push(format!("Now live: {}.", show.name));
-                }
-                (None, None) => messages.push(String::from("Now live: something?")),
-            }
+        if self.live_status.update(header.is_live) {
+            let game = game.as_ref().map(|game| game.effective_name(game_entry.as_ref()));
+            messages.push(
+                self.config
+                    .autotopic_live
+                    .format(&header.channel, game, show.as_ref().map(|show| &show.name[..]))
+                    .context("failed to format the live topic")?,
+            );
 
             match self.uptime_msg(&header.channel).await {
                 Ok(msg) => messages.push(msg),
@@ -263,7 +521,26 @@ impl Autotopic {
                 messages.extend(desertbus);
                 is_dynamic |= desertbus_is_dynamic;
             } else {
-                messages.extend(events.iter().map(|event| EventDisplay { event }.to_string()));
+                let fan_streams = match self.config.autotopic_fan_stream_horizon {
+                    Some(_) => crate::calendar::get_next_event(
+                        &self.calendar,
+                        crate::calendar::FANSTREAMS,
+                        now,
+                        false,
+                    )
+                    .await
+                    .unwrap_or_else(|error| {
+                        error!(?error, "failed to get the next fan stream");
+                        vec![]
+                    }),
+                    None => vec![],
+                };
+                messages.extend(schedule_messages(
+                    &events,
+                    &fan_streams,
+                    now,
+                    self.config.autotopic_fan_stream_horizon,
+                ));
             }
         }
 
@@ -335,6 +612,7 @@ impl Autotopic {
                 messages.push(
                     EventDisplay {
                         event: &Event {
+                            id: None,
                             start,
                             summary: String::from("Desert Bus for Hope"),
                             end,
@@ -346,19 +624,13 @@ impl Autotopic {
                     }
                     .to_string(),
                 );
-                messages.push(format!(
-                    "${} raised.",
-                    money_raised.separated_string_with_fixed_place(2)
-                ));
+                messages.push(format!("{} raised.", self.config.money_format.format(money_raised)));
                 is_dynamic = true;
             } else if now <= end || self.is_desertbus_live().await? {
                 messages.push(String::from(
                     "DESERT BUS! (https://desertbus.org/ or https://twitch.tv/desertbus)",
                 ));
-                messages.push(format!(
-                    "${} raised.",
-                    money_raised.separated_string_with_fixed_place(2)
-                ));
+                messages.push(format!("{} raised.", self.config.money_format.format(money_raised)));
                 let bussed = now - start;
                 messages.push(format!(
                     "{}:{:02} hours of {total_hours} so far.",
@@ -385,3 +657,207 @@ impl Autotopic {
             .is_empty())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::{TimeDelta, TimeZone, Utc};
+    use google_calendar3::hyper_rustls::HttpsConnectorBuilder;
+    use twilight_http::Client as DiscordClient;
+    use twilight_model::channel::ChannelType;
+    use twilight_model::id::Id;
+
+    use super::{
+        has_topic, schedule_messages, shorten_topic, Decision, LiveStatus, Pause, Target,
+        TopicOverride, DYNAMIC_TAIL_SEPARATOR,
+    };
+    use crate::calendar::Event;
+
+    #[test]
+    fn live_status_hysteresis() {
+        let mut status = LiveStatus::new(3);
+        let readings = [false, true, false, true, false, false, true, false, false, false, false];
+        let expected = [false, true, true, true, true, true, true, true, true, false, false];
+        for (reading, expected) in readings.into_iter().zip(expected) {
+            assert_eq!(status.update(reading), expected);
+        }
+    }
+
+    #[test]
+    fn live_status_without_hysteresis() {
+        let mut status = LiveStatus::new(0);
+        assert!(status.update(true));
+        assert!(!status.update(false));
+        assert!(status.update(true));
+    }
+
+    #[test]
+    fn paused_until_expiry() {
+        let now = Utc::now();
+        let pause = Pause { until: now + TimeDelta::minutes(30), topic: None };
+
+        let topic_override = TopicOverride::new();
+        assert_eq!(topic_override.current(now), None);
+
+        topic_override.pause(pause.clone());
+        assert_eq!(topic_override.current(now), Some(pause.clone()));
+        assert_eq!(topic_override.current(now + TimeDelta::minutes(29)), Some(pause.clone()));
+        assert_eq!(topic_override.current(now + TimeDelta::minutes(30)), None);
+        // Expired pauses are cleared.
+        assert_eq!(topic_override.current(now), None);
+
+        topic_override.pause(pause.clone());
+        assert!(topic_override.resume());
+        assert_eq!(topic_override.current(now), None);
+        assert!(!topic_override.resume());
+    }
+
+    #[test]
+    fn advice_survives_shortening() {
+        let events =
+            "Next scheduled stream: Friday Nights (Magic: The Gathering) <t:1704067200:R>.";
+        let advice = "Don't forget to hydrate!";
+        let topic = format!("{events}{DYNAMIC_TAIL_SEPARATOR}{advice}");
+
+        assert_eq!(shorten_topic(&topic, 1024), topic);
+
+        // The event list is cut to make room for the advice.
+        let shortened = shorten_topic(&topic, 64);
+        assert_eq!(shortened.chars().count(), 64);
+        assert_eq!(
+            shortened,
+            format!("Next scheduled stream: Friday Nig[…]{DYNAMIC_TAIL_SEPARATOR}{advice}")
+        );
+
+        // Advice that would take too much of the topic is left out instead of cut.
+        assert_eq!(shorten_topic(&topic, 40), "Next scheduled stream: Friday Nights […]");
+
+        // Topics without the separator are cut as before.
+        assert_eq!(shorten_topic(events, 24), "Next scheduled stream[…]");
+    }
+
+    #[test]
+    fn mirrored_topics() {
+        let now = Utc::now();
+        let general = Target::new(Id::new(1), 1024);
+        let mut status = Target::new(Id::new(2), 24);
+
+        let topic = "Now live: Magic: The Gathering on Friday Nights.";
+        assert_eq!(general.topic_update("", topic, false, now).as_deref(), Some(topic));
+        assert_eq!(
+            status.topic_update("", topic, false, now).as_deref(),
+            Some("Now live: Magic: The […]")
+        );
+
+        // Each target is compared against its own shortened topic.
+        assert_eq!(general.topic_update(topic, topic, false, now), None);
+        assert_eq!(status.topic_update("Now live: Magic: The […]", topic, false, now), None);
+
+        // And rate limited on its own.
+        let dynamic = format!("Desert Bus 2024{DYNAMIC_TAIL_SEPARATOR}$1,234");
+        let updated = format!("Desert Bus 2025{DYNAMIC_TAIL_SEPARATOR}$1,234");
+        status.last_updated = Some(now);
+        assert_eq!(status.topic_update(&dynamic, &updated, true, now), None);
+        assert!(general.topic_update(&dynamic, &updated, true, now).is_some());
+    }
+
+    #[test]
+    fn decisions() {
+        let now = Utc.with_ymd_and_hms(2024, 1, 5, 12, 0, 0).unwrap();
+        let mut target = Target::new(Id::new(1), 1024);
+        let live = "Now live: Magic: The Gathering";
+
+        // Static topics change whenever the text does.
+        assert_eq!(target.decide(live, live, false, now), (Decision::Skipped, None));
+        assert_eq!(target.decide(live, "Now live: Magic", false, now), (Decision::Updated, None));
+
+        // Dynamic topics that differ by enough are updated right away.
+        assert_eq!(
+            target.decide(live, "Now live: Magic: The Gathering Arena", true, now),
+            (Decision::Updated, Some(6))
+        );
+        assert_eq!(target.decide(live, live, true, now), (Decision::Skipped, Some(0)));
+
+        // Small changes wait until the topic hasn't been updated in a while.
+        let similar = "Now live: Magic: The Gatherin'";
+        assert_eq!(target.decide(live, similar, true, now), (Decision::Forced, Some(1)));
+        target.last_updated = Some(now - TimeDelta::minutes(10));
+        assert_eq!(target.decide(live, similar, true, now), (Decision::Skipped, Some(1)));
+        target.last_updated = Some(now - TimeDelta::minutes(45));
+        assert_eq!(target.decide(live, similar, true, now), (Decision::Forced, Some(1)));
+    }
+
+    #[test]
+    fn voice_channels_have_no_topic() {
+        assert!(has_topic(ChannelType::GuildText));
+        assert!(has_topic(ChannelType::GuildAnnouncement));
+        assert!(!has_topic(ChannelType::GuildVoice));
+        assert!(!has_topic(ChannelType::GuildStageVoice));
+    }
+
+    #[tokio::test]
+    async fn dry_run() {
+        // Building the Discord client needs a default crypto provider, which building the TLS
+        // config for the Google clients installs.
+        let _ = HttpsConnectorBuilder::new().with_native_roots();
+        // Nothing listens on the discard port, so a request that did go out would fail.
+        let discord = DiscordClient::builder().proxy(String::from("127.0.0.1:9"), true).build();
+        let now = Utc.with_ymd_and_hms(2024, 1, 5, 12, 0, 0).unwrap();
+        let topic = "Now live: Magic: The Gathering";
+
+        let mut target = Target::new(Id::new(1), 1024);
+        assert_eq!(
+            target.topic_update(target.old_topic(""), topic, false, now).as_deref(),
+            Some(topic)
+        );
+        target.set_topic(&discord, true, topic, now).await.unwrap();
+        assert_eq!(target.last_updated, Some(now));
+
+        // The channel keeps its old topic but the same update isn't logged again.
+        assert_eq!(target.old_topic(""), topic);
+        assert_eq!(target.topic_update(target.old_topic(""), topic, false, now), None);
+
+        // Real updates are compared against the channel's topic.
+        let mut target = Target::new(Id::new(1), 1024);
+        target.updated(false, topic, now);
+        assert_eq!(target.old_topic(topic), topic);
+        assert_eq!(target.old_topic(""), "");
+    }
+
+    #[test]
+    fn fan_streams() {
+        let now = Utc.with_ymd_and_hms(2024, 1, 5, 12, 0, 0).unwrap();
+        let event = |summary: &str, hours| Event {
+            id: None,
+            start: now + TimeDelta::hours(hours),
+            summary: String::from(summary),
+            end: now + TimeDelta::hours(hours + 2),
+            location: None,
+            description: None,
+        };
+        let events = [event("Friday Nights", 6)];
+        let fan_streams = [event("Fan Stream", 20), event("Later Fan Stream", 30)];
+        let horizon = Some(Duration::from_secs(24 * 60 * 60));
+
+        assert_eq!(
+            schedule_messages(&events, &fan_streams, now, horizon),
+            vec![
+                String::from("<t:1704477600:R>: Friday Nights on <t:1704477600:F>."),
+                String::from("Next fan stream: <t:1704528000:R>: Fan Stream on <t:1704528000:F>."),
+            ]
+        );
+
+        // Only the scheduled stream if the fan stream is too far away or the line is disabled.
+        assert_eq!(schedule_messages(&events, &fan_streams[1..], now, horizon).len(), 1);
+        assert_eq!(schedule_messages(&events, &fan_streams, now, None).len(), 1);
+
+        // And the fan stream on its own.
+        assert_eq!(
+            schedule_messages(&[], &fan_streams, now, horizon),
+            vec![String::from(
+                "Next fan stream: <t:1704528000:R>: Fan Stream on <t:1704528000:F>."
+            )]
+        );
+    }
+}
@@ -1,20 +1,40 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::Error;
 use tokio::sync::watch;
+use tracing::error;
 use twilight_cache_inmemory::InMemoryCache;
 use twilight_model::gateway::event::Event;
-use twilight_model::id::marker::GuildMarker;
+use twilight_model::id::marker::{ChannelMarker, GuildMarker, RoleMarker, UserMarker};
 use twilight_model::id::Id;
 
+/// How long to remember the roles of members that had to be fetched over HTTP, or that the user
+/// isn't a member at all.
+const FETCHED_MEMBER_TTL: Duration = Duration::from_secs(60);
+
+type FetchedMembers =
+    HashMap<(Id<GuildMarker>, Id<UserMarker>), (Instant, Option<Vec<Id<RoleMarker>>>)>;
+
 /// A wrapper around [InMemoryCache] to prevent holding on to references to the cached data across
 /// yield points.
 pub struct Cache {
     cache: InMemoryCache,
     guild_id: Id<GuildMarker>,
     ready: watch::Sender<bool>,
+    fetched_members: Mutex<FetchedMembers>,
 }
 
 impl Cache {
     pub fn new(guild_id: Id<GuildMarker>) -> Self {
-        Self { cache: InMemoryCache::new(), ready: watch::Sender::new(false), guild_id }
+        Self {
+            cache: InMemoryCache::new(),
+            ready: watch::Sender::new(false),
+            guild_id,
+            fetched_members: Mutex::new(HashMap::new()),
+        }
     }
 
     pub fn with<T>(&self, f: impl FnOnce(&InMemoryCache) -> T) -> T {
@@ -31,9 +51,127 @@ impl Cache {
         }
     }
 
+    /// The threads in `channel_id`, like the posts in a forum, newest first. `None` if the channel
+    /// or its guild's channels aren't in the cache.
+    pub fn threads_in(&self, channel_id: Id<ChannelMarker>) -> Option<Vec<Id<ChannelMarker>>> {
+        let guild_id = self.cache.channel(channel_id)?.guild_id?;
+        let mut threads = self
+            .cache
+            .guild_channels(guild_id)?
+            .iter()
+            .copied()
+            .filter_map(|id| {
+                let channel = self.cache.channel(id);
+                if channel.is_none() {
+                    error!(
+                        channel.id = id.get(),
+                        "channel referenced by cache but not itself in cache"
+                    );
+                }
+                channel
+            })
+            .filter(|thread| thread.parent_id == Some(channel_id))
+            .map(|thread| thread.id)
+            .collect::<Vec<_>>();
+        threads.sort_by(|a, b| a.cmp(b).reverse());
+        Some(threads)
+    }
+
+    /// Get the roles of a member that is missing from the cache.
+    ///
+    /// The roles are fetched with `fetch` and remembered for a short while so that a burst of
+    /// commands doesn't turn into a burst of HTTP requests. `None` means the user isn't a member,
+    /// which is remembered just the same.
+    pub async fn fetched_member_roles<F, Fut>(
+        &self,
+        guild_id: Id<GuildMarker>,
+        user_id: Id<UserMarker>,
+        fetch: F,
+    ) -> Result<Option<Vec<Id<RoleMarker>>>, Error>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Option<Vec<Id<RoleMarker>>>, Error>>,
+    {
+        if let Some((fetched_at, roles)) =
+            self.fetched_members.lock().unwrap().get(&(guild_id, user_id))
+        {
+            if fetched_at.elapsed() < FETCHED_MEMBER_TTL {
+                return Ok(roles.clone());
+            }
+        }
+
+        let roles = fetch().await?;
+
+        let mut fetched_members = self.fetched_members.lock().unwrap();
+        fetched_members.retain(|_, (fetched_at, _)| fetched_at.elapsed() < FETCHED_MEMBER_TTL);
+        fetched_members.insert((guild_id, user_id), (Instant::now(), roles.clone()));
+
+        Ok(roles)
+    }
+
     pub async fn wait_until_ready(&self) {
         if let Err(_) = self.ready.subscribe().wait_for(|is_ready| *is_ready).await {
             unreachable!("`self.ready` is closed")
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use twilight_model::gateway::event::Event;
+    use twilight_model::gateway::payload::incoming::GuildCreate;
+    use twilight_model::id::Id;
+
+    use super::Cache;
+
+    #[test]
+    fn threads_in() {
+        let channel = |id: &str, kind: u8, parent_id: Option<&str>| {
+            json!({
+                "id": id,
+                "type": kind,
+                "guild_id": "1",
+                "name": "channel",
+                "parent_id": parent_id,
+            })
+        };
+        let guild = serde_json::from_value(json!({
+            "afk_timeout": 300,
+            "channels": [channel("10", 15, None), channel("11", 0, None), channel("12", 4, None)],
+            "threads": [
+                channel("20", 11, Some("10")),
+                channel("21", 11, Some("11")),
+                channel("23", 11, Some("10")),
+                channel("22", 11, Some("10")),
+            ],
+            "default_message_notifications": 1,
+            "explicit_content_filter": 0,
+            "features": [],
+            "icon": null,
+            "id": "1",
+            "mfa_level": 0,
+            "name": "LoadingReadyRun",
+            "nsfw_level": 0,
+            "owner_id": "2",
+            "preferred_locale": "en-US",
+            "premium_progress_bar_enabled": false,
+            "premium_tier": 0,
+            "roles": [],
+            "system_channel_flags": 0,
+            "verification_level": 0,
+        }))
+        .unwrap();
+        let cache = Cache::new(Id::new(1));
+        assert_eq!(cache.threads_in(Id::new(10)), None);
+        cache.update(&Event::GuildCreate(Box::new(GuildCreate::Available(guild))));
+
+        assert_eq!(
+            cache.threads_in(Id::new(10)),
+            Some(vec![Id::new(23), Id::new(22), Id::new(20)])
+        );
+        assert_eq!(cache.threads_in(Id::new(11)), Some(vec![Id::new(21)]));
+        assert_eq!(cache.threads_in(Id::new(12)), Some(vec![]));
+        assert_eq!(cache.threads_in(Id::new(13)), None);
+    }
+}
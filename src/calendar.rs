@@ -3,13 +3,16 @@ use chrono::{DateTime, TimeDelta, Utc};
 use google_calendar3::api::EventDateTime;
 use google_calendar3::hyper_rustls::HttpsConnector;
 use google_calendar3::hyper_util::client::legacy::connect::HttpConnector;
-use tracing::info;
+use tracing::warn;
 
 use crate::tz::Tz;
 
 pub const LRR: &str = "loadingreadyrun.com_72jmf1fn564cbbr84l048pv1go@group.calendar.google.com";
 pub const FANSTREAMS: &str = "caffeinatedlemur@gmail.com";
 
+/// The time zone for all-day events when the calendar doesn't specify one.
+const DEFAULT_TIMEZONE: &str = "America/Vancouver";
+
 const ONE_HOUR: TimeDelta = match TimeDelta::try_hours(1) {
     Some(delta) => delta,
     None => panic!("1 hour is not a valid `chrono::TimeDelta`"),
@@ -18,6 +21,7 @@ const ONE_HOUR: TimeDelta = match TimeDelta::try_hours(1) {
 pub type CalendarHub = google_calendar3::CalendarHub<HttpsConnector<HttpConnector>>;
 
 pub struct Event {
+    pub id: Option<String>,
     pub start: DateTime<Utc>,
     pub summary: String,
     pub end: DateTime<Utc>,
@@ -28,6 +32,7 @@ pub struct Event {
 impl Event {
     fn from_api_event(event: google_calendar3::api::Event, timezone: &Tz) -> Result<Self, Error> {
         Ok(Self {
+            id: event.id,
             start: parse_timestamp(&event.start.context("no event start time")?, timezone)
                 .context("failed to parse the event start time")?,
             summary: event.summary.context("event summary missing")?,
@@ -74,16 +79,40 @@ pub fn format_description(description: &str) -> String {
     }
 }
 
-pub async fn get_next_event(
+/// Normalize the events, logging and skipping the ones that can't be normalized.
+fn normalize_events(events: Vec<google_calendar3::api::Event>, timezone: &Tz) -> Vec<Event> {
+    events
+        .into_iter()
+        .filter_map(|event| {
+            let id = event.id.clone();
+            let summary = event.summary.clone();
+            match Event::from_api_event(event, timezone) {
+                Ok(event) => Some(event),
+                Err(error) => {
+                    warn!(
+                        ?error,
+                        event.id = id.as_deref(),
+                        event.summary = summary.as_deref(),
+                        "Dropping an event that failed to normalize"
+                    );
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// The first `max_results` events that end after `at`, in the order they start.
+pub async fn get_upcoming_events(
     client: &CalendarHub,
     calendar_id: &str,
     at: DateTime<Utc>,
-    include_current: bool,
+    max_results: i32,
 ) -> Result<Vec<Event>, Error> {
     let (_, res) = client
         .events()
         .list(calendar_id)
-        .max_results(10)
+        .max_results(max_results)
         .order_by("startTime")
         .single_events(true)
         .time_min(at)
@@ -91,20 +120,53 @@ pub async fn get_next_event(
         .await
         .context("failed to get the calendar events")?;
 
-    let timezone = Tz::from_name(res.time_zone.as_deref().unwrap_or("America/Vancouver"))
+    let timezone = Tz::from_name(res.time_zone.as_deref().unwrap_or(DEFAULT_TIMEZONE))
         .context("calendar in an unknown timezone")?;
 
     let Some(events) = res.items else { return Ok(vec![]) };
-    let events = events
-        .into_iter()
-        .filter_map(|event| match Event::from_api_event(event, &timezone) {
-            Ok(event) => Some(event),
-            Err(error) => {
-                info!(?error, "failed to normalize the event");
-                None
-            }
-        })
-        .collect::<Vec<_>>();
+    Ok(normalize_events(events, &timezone))
+}
+
+/// Whether the request failed because the event doesn't exist (anymore).
+fn is_gone(error: &google_calendar3::Error) -> bool {
+    const GONE: [u16; 2] = [404, 410];
+    match error {
+        google_calendar3::Error::BadRequest(body) => body["error"]["code"]
+            .as_u64()
+            .is_some_and(|code| GONE.iter().any(|&gone| u64::from(gone) == code)),
+        google_calendar3::Error::Failure(res) => GONE.contains(&res.status().as_u16()),
+        _ => false,
+    }
+}
+
+/// A single event, or `None` if it has been cancelled or deleted.
+pub async fn get_event(
+    client: &CalendarHub,
+    calendar_id: &str,
+    event_id: &str,
+) -> Result<Option<Event>, Error> {
+    let event = match client.events().get(calendar_id, event_id).doit().await {
+        Ok((_, event)) => event,
+        Err(error) if is_gone(&error) => return Ok(None),
+        Err(error) => return Err(Error::from(error).context("failed to get the calendar event")),
+    };
+    if event.status.as_deref() == Some("cancelled") {
+        return Ok(None);
+    }
+
+    let timezone = event.start.as_ref().and_then(|start| start.time_zone.as_deref());
+    let timezone = Tz::from_name(timezone.unwrap_or(DEFAULT_TIMEZONE))
+        .context("event in an unknown timezone")?;
+    Event::from_api_event(event, &timezone).map(Some)
+}
+
+pub async fn get_next_event(
+    client: &CalendarHub,
+    calendar_id: &str,
+    at: DateTime<Utc>,
+    include_current: bool,
+) -> Result<Vec<Event>, Error> {
+    let events = get_upcoming_events(client, calendar_id, at, 10).await?;
 
     let mut first_future_event = None;
 
@@ -130,3 +192,50 @@ pub async fn get_next_event(
         .map(|(_, event)| event)
         .collect())
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use google_calendar3::api::EventDateTime;
+    use tracing::Level;
+
+    use super::normalize_events;
+    use crate::testing::TraceCapture;
+    use crate::tz::Tz;
+
+    #[test]
+    fn dropped_events() {
+        let capture = TraceCapture::default();
+        let _guard = capture.set_default();
+
+        let start = Utc.with_ymd_and_hms(2024, 11, 9, 18, 0, 0).unwrap();
+        let at = |timestamp| EventDateTime { date_time: Some(timestamp), ..Default::default() };
+        let good = google_calendar3::api::Event {
+            id: Some(String::from("good")),
+            summary: Some(String::from("Desert Bus")),
+            start: Some(at(start)),
+            end: Some(at(start + chrono::TimeDelta::days(7))),
+            ..Default::default()
+        };
+        let bad = google_calendar3::api::Event {
+            id: Some(String::from("bad")),
+            summary: Some(String::from("Friday Nights")),
+            start: Some(EventDateTime::default()),
+            end: Some(at(start)),
+            ..Default::default()
+        };
+
+        let events =
+            normalize_events(vec![bad, good], &Tz::from_name("America/Vancouver").unwrap());
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].summary, "Desert Bus");
+        assert_eq!(events[0].start, start);
+
+        let logged = capture.events();
+        assert_eq!(logged.len(), 1);
+        let (level, fields) = &logged[0];
+        assert_eq!(*level, Level::WARN);
+        assert!(fields.contains(&("event.id", String::from("bad"))), "{fields:?}");
+        assert!(fields.contains(&("event.summary", String::from("Friday Nights"))), "{fields:?}");
+    }
+}
@@ -51,6 +51,13 @@ pub async fn channel_reaper(
                 });
 
                 for channel_id in channels_to_delete {
+                    if config.dry_run {
+                        info!(
+                            channel.id = channel_id.get(),
+                            "Dry run: not deleting a temporary channel"
+                        );
+                        continue;
+                    }
                     if let Err(error) = discord.delete_channel(channel_id).await {
                         error!(
                             ?error,
@@ -1,25 +1,34 @@
 use std::borrow::Cow;
+use std::collections::{HashMap, VecDeque};
 use std::future::Future;
 use std::pin::Pin;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use anyhow::{Context, Error};
-use regex::{Captures, Regex, RegexSet};
+use anyhow::{anyhow, Context, Error};
+use chrono::{DateTime, Utc};
+use regex::{Captures, Regex, RegexBuilder};
 use sea_orm::{DeriveActiveEnum, EnumIter};
 use tokio::sync::mpsc::Sender;
-use tokio::task::JoinHandle;
 use tracing::{error, info, Instrument};
+use twilight_cache_inmemory::InMemoryCache;
 use twilight_gateway::Event;
+use twilight_http::error::ErrorType;
 use twilight_http::Client as DiscordClient;
 use twilight_model::channel::message::MessageFlags;
 use twilight_model::channel::Message;
 use twilight_model::gateway::payload::incoming::MessageCreate;
 use twilight_model::guild::Permissions;
-use twilight_model::id::marker::{ChannelMarker, GuildMarker, MessageMarker, UserMarker};
+use twilight_model::id::marker::{
+    ChannelMarker, GuildMarker, MessageMarker, RoleMarker, UserMarker,
+};
 use twilight_model::id::Id;
 
 use crate::cache::Cache;
 use crate::config::Config;
+use crate::influxdb::InfluxDb;
+use crate::prometheus::Prometheus;
+use crate::shutdown::Task;
 
 pub trait CommandHandler: Send + Sync {
     fn pattern(&self) -> &str;
@@ -40,6 +49,25 @@ pub trait CommandHandler: Send + Sync {
     fn access(&self) -> Access {
         Access::All
     }
+    /// The heading the command is listed under in `!help`.
+    fn category(&self) -> &'static str {
+        "General"
+    }
+    /// Whether the whole pattern, arguments included, is matched case-insensitively.
+    fn case_insensitive(&self) -> bool {
+        false
+    }
+}
+
+/// Sees every message that isn't from a bot, whether or not it's a command.
+pub trait MessageObserver: Send + Sync {
+    fn on_message<'a>(
+        &'a self,
+        cache: &'a Cache,
+        config: &'a Config,
+        discord: &'a DiscordClient,
+        message: &'a Message,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>;
 }
 
 #[derive(Debug, Clone, Copy, DeriveActiveEnum, EnumIter, Eq, PartialEq)]
@@ -101,6 +129,87 @@ impl Access {
         }
     }
 
+    /// Like [`Access::user_has_access`] but if the user isn't in the cache (for example during
+    /// startup or after a reconnect) fetch their roles from Discord before refusing access.
+    pub async fn user_has_access_or_fetch(
+        self,
+        user_id: Id<UserMarker>,
+        guild_id: Id<GuildMarker>,
+        cache: &Cache,
+        discord: &DiscordClient,
+    ) -> Result<bool, Error> {
+        self.user_has_access_or_else(user_id, guild_id, cache, || async {
+            let response = match discord.guild_member(guild_id, user_id).await {
+                Ok(response) => response,
+                Err(error) => {
+                    if let ErrorType::Response { status, .. } = error.kind() {
+                        if status.get() == 404 {
+                            return Ok(None);
+                        }
+                    }
+                    return Err(Error::from(error).context("failed to fetch the member"));
+                }
+            };
+            Ok(Some(response.model().await.context("failed to parse the member")?.roles))
+        })
+        .await
+    }
+
+    async fn user_has_access_or_else<F, Fut>(
+        self,
+        user_id: Id<UserMarker>,
+        guild_id: Id<GuildMarker>,
+        cache: &Cache,
+        fetch_roles: F,
+    ) -> Result<bool, Error>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Option<Vec<Id<RoleMarker>>>, Error>>,
+    {
+        if self.user_has_access(user_id, guild_id, cache) {
+            return Ok(true);
+        }
+
+        let is_cached = cache.with(|cache| cache.member(guild_id, user_id).is_some());
+        if is_cached || !matches!(self, Access::SubOnly | Access::ModOnly) {
+            return Ok(false);
+        }
+
+        let Some(roles) = cache.fetched_member_roles(guild_id, user_id, fetch_roles).await? else {
+            return Ok(false);
+        };
+
+        Ok(cache.with(|cache| self.roles_have_access(user_id, guild_id, &roles, cache)))
+    }
+
+    /// The access check for a member that isn't in the cache but whose roles are known.
+    fn roles_have_access(
+        self,
+        user_id: Id<UserMarker>,
+        guild_id: Id<GuildMarker>,
+        roles: &[Id<RoleMarker>],
+        cache: &InMemoryCache,
+    ) -> bool {
+        match self {
+            Access::SubOnly => {
+                roles.iter().filter_map(|&role_id| cache.role(role_id)).any(|role| role.color != 0)
+            }
+            Access::ModOnly => {
+                let is_owner =
+                    cache.guild(guild_id).is_some_and(|guild| guild.owner_id() == user_id);
+                // The `@everyone` role has the same ID as the guild.
+                is_owner
+                    || roles
+                        .iter()
+                        .copied()
+                        .chain(Some(guild_id.cast()))
+                        .filter_map(|role_id| cache.role(role_id))
+                        .any(|role| role.permissions.contains(Permissions::ADMINISTRATOR))
+            }
+            Access::All | Access::OwnerOnly => false,
+        }
+    }
+
     fn refuse_reason(self) -> &'static str {
         match self {
             Access::All => "That is a unrestricted command.",
@@ -131,12 +240,96 @@ impl Args {
 
 pub struct Commands<'a> {
     handlers: &'a [(Regex, Box<dyn CommandHandler>)],
+    errors: &'a RecentErrors,
+    uses: &'a RecentUses,
 }
 
 impl<'a> Commands<'a> {
     pub fn iter<'b>(&'b self) -> impl Iterator<Item = &'a dyn CommandHandler> + 'b {
         self.handlers.iter().map(|(_, handler)| &**handler)
     }
+
+    /// The most recent command handler errors, oldest first.
+    pub fn recent_errors(&self) -> Vec<CommandError> {
+        self.errors.snapshot()
+    }
+
+    /// The commands run since `since`, oldest first. Only the last `MAX_USE_AGE` is kept.
+    pub fn uses_since(&self, since: DateTime<Utc>) -> Vec<CommandUse> {
+        self.uses.since(since)
+    }
+}
+
+/// How many of the most recent command handler errors are kept.
+const MAX_RECENT_ERRORS: usize = 10;
+
+/// A command handler that failed.
+#[derive(Debug, Clone)]
+pub struct CommandError {
+    pub command: String,
+    pub error: String,
+    pub time: DateTime<Utc>,
+}
+
+/// The most recent command handler errors, oldest first.
+#[derive(Clone, Default)]
+struct RecentErrors(Arc<Mutex<VecDeque<CommandError>>>);
+
+impl RecentErrors {
+    fn record(&self, error: CommandError) {
+        let mut errors = self.0.lock().unwrap();
+        if errors.len() >= MAX_RECENT_ERRORS {
+            errors.pop_front();
+        }
+        errors.push_back(error);
+    }
+
+    fn snapshot(&self) -> Vec<CommandError> {
+        self.0.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+/// How long the command uses are kept.
+pub const MAX_USE_AGE: chrono::TimeDelta = match chrono::TimeDelta::try_days(7) {
+    Some(delta) => delta,
+    None => panic!("MAX_USE_AGE is invalid"),
+};
+/// How many command uses are kept at most, in case someone gets carried away.
+const MAX_RECENT_USES: usize = 50_000;
+
+/// A command that was run.
+#[derive(Debug, Clone)]
+pub struct CommandUse {
+    pub command: String,
+    pub channel_id: Id<ChannelMarker>,
+    pub time: DateTime<Utc>,
+}
+
+/// The commands run within `MAX_USE_AGE`, oldest first.
+#[derive(Clone, Default)]
+struct RecentUses(Arc<Mutex<VecDeque<CommandUse>>>);
+
+impl RecentUses {
+    fn record(&self, command_use: CommandUse) {
+        let mut uses = self.0.lock().unwrap();
+        let cutoff = command_use.time - MAX_USE_AGE;
+        while uses.front().is_some_and(|oldest| oldest.time < cutoff)
+            || uses.len() >= MAX_RECENT_USES
+        {
+            uses.pop_front();
+        }
+        uses.push_back(command_use);
+    }
+
+    fn since(&self, since: DateTime<Utc>) -> Vec<CommandUse> {
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|command_use| command_use.time >= since)
+            .cloned()
+            .collect()
+    }
 }
 
 #[derive(Clone)]
@@ -153,101 +346,260 @@ pub struct CommandParser {
     cache: Arc<Cache>,
     config: Arc<Config>,
     discord: Arc<DiscordClient>,
-    matcher: Arc<RegexSet>,
     handlers: Arc<Vec<(Regex, Box<dyn CommandHandler>)>>,
+    /// Messages that have already triggered a command, so that editing them doesn't trigger it
+    /// again. Only tracked if `command_edit_window` is set.
+    dispatched: Arc<Mutex<HashMap<Id<MessageMarker>, Instant>>>,
+    /// The most recent command handler errors, for `!errors`.
+    errors: RecentErrors,
+    /// The commands run recently and where, for `!quote blame-channel`.
+    uses: RecentUses,
+    /// Where to record how long the command handlers take, if anywhere.
+    influxdb: Option<InfluxDb>,
+    /// Where to count the commands for scraping, if anywhere.
+    prometheus: Option<Prometheus>,
+    observers: Arc<Vec<Box<dyn MessageObserver>>>,
+}
+
+/// Was `message` edited within `window` of being sent?
+///
+/// Updates that aren't edits, like embeds being resolved, don't have an edit timestamp.
+fn edited_within(message: &Message, window: Duration) -> bool {
+    let Some(edited_at) = message.edited_timestamp else { return false };
+    let elapsed = edited_at.as_micros() - message.timestamp.as_micros();
+    u64::try_from(elapsed).is_ok_and(|elapsed| Duration::from_micros(elapsed) <= window)
+}
+
+/// The message from `event` and the index of the handler it should be dispatched to.
+fn command_for<'a, 'p>(
+    config: &Config,
+    patterns: impl IntoIterator<Item = &'p Regex>,
+    dispatched: &Mutex<HashMap<Id<MessageMarker>, Instant>>,
+    event: &'a Event,
+) -> Option<(&'a Message, usize)> {
+    let message = match event {
+        Event::MessageCreate(event) => {
+            let MessageCreate(ref message) = **event;
+            message
+        }
+        Event::MessageUpdate(event) => {
+            let window = config.command_edit_window?;
+            if !edited_within(&event.0, window) {
+                return None;
+            }
+            &event.0
+        }
+        _ => return None,
+    };
+
+    let i = matching_handler(config, patterns, message)?;
+
+    if let Some(window) = config.command_edit_window {
+        let mut dispatched = dispatched.lock().unwrap();
+        dispatched.retain(|_, dispatched_at| dispatched_at.elapsed() <= window);
+        if dispatched.insert(message.id, Instant::now()).is_some() {
+            return None;
+        }
+    }
+
+    Some((message, i))
 }
 
 impl CommandParser {
     pub fn builder() -> Builder {
-        Builder { handlers: vec![] }
+        Builder { handlers: vec![], influxdb: None, prometheus: None, observers: vec![] }
     }
 
-    pub async fn on_event(&self, handler_tx: &Sender<JoinHandle<()>>, event: &Event) {
-        let Event::MessageCreate(event) = event else { return };
-        let MessageCreate(ref message) = **event;
+    /// The compiled patterns of the handlers, in the order they're tried.
+    fn patterns(&self) -> impl Iterator<Item = &Regex> {
+        self.handlers.iter().map(|(pattern, _)| pattern)
+    }
 
-        if message.author.bot {
-            return;
+    pub async fn on_event(&self, handler_tx: &Sender<Task>, event: &Event) {
+        if let Event::MessageCreate(event) = event {
+            if !event.author.bot && !self.observers.is_empty() {
+                let _ = handler_tx
+                    .send((
+                        "message observers".into(),
+                        tokio::spawn({
+                            let parser = self.clone();
+                            let message = event.0.clone();
+
+                            async move {
+                                parser.observe(&message).await;
+                            }
+                        }),
+                    ))
+                    .await;
+            }
         }
 
-        if let Some(i) = self.matcher.matches(&message.content).into_iter().next() {
+        if let Some((message, i)) =
+            command_for(&self.config, self.patterns(), &self.dispatched, event)
+        {
             let _ = handler_tx
-                .send(tokio::spawn({
-                    let cache = self.cache.clone();
-                    let config = self.config.clone();
-                    let discord = self.discord.clone();
-                    let handlers = self.handlers.clone();
-                    let message = message.clone();
-
-                    async move {
-                        let Some((pattern, handler)) = handlers.get(i) else { return };
-
-                        let span = tracing::info_span!(
-                            "handle_command",
-                            handler.name = handler.name(),
-                            message.content = message.content.as_str(),
-                            message.id = message.id.get(),
-                            message.author.id = message.author.id.get(),
-                            message.author.name = message.author.name.as_str(),
-                            message.author.discriminator = message.author.discriminator,
-                        );
-
-                        async {
-                            info!("Command received");
-
-                            let guild_id = message.guild_id.unwrap_or(config.guild);
-                            let access = handler.access();
-                            if !access.user_has_access(message.author.id, guild_id, &cache) {
-                                info!(?access, guild.id = guild_id.get(), "refusing access");
-
-                                if let Err(error) =
-                                    refuse_access(&discord, message.channel_id, message.id, access)
-                                        .await
-                                {
-                                    error!(?error, "failed to report access refusal to the user");
-                                }
-
-                                return;
-                            }
+                .send((
+                    "command".into(),
+                    tokio::spawn({
+                        let parser = self.clone();
+                        let message = message.clone();
 
-                            let args = (pattern.captures_len() > 1)
-                                .then_some(())
-                                .and_then(|()| pattern.captures(&message.content))
-                                .map_or_else(Args::empty, |captures| {
-                                    Args::from_captures(&captures)
-                                });
-
-                            let cmds = Commands { handlers: &handlers };
-
-                            if let Err(error) = handler
-                                .handle(&cache, &config, &discord, cmds, &message, &args)
-                                .await
-                            {
-                                error!(?error, "command handler failed");
-                                if let Err(error) =
-                                    error_feedback(&discord, message.channel_id, message.id, error)
-                                        .await
-                                {
-                                    error!(?error, "failed to report the error to the user");
-                                }
-                            } else {
-                                info!("Command processed successfully");
-                            }
+                        async move {
+                            parser.dispatch(i, &message).await;
                         }
-                        .instrument(span)
-                        .await;
-                    }
-                }))
+                    }),
+                ))
                 .await;
         }
     }
+
+    /// Run `message` through the same matching, access check, and handler as [`on_event`] but
+    /// wait for the handler to finish and return the outcome.
+    ///
+    /// [`on_event`]: CommandParser::on_event
+    #[cfg(test)]
+    pub async fn dispatch_message(&self, message: &Message) -> Dispatched {
+        match matching_handler(&self.config, self.patterns(), message) {
+            Some(i) => self.dispatch(i, message).await,
+            None => Dispatched::NoMatch,
+        }
+    }
+
+    async fn observe(&self, message: &Message) {
+        for observer in self.observers.iter() {
+            if let Err(error) =
+                observer.on_message(&self.cache, &self.config, &self.discord, message).await
+            {
+                error!(?error, message.id = message.id.get(), "message observer failed");
+            }
+        }
+    }
+
+    /// Run the `i`th handler on `message`, unless the author isn't allowed to use the command.
+    async fn dispatch(&self, i: usize, message: &Message) -> Dispatched {
+        let Self { cache, config, discord, handlers, errors, uses, influxdb, prometheus, .. } =
+            self;
+        let Some((pattern, handler)) = handlers.get(i) else {
+            return Dispatched::Handled(Err(anyhow!("no command handler {i}")));
+        };
+
+        let span = tracing::info_span!(
+            "handle_command",
+            handler.name = handler.name(),
+            message.content = message.content.as_str(),
+            message.id = message.id.get(),
+            message.author.id = message.author.id.get(),
+            message.author.name = message.author.name.as_str(),
+            message.author.discriminator = message.author.discriminator,
+        );
+
+        async {
+            info!("Command received");
+
+            let guild_id = message.guild_id.unwrap_or(config.guild);
+            let access = handler.access();
+            let has_access = access
+                .user_has_access_or_fetch(message.author.id, guild_id, cache, discord)
+                .await
+                .unwrap_or_else(|error| {
+                    error!(?error, "failed to check access");
+                    false
+                });
+            if !has_access {
+                info!(?access, guild.id = guild_id.get(), "refusing access");
+
+                if let Err(error) =
+                    refuse_access(discord, message.channel_id, message.id, access).await
+                {
+                    error!(?error, "failed to report access refusal to the user");
+                }
+
+                return Dispatched::Refused(access);
+            }
+
+            let args = (pattern.captures_len() > 1)
+                .then_some(())
+                .and_then(|()| pattern.captures(strip_prefix(config, message)?))
+                .map_or_else(Args::empty, |captures| Args::from_captures(&captures));
+
+            let cmds = Commands { handlers, errors, uses };
+            let command: String =
+                handler.help().map_or_else(|| handler.name().into(), |help| help.name.into());
+
+            let started = Instant::now();
+            let result = handler.handle(cache, config, discord, cmds, message, &args).await;
+            if let Some(influxdb) = influxdb {
+                crate::metrics::on_command(influxdb, &command, started.elapsed());
+            }
+            if let Some(prometheus) = prometheus {
+                prometheus.on_command(&command, started.elapsed());
+            }
+            uses.record(CommandUse {
+                command: command.clone(),
+                channel_id: message.channel_id,
+                time: Utc::now(),
+            });
+
+            if let Err(ref error) = result {
+                error!(?error, "command handler failed");
+                errors.record(CommandError {
+                    command,
+                    error: format!("{error:#}"),
+                    time: Utc::now(),
+                });
+                if let Err(error) =
+                    error_feedback(discord, message.channel_id, message.id, error).await
+                {
+                    error!(?error, "failed to report the error to the user");
+                }
+            } else {
+                info!("Command processed successfully");
+            }
+
+            Dispatched::Handled(result)
+        }
+        .instrument(span)
+        .await
+    }
+}
+
+/// The outcome of dispatching a message to the command handlers.
+// Only the tests look at the outcome, the handlers already log it.
+#[allow(dead_code)]
+#[derive(Debug)]
+pub enum Dispatched {
+    /// The message isn't a command.
+    NoMatch,
+    /// The author isn't allowed to use the command.
+    Refused(Access),
+    /// The command handler ran and returned this result.
+    Handled(Result<(), Error>),
+}
+
+/// The command in `message` with the prefix of the guild it was sent in stripped.
+fn strip_prefix<'a>(config: &Config, message: &'a Message) -> Option<&'a str> {
+    message.content.trim_start().strip_prefix(config.command_prefix_for(message.guild_id))
+}
+
+/// The index of the handler for `message`, if it's a command.
+fn matching_handler<'a>(
+    config: &Config,
+    patterns: impl IntoIterator<Item = &'a Regex>,
+    message: &Message,
+) -> Option<usize> {
+    if message.author.bot {
+        return None;
+    }
+
+    let command = strip_prefix(config, message)?;
+    patterns.into_iter().position(|pattern| pattern.is_match(command))
 }
 
 async fn error_feedback(
     discord: &DiscordClient,
     channel_id: Id<ChannelMarker>,
     message_id: Id<MessageMarker>,
-    error: Error,
+    error: &Error,
 ) -> Result<(), Error> {
     discord
         .create_message(channel_id)
@@ -276,6 +628,9 @@ pub async fn refuse_access(
 
 pub struct Builder {
     handlers: Vec<Box<dyn CommandHandler>>,
+    influxdb: Option<InfluxDb>,
+    prometheus: Option<Prometheus>,
+    observers: Vec<Box<dyn MessageObserver>>,
 }
 
 impl Builder {
@@ -284,6 +639,16 @@ impl Builder {
         self
     }
 
+    pub fn commands<C: CommandHandler + 'static>(
+        mut self,
+        commands: impl IntoIterator<Item = C>,
+    ) -> Self {
+        for command in commands {
+            self.handlers.push(Box::new(command));
+        }
+        self
+    }
+
     pub fn command_opt(mut self, command: Option<impl CommandHandler + 'static>) -> Self {
         if let Some(command) = command {
             self.handlers.push(Box::new(command));
@@ -291,12 +656,33 @@ impl Builder {
         self
     }
 
-    fn expand_pattern(prefix: &str, pattern: &str) -> Result<Regex, Error> {
-        let prefix = regex::escape(prefix);
+    /// Pass every message that isn't from a bot to `observer`.
+    pub fn observer(mut self, observer: impl MessageObserver + 'static) -> Self {
+        self.observers.push(Box::new(observer));
+        self
+    }
+
+    /// Record the latency of every command in `influxdb`.
+    pub fn metrics(mut self, influxdb: Option<InfluxDb>) -> Self {
+        self.influxdb = influxdb;
+        self
+    }
+
+    /// Count every command and its latency in `prometheus`.
+    pub fn prometheus(mut self, prometheus: Option<Prometheus>) -> Self {
+        self.prometheus = prometheus;
+        self
+    }
+
+    /// Compile the pattern to match a message with the command prefix already stripped.
+    pub(crate) fn expand_pattern(pattern: &str, case_insensitive: bool) -> Result<Regex, Error> {
         let expanded = pattern.replace(' ', r"(?:\s+)");
-        Regex::new(&format!(r"^\s*{prefix}\s*{expanded}\s*$")).map_err(|err| {
-            Error::new(err).context(format!("failed to compile pattern {pattern:?}"))
-        })
+        RegexBuilder::new(&format!(r"^\s*{expanded}\s*$"))
+            .case_insensitive(case_insensitive)
+            .build()
+            .map_err(|err| {
+                Error::new(err).context(format!("failed to compile pattern {pattern:?}"))
+            })
     }
 
     pub fn build(
@@ -309,16 +695,506 @@ impl Builder {
             .handlers
             .into_iter()
             .map(|handler| {
-                let pattern = Self::expand_pattern(&config.command_prefix, handler.pattern())?;
+                let pattern = Self::expand_pattern(handler.pattern(), handler.case_insensitive())?;
                 Ok((pattern, handler))
             })
             .collect::<Result<Vec<_>, Error>>()
             .context("failed to expand patterns")?;
 
-        let matcher = RegexSet::new(handlers.iter().map(|(pattern, _)| pattern.as_str()))
-            .context("failed to build the matcher")?;
-        let matcher = Arc::new(matcher);
+        Ok(CommandParser {
+            cache,
+            config,
+            discord,
+            handlers: Arc::new(handlers),
+            dispatched: Arc::new(Mutex::new(HashMap::new())),
+            errors: RecentErrors::default(),
+            uses: RecentUses::default(),
+            influxdb: self.influxdb,
+            prometheus: self.prometheus,
+            observers: Arc::new(self.observers),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    use anyhow::Error;
+    use google_calendar3::hyper_rustls::HttpsConnectorBuilder;
+    use regex::Regex;
+    use serde_json::json;
+    use twilight_http::Client as DiscordClient;
+    use twilight_model::channel::Message;
+    use twilight_model::gateway::event::Event;
+    use twilight_model::gateway::payload::incoming::{MessageCreate, MessageUpdate, RoleCreate};
+    use twilight_model::guild::{Permissions, Role, RoleFlags};
+    use twilight_model::id::marker::MessageMarker;
+    use twilight_model::id::Id;
+
+    use super::{
+        command_for, Access, Args, Builder, CommandHandler, CommandUse, Commands, Dispatched, Help,
+        RecentUses, MAX_RECENT_ERRORS, MAX_USE_AGE,
+    };
+    use crate::cache::Cache;
+    use crate::config::Config;
+    use crate::influxdb::InfluxDb;
+
+    fn role(id: u64, color: u32) -> Event {
+        Event::RoleCreate(RoleCreate {
+            guild_id: Id::new(1),
+            role: Role {
+                color,
+                hoist: false,
+                icon: None,
+                id: Id::new(id),
+                managed: false,
+                mentionable: false,
+                name: format!("role {id}"),
+                permissions: Permissions::empty(),
+                position: 0,
+                flags: RoleFlags::empty(),
+                tags: None,
+                unicode_emoji: None,
+            },
+        })
+    }
+
+    #[tokio::test]
+    async fn fetch_missing_member() {
+        let cache = Cache::new(Id::new(1));
+        cache.update(&role(1, 0));
+        cache.update(&role(2, 0x1f8b4c));
+
+        let user_id = Id::new(3);
+        let guild_id = Id::new(1);
+        assert!(!Access::SubOnly.user_has_access(user_id, guild_id, &cache));
+
+        let has_access = Access::SubOnly
+            .user_has_access_or_else(user_id, guild_id, &cache, || async {
+                Ok(Some(vec![Id::new(2)]))
+            })
+            .await
+            .unwrap();
+        assert!(has_access);
+
+        // The roles are remembered.
+        let has_access = Access::SubOnly
+            .user_has_access_or_else(user_id, guild_id, &cache, || async {
+                Err(anyhow::anyhow!("should not be fetched again"))
+            })
+            .await
+            .unwrap();
+        assert!(has_access);
+
+        let has_access = Access::ModOnly
+            .user_has_access_or_else(user_id, guild_id, &cache, || async { unreachable!() })
+            .await
+            .unwrap();
+        assert!(!has_access);
+
+        // So is not being a member at all.
+        let stranger = Id::new(4);
+        for _ in 0..2 {
+            let has_access = Access::ModOnly
+                .user_has_access_or_else(stranger, guild_id, &cache, || async {
+                    static FETCHED: AtomicBool = AtomicBool::new(false);
+                    assert!(!FETCHED.swap(true, Ordering::SeqCst), "fetched again");
+                    Ok(None)
+                })
+                .await
+                .unwrap();
+            assert!(!has_access);
+        }
+    }
+
+    struct Parser {
+        config: Config,
+        patterns: Vec<Regex>,
+        dispatched: Mutex<HashMap<Id<MessageMarker>, Instant>>,
+    }
+
+    impl Parser {
+        fn new(extra: &str) -> Self {
+            Self {
+                config: Config::for_tests(extra),
+                patterns: vec![Builder::expand_pattern("ping", false).unwrap()],
+                dispatched: Mutex::new(HashMap::new()),
+            }
+        }
+
+        fn command_for<'a>(&self, event: &'a Event) -> Option<(&'a Message, usize)> {
+            command_for(&self.config, &self.patterns, &self.dispatched, event)
+        }
+    }
+
+    fn message(content: &str, edited_timestamp: Option<&str>) -> Message {
+        guild_message(content, edited_timestamp, None)
+    }
+
+    fn guild_message(
+        content: &str,
+        edited_timestamp: Option<&str>,
+        guild_id: Option<&str>,
+    ) -> Message {
+        serde_json::from_value(json!({
+            "attachments": [],
+            "author": {
+                "avatar": null,
+                "discriminator": "0",
+                "id": "2",
+                "username": "qrpth",
+            },
+            "channel_id": "3",
+            "content": content,
+            "edited_timestamp": edited_timestamp,
+            "embeds": [],
+            "guild_id": guild_id,
+            "id": "4",
+            "mention_everyone": false,
+            "mention_roles": [],
+            "mentions": [],
+            "pinned": false,
+            "timestamp": "2024-01-01T12:00:00.000000+00:00",
+            "tts": false,
+            "type": 0,
+        }))
+        .unwrap()
+    }
+
+    fn create(content: &str) -> Event {
+        Event::MessageCreate(Box::new(MessageCreate(message(content, None))))
+    }
+
+    fn update(content: &str, edited_timestamp: &str) -> Event {
+        Event::MessageUpdate(Box::new(MessageUpdate(message(content, Some(edited_timestamp)))))
+    }
+
+    #[test]
+    fn edited_command() {
+        let parser = Parser::new("[eris]\ncommand_edit_window=10");
 
-        Ok(CommandParser { cache, config, discord, matcher, handlers: Arc::new(handlers) })
+        assert!(parser.command_for(&create("!pnig")).is_none());
+        assert!(parser.command_for(&update("!ping", "2024-01-01T12:00:05.000000+00:00")).is_some());
+        // Already dispatched.
+        assert!(parser
+            .command_for(&update("!ping ", "2024-01-01T12:00:06.000000+00:00"))
+            .is_none());
+    }
+
+    #[test]
+    fn edited_command_too_late() {
+        let parser = Parser::new("[eris]\ncommand_edit_window=10");
+
+        assert!(parser.command_for(&create("!pnig")).is_none());
+        assert!(parser.command_for(&update("!ping", "2024-01-01T12:00:11.000000+00:00")).is_none());
+    }
+
+    #[test]
+    fn edited_command_disabled() {
+        let parser = Parser::new("");
+
+        assert!(parser.command_for(&create("!pnig")).is_none());
+        assert!(parser.command_for(&update("!ping", "2024-01-01T12:00:05.000000+00:00")).is_none());
+        assert!(parser.command_for(&create("!ping")).is_some());
+    }
+
+    #[test]
+    fn not_redispatched_after_edit() {
+        let parser = Parser::new("[eris]\ncommand_edit_window=10");
+
+        assert!(parser.command_for(&create("!ping")).is_some());
+        assert!(parser.command_for(&update("!ping", "2024-01-01T12:00:05.000000+00:00")).is_none());
+    }
+
+    #[test]
+    fn guild_prefixes() {
+        let parser = Parser::new("[eris.command_prefixes]\n5 = ?\n6 = eris ");
+        let command_for = |content: &str, guild_id: Option<&str>| {
+            let event = Event::MessageCreate(Box::new(MessageCreate(guild_message(
+                content, None, guild_id,
+            ))));
+            parser.command_for(&event).is_some()
+        };
+
+        assert!(command_for("!ping", None));
+        assert!(command_for("!ping", Some("7")));
+        assert!(!command_for("?ping", Some("7")));
+        assert!(command_for("?ping", Some("5")));
+        assert!(command_for(" ? ping", Some("5")));
+        assert!(!command_for("!ping", Some("5")));
+        assert!(command_for("eris ping", Some("6")));
+        assert!(command_for("erisping", Some("6")));
+        assert!(!command_for("?ping", Some("6")));
+    }
+
+    /// The patterns of the invoked commands and the arguments they were invoked with.
+    type Calls = Arc<Mutex<Vec<(&'static str, Vec<Option<String>>)>>>;
+
+    /// A command that records the arguments it was invoked with.
+    struct Recorder {
+        pattern: &'static str,
+        access: Access,
+        case_insensitive: bool,
+        calls: Calls,
+    }
+
+    impl CommandHandler for Recorder {
+        fn pattern(&self) -> &str {
+            self.pattern
+        }
+
+        fn help(&self) -> Option<Help> {
+            None
+        }
+
+        fn access(&self) -> Access {
+            self.access
+        }
+
+        fn case_insensitive(&self) -> bool {
+            self.case_insensitive
+        }
+
+        fn handle<'a>(
+            &'a self,
+            _: &'a Cache,
+            _: &'a Config,
+            _: &'a DiscordClient,
+            _: Commands<'a>,
+            _: &'a Message,
+            args: &'a Args,
+        ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>> {
+            self.calls.lock().unwrap().push((self.pattern, args.matches.clone()));
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    /// A command that fails with its argument as the error.
+    struct Failing;
+
+    impl CommandHandler for Failing {
+        fn pattern(&self) -> &str {
+            r"fail (\d+)"
+        }
+
+        fn help(&self) -> Option<Help> {
+            None
+        }
+
+        fn handle<'a>(
+            &'a self,
+            _: &'a Cache,
+            _: &'a Config,
+            _: &'a DiscordClient,
+            _: Commands<'a>,
+            _: &'a Message,
+            args: &'a Args,
+        ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>> {
+            Box::pin(async move { Err(anyhow::anyhow!("failure {}", args.get(0).unwrap())) })
+        }
+    }
+
+    /// How long [`Sleeping`] takes to handle a command.
+    const SLEEP: Duration = Duration::from_millis(50);
+
+    struct Sleeping;
+
+    impl CommandHandler for Sleeping {
+        fn pattern(&self) -> &str {
+            "sleep"
+        }
+
+        fn help(&self) -> Option<Help> {
+            None
+        }
+
+        fn handle<'a>(
+            &'a self,
+            _: &'a Cache,
+            _: &'a Config,
+            _: &'a DiscordClient,
+            _: Commands<'a>,
+            _: &'a Message,
+            _: &'a Args,
+        ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>> {
+            Box::pin(async move {
+                tokio::time::sleep(SLEEP).await;
+                Ok(())
+            })
+        }
+    }
+
+    fn dispatch_parser(calls: &Calls) -> super::CommandParser {
+        // Building the Discord client needs a default crypto provider, which building the TLS
+        // config for the Google clients installs.
+        let _ = HttpsConnectorBuilder::new().with_native_roots();
+        // Nothing listens on the discard port, so replies fail fast instead of reaching Discord.
+        let discord = DiscordClient::builder().proxy(String::from("127.0.0.1:9"), true).build();
+
+        let recorder = |pattern, access| Recorder {
+            pattern,
+            access,
+            case_insensitive: false,
+            calls: calls.clone(),
+        };
+        let influxdb =
+            InfluxDb::new(reqwest::Client::new(), "http://127.0.0.1:9/", "eris").unwrap();
+        Builder { handlers: vec![], influxdb: None, prometheus: None, observers: vec![] }
+            .command(Recorder { case_insensitive: true, ..recorder("ping", Access::All) })
+            .command(recorder("echo (.+)", Access::All))
+            .command(recorder("shutdown", Access::OwnerOnly))
+            .command(Failing)
+            .command(Sleeping)
+            .metrics(Some(influxdb))
+            .build(
+                Arc::new(Cache::new(Id::new(1))),
+                Arc::new(Config::for_tests("")),
+                Arc::new(discord),
+            )
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn dispatch_to_handler() {
+        let calls = Arc::new(Mutex::new(vec![]));
+        let parser = dispatch_parser(&calls);
+
+        assert!(matches!(
+            parser.dispatch_message(&message("!echo hello", None)).await,
+            Dispatched::Handled(Ok(()))
+        ));
+        assert!(matches!(
+            parser.dispatch_message(&message("!ping", None)).await,
+            Dispatched::Handled(Ok(()))
+        ));
+        assert!(matches!(
+            parser.dispatch_message(&message("!pong", None)).await,
+            Dispatched::NoMatch
+        ));
+
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![("echo (.+)", vec![Some(String::from("hello"))]), ("ping", vec![])]
+        );
+    }
+
+    #[test]
+    fn recent_uses() {
+        let start = chrono::TimeZone::with_ymd_and_hms(&chrono::Utc, 2024, 1, 5, 12, 0, 0).unwrap();
+        let command_use = |minutes| CommandUse {
+            command: String::from("quote"),
+            channel_id: Id::new(minutes as u64 + 1),
+            time: start + chrono::TimeDelta::minutes(minutes),
+        };
+
+        let uses = RecentUses::default();
+        for minutes in [0, 30, 60] {
+            uses.record(command_use(minutes));
+        }
+        let channels =
+            |since| uses.since(since).iter().map(|u| u.channel_id.get()).collect::<Vec<_>>();
+        assert_eq!(channels(start), [1, 31, 61]);
+        assert_eq!(channels(start + chrono::TimeDelta::minutes(30)), [31, 61]);
+
+        // Uses older than `MAX_USE_AGE` are dropped as new ones come in.
+        uses.record(command_use(MAX_USE_AGE.num_minutes() + 30));
+        assert_eq!(channels(start), [31, 61, MAX_USE_AGE.num_minutes() as u64 + 31]);
+    }
+
+    #[tokio::test]
+    async fn dispatch_refused() {
+        let calls = Arc::new(Mutex::new(vec![]));
+        let parser = dispatch_parser(&calls);
+
+        assert!(matches!(
+            parser.dispatch_message(&message("!shutdown", None)).await,
+            Dispatched::Refused(Access::OwnerOnly)
+        ));
+        assert!(calls.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn recent_errors() {
+        let calls = Arc::new(Mutex::new(vec![]));
+        let parser = dispatch_parser(&calls);
+
+        assert!(matches!(
+            parser.dispatch_message(&message("!fail 0", None)).await,
+            Dispatched::Handled(Err(_))
+        ));
+        let errors = parser.errors.snapshot();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].command, std::any::type_name::<Failing>());
+        assert_eq!(errors[0].error, "failure 0");
+
+        // Successful commands aren't recorded.
+        parser.dispatch_message(&message("!ping", None)).await;
+        assert_eq!(parser.errors.snapshot().len(), 1);
+
+        for i in 1..=MAX_RECENT_ERRORS {
+            parser.dispatch_message(&message(&format!("!fail {i}"), None)).await;
+        }
+        let errors = parser.errors.snapshot();
+        assert_eq!(errors.len(), MAX_RECENT_ERRORS);
+        assert_eq!(errors[0].error, "failure 1");
+        assert_eq!(errors[MAX_RECENT_ERRORS - 1].error, format!("failure {MAX_RECENT_ERRORS}"));
+    }
+
+    #[tokio::test]
+    async fn command_latency() {
+        let calls = Arc::new(Mutex::new(vec![]));
+        let parser = dispatch_parser(&calls);
+
+        parser.dispatch_message(&message("!sleep", None)).await;
+
+        let buffered = parser.influxdb.as_ref().unwrap().buffered();
+        let prefix = format!(
+            "command_latency,event=command,command={} count=1,latency=",
+            std::any::type_name::<Sleeping>()
+        );
+        let latency = buffered.strip_prefix(&prefix).unwrap_or_else(|| panic!("{buffered}"));
+        let latency = latency.split(' ').next().unwrap().parse::<f64>().unwrap();
+        assert!(latency >= SLEEP.as_secs_f64(), "{latency}");
+        assert!(latency < 5.0, "{latency}");
+    }
+
+    #[test]
+    fn case_insensitive() {
+        let quote = Builder::expand_pattern("(?:find)?quote(?: (.+))?", true).unwrap();
+        for content in ["quote", "Quote", "QUOTE", "qUoTe", "FindQuote"] {
+            assert!(quote.is_match(content), "{content}");
+        }
+        let captures = quote.captures("QUOTE from:Alex").unwrap();
+        assert_eq!(captures.get(1).unwrap().as_str(), "from:Alex");
+
+        // Patterns are case-sensitive unless the handler opts in.
+        let time = Builder::expand_pattern(r"time (now|[+-]\S+)(?: ([tTdDfFR]))?", false).unwrap();
+        assert!(time.is_match("time now T"));
+        assert!(!time.is_match("TIME now"));
+        assert!(!time.is_match("time NOW"));
+    }
+
+    #[tokio::test]
+    async fn dispatch_case_insensitive() {
+        let calls = Arc::new(Mutex::new(vec![]));
+        let parser = dispatch_parser(&calls);
+
+        for content in ["!Ping", "!PING", "!pInG"] {
+            assert!(matches!(
+                parser.dispatch_message(&message(content, None)).await,
+                Dispatched::Handled(Ok(()))
+            ));
+        }
+        assert!(matches!(
+            parser.dispatch_message(&message("!ECHO Hello", None)).await,
+            Dispatched::NoMatch
+        ));
+        assert_eq!(calls.lock().unwrap().len(), 3);
     }
 }
@@ -0,0 +1,245 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context as _, Error};
+use twilight_http::Client as DiscordClient;
+use twilight_model::channel::message::MessageFlags;
+use twilight_model::channel::Message;
+use twilight_model::id::marker::{MessageMarker, UserMarker};
+use twilight_model::id::Id;
+
+use crate::cache::Cache;
+use crate::command_parser::{Args, CommandHandler, Commands, Help, MessageObserver};
+use crate::config::Config;
+use crate::markdown::escape;
+
+struct Status {
+    reason: Option<String>,
+    /// The `!afk` message, which shouldn't count as the user coming back.
+    message_id: Id<MessageMarker>,
+}
+
+/// Lets users mark themselves as away. Mentioning an away user says so, and the user is back as
+/// soon as they post again.
+///
+/// The statuses are only kept in memory so a restart clears them.
+#[derive(Clone, Default)]
+pub struct Afk {
+    statuses: Arc<Mutex<HashMap<Id<UserMarker>, Status>>>,
+}
+
+impl Afk {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn set(&self, message: &Message, reason: Option<&str>) -> String {
+        self.statuses.lock().unwrap().insert(
+            message.author.id,
+            Status { reason: reason.map(String::from), message_id: message.id },
+        );
+
+        match reason {
+            Some(reason) => format!("You are now AFK: {}", escape(reason)),
+            None => String::from("You are now AFK."),
+        }
+    }
+
+    /// Clear the AFK status of the author of `message` and note which of the mentioned users are
+    /// AFK.
+    fn observe(&self, message: &Message) -> Option<String> {
+        let mut statuses = self.statuses.lock().unwrap();
+        let mut lines = vec![];
+
+        if statuses.get(&message.author.id).is_some_and(|status| status.message_id != message.id) {
+            statuses.remove(&message.author.id);
+            let name = message
+                .member
+                .as_ref()
+                .and_then(|member| member.nick.as_deref())
+                .unwrap_or(&message.author.name);
+            lines.push(format!("Welcome back, {}! You are no longer AFK.", escape(name)));
+        }
+
+        for mention in &message.mentions {
+            if mention.id == message.author.id {
+                continue;
+            }
+            let Some(status) = statuses.get(&mention.id) else { continue };
+            let name = mention
+                .member
+                .as_ref()
+                .and_then(|member| member.nick.as_deref())
+                .unwrap_or(&mention.name);
+            lines.push(match status.reason {
+                Some(ref reason) => format!("{} is AFK: {}", escape(name), escape(reason)),
+                None => format!("{} is AFK.", escape(name)),
+            });
+        }
+
+        if lines.is_empty() {
+            None
+        } else {
+            Some(lines.join("\n"))
+        }
+    }
+}
+
+impl CommandHandler for Afk {
+    fn pattern(&self) -> &str {
+        "afk(?: (.+))?"
+    }
+
+    fn help(&self) -> Option<Help> {
+        Some(Help {
+            name: "afk".into(),
+            usage: "afk [REASON]".into(),
+            summary: "Let others know you're away".into(),
+            description: concat!(
+                "Mark yourself as away from the keyboard. Anyone who mentions you is told that ",
+                "you're away, and why if you give a reason. Posting anything clears the status.",
+            )
+            .into(),
+            examples: Cow::Borrowed(&[
+                Cow::Borrowed("afk"),
+                Cow::Borrowed("afk watching the stream"),
+            ]),
+        })
+    }
+
+    fn handle<'a>(
+        &'a self,
+        _: &'a Cache,
+        _: &'a Config,
+        discord: &'a DiscordClient,
+        _: Commands<'a>,
+        message: &'a Message,
+        args: &'a Args,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>> {
+        Box::pin(async move {
+            let reason = args.get(0).map(str::trim).filter(|reason| !reason.is_empty());
+
+            discord
+                .create_message(message.channel_id)
+                .reply(message.id)
+                .flags(MessageFlags::SUPPRESS_EMBEDS)
+                .content(&self.set(message, reason))
+                .await
+                .context("failed to reply to command")?;
+
+            Ok(())
+        })
+    }
+}
+
+impl MessageObserver for Afk {
+    fn on_message<'a>(
+        &'a self,
+        _: &'a Cache,
+        _: &'a Config,
+        discord: &'a DiscordClient,
+        message: &'a Message,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>> {
+        Box::pin(async move {
+            let Some(content) = self.observe(message) else { return Ok(()) };
+
+            discord
+                .create_message(message.channel_id)
+                .reply(message.id)
+                .flags(MessageFlags::SUPPRESS_EMBEDS)
+                .content(&content)
+                .await
+                .context("failed to report the AFK statuses")?;
+
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use twilight_model::channel::Message;
+
+    use super::Afk;
+
+    fn message(id: u64, author: (u64, &str), mentions: &[(u64, &str)]) -> Message {
+        let user = |(id, name): (u64, &str)| {
+            json!({
+                "avatar": null,
+                "discriminator": "0",
+                "id": id.to_string(),
+                "public_flags": 0,
+                "username": name,
+            })
+        };
+        serde_json::from_value(json!({
+            "attachments": [],
+            "author": user(author),
+            "channel_id": "3",
+            "content": "",
+            "edited_timestamp": null,
+            "embeds": [],
+            "guild_id": "1",
+            "id": id.to_string(),
+            "mention_everyone": false,
+            "mention_roles": [],
+            "mentions": mentions.iter().copied().map(user).collect::<Vec<_>>(),
+            "pinned": false,
+            "timestamp": "2024-01-01T12:00:00.000000+00:00",
+            "tts": false,
+            "type": 0,
+        }))
+        .unwrap()
+    }
+
+    const GRAHAM: (u64, &str) = (10, "graham");
+    const PAUL: (u64, &str) = (11, "paul_saunders");
+
+    #[test]
+    fn set() {
+        let afk = Afk::new();
+
+        let command = message(1, GRAHAM, &[]);
+        assert_eq!(afk.set(&command, Some("making coffee")), "You are now AFK: making coffee");
+        // The command itself doesn't clear the status.
+        assert_eq!(afk.observe(&command), None);
+        assert!(afk.statuses.lock().unwrap().contains_key(&command.author.id));
+
+        assert_eq!(afk.set(&message(2, PAUL, &[]), None), "You are now AFK.");
+    }
+
+    #[test]
+    fn mention() {
+        let afk = Afk::new();
+        afk.set(&message(1, GRAHAM, &[]), Some("making coffee"));
+        afk.set(&message(2, PAUL, &[]), None);
+
+        assert_eq!(
+            afk.observe(&message(3, (12, "cameron"), &[GRAHAM])).as_deref(),
+            Some("graham is AFK: making coffee")
+        );
+        assert_eq!(
+            afk.observe(&message(4, (12, "cameron"), &[GRAHAM, PAUL])).as_deref(),
+            Some("graham is AFK: making coffee\npaul\\_saunders is AFK.")
+        );
+        assert_eq!(afk.observe(&message(5, (12, "cameron"), &[(12, "cameron")])), None);
+        assert_eq!(afk.observe(&message(6, (12, "cameron"), &[])), None);
+    }
+
+    #[test]
+    fn auto_clear() {
+        let afk = Afk::new();
+        afk.set(&message(1, GRAHAM, &[]), Some("making coffee"));
+
+        assert_eq!(
+            afk.observe(&message(2, GRAHAM, &[PAUL])).as_deref(),
+            Some("Welcome back, graham! You are no longer AFK.")
+        );
+        assert_eq!(afk.observe(&message(3, PAUL, &[GRAHAM])), None);
+        assert_eq!(afk.observe(&message(4, GRAHAM, &[])), None);
+    }
+}
@@ -0,0 +1,159 @@
+use std::borrow::Cow;
+use std::future::Future;
+use std::pin::Pin;
+
+use anyhow::{Context, Error};
+use chrono::{TimeDelta, Utc};
+use twilight_http::Client as DiscordClient;
+use twilight_model::channel::Message;
+
+use crate::autotopic::TopicOverride;
+use crate::cache::Cache;
+use crate::command_parser::{Access, Args, CommandHandler, Commands, Help};
+use crate::config::Config;
+use crate::time::HumanReadable;
+
+pub struct Pause {
+    topic_override: TopicOverride,
+}
+
+impl Pause {
+    pub fn new(topic_override: TopicOverride) -> Self {
+        Self { topic_override }
+    }
+}
+
+impl CommandHandler for Pause {
+    fn pattern(&self) -> &str {
+        r"autotopic pause (\S+)(?: (.+))?"
+    }
+
+    fn help(&self) -> Option<Help> {
+        Some(Help {
+            name: "autotopic pause".into(),
+            usage: "autotopic pause <DURATION> [TOPIC]".into(),
+            summary: "Pause the automatic topic updates".into(),
+            description: concat!(
+                "Stop the automatic topic updates for `DURATION` (like `2h` or `1h30m`). If ",
+                "`TOPIC` is given, it's set as the topic and kept in place until the pause ends.",
+            )
+            .into(),
+            examples: Cow::Borrowed(&[
+                Cow::Borrowed("autotopic pause 3h"),
+                Cow::Borrowed("autotopic pause 1d Desert Bus for Hope starts soon!"),
+            ]),
+        })
+    }
+
+    fn access(&self) -> Access {
+        Access::ModOnly
+    }
+
+    fn category(&self) -> &'static str {
+        "Moderation"
+    }
+
+    fn handle<'a>(
+        &'a self,
+        _: &'a Cache,
+        _: &'a Config,
+        discord: &'a DiscordClient,
+        _: Commands<'a>,
+        message: &'a Message,
+        args: &'a Args,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>> {
+        Box::pin(async move {
+            let duration = args
+                .get(0)
+                .and_then(crate::time::parse_duration)
+                .filter(|duration| *duration > TimeDelta::zero());
+            let Some(duration) = duration else {
+                discord
+                    .create_message(message.channel_id)
+                    .reply(message.id)
+                    .content("That's not a valid duration.")
+                    .await
+                    .context("failed to reply to command")?;
+                return Ok(());
+            };
+
+            self.topic_override.pause(crate::autotopic::Pause {
+                until: Utc::now() + duration,
+                topic: args.get(1).map(String::from),
+            });
+
+            discord
+                .create_message(message.channel_id)
+                .reply(message.id)
+                .content(&format!(
+                    "Automatic topic updates paused for {}.",
+                    HumanReadable::new(duration)
+                ))
+                .await
+                .context("failed to reply to command")?;
+
+            Ok(())
+        })
+    }
+}
+
+pub struct Resume {
+    topic_override: TopicOverride,
+}
+
+impl Resume {
+    pub fn new(topic_override: TopicOverride) -> Self {
+        Self { topic_override }
+    }
+}
+
+impl CommandHandler for Resume {
+    fn pattern(&self) -> &str {
+        "autotopic resume"
+    }
+
+    fn help(&self) -> Option<Help> {
+        Some(Help {
+            name: "autotopic resume".into(),
+            usage: "autotopic resume".into(),
+            summary: "Resume the automatic topic updates".into(),
+            description: "Resume the automatic topic updates before the pause ends.".into(),
+            examples: Cow::Borrowed(&[]),
+        })
+    }
+
+    fn access(&self) -> Access {
+        Access::ModOnly
+    }
+
+    fn category(&self) -> &'static str {
+        "Moderation"
+    }
+
+    fn handle<'a>(
+        &'a self,
+        _: &'a Cache,
+        _: &'a Config,
+        discord: &'a DiscordClient,
+        _: Commands<'a>,
+        message: &'a Message,
+        _: &'a Args,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>> {
+        Box::pin(async move {
+            let content = if self.topic_override.resume() {
+                "Automatic topic updates resumed."
+            } else {
+                "Automatic topic updates weren't paused."
+            };
+
+            discord
+                .create_message(message.channel_id)
+                .reply(message.id)
+                .content(content)
+                .await
+                .context("failed to reply to command")?;
+
+            Ok(())
+        })
+    }
+}
@@ -4,15 +4,19 @@ use std::future::Future;
 use std::pin::Pin;
 
 use anyhow::{Context as _, Error};
-use chrono::Utc;
+use chrono::{DateTime, Utc};
+use sea_orm::DatabaseConnection;
 use twilight_http::Client as DiscordClient;
 use twilight_model::channel::message::MessageFlags;
 use twilight_model::channel::Message;
 
 use crate::cache::Cache;
-use crate::calendar::{CalendarHub, FANSTREAMS, LRR};
+use crate::calendar::{CalendarHub, Event, FANSTREAMS, LRR};
 use crate::command_parser::{Args, CommandHandler, Commands, Help};
 use crate::config::Config;
+use crate::schedule_reminders::{
+    load_subscriptions, subscribe, unsubscribe, update_subscriptions, REMINDER_LEAD,
+};
 use crate::time::HumanReadable;
 use crate::tz::Tz;
 
@@ -175,6 +179,10 @@ impl CommandHandler for Next {
         Some(self.mode.help())
     }
 
+    fn category(&self) -> &'static str {
+        "Calendar"
+    }
+
     fn handle<'a>(
         &'a self,
         _: &'a Cache,
@@ -196,3 +204,156 @@ impl CommandHandler for Next {
         })
     }
 }
+
+/// How many upcoming streams can be subscribed to.
+const SUBSCRIBABLE_EVENTS: usize = 10;
+
+pub struct Subscribe {
+    calendar: CalendarHub,
+    db: DatabaseConnection,
+}
+
+impl Subscribe {
+    pub const fn new(calendar: CalendarHub, db: DatabaseConnection) -> Subscribe {
+        Subscribe { calendar, db }
+    }
+}
+
+/// The upcoming streams that haven't started yet, paired with their event IDs.
+fn subscribable(events: Vec<Event>, now: DateTime<Utc>) -> Vec<(String, Event)> {
+    events
+        .into_iter()
+        .filter(|event| event.start > now)
+        .filter_map(|event| Some((event.id.clone()?, event)))
+        .take(SUBSCRIBABLE_EVENTS)
+        .collect()
+}
+
+fn subscription_list(events: &[(String, Event)], subscribed: &[String]) -> String {
+    if events.is_empty() {
+        return String::from("There are no upcoming streams on the schedule.");
+    }
+
+    let mut list = String::from("Upcoming streams:");
+    for (i, (event_id, event)) in events.iter().enumerate() {
+        list.push_str(&format!(
+            "\n{}. {} <t:{}:F>",
+            i + 1,
+            crate::markdown::escape(&event.summary),
+            event.start.timestamp()
+        ));
+        if subscribed.contains(event_id) {
+            list.push_str(" (subscribed)");
+        }
+    }
+    list.push_str("\nGet a reminder with `schedule subscribe NUMBER`.");
+    list
+}
+
+impl CommandHandler for Subscribe {
+    fn pattern(&self) -> &str {
+        r"schedule (un)?subscribe(?: (\d+))?"
+    }
+
+    fn help(&self) -> Option<Help> {
+        Some(Help {
+            name: "schedule subscribe".into(),
+            usage: "schedule [un]subscribe [NUMBER]".into(),
+            summary: "Get a DM before a stream starts".into(),
+            description: format!(
+                "List the upcoming streams, or subscribe to one of them by its number in the list \
+                 to get a direct message {} minutes before it starts. If the stream is cancelled \
+                 you'll get a message about that instead. Unsubscribe with `schedule \
+                 unsubscribe NUMBER`.",
+                REMINDER_LEAD.num_minutes()
+            )
+            .into(),
+            examples: Cow::Borrowed(&[
+                Cow::Borrowed("schedule subscribe"),
+                Cow::Borrowed("schedule subscribe 2"),
+                Cow::Borrowed("schedule unsubscribe 2"),
+            ]),
+        })
+    }
+
+    fn category(&self) -> &'static str {
+        "Calendar"
+    }
+
+    fn handle<'a>(
+        &'a self,
+        _: &'a Cache,
+        _: &'a Config,
+        discord: &'a DiscordClient,
+        _: Commands<'a>,
+        message: &'a Message,
+        args: &'a Args,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>> {
+        Box::pin(async move {
+            let now = Utc::now();
+            let events = crate::calendar::get_upcoming_events(
+                &self.calendar,
+                LRR,
+                now,
+                SUBSCRIBABLE_EVENTS as i32 + 1,
+            )
+            .await
+            .context("failed to get the upcoming events")?;
+            let events = subscribable(events, now);
+            let user_id = message.author.id;
+
+            let content = match args.get(1).map(str::parse::<usize>) {
+                None => {
+                    let subscribed = load_subscriptions(&self.db)
+                        .await?
+                        .into_iter()
+                        .filter(|sub| sub.user_id == user_id)
+                        .map(|sub| sub.event_id)
+                        .collect::<Vec<_>>();
+                    subscription_list(&events, &subscribed)
+                }
+                Some(Err(error)) => format!("Failed to parse the number: {error}"),
+                Some(Ok(i)) => match i.checked_sub(1).and_then(|i| events.get(i)) {
+                    None => {
+                        format!("There's no stream #{i}. See `schedule subscribe` for the list.")
+                    }
+                    Some((event_id, event)) if args.get(0).is_some() => {
+                        let unsubscribed = update_subscriptions(&self.db, |subscriptions| {
+                            unsubscribe(subscriptions, user_id, event_id)
+                        })
+                        .await
+                        .context("failed to unsubscribe")?;
+                        let summary = crate::markdown::escape(&event.summary);
+                        if unsubscribed {
+                            format!("Unsubscribed from {summary}.")
+                        } else {
+                            format!("You aren't subscribed to {summary}.")
+                        }
+                    }
+                    Some((event_id, event)) => {
+                        update_subscriptions(&self.db, |subscriptions| {
+                            subscribe(subscriptions, user_id, event_id, event)
+                        })
+                        .await
+                        .context("failed to subscribe")?;
+                        format!(
+                            "You'll get a DM {} minutes before {} starts <t:{}:R>.",
+                            REMINDER_LEAD.num_minutes(),
+                            crate::markdown::escape(&event.summary),
+                            event.start.timestamp()
+                        )
+                    }
+                },
+            };
+
+            discord
+                .create_message(message.channel_id)
+                .reply(message.id)
+                .flags(MessageFlags::SUPPRESS_EMBEDS)
+                .content(&content)
+                .await
+                .context("failed to reply to command")?;
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,110 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use anyhow::{Context, Error};
+use twilight_http::Client as DiscordClient;
+use twilight_model::channel::message::MessageFlags;
+use twilight_model::channel::Message;
+use twilight_validate::message::MESSAGE_CONTENT_LENGTH_MAX;
+
+use crate::cache::Cache;
+use crate::command_parser::{Access, Args, CommandError, CommandHandler, Commands};
+use crate::config::Config;
+use crate::shorten::shorten;
+
+/// How much of each error message is shown.
+const ERROR_LENGTH: usize = 150;
+
+pub struct Errors;
+
+impl Errors {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// The recent errors, one per line, newest last.
+fn list_errors(errors: &[CommandError]) -> String {
+    if errors.is_empty() {
+        return String::from("No commands have failed since the last restart.");
+    }
+
+    let list = errors
+        .iter()
+        .map(|error| {
+            format!(
+                "<t:{}:R> `{}`: {}",
+                error.time.timestamp(),
+                error.command.replace('`', ""),
+                crate::markdown::escape(&shorten(&error.error, ERROR_LENGTH)),
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    shorten(&list, MESSAGE_CONTENT_LENGTH_MAX).into_owned()
+}
+
+impl CommandHandler for Errors {
+    fn pattern(&self) -> &str {
+        "errors"
+    }
+
+    fn help(&self) -> Option<crate::command_parser::Help> {
+        None
+    }
+
+    fn access(&self) -> Access {
+        Access::OwnerOnly
+    }
+
+    fn handle<'a>(
+        &'a self,
+        _: &'a Cache,
+        _: &'a Config,
+        discord: &'a DiscordClient,
+        commands: Commands<'a>,
+        message: &'a Message,
+        _: &'a Args,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>> {
+        Box::pin(async move {
+            discord
+                .create_message(message.channel_id)
+                .reply(message.id)
+                .flags(MessageFlags::SUPPRESS_EMBEDS)
+                .content(&list_errors(&commands.recent_errors()))
+                .await
+                .context("failed to reply to command")?;
+
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::list_errors;
+    use crate::command_parser::CommandError;
+
+    #[test]
+    fn listing() {
+        assert_eq!(list_errors(&[]), "No commands have failed since the last restart.");
+
+        let error = |command: &str, error: &str| CommandError {
+            command: String::from(command),
+            error: String::from(error),
+            time: Utc.timestamp_opt(1700000000, 0).unwrap(),
+        };
+        assert_eq!(
+            list_errors(&[
+                error("quote", "failed to load the quote: connection refused"),
+                error("live", "*everything* is on fire"),
+            ]),
+            concat!(
+                "<t:1700000000:R> `quote`: failed to load the quote: connection refused\n",
+                r"<t:1700000000:R> `live`: \*everything\* is on fire",
+            )
+        );
+    }
+}
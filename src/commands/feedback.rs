@@ -0,0 +1,188 @@
+use std::borrow::Cow;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Error};
+use chrono::{NaiveDate, NaiveDateTime, Utc};
+use google_sheets4::api::ValueRange;
+use google_sheets4::hyper_rustls::HttpsConnector;
+use google_sheets4::hyper_util::client::legacy::connect::HttpConnector;
+use google_sheets4::Sheets;
+use serde_json::Value;
+use twilight_http::Client;
+use twilight_model::channel::Message;
+use twilight_model::id::marker::UserMarker;
+use twilight_model::id::Id;
+
+use crate::cache::Cache;
+use crate::command_parser::{Args, CommandHandler, Commands, Help};
+use crate::config::Config;
+use crate::contact::spreadsheet_timezone;
+
+/// How often a single user can submit feedback.
+const RATE_LIMIT: Duration = Duration::from_secs(5 * 60);
+
+pub struct Feedback {
+    spreadsheet_id: String,
+    sheets: Sheets<HttpsConnector<HttpConnector>>,
+    last_submitted: Mutex<HashMap<Id<UserMarker>, Instant>>,
+}
+
+impl Feedback {
+    pub fn new(config: &Config, sheets: Sheets<HttpsConnector<HttpConnector>>) -> Option<Self> {
+        Some(Self {
+            spreadsheet_id: config.contact_spreadsheet.clone()?,
+            sheets,
+            last_submitted: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Record a submission by `user_id`, unless they have submitted something recently.
+    fn try_submit(&self, user_id: Id<UserMarker>) -> bool {
+        let mut last_submitted = self.last_submitted.lock().unwrap();
+        last_submitted.retain(|_, submitted_at| submitted_at.elapsed() < RATE_LIMIT);
+        match last_submitted.entry(user_id) {
+            Entry::Occupied(_) => false,
+            Entry::Vacant(entry) => {
+                entry.insert(Instant::now());
+                true
+            }
+        }
+    }
+
+    /// Forget the submission by `user_id` so that they can try again right away.
+    fn release(&self, user_id: Id<UserMarker>) {
+        self.last_submitted.lock().unwrap().remove(&user_id);
+    }
+
+    /// Append the feedback to the contact spreadsheet, timestamped in the spreadsheet's time zone
+    /// like the contact form responses.
+    async fn append(&self, feedback: &str, username: &str) -> Result<(), Error> {
+        let (_, spreadsheet) = self
+            .sheets
+            .spreadsheets()
+            .get(&self.spreadsheet_id)
+            .param("fields", "properties.timeZone")
+            .doit()
+            .await
+            .context("failed to fetch the time zone of the contact spreadsheet")?;
+        let timezone = spreadsheet_timezone(&spreadsheet);
+        let timestamp = Utc::now().with_timezone(&&timezone).naive_local();
+
+        self.sheets
+            .spreadsheets()
+            .values_append(feedback_row(timestamp, feedback, username), &self.spreadsheet_id, "A:C")
+            .value_input_option("RAW")
+            .insert_data_option("INSERT_ROWS")
+            .doit()
+            .await
+            .context("failed to append the feedback to the contact spreadsheet")?;
+
+        Ok(())
+    }
+}
+
+/// Convert a timestamp to the spreadsheet serial number format: days since 1899-12-30.
+fn to_serial_number(timestamp: NaiveDateTime) -> f64 {
+    let epoch = NaiveDate::from_ymd_opt(1899, 12, 30).unwrap().and_hms_opt(0, 0, 0).unwrap();
+    (timestamp - epoch).num_milliseconds() as f64 / 86_400_000.0
+}
+
+/// A new row for the contact spreadsheet, in the same layout as the contact form responses.
+fn feedback_row(timestamp: NaiveDateTime, message: &str, username: &str) -> ValueRange {
+    ValueRange {
+        major_dimension: Some(String::from("ROWS")),
+        range: Some(String::from("A:C")),
+        values: Some(vec![vec![
+            Value::from(to_serial_number(timestamp)),
+            Value::from(message),
+            Value::from(username),
+        ]]),
+    }
+}
+
+impl CommandHandler for Feedback {
+    fn pattern(&self) -> &str {
+        r"feedback ((?s:.+))"
+    }
+
+    fn help(&self) -> Option<Help> {
+        Some(Help {
+            name: "feedback".into(),
+            usage: "feedback <MESSAGE>".into(),
+            summary: "Send a message to the moderators".into(),
+            description: concat!(
+                "Send a message to the moderators through the contact form.\n\n",
+                "Works in direct messages too.",
+            )
+            .into(),
+            examples: Cow::Borrowed(&[Cow::Borrowed("feedback The stream is out of sync.")]),
+        })
+    }
+
+    fn handle<'a>(
+        &'a self,
+        _: &'a Cache,
+        _: &'a Config,
+        discord: &'a Client,
+        _: Commands<'a>,
+        message: &'a Message,
+        args: &'a Args,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>> {
+        Box::pin(async move {
+            let feedback = args.get(0).context("feedback missing")?;
+
+            if !self.try_submit(message.author.id) {
+                discord
+                    .create_message(message.channel_id)
+                    .reply(message.id)
+                    .content("You've already sent a message recently. Please try again later.")
+                    .await
+                    .context("failed to reply to command")?;
+                return Ok(());
+            }
+
+            // The slot is held while appending so that a double submission can't slip through.
+            if let Err(error) = self.append(feedback, &message.author.name).await {
+                self.release(message.author.id);
+                return Err(error);
+            }
+
+            discord
+                .create_message(message.channel_id)
+                .reply(message.id)
+                .content("Thanks, your message has been sent to the moderators.")
+                .await
+                .context("failed to reply to command")?;
+
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use serde_json::json;
+
+    use super::feedback_row;
+
+    #[test]
+    fn append_request_body() {
+        let timestamp = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap().and_hms_opt(12, 0, 0).unwrap();
+        let body = feedback_row(timestamp, "=SUM(A1:A2) is not a formula", "qrpth");
+
+        assert_eq!(
+            serde_json::to_value(&body).unwrap(),
+            json!({
+                "majorDimension": "ROWS",
+                "range": "A:C",
+                "values": [[45292.5, "=SUM(A1:A2) is not a formula", "qrpth"]],
+            })
+        );
+    }
+}
@@ -1,4 +1,5 @@
 use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::fmt::Write;
 use std::future::Future;
 use std::pin::Pin;
@@ -8,6 +9,7 @@ use twilight_http::Client as DiscordClient;
 use twilight_model::channel::message::embed::EmbedField;
 use twilight_model::channel::Message;
 use twilight_util::builder::embed::EmbedBuilder;
+use twilight_validate::embed::FIELD_VALUE_LENGTH;
 
 use crate::cache::Cache;
 use crate::command_parser::{Args, CommandHandler, Commands};
@@ -15,6 +17,53 @@ use crate::config::Config;
 
 pub struct Help;
 
+/// Group the commands by category, with both the categories and the commands within them sorted
+/// by name.
+fn group_by_category(
+    commands: impl Iterator<Item = (&'static str, crate::command_parser::Help)>,
+) -> Vec<(&'static str, Vec<crate::command_parser::Help>)> {
+    let mut categories = BTreeMap::<&'static str, Vec<crate::command_parser::Help>>::new();
+    for (category, help) in commands {
+        categories.entry(category).or_default().push(help);
+    }
+    categories
+        .into_iter()
+        .map(|(category, mut helps)| {
+            helps.sort_by(|a, b| a.name.cmp(&b.name));
+            (category, helps)
+        })
+        .collect()
+}
+
+/// The embed fields listing the commands in a category. A category that doesn't fit in a single
+/// field is continued in the next ones.
+fn category_fields(
+    prefix: &str,
+    category: &str,
+    helps: &[crate::command_parser::Help],
+) -> Vec<EmbedField> {
+    let mut values = vec![];
+    let mut value = String::new();
+    for help in helps {
+        let line = format!("`{prefix}{}`: {}\n", help.name, help.summary);
+        if !value.is_empty() && value.chars().count() + line.chars().count() > FIELD_VALUE_LENGTH {
+            values.push(std::mem::take(&mut value));
+        }
+        value.push_str(&line);
+    }
+    values.push(value);
+
+    values
+        .into_iter()
+        .enumerate()
+        .map(|(i, value)| EmbedField {
+            inline: false,
+            name: if i == 0 { category.into() } else { format!("{category} (cont.)") },
+            value,
+        })
+        .collect()
+}
+
 impl Help {
     pub fn new() -> Self {
         Self
@@ -28,30 +77,21 @@ impl Help {
         commands: Commands<'_>,
         message: &Message,
     ) -> Result<(), Error> {
-        let mut embed = EmbedBuilder::new().description(concat!(
-            "To get help with an individual command, pass its name as an argument to this ",
-            "command. Simple text response commands (like `!advice`) are not listed here, ",
-            "for those see [LRRbot's website](https://lrrbot.com/help#help-section-text).",
-        ));
+        let mut embed = EmbedBuilder::new().description(&config.help_intro);
 
         let guild_id = message.guild_id.unwrap_or(config.guild);
 
-        let mut fields = vec![];
-        for cmd in commands.iter() {
-            if cmd.access().user_has_access(message.author.id, guild_id, cache) {
-                if let Some(help) = cmd.help() {
-                    fields.push(EmbedField {
-                        inline: true,
-                        name: format!("{}{}", config.command_prefix, help.name),
-                        value: help.summary.into(),
-                    });
-                }
+        let listed = commands
+            .iter()
+            .filter(|cmd| cmd.access().user_has_access(message.author.id, guild_id, cache))
+            .filter_map(|cmd| Some((cmd.category(), cmd.help()?)));
+        for (category, helps) in group_by_category(listed) {
+            for field in
+                category_fields(config.command_prefix_for(message.guild_id), category, &helps)
+            {
+                embed = embed.field(field);
             }
         }
-        fields.sort_by(|a, b| a.name.cmp(&b.name));
-        for field in fields {
-            embed = embed.field(field);
-        }
 
         discord
             .create_message(message.channel_id)
@@ -83,12 +123,13 @@ impl Help {
 
         match commands.iter().filter_map(CommandHandler::help).find(|help| help.name == command) {
             Some(help) => {
+                let prefix = config.command_prefix_for(message.guild_id);
                 let examples = help.examples.iter().fold(String::new(), |mut examples, example| {
-                    writeln!(examples, "`{}{example}`", config.command_prefix).unwrap();
+                    writeln!(examples, "`{prefix}{example}`").unwrap();
                     examples
                 });
                 let mut embed = EmbedBuilder::new()
-                    .title(format!("`{}{}`", config.command_prefix, help.usage))
+                    .title(format!("`{prefix}{}`", help.usage))
                     .description(help.description);
                 if !examples.is_empty() {
                     embed = embed.field(EmbedField {
@@ -155,3 +196,81 @@ impl CommandHandler for Help {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use twilight_validate::embed::FIELD_VALUE_LENGTH;
+
+    use super::{category_fields, group_by_category};
+    use crate::command_parser::Help;
+
+    fn help(name: &'static str) -> Help {
+        Help {
+            name: name.into(),
+            usage: name.into(),
+            summary: format!("Summary of {name}").into(),
+            description: Cow::Borrowed(""),
+            examples: Cow::Borrowed(&[]),
+        }
+    }
+
+    #[test]
+    fn grouping() {
+        let groups = group_by_category(
+            [
+                ("Quotes", help("quote")),
+                ("General", help("time")),
+                ("Calendar", help("nextfan")),
+                ("General", help("help")),
+                ("Quotes", help("quote details")),
+                ("Calendar", help("next")),
+            ]
+            .into_iter(),
+        );
+        let names = groups
+            .iter()
+            .map(|(category, helps)| {
+                (*category, helps.iter().map(|help| &help.name[..]).collect::<Vec<_>>())
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            names,
+            vec![
+                ("Calendar", vec!["next", "nextfan"]),
+                ("General", vec!["help", "time"]),
+                ("Quotes", vec!["quote", "quote details"]),
+            ]
+        );
+
+        let fields = category_fields("!", groups[0].0, &groups[0].1);
+        assert_eq!(fields.len(), 1);
+        assert_eq!(fields[0].name, "Calendar");
+        assert_eq!(fields[0].value, "`!next`: Summary of next\n`!nextfan`: Summary of nextfan\n");
+        assert!(!fields[0].inline);
+    }
+
+    #[test]
+    fn long_category() {
+        let helps = (0..40)
+            .map(|i| Help {
+                name: format!("quote command-{i}").into(),
+                usage: Cow::Borrowed(""),
+                summary: "A rather long summary of what this command does".into(),
+                description: Cow::Borrowed(""),
+                examples: Cow::Borrowed(&[]),
+            })
+            .collect::<Vec<_>>();
+        let fields = category_fields("!", "Quotes", &helps);
+
+        assert!(fields.len() > 1);
+        assert_eq!(fields[0].name, "Quotes");
+        assert!(fields[1..].iter().all(|field| field.name == "Quotes (cont.)"));
+        for field in &fields {
+            assert!(field.value.chars().count() <= FIELD_VALUE_LENGTH, "{}", field.value);
+        }
+        assert_eq!(fields.iter().map(|field| field.value.lines().count()).sum::<usize>(), 40);
+    }
+}
@@ -0,0 +1,212 @@
+//! Commands that reply with values from an HTTP API, set up in the config file. For example:
+//!
+//! ```ini
+//! [eris.http.weather]
+//! url=https://wttr.in/{args}?format=j1
+//! field.temperature=/current_condition/0/temp_C
+//! field.conditions=/current_condition/0/weatherDesc/0/value
+//! response={args}: {conditions}, {temperature} °C
+//! summary=Post the current weather
+//! ```
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+use anyhow::{Context as _, Error};
+use reqwest::Client;
+use serde_json::Value;
+use twilight_http::Client as DiscordClient;
+use twilight_model::channel::message::MessageFlags;
+use twilight_model::channel::Message;
+use url::Url;
+
+use crate::cache::Cache;
+use crate::command_parser::{Args, CommandHandler, Commands, Help};
+use crate::config::{Config, HttpCommand};
+
+/// How long to wait for the API to respond.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct Http {
+    http: Client,
+    command: HttpCommand,
+    pattern: String,
+}
+
+impl Http {
+    pub fn new(http: Client, command: HttpCommand) -> Self {
+        let pattern = format!("{}(?: (.+))?", regex::escape(&command.name));
+        Self { http, command, pattern }
+    }
+
+    /// The URL to fetch for `args`.
+    fn url(&self, args: &str) -> Result<Url, Error> {
+        let args = url::form_urlencoded::byte_serialize(args.as_bytes()).collect::<String>();
+        let url = strfmt::strfmt(&self.command.url, &HashMap::from([(String::from("args"), args)]))
+            .context("failed to fill in the URL template")?;
+        Url::parse(&url).with_context(|| format!("failed to parse the URL {url:?}"))
+    }
+
+    /// The reply to `args` with the values extracted from `json`.
+    fn response(&self, args: &str, json: &Value) -> Result<String, Error> {
+        let mut vars = extract(&self.command.fields, json)?;
+        vars.insert("args".into(), args.into());
+        strfmt::strfmt(&self.command.response, &vars)
+            .context("failed to fill in the response template")
+    }
+}
+
+/// Pick out the values for the template variables in `fields` from `json`.
+fn extract(fields: &[(String, String)], json: &Value) -> Result<HashMap<String, String>, Error> {
+    fields
+        .iter()
+        .map(|(name, pointer)| {
+            let value = match json.pointer(pointer) {
+                Some(Value::String(value)) => value.clone(),
+                Some(Value::Null) | None => {
+                    anyhow::bail!("the response has no value for {name:?} at {pointer:?}")
+                }
+                Some(value) => value.to_string(),
+            };
+            Ok((name.clone(), value))
+        })
+        .collect()
+}
+
+impl CommandHandler for Http {
+    fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    fn help(&self) -> Option<Help> {
+        let summary = match self.command.summary {
+            Some(ref summary) => String::from(summary.strip_suffix('.').unwrap_or(summary)),
+            None => format!(
+                "Look something up on {}",
+                self.command.url.split('/').nth(2).unwrap_or("the web")
+            ),
+        };
+        Some(Help {
+            name: self.command.name.clone().into(),
+            usage: format!("{} [ARGS]", self.command.name).into(),
+            description: format!("{summary}.").into(),
+            summary: summary.into(),
+            examples: Cow::Owned(vec![Cow::Owned(self.command.name.clone())]),
+        })
+    }
+
+    fn handle<'a>(
+        &'a self,
+        _: &'a Cache,
+        _: &'a Config,
+        discord: &'a DiscordClient,
+        _: Commands<'a>,
+        message: &'a Message,
+        args: &'a Args,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>> {
+        Box::pin(async move {
+            let args = args.get(0).map_or("", str::trim);
+
+            let json = self
+                .http
+                .get(self.url(args)?)
+                .timeout(TIMEOUT)
+                .send()
+                .await
+                .context("failed to send the request")?
+                .error_for_status()
+                .context("request failed")?
+                .json::<Value>()
+                .await
+                .context("failed to parse the response")?;
+
+            discord
+                .create_message(message.channel_id)
+                .reply(message.id)
+                .flags(MessageFlags::SUPPRESS_EMBEDS)
+                .content(&self.response(args, &json)?)
+                .await
+                .context("failed to reply to command")?;
+
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{extract, Http};
+    use crate::command_parser::CommandHandler;
+    use crate::config::Config;
+
+    fn weather() -> Http {
+        let config = Config::for_tests(concat!(
+            "[eris.http.weather]\n",
+            "url=https://wttr.in/{args}?format=j1\n",
+            "field.temperature=/current_condition/0/temp_C\n",
+            "field.conditions=/current_condition/0/weatherDesc/0/value\n",
+            "field.humidity=/current_condition/0/humidity\n",
+            "response={args}: {conditions}, {temperature} °C, {humidity}% humidity\n",
+        ));
+        Http::new(reqwest::Client::new(), config.http_commands[0].clone())
+    }
+
+    #[test]
+    fn url() {
+        let weather = weather();
+        assert_eq!(weather.url("Victoria").unwrap().as_str(), "https://wttr.in/Victoria?format=j1");
+        assert_eq!(
+            weather.url("New York?&").unwrap().as_str(),
+            "https://wttr.in/New+York%3F%26?format=j1"
+        );
+    }
+
+    #[test]
+    fn help() {
+        let help = weather().help().unwrap();
+        assert_eq!(help.summary, "Look something up on wttr.in");
+        assert_eq!(help.description, "Look something up on wttr.in.");
+
+        let mut weather = weather();
+        weather.command.summary = Some(String::from("Post the current weather."));
+        let help = weather.help().unwrap();
+        assert_eq!(help.summary, "Post the current weather");
+        assert_eq!(help.description, "Post the current weather.");
+    }
+
+    #[test]
+    fn extraction() {
+        let json = json!({
+            "current_condition": [{
+                "temp_C": "12",
+                "humidity": 81,
+                "weatherDesc": [{"value": "Light rain"}],
+                "uvIndex": null,
+            }],
+        });
+
+        let fields = [
+            (String::from("temperature"), String::from("/current_condition/0/temp_C")),
+            (String::from("humidity"), String::from("/current_condition/0/humidity")),
+        ];
+        let vars = extract(&fields, &json).unwrap();
+        assert_eq!(vars["temperature"], "12");
+        assert_eq!(vars["humidity"], "81");
+
+        let missing = [(String::from("uv"), String::from("/current_condition/0/uvIndex"))];
+        assert!(extract(&missing, &json).is_err());
+        let missing = [(String::from("uv"), String::from("/current_condition/1/uvIndex"))];
+        assert!(extract(&missing, &json).is_err());
+
+        assert_eq!(
+            weather().response("Victoria", &json).unwrap(),
+            "Victoria: Light rain, 12 °C, 81% humidity"
+        );
+        assert!(weather().response("Victoria", &json!({})).is_err());
+    }
+}
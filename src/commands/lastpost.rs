@@ -0,0 +1,109 @@
+use std::borrow::Cow;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use anyhow::{Context as _, Error};
+use reqwest::Client as HttpClient;
+use tokio::sync::OnceCell;
+use twilight_http::Client as DiscordClient;
+use twilight_model::channel::Message;
+
+use crate::announcements::TootAnnouncer;
+use crate::cache::Cache;
+use crate::command_parser::{Args, CommandHandler, Commands, Help};
+use crate::config::Config;
+
+pub struct LastPost {
+    config: Arc<Config>,
+    http_client: HttpClient,
+    announcer: OnceCell<TootAnnouncer>,
+}
+
+impl LastPost {
+    pub fn new(config: Arc<Config>, http_client: HttpClient) -> Self {
+        Self { config, http_client, announcer: OnceCell::new() }
+    }
+
+    async fn announcer(&self) -> Result<&TootAnnouncer, Error> {
+        self.announcer
+            .get_or_try_init(|| TootAnnouncer::new(self.config.clone(), self.http_client.clone()))
+            .await
+            .context("failed to initialize the toot announcer")
+    }
+}
+
+impl CommandHandler for LastPost {
+    fn pattern(&self) -> &str {
+        r"last(?:post|toot|tweet)(?: (\S+))?"
+    }
+
+    fn help(&self) -> Option<Help> {
+        Some(Help {
+            name: "lastpost".into(),
+            usage: "lastpost [ACCOUNT]".into(),
+            summary: "Post the most recent announced social media post".into(),
+            description: concat!(
+                "Post the most recent social media post from `ACCOUNT` that would have been announced.\n\n",
+                "`ACCOUNT` can be omitted if only one account is being watched.",
+            )
+            .into(),
+            examples: Cow::Borrowed(&[
+                Cow::Borrowed("lastpost"),
+                Cow::Borrowed("lastpost loadingreadyrun@kind.social"),
+            ]),
+        })
+    }
+
+    fn handle<'a>(
+        &'a self,
+        _: &'a Cache,
+        config: &'a Config,
+        discord: &'a DiscordClient,
+        _: Commands<'a>,
+        message: &'a Message,
+        args: &'a Args,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>> {
+        Box::pin(async move {
+            let username = match args.get(0) {
+                Some(username) => username,
+                None if config.mastodon_users.len() == 1 => {
+                    config.mastodon_users.keys().next().unwrap().as_str()
+                }
+                None => {
+                    let mut usernames =
+                        config.mastodon_users.keys().map(String::as_str).collect::<Vec<_>>();
+                    usernames.sort();
+                    discord
+                        .create_message(message.channel_id)
+                        .reply(message.id)
+                        .content(&format!(
+                            "Which account? Watching: {}",
+                            crate::markdown::escape(&usernames.join(", "))
+                        ))
+                        .await
+                        .context("failed to reply to command")?;
+                    return Ok(());
+                }
+            };
+
+            let announcer = self.announcer().await?;
+            let content = match announcer.latest_toot(username).await? {
+                Some(content) => content,
+                None if announcer.is_watching(username) => {
+                    format!("No posts from {}.", crate::markdown::escape(username))
+                }
+                None => format!("Not watching {}.", crate::markdown::escape(username)),
+            };
+
+            discord
+                .create_message(message.channel_id)
+                .reply(message.id)
+                .content(&content)
+                .await
+                .context("failed to reply to command")?;
+
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,108 @@
+use std::borrow::Cow;
+use std::fmt::Write;
+use std::future::Future;
+use std::pin::Pin;
+
+use anyhow::{Context, Error};
+use twilight_http::Client as DiscordClient;
+use twilight_model::channel::message::Embed;
+use twilight_model::channel::Message;
+use twilight_util::builder::embed::EmbedBuilder;
+use url::Url;
+
+use crate::cache::Cache;
+use crate::command_parser::{Args, CommandHandler, Commands, Help};
+use crate::config::Config;
+
+pub struct Links {
+    links: Vec<(String, Url)>,
+}
+
+impl Links {
+    pub fn new(config: &Config) -> Option<Self> {
+        if config.links.is_empty() {
+            None
+        } else {
+            Some(Self { links: config.links.clone() })
+        }
+    }
+
+    fn embed(&self) -> Result<Embed, Error> {
+        let mut description = String::new();
+        for (name, url) in &self.links {
+            let _ = writeln!(description, "[{}]({url})", crate::markdown::escape(name));
+        }
+
+        Ok(EmbedBuilder::new()
+            .title("Useful links")
+            .description(description.trim_end())
+            .validate()
+            .context("links embed failed validation")?
+            .build())
+    }
+}
+
+impl CommandHandler for Links {
+    fn pattern(&self) -> &str {
+        "(?:links|invite)"
+    }
+
+    fn help(&self) -> Option<Help> {
+        Some(Help {
+            name: "links".into(),
+            usage: "links".into(),
+            summary: "Post useful links".into(),
+            description: "Post links to the website, the schedule, the store, and so on.".into(),
+            examples: Cow::Borrowed(&[Cow::Borrowed("links"), Cow::Borrowed("invite")]),
+        })
+    }
+
+    fn handle<'a>(
+        &'a self,
+        _: &'a Cache,
+        _: &'a Config,
+        discord: &'a DiscordClient,
+        _: Commands<'a>,
+        message: &'a Message,
+        _: &'a Args,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>> {
+        Box::pin(async move {
+            discord
+                .create_message(message.channel_id)
+                .reply(message.id)
+                .embeds(&[self.embed()?])
+                .await
+                .context("failed to reply to command")?;
+
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Links;
+    use crate::config::Config;
+
+    #[test]
+    fn embed() {
+        assert!(Links::new(&Config::for_tests("")).is_none());
+
+        let config = Config::for_tests(concat!(
+            "[eris.links]\n",
+            "Website=https://loadingreadyrun.com/\n",
+            "Schedule=https://lrr.cc/schedule\n",
+            "*Store*=https://store.loadingreadyrun.com/\n",
+        ));
+        let embed = Links::new(&config).unwrap().embed().unwrap();
+        assert_eq!(embed.title.as_deref(), Some("Useful links"));
+        assert_eq!(
+            embed.description.as_deref(),
+            Some(concat!(
+                "[Website](https://loadingreadyrun.com/)\n",
+                "[Schedule](https://lrr.cc/schedule)\n",
+                "[\\*Store\\*](https://store.loadingreadyrun.com/)",
+            ))
+        );
+    }
+}
@@ -1,9 +1,21 @@
+pub mod afk;
+pub mod autotopic;
 pub mod calendar;
+pub mod errors;
+pub mod feedback;
 pub mod help;
+pub mod http;
+pub mod lastpost;
+pub mod links;
 pub mod live;
+pub mod poll;
 pub mod quote;
+pub mod serverinfo;
 pub mod static_response;
 pub mod time;
+pub mod timeout;
+pub mod timer;
 pub mod tracing;
+pub mod userinfo;
 pub mod video;
 pub mod voice;
@@ -0,0 +1,154 @@
+use std::borrow::Cow;
+use std::future::Future;
+use std::pin::Pin;
+
+use anyhow::{Context, Error};
+use twilight_http::request::channel::reaction::RequestReactionType;
+use twilight_http::Client as DiscordClient;
+use twilight_model::channel::Message;
+use twilight_util::builder::embed::EmbedBuilder;
+
+use crate::cache::Cache;
+use crate::command_parser::{Args, CommandHandler, Commands, Help};
+use crate::config::Config;
+
+const MIN_OPTIONS: usize = 2;
+const MAX_OPTIONS: usize = 10;
+
+pub struct Poll;
+
+impl Poll {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Split the pipe-separated options.
+fn parse_options(options: &str) -> Result<Vec<&str>, String> {
+    let options = options.split('|').map(str::trim).collect::<Vec<_>>();
+
+    if options.iter().any(|option| option.is_empty()) {
+        return Err(String::from("Poll options can't be empty."));
+    }
+    if options.len() < MIN_OPTIONS {
+        return Err(format!("A poll needs at least {MIN_OPTIONS} options."));
+    }
+    if options.len() > MAX_OPTIONS {
+        return Err(format!("A poll can have at most {MAX_OPTIONS} options."));
+    }
+
+    Ok(options)
+}
+
+/// The emoji used to vote for the `i`th option: 🇦, 🇧, 🇨, ...
+fn option_emoji(i: usize) -> String {
+    let c = u32::try_from(i).ok().and_then(|i| char::from_u32('\u{1F1E6}' as u32 + i));
+    c.expect("too many poll options").to_string()
+}
+
+impl CommandHandler for Poll {
+    fn pattern(&self) -> &str {
+        r#"poll "([^"]+)" (.+)"#
+    }
+
+    fn help(&self) -> Option<Help> {
+        Some(Help {
+            name: "poll".into(),
+            usage: r#"poll "<QUESTION>" <OPTION> | <OPTION> [| <OPTION>...]"#.into(),
+            summary: "Create a poll that is voted on with reactions".into(),
+            description: format!(
+                "Create a poll that is voted on with reactions. A poll has between {MIN_OPTIONS} and {MAX_OPTIONS} options."
+            )
+            .into(),
+            examples: Cow::Borrowed(&[Cow::Borrowed(
+                r#"poll "What should we play next?" Magic | Dominion | Dragon Quest"#,
+            )]),
+        })
+    }
+
+    fn handle<'a>(
+        &'a self,
+        _: &'a Cache,
+        _: &'a Config,
+        discord: &'a DiscordClient,
+        _: Commands<'a>,
+        message: &'a Message,
+        args: &'a Args,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>> {
+        Box::pin(async move {
+            let question = args.get(0).context("question missing")?;
+            let options = match parse_options(args.get(1).context("options missing")?) {
+                Ok(options) => options,
+                Err(error) => {
+                    discord
+                        .create_message(message.channel_id)
+                        .reply(message.id)
+                        .content(&error)
+                        .await
+                        .context("failed to report the parse error")?;
+                    return Ok(());
+                }
+            };
+
+            let emoji = (0..options.len()).map(option_emoji).collect::<Vec<_>>();
+            let description = emoji
+                .iter()
+                .zip(&options)
+                .map(|(emoji, option)| format!("{emoji} {}", crate::markdown::escape(option)))
+                .collect::<Vec<_>>()
+                .join("\n");
+            let embed = EmbedBuilder::new()
+                .title(crate::markdown::escape(question))
+                .description(description)
+                .validate()
+                .context("poll embed failed validation")?
+                .build();
+
+            let poll = discord
+                .create_message(message.channel_id)
+                .embeds(&[embed])
+                .await
+                .context("failed to post the poll")?
+                .model()
+                .await
+                .context("failed to parse the poll message")?;
+
+            for name in &emoji {
+                discord
+                    .create_reaction(
+                        poll.channel_id,
+                        poll.id,
+                        &RequestReactionType::Unicode { name },
+                    )
+                    .await
+                    .context("failed to add a reaction to the poll")?;
+            }
+
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{option_emoji, parse_options};
+
+    #[test]
+    fn options() {
+        assert_eq!(
+            parse_options("Magic | Dominion|Dragon Quest "),
+            Ok(vec!["Magic", "Dominion", "Dragon Quest"])
+        );
+        assert!(parse_options("Magic").is_err());
+        assert!(parse_options("Magic | | Dominion").is_err());
+        assert_eq!(parse_options("1|2|3|4|5|6|7|8|9|10").map(|options| options.len()), Ok(10));
+        assert!(parse_options("1|2|3|4|5|6|7|8|9|10|11").is_err());
+    }
+
+    #[test]
+    fn emoji() {
+        assert_eq!(option_emoji(0), "\u{1F1E6}");
+        assert_eq!(option_emoji(1), "\u{1F1E7}");
+        assert_eq!(option_emoji(9), "\u{1F1EF}");
+    }
+}
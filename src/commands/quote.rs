@@ -1,31 +1,52 @@
 use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::convert::Infallible;
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::OnceLock;
 
 use anyhow::{Context as _, Error};
-use chrono::NaiveDate;
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use futures_util::TryStreamExt;
 use lalrpop_util::ParseError;
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
 use regex::{Captures, Regex, Replacer};
 use sea_orm::sea_query::extension::postgres::PgExpr;
-use sea_orm::sea_query::{ConditionExpression, Expr, Func, PgFunc, SimpleExpr};
+use sea_orm::sea_query::{
+    Alias, ConditionExpression, Expr, Func, JoinType, NullOrdering, PgFunc, Query, SelectStatement,
+    SimpleExpr,
+};
 use sea_orm::{
-    ColumnTrait, Condition, ConnectionTrait, DatabaseBackend, DatabaseConnection, EntityTrait,
-    ModelTrait, QueryFilter, QuerySelect, QueryTrait, Statement,
+    ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait, DatabaseBackend, DatabaseConnection,
+    DbErr, EntityTrait, ModelTrait, Order, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect,
+    QueryTrait, Select, SelectGetableTuple, Selector, Set, Statement, TransactionTrait,
+    TryGetableMany,
 };
+use serde::{Deserialize, Serialize};
 use tokio::sync::OnceCell;
+use tracing::{error, info};
+use twilight_cache_inmemory::InMemoryCache;
 use twilight_http::Client as DiscordClient;
+use twilight_mention::Mention;
 use twilight_model::channel::message::MessageFlags;
 use twilight_model::channel::Message;
+use twilight_model::http::attachment::Attachment;
+use twilight_model::id::marker::{ChannelMarker, GuildMarker, UserMarker};
+use twilight_model::id::Id;
+use twilight_model::user::User;
 use twilight_util::builder::embed::{EmbedBuilder, EmbedFieldBuilder};
+use twilight_validate::message::MESSAGE_CONTENT_LENGTH_MAX;
 use unicode_width::UnicodeWidthStr;
+use url::Url;
 
 use crate::cache::Cache;
-use crate::command_parser::{Access, Args, CommandHandler, Commands, Help};
+use crate::command_parser::{
+    Access, Args, CommandHandler, CommandUse, Commands, Help, MAX_USE_AGE,
+};
 use crate::config::Config;
-use crate::models::{game, game_entry, quote, show};
+use crate::models::{game, game_entry, quote, show, state};
 
 // regconfig for `english`
 static ENGLISH: OnceCell<u32> = OnceCell::const_new();
@@ -44,6 +65,8 @@ pub enum Op {
     LessEqual,
     /// The `>=` operator.
     GreaterEqual,
+    /// The `==` operator. Ignores case when matching names, otherwise the same as `=`.
+    Exact,
 }
 
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
@@ -55,6 +78,7 @@ pub enum Column {
     Name,
     Quote,
     Show,
+    Tag,
 }
 
 impl Column {
@@ -91,7 +115,7 @@ fn single_predicate<C: ColumnTrait, T: Into<sea_orm::Value>>(
 ) -> SimpleExpr {
     match op {
         Op::Fuzzy => fuzzy(column, value),
-        Op::Equal => column.eq(value),
+        Op::Equal | Op::Exact => column.eq(value),
         Op::Less => column.lt(value),
         Op::LessEqual => column.lte(value),
         Op::Greater => column.gt(value),
@@ -228,6 +252,11 @@ impl<'a> Ast<'a> {
                     })
                     .into())
                 }
+                Column::Name if *op == Op::Exact => {
+                    Ok(Expr::expr(Func::lower(Expr::col(quote::Column::AttribName)))
+                        .eq(term.to_lowercase())
+                        .into())
+                }
                 Column::Name => {
                     Ok(single_predicate(quote::Column::AttribName, *op, &term[..], |c, v| {
                         // TODO: `sea_query` has `LIKE` but not `ILIKE`
@@ -254,24 +283,48 @@ impl<'a> Ast<'a> {
                     .into())
                 }
                 Column::Game => {
-                    Ok(Expr::col(quote::Column::GameId)
-                        .in_subquery(
-                            QuerySelect::query(
-                                &mut game::Entity::find()
-                                    .filter(single_predicate(
-                                        game::Column::Name,
-                                        *op,
-                                        &term[..],
-                                        |c, v| {
-                                            // TODO: `sea_query` has `LIKE` but not `ILIKE`
-                                            Expr::expr(Func::lower(Expr::col(c)))
-                                                .like(as_ilike(v).to_lowercase())
-                                        },
-                                    ))
-                                    .select_only()
-                                    .column(game::Column::Id),
-                            )
-                            .take(),
+                    fn name<C: ColumnTrait>(c: C, v: &str) -> SimpleExpr {
+                        // TODO: `sea_query` has `LIKE` but not `ILIKE`
+                        Expr::expr(Func::lower(Expr::col(c))).like(as_ilike(v).to_lowercase())
+                    }
+                    // The game's own name or the name it's displayed under on the quote's show.
+                    Ok(Condition::any()
+                        .add(
+                            Expr::col(quote::Column::GameId).in_subquery(
+                                QuerySelect::query(
+                                    &mut game::Entity::find()
+                                        .filter(single_predicate(
+                                            game::Column::Name,
+                                            *op,
+                                            &term[..],
+                                            name,
+                                        ))
+                                        .select_only()
+                                        .column(game::Column::Id),
+                                )
+                                .take(),
+                            ),
+                        )
+                        .add(
+                            Expr::tuple([
+                                Expr::col(quote::Column::GameId).into(),
+                                Expr::col(quote::Column::ShowId).into(),
+                            ])
+                            .in_subquery(
+                                QuerySelect::query(
+                                    &mut game_entry::Entity::find()
+                                        .filter(single_predicate(
+                                            game_entry::Column::DisplayName,
+                                            *op,
+                                            &term[..],
+                                            name,
+                                        ))
+                                        .select_only()
+                                        .column(game_entry::Column::GameId)
+                                        .column(game_entry::Column::ShowId),
+                                )
+                                .take(),
+                            ),
                         )
                         .into())
                 }
@@ -297,6 +350,38 @@ impl<'a> Ast<'a> {
                         )
                         .into())
                 }
+                Column::Tag => {
+                    // Tags are stored in lowercase, so `:` is the same as `==`.
+                    let predicate = match op {
+                        Op::Fuzzy | Op::Equal | Op::Exact => {
+                            Expr::col((Alias::new("tags"), Alias::new("key")))
+                                .eq(term.to_lowercase())
+                        }
+                        Op::Less | Op::LessEqual | Op::Greater | Op::GreaterEqual => {
+                            anyhow::bail!("tags can only be matched with `:`, `=`, or `==`")
+                        }
+                    };
+                    Ok(Expr::col(quote::Column::Id)
+                        .in_subquery(
+                            Query::select()
+                                .expr(Expr::cust(
+                                    r#"JSONB_ARRAY_ELEMENTS_TEXT("tags"."value")::integer"#,
+                                ))
+                                .from(state::Entity)
+                                .from_function(
+                                    Func::cust(Alias::new("JSONB_EACH"))
+                                        .arg(Expr::col((state::Entity, state::Column::Value))),
+                                    Alias::new("tags"),
+                                )
+                                .and_where(
+                                    Expr::col((state::Entity, state::Column::Key))
+                                        .eq(TAGS_STATE_KEY),
+                                )
+                                .and_where(predicate)
+                                .take(),
+                        )
+                        .into())
+                }
             },
             Ast::Bare(term) => Ok(Expr::expr(PgFunc::to_tsvector(
                 Expr::col(quote::Column::Quote).concatenate(Expr::val(" ")).concatenate(
@@ -313,6 +398,130 @@ impl<'a> Ast<'a> {
     }
 }
 
+/// Which of the matching quotes gets picked, set with the `order:` modifier.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+enum QuoteOrder {
+    #[default]
+    Random,
+    Newest,
+    Oldest,
+}
+
+impl QuoteOrder {
+    /// Sort the query so that the first result is the one to pick.
+    ///
+    /// `Random` leaves the query as is as the random pick happens after loading the results.
+    fn apply(self, select: Select<quote::Entity>) -> Select<quote::Entity> {
+        let order = match self {
+            QuoteOrder::Random => return select,
+            QuoteOrder::Newest => Order::Desc,
+            QuoteOrder::Oldest => Order::Asc,
+        };
+        select
+            .order_by_with_nulls(quote::Column::AttribDate, order.clone(), NullOrdering::Last)
+            .order_by(quote::Column::Id, order)
+    }
+}
+
+/// Remove the `order:` modifier from the query.
+///
+/// The modifier can appear anywhere in the query as a separate word outside of quoted phrases.
+fn split_order(query: &str) -> Result<(Cow<'_, str>, QuoteOrder), String> {
+    const PREFIX: &str = "order:";
+
+    let mut order = None;
+    let mut rest = String::with_capacity(query.len());
+    let mut last_end = 0;
+    let mut in_quotes = false;
+    let mut escaped = false;
+    let mut at_word_start = true;
+
+    for (i, c) in query.char_indices() {
+        if in_quotes {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_quotes = false,
+                _ => (),
+            }
+        } else if c == '"' {
+            in_quotes = true;
+        } else if at_word_start && i >= last_end {
+            let word_end =
+                query[i..].find(char::is_whitespace).map_or(query.len(), |offset| i + offset);
+            let word = &query[i..word_end];
+            if word.len() > PREFIX.len() && word[..PREFIX.len()].eq_ignore_ascii_case(PREFIX) {
+                let value = &word[PREFIX.len()..];
+                let parsed = match value.to_lowercase().as_str() {
+                    "random" => QuoteOrder::Random,
+                    "newest" => QuoteOrder::Newest,
+                    "oldest" => QuoteOrder::Oldest,
+                    _ => {
+                        return Err(format!(
+                            "Unknown order {value:?}. Expected `newest`, `oldest`, or `random`."
+                        ))
+                    }
+                };
+                if order.replace(parsed).is_some() {
+                    return Err(String::from("The `order:` modifier can only be used once."));
+                }
+                rest.push_str(&query[last_end..i]);
+                // Also drop the whitespace after the modifier so that no double spaces are left.
+                last_end = query[word_end..]
+                    .find(|c: char| !c.is_whitespace())
+                    .map_or(query.len(), |offset| word_end + offset);
+            }
+        }
+        at_word_start = !in_quotes && c.is_whitespace();
+    }
+
+    let order = order.unwrap_or_default();
+    if last_end == 0 {
+        Ok((Cow::Borrowed(query), order))
+    } else {
+        rest.push_str(&query[last_end..]);
+        Ok((Cow::Owned(rest.trim().to_string()), order))
+    }
+}
+
+/// Rewrite a leading `since DATE` or `between DATE DATE` into the equivalent `date` terms.
+///
+/// Only applies if a date follows so that searching for the words themselves still works.
+fn expand_date_range(query: &str) -> Result<Cow<'_, str>, String> {
+    let (keyword, rest) = query.split_once(char::is_whitespace).unwrap_or((query, ""));
+    let ops: &[&str] = if keyword.eq_ignore_ascii_case("since") {
+        &[">="]
+    } else if keyword.eq_ignore_ascii_case("between") {
+        &[">=", "<="]
+    } else {
+        return Ok(Cow::Borrowed(query));
+    };
+
+    let mut rest = rest.trim_start();
+    if !rest.starts_with(|c: char| c.is_ascii_digit()) {
+        return Ok(Cow::Borrowed(query));
+    }
+
+    let mut terms = vec![];
+    for op in ops {
+        let (date, tail) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        if NaiveDate::parse_from_str(date, "%Y-%m-%d").is_err() {
+            return Err(format!(
+                "`{}` expects {} in the YYYY-MM-DD format, got {date:?}.",
+                keyword.to_lowercase(),
+                if ops.len() == 1 { "a date" } else { "two dates" },
+            ));
+        }
+        terms.push(format!("date {op} {date}"));
+        rest = tail.trim_start();
+    }
+    if !rest.is_empty() {
+        terms.push(String::from(rest));
+    }
+
+    Ok(Cow::Owned(terms.join(" ")))
+}
+
 fn unescape(s: &str) -> Cow<str> {
     static RE_ESCAPE: OnceLock<Regex> = OnceLock::new();
     let re_escape = RE_ESCAPE.get_or_init(|| Regex::new(r"\\(.)").unwrap());
@@ -356,6 +565,22 @@ async fn report_parse_error(
     query: &str,
     err: ParseError<usize, parser::Token<'_>, Infallible>,
 ) -> Result<(), Error> {
+    discord
+        .create_message(message.channel_id)
+        .reply(message.id)
+        .flags(MessageFlags::SUPPRESS_EMBEDS)
+        .content(&parse_error_message(query, err))
+        .await
+        .context("failed to report the parse error")?;
+
+    Ok(())
+}
+
+/// The parse error with a caret line pointing at where in the query it is.
+fn parse_error_message(
+    query: &str,
+    err: ParseError<usize, parser::Token<'_>, Infallible>,
+) -> String {
     let (start, end) = match &err {
         ParseError::InvalidToken { location } | ParseError::UnrecognizedEof { location, .. } => {
             (*location, *location)
@@ -378,37 +603,171 @@ async fn report_parse_error(
         caret_line.push('^');
     }
 
-    discord
-        .create_message(message.channel_id)
-        .reply(message.id)
-        .flags(MessageFlags::SUPPRESS_EMBEDS)
-        .content(&format!(
-            "Failed to parse the query: {}\n```{}\n{caret_line}```",
-            crate::markdown::escape(&err.to_string()),
-            crate::markdown::escape_code_block(&query),
+    format!(
+        "Failed to parse the query: {}\n{}",
+        crate::markdown::escape(&err.to_string()),
+        crate::markdown::code_block(None, &format!("{query}\n{caret_line}")),
+    )
+}
+
+/// The `state` key the OID of the `english` regconfig is remembered under.
+const REGCONFIG_STATE_KEY: &str = "eris.quote.english_regconfig_oid";
+
+async fn load_regconfig(conn: &DatabaseConnection) -> Result<(), Error> {
+    ENGLISH.get_or_try_init(|| regconfig_oid(conn)).await?;
+    Ok(())
+}
+
+/// Look up the OID of the `english` regconfig, from the `state` table if it has been stored
+/// there by an earlier run.
+///
+/// The OID is only stored to skip the query next time, so failing to load or store it isn't
+/// fatal.
+async fn regconfig_oid(conn: &DatabaseConnection) -> Result<u32, Error> {
+    match state::get::<u32>(REGCONFIG_STATE_KEY, conn).await {
+        Ok(Some(oid)) => return Ok(oid),
+        Ok(None) => (),
+        Err(error) => {
+            error!(?error, "Failed to load the stored `english` regconfig OID");
+            // Don't leave a value that can't be loaded around if the query below fails too.
+            if let Err(error) = state::delete(REGCONFIG_STATE_KEY, conn).await {
+                error!(?error, "Failed to clear the stored `english` regconfig OID");
+            }
+        }
+    }
+
+    let row = conn
+        .query_one(Statement::from_sql_and_values(
+            DatabaseBackend::Postgres,
+            "SELECT 'english'::REGCONFIG::OID AS english",
+            [],
         ))
         .await
-        .context("failed to report the parse error")?;
+        .context("failed to query the `english` regconfig")?
+        .context("`english` regconfig missing")?;
+    let oid = row.try_get("", "english").context("failed to get the column")?;
 
-    Ok(())
+    if let Err(error) = state::set(String::from(REGCONFIG_STATE_KEY), oid, conn).await {
+        error!(?error, "Failed to store the `english` regconfig OID");
+    }
+
+    Ok(oid)
 }
 
-async fn load_regconfig(conn: &DatabaseConnection) -> Result<(), Error> {
-    ENGLISH
-        .get_or_try_init::<Error, _, _>(|| async {
-            let row = conn
-                .query_one(Statement::from_sql_and_values(
-                    DatabaseBackend::Postgres,
-                    "SELECT 'english'::REGCONFIG::OID AS english",
-                    [],
-                ))
+/// The non-deleted quotes matching an ID or a query, or `None` if the query failed to parse.
+///
+/// Parse errors are reported to the user.
+async fn matching_quotes(
+    discord: &DiscordClient,
+    message: &Message,
+    query: &str,
+) -> Result<Option<Select<quote::Entity>>, Error> {
+    match quote_select(query)? {
+        Ok(select) => Ok(Some(select)),
+        Err(reply) => {
+            discord
+                .create_message(message.channel_id)
+                .reply(message.id)
+                .flags(MessageFlags::SUPPRESS_EMBEDS)
+                .content(&reply)
                 .await
-                .context("failed to query the `english` regconfig")?
-                .context("`english` regconfig missing")?;
-            row.try_get("", "english").context("failed to get the column")
-        })
-        .await?;
-    Ok(())
+                .context("failed to report the parse error")?;
+            Ok(None)
+        }
+    }
+}
+
+/// The non-deleted quotes matching an ID or a query, or the reply explaining the parse error.
+fn quote_select(query: &str) -> Result<Result<Select<quote::Entity>, String>, Error> {
+    let select = if query.is_empty() {
+        quote::Entity::find()
+    } else if let Ok(id) = query.parse::<i32>() {
+        quote::Entity::find_by_id(id)
+    } else {
+        let parser = parser::QueryParser::new();
+        let query = match parser.parse(query) {
+            Ok(query) => query,
+            Err(err) => return Ok(Err(parse_error_message(query, err))),
+        };
+        quote::Entity::find().filter(Condition::all().add(query.to_condition()?))
+    };
+
+    Ok(Ok(select.filter(Expr::col(quote::Column::Deleted).not())))
+}
+
+/// The reply to `quote QUERY`: a matching quote, or why there isn't one.
+pub async fn find_reply(
+    db: &DatabaseConnection,
+    config: &Config,
+    query: &str,
+) -> Result<String, Error> {
+    load_regconfig(db).await.context("failed to load `english` regconfig")?;
+
+    let parsed = split_order(query)
+        .and_then(|(query, order)| Ok((expand_date_range(&query)?.into_owned(), order)));
+    let (query, order) = match parsed {
+        Ok(res) => res,
+        Err(error) => return Ok(error),
+    };
+
+    let select = match quote_select(&query)? {
+        Ok(select) => select,
+        Err(reply) => return Ok(reply),
+    };
+
+    let quote = match order {
+        QuoteOrder::Random => select.all(db).await?.choose(&mut rand::thread_rng()).cloned(),
+        QuoteOrder::Newest | QuoteOrder::Oldest => order.apply(select).one(db).await?,
+    };
+
+    Ok(match quote {
+        Some(quote) => {
+            let show = quote
+                .find_related(show::Entity)
+                .one(db)
+                .await
+                .context("failed to load the show")?;
+            let needs_context = context_flags(db).await?.contains(&quote.id);
+            quote_reply(config, &quote, show.as_ref(), needs_context)
+        }
+        None => String::from("Could not find any matching quotes."),
+    })
+}
+
+/// Escape the text and, if the quote is from one of the `spoiler_shows`, wrap it in spoiler tags.
+///
+/// The escaping takes care of any `||` in the quote itself.
+fn spoiler_escape<'a>(config: &Config, show: Option<&show::Model>, text: &'a str) -> Cow<'a, str> {
+    let text = crate::markdown::escape(text);
+    match show {
+        Some(show) if config.spoiler_shows.contains(&show.key) => {
+            Cow::Owned(crate::markdown::spoiler(&text))
+        }
+        _ => text,
+    }
+}
+
+/// A quote as it's posted in reply to a command.
+fn quote_reply(
+    config: &Config,
+    quote: &quote::Model,
+    show: Option<&show::Model>,
+    needs_context: bool,
+) -> String {
+    let text = if needs_context { format!("{quote} (out of context)") } else { quote.to_string() };
+    format!("Quote {}", spoiler_escape(config, show, &text))
+}
+
+/// The IDs of the quotes flagged with `quote flag-context`. LRRbot owns the `quotes` table, so the
+/// flags are kept in the state instead.
+const NEEDS_CONTEXT_STATE_KEY: &str = "eris.quote.needs_context";
+
+/// The IDs of the quotes that are easily misread without their context.
+async fn context_flags(db: &DatabaseConnection) -> Result<BTreeSet<i32>, Error> {
+    Ok(state::get::<BTreeSet<i32>>(NEEDS_CONTEXT_STATE_KEY, db)
+        .await
+        .context("failed to load the context flags")?
+        .unwrap_or_default())
 }
 
 pub struct Find {
@@ -426,6 +785,10 @@ impl CommandHandler for Find {
         "(?:find)?quote(?: (.+))?"
     }
 
+    fn case_insensitive(&self) -> bool {
+        true
+    }
+
     fn help(&self) -> Option<crate::command_parser::Help> {
         Some(Help {
             name: "quote".into(),
@@ -440,9 +803,10 @@ impl CommandHandler for Find {
                 "\n",
                 "A query is broken up into terms. A term is either an unquoted word ",
                 "(eg. `butts`), a quoted phrase (eg. `\"my butt\"`), or a column name (`context`, ",
-                "`date`, `from`/`name`, `game`, `id`, `quote`/`text`, `show`) followed by an ",
-                "operator (the fuzzy search operator `:` or a relational operator `<`, `=`, `>`, ",
-                "`>=`, `<=`) followed by an unquoted word or a quoted phrase (eg. `quote:butts`).\n",
+                "`date`, `from`/`name`, `game`, `id`, `quote`/`text`, `show`, `tag`) followed by ",
+                "an operator (the fuzzy search operator `:` or a relational operator `<`, `=`, `>`, ",
+                "`>=`, `<=`) followed by an unquoted word or a quoted phrase (eg. `quote:butts`). ",
+                "The `==` operator matches a name exactly but ignoring case (eg. `from==alex`).\n",
                 "\n",
                 "Multiple terms can be combined together to form a more complex query. By default ",
                 "when you write two terms one after the other both need to match the quote ",
@@ -450,15 +814,21 @@ impl CommandHandler for Find {
                 "to match the quote (boolean OR). AND has higher precedence than OR but you can ",
                 "use parentheses to override that.\n",
                 "\n",
-                "When a query matches multiple quotes a random one is picked. An empty query ",
-                "matches all quotes.\n",
+                "When a query matches multiple quotes a random one is picked. Adding ",
+                "`order:newest` or `order:oldest` to the query picks the most recent or the ",
+                "oldest quote instead. An empty query matches all quotes.\n",
+                "\n",
+                "A query can also start with `since DATE` or `between DATE DATE` as a shorthand ",
+                "for `date >= DATE` and `date >= DATE date <= DATE`.\n",
                 "\n",
                 "Please keep in mind that many of the quotes are taken out of context, be it for ",
                 "comedic effect or out of necessity. Take all of them with a grain of salt and ",
                 "bear in mind they don't necessarily reflect their originators' views and ",
                 "opinions. That being said, if you find any quote to be particularly awful, ",
-                "please notify the moderator of your choice to have its removal evaluated.",
-            ).into(),
+                "please report it with `quote report` or notify the moderator of your choice to ",
+                "have its removal evaluated.",
+            )
+            .into(),
             examples: Cow::Borrowed(&[
                 Cow::Borrowed("quote "),
                 Cow::Borrowed("quote 3849"),
@@ -467,6 +837,8 @@ impl CommandHandler for Find {
                 Cow::Borrowed("quote from:alex butts"),
                 Cow::Borrowed("quote id < 1000"),
                 Cow::Borrowed("quote date >= 2019-01-01"),
+                Cow::Borrowed("quote between 2019-01-01 2019-12-31 from:graham"),
+                Cow::Borrowed("quote order:newest from:graham"),
                 Cow::Borrowed(concat!(
                     "quote ",
                     "(show:\"IDDQDerp\" | show:\"Let's NOPE\" | show:\"Watch and Play\") ",
@@ -476,59 +848,26 @@ impl CommandHandler for Find {
         })
     }
 
+    fn category(&self) -> &'static str {
+        "Quotes"
+    }
+
     fn handle<'a>(
         &'a self,
         _: &'a Cache,
-        _: &'a Config,
+        config: &'a Config,
         discord: &'a DiscordClient,
         _: Commands<'a>,
         message: &'a Message,
         args: &'a Args,
     ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>> {
         Box::pin(async move {
-            load_regconfig(&self.db).await.context("failed to load `english` regconfig")?;
-
-            let query = args.get(0).unwrap_or("");
-            let quotes = if query.is_empty() {
-                quote::Entity::find()
-                    .filter(Expr::col(quote::Column::Deleted).not())
-                    .all(&self.db)
-                    .await?
-            } else if let Ok(id) = query.parse::<i32>() {
-                quote::Entity::find_by_id(id)
-                    .filter(Expr::col(quote::Column::Deleted).not())
-                    .all(&self.db)
-                    .await?
-            } else {
-                let parser = parser::QueryParser::new();
-                let query = match parser.parse(query) {
-                    Ok(query) => query,
-                    Err(err) => return report_parse_error(discord, message, query, err).await,
-                };
-                quote::Entity::find()
-                    .filter(
-                        Condition::all()
-                            .add(query.to_condition()?)
-                            .add(Expr::col(quote::Column::Deleted).not()),
-                    )
-                    .all(&self.db)
-                    .await?
-            };
-
-            let quote = quotes.choose(&mut rand::thread_rng());
-
-            let content;
+            let content = find_reply(&self.db, config, args.get(0).unwrap_or("")).await?;
             discord
                 .create_message(message.channel_id)
                 .reply(message.id)
                 .flags(MessageFlags::SUPPRESS_EMBEDS)
-                .content(match quote {
-                    Some(quote) => {
-                        content = format!("Quote {}", crate::markdown::escape(&quote.to_string()));
-                        &content
-                    }
-                    None => "Could not find any matching quotes.",
-                })
+                .content(&content)
                 .await
                 .context("failed to reply to command")?;
 
@@ -558,6 +897,10 @@ impl CommandHandler for QueryDebugger {
         Access::ModOnly
     }
 
+    fn category(&self) -> &'static str {
+        "Quotes"
+    }
+
     fn handle<'a>(
         &'a self,
         _: &'a Cache,
@@ -597,8 +940,8 @@ impl CommandHandler for QueryDebugger {
                         .to_string();
 
                     content = format!(
-                        "AST:\n```{}```\nSQL:\n`{}`",
-                        crate::markdown::escape_code_block(&format!("{query:#?}")),
+                        "AST:\n{}\nSQL:\n`{}`",
+                        crate::markdown::code_block(None, &format!("{query:#?}")),
                         crate::markdown::escape(&sql),
                     );
                     &content
@@ -636,10 +979,14 @@ impl CommandHandler for Details {
         })
     }
 
+    fn category(&self) -> &'static str {
+        "Quotes"
+    }
+
     fn handle<'a>(
         &'a self,
         _: &'a Cache,
-        _: &'a Config,
+        config: &'a Config,
         discord: &'a DiscordClient,
         _: Commands<'a>,
         message: &'a Message,
@@ -694,7 +1041,10 @@ impl CommandHandler for Details {
 
             let mut embed = EmbedBuilder::new()
                 .field(EmbedFieldBuilder::new("ID", quote.id.to_string()))
-                .field(EmbedFieldBuilder::new("Quote", crate::markdown::escape(&quote.quote)));
+                .field(EmbedFieldBuilder::new(
+                    "Quote",
+                    spoiler_escape(config, show.as_ref(), &quote.quote),
+                ));
             if let Some(ref name) = quote.attrib_name {
                 embed = embed.field(EmbedFieldBuilder::new("Name", crate::markdown::escape(name)));
             }
@@ -718,15 +1068,19 @@ impl CommandHandler for Details {
                     ));
                 }
             }
-            if let Some(show) = show {
+            if let Some(ref show) = show {
                 embed = embed.field(EmbedFieldBuilder::new("Show ID", show.id.to_string())).field(
                     EmbedFieldBuilder::new("Show name", crate::markdown::escape(&show.name)),
                 );
             }
+            let needs_context = context_flags(&self.db).await?.contains(&quote.id);
+            if needs_context {
+                embed = embed.field(EmbedFieldBuilder::new("Needs context", "Yes"));
+            }
             discord
                 .create_message(message.channel_id)
                 .reply(message.id)
-                .content(&format!("Quote {}", crate::markdown::escape(&quote.to_string())))
+                .content(&quote_reply(config, &quote, show.as_ref(), needs_context))
                 .embeds(&[embed.build()])
                 .await
                 .context("failed to reply to command")?;
@@ -736,40 +1090,3608 @@ impl CommandHandler for Details {
     }
 }
 
-#[cfg(test)]
-mod test {
-    use std::borrow::Cow;
+pub struct FlagContext {
+    db: DatabaseConnection,
+}
 
-    use super::parser::QueryParser;
-    use super::{as_ilike, unescape, Ast, Column, Op};
+impl FlagContext {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+}
 
-    #[test]
-    fn parsing() {
-        let parser = QueryParser::new();
-        assert_eq!(parser.parse("butts").unwrap(), Ast::Bare(Cow::Borrowed("butts")));
-        assert_eq!(
-            parser.parse("bare words get concatenated").unwrap(),
-            Ast::Bare(Cow::Borrowed("bare words get concatenated"))
-        );
-        assert_eq!(
-            parser.parse("quote:also quote:FTS quote:fields").unwrap(),
-            Ast::Column {
-                column: Column::Quote,
-                op: Op::Fuzzy,
-                term: Cow::Borrowed("also FTS fields"),
-            }
-        );
+/// Flag or unflag `quote_id`. Returns whether the flags changed.
+fn set_context_flag(flagged: &mut BTreeSet<i32>, quote_id: i32, needs_context: bool) -> bool {
+    if needs_context {
+        flagged.insert(quote_id)
+    } else {
+        flagged.remove(&quote_id)
     }
+}
 
-    #[test]
-    fn unquote() {
-        assert_eq!(unescape("\"test\""), "test");
-        assert_eq!(unescape("\"quote: \\\" \\n\""), "quote: \" \n");
+impl CommandHandler for FlagContext {
+    fn pattern(&self) -> &str {
+        r"quote (un)?flag-context (\d+)"
     }
 
-    #[test]
-    fn ilike() {
-        assert_eq!(as_ilike("dark souls"), "%dark%souls%");
-        assert_eq!(as_ilike("%"), "%\\%%");
+    fn help(&self) -> Option<Help> {
+        Some(Help {
+            name: "quote flag-context".into(),
+            usage: "quote [un]flag-context <ID>".into(),
+            summary: "Mark a quote as needing its context".into(),
+            description: concat!(
+                "Mark a quote that's easily misread without its context, so it's posted with an ",
+                "\"(out of context)\" note. Remove the mark with `quote unflag-context`.",
+            )
+            .into(),
+            examples: Cow::Borrowed(&[
+                Cow::Borrowed("quote flag-context 3849"),
+                Cow::Borrowed("quote unflag-context 3849"),
+            ]),
+        })
+    }
+
+    fn access(&self) -> Access {
+        Access::ModOnly
+    }
+
+    fn category(&self) -> &'static str {
+        "Quotes"
+    }
+
+    fn handle<'a>(
+        &'a self,
+        _: &'a Cache,
+        _: &'a Config,
+        discord: &'a DiscordClient,
+        _: Commands<'a>,
+        message: &'a Message,
+        args: &'a Args,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>> {
+        Box::pin(async move {
+            let needs_context = args.get(0).is_none();
+            let content = match args.get(1).context("quote ID missing")?.parse::<i32>() {
+                Err(error) => format!("Failed to parse the quote ID: {error}"),
+                Ok(quote_id) => {
+                    let quote = quote::Entity::find_by_id(quote_id)
+                        .filter(Expr::col(quote::Column::Deleted).not())
+                        .one(&self.db)
+                        .await
+                        .context("failed to load the quote")?;
+                    if quote.is_none() {
+                        format!("Could not find quote #{quote_id}")
+                    } else {
+                        let txn = self.db.begin().await.context("failed to start a transaction")?;
+                        let mut flagged =
+                            state::get_for_update::<BTreeSet<i32>>(NEEDS_CONTEXT_STATE_KEY, &txn)
+                                .await
+                                .context("failed to load the context flags")?;
+                        if set_context_flag(&mut flagged, quote_id, needs_context) {
+                            state::set(String::from(NEEDS_CONTEXT_STATE_KEY), flagged, &txn)
+                                .await
+                                .context("failed to save the context flags")?;
+                        }
+                        txn.commit().await.context("failed to commit the context flags")?;
+                        if needs_context {
+                            format!(
+                                "Quote #{quote_id} will be posted with an \"(out of context)\" note."
+                            )
+                        } else {
+                            format!("Quote #{quote_id} will be posted without a note.")
+                        }
+                    }
+                }
+            };
+
+            discord
+                .create_message(message.channel_id)
+                .reply(message.id)
+                .flags(MessageFlags::SUPPRESS_EMBEDS)
+                .content(&content)
+                .await
+                .context("failed to reply to command")?;
+
+            Ok(())
+        })
+    }
+}
+
+pub struct Around {
+    db: DatabaseConnection,
+}
+
+impl Around {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+}
+
+/// The non-deleted quote from the same show and game as `quote` with the nearest lower
+/// (`Order::Desc`) or higher (`Order::Asc`) ID.
+fn neighbour(quote: &quote::Model, order: Order) -> Select<quote::Entity> {
+    let id = match order {
+        Order::Desc => quote::Column::Id.lt(quote.id),
+        _ => quote::Column::Id.gt(quote.id),
+    };
+    let show = match quote.show_id {
+        Some(show_id) => quote::Column::ShowId.eq(show_id),
+        None => quote::Column::ShowId.is_null(),
+    };
+    let game = match quote.game_id {
+        Some(game_id) => quote::Column::GameId.eq(game_id),
+        None => quote::Column::GameId.is_null(),
+    };
+
+    quote::Entity::find()
+        .filter(id)
+        .filter(show)
+        .filter(game)
+        .filter(Expr::col(quote::Column::Deleted).not())
+        .order_by(quote::Column::Id, order)
+        .limit(1)
+}
+
+/// The reply to `!quote around`: the quote between its neighbours, with the quote itself in bold.
+fn around_reply(
+    config: &Config,
+    show: Option<&show::Model>,
+    before: Option<&quote::Model>,
+    quote: &quote::Model,
+    after: Option<&quote::Model>,
+) -> String {
+    let mut lines = vec![];
+    if let Some(before) = before {
+        lines.push(format!("Quote {}", spoiler_escape(config, show, &before.to_string())));
+    }
+    lines.push(format!("**Quote {}**", spoiler_escape(config, show, &quote.to_string())));
+    if let Some(after) = after {
+        lines.push(format!("Quote {}", spoiler_escape(config, show, &after.to_string())));
+    }
+    if before.is_none() && after.is_none() {
+        lines.push(String::from("There are no other quotes from the same show and game."));
+    }
+    lines.join("\n")
+}
+
+impl CommandHandler for Around {
+    fn pattern(&self) -> &str {
+        r"quote around (\d+)"
+    }
+
+    fn help(&self) -> Option<Help> {
+        Some(Help {
+            name: "quote around".into(),
+            usage: "quote around <ID>".into(),
+            summary: "Post a quote with the quotes before and after it".into(),
+            description: concat!(
+                "Post a quote together with the previous and the next quote from the same show ",
+                "and game, for some context.",
+            )
+            .into(),
+            examples: Cow::Borrowed(&[Cow::Borrowed("quote around 110")]),
+        })
+    }
+
+    fn category(&self) -> &'static str {
+        "Quotes"
+    }
+
+    fn handle<'a>(
+        &'a self,
+        _: &'a Cache,
+        config: &'a Config,
+        discord: &'a DiscordClient,
+        _: Commands<'a>,
+        message: &'a Message,
+        args: &'a Args,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>> {
+        Box::pin(async move {
+            let quote_id = match args.get(0).context("quote ID missing")?.parse::<i32>() {
+                Ok(id) => id,
+                Err(error) => {
+                    discord
+                        .create_message(message.channel_id)
+                        .reply(message.id)
+                        .flags(MessageFlags::SUPPRESS_EMBEDS)
+                        .content(&format!("Failed to parse the quote ID: {error}"))
+                        .await
+                        .context("failed to report the parse error")?;
+                    return Ok(());
+                }
+            };
+
+            let Some(quote) = quote::Entity::find_by_id(quote_id)
+                .filter(Expr::col(quote::Column::Deleted).not())
+                .one(&self.db)
+                .await
+                .context("failed to load the quote")?
+            else {
+                discord
+                    .create_message(message.channel_id)
+                    .reply(message.id)
+                    .flags(MessageFlags::SUPPRESS_EMBEDS)
+                    .content(&format!("Could not find quote #{quote_id}"))
+                    .await
+                    .context("failed to reply to command")?;
+                return Ok(());
+            };
+
+            let before = neighbour(&quote, Order::Desc)
+                .one(&self.db)
+                .await
+                .context("failed to load the previous quote")?;
+            let after = neighbour(&quote, Order::Asc)
+                .one(&self.db)
+                .await
+                .context("failed to load the next quote")?;
+            let show = quote
+                .find_related(show::Entity)
+                .one(&self.db)
+                .await
+                .context("failed to load the show")?;
+
+            discord
+                .create_message(message.channel_id)
+                .reply(message.id)
+                .flags(MessageFlags::SUPPRESS_EMBEDS)
+                .content(&around_reply(
+                    config,
+                    show.as_ref(),
+                    before.as_ref(),
+                    &quote,
+                    after.as_ref(),
+                ))
+                .await
+                .context("failed to reply to command")?;
+
+            Ok(())
+        })
+    }
+}
+
+/// The most quotes that `!quote export` puts in a single file.
+const MAX_EXPORTED_QUOTES: u64 = 10_000;
+
+pub struct Export {
+    db: DatabaseConnection,
+}
+
+impl Export {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+}
+
+/// Append a CSV field, quoting it if necessary.
+fn push_csv_field(csv: &mut String, field: &str) {
+    if field.contains([',', '"', '\r', '\n']) {
+        csv.push('"');
+        csv.push_str(&field.replace('"', "\"\""));
+        csv.push('"');
+    } else {
+        csv.push_str(field);
+    }
+}
+
+fn push_csv_record<'a>(csv: &mut String, fields: impl IntoIterator<Item = &'a str>) {
+    for (i, field) in fields.into_iter().enumerate() {
+        if i != 0 {
+            csv.push(',');
+        }
+        push_csv_field(csv, field);
+    }
+    csv.push_str("\r\n");
+}
+
+const CSV_HEADER: [&str; 7] = ["id", "quote", "name", "date", "context", "game_id", "show_id"];
+
+fn push_csv_quote(csv: &mut String, quote: &quote::Model) {
+    let id = quote.id.to_string();
+    let date = quote.attrib_date.map(|date| date.to_string());
+    let game_id = quote.game_id.map(|id| id.to_string());
+    let show_id = quote.show_id.map(|id| id.to_string());
+    push_csv_record(
+        csv,
+        [
+            id.as_str(),
+            quote.quote.as_str(),
+            quote.attrib_name.as_deref().unwrap_or(""),
+            date.as_deref().unwrap_or(""),
+            quote.context.as_deref().unwrap_or(""),
+            game_id.as_deref().unwrap_or(""),
+            show_id.as_deref().unwrap_or(""),
+        ],
+    );
+}
+
+impl CommandHandler for Export {
+    fn pattern(&self) -> &str {
+        "quote export(?: (.+))?"
+    }
+
+    fn help(&self) -> Option<Help> {
+        Some(Help {
+            name: "quote export".into(),
+            usage: "quote export [QUERY]".into(),
+            summary: "Get all the quotes that match a query as a CSV file".into(),
+            description: format!(
+                concat!(
+                    "Get all the quotes that match a query as a CSV file in a direct message. ",
+                    "The query language is the same as for `quote`. At most {} quotes are ",
+                    "exported.",
+                ),
+                MAX_EXPORTED_QUOTES
+            )
+            .into(),
+            examples: Cow::Borrowed(&[Cow::Borrowed("quote export from:graham")]),
+        })
+    }
+
+    fn access(&self) -> Access {
+        Access::ModOnly
+    }
+
+    fn category(&self) -> &'static str {
+        "Quotes"
+    }
+
+    fn handle<'a>(
+        &'a self,
+        _: &'a Cache,
+        _: &'a Config,
+        discord: &'a DiscordClient,
+        _: Commands<'a>,
+        message: &'a Message,
+        args: &'a Args,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>> {
+        Box::pin(async move {
+            load_regconfig(&self.db).await.context("failed to load `english` regconfig")?;
+
+            let query = args.get(0).unwrap_or("");
+            let Some(select) = matching_quotes(discord, message, query).await? else {
+                return Ok(());
+            };
+
+            let mut quotes = select
+                .order_by_asc(quote::Column::Id)
+                .limit(MAX_EXPORTED_QUOTES + 1)
+                .stream(&self.db)
+                .await
+                .context("failed to query the quotes")?;
+
+            let mut csv = String::new();
+            push_csv_record(&mut csv, CSV_HEADER);
+            let mut count = 0;
+            let mut truncated = false;
+            while let Some(quote) = quotes.try_next().await.context("failed to load a quote")? {
+                if count == MAX_EXPORTED_QUOTES {
+                    truncated = true;
+                    break;
+                }
+                push_csv_quote(&mut csv, &quote);
+                count += 1;
+            }
+            drop(quotes);
+
+            let mut content = format!("{count} quotes matching `{}`.", query.replace('`', "'"));
+            if truncated {
+                content.push_str(&format!(" Only the first {MAX_EXPORTED_QUOTES} are included."));
+            }
+
+            let dm = discord
+                .create_private_channel(message.author.id)
+                .await
+                .context("failed to open a DM channel")?
+                .model()
+                .await
+                .context("failed to parse the DM channel")?;
+            discord
+                .create_message(dm.id)
+                .content(&content)
+                .attachments(&[Attachment::from_bytes(
+                    String::from("quotes.csv"),
+                    csv.into_bytes(),
+                    0,
+                )])
+                .await
+                .context("failed to send the export")?;
+
+            discord
+                .create_message(message.channel_id)
+                .reply(message.id)
+                .content("Sent you the quotes in a direct message.")
+                .await
+                .context("failed to reply to command")?;
+
+            Ok(())
+        })
+    }
+}
+
+pub struct Identify {
+    db: DatabaseConnection,
+}
+
+impl Identify {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+}
+
+/// The non-deleted quote whose text best matches `text`, ranked by `ts_rank`.
+///
+/// Ties go to the oldest quote.
+fn identify_select(text: &str) -> Select<quote::Entity> {
+    let vector = || PgFunc::to_tsvector(Expr::col(quote::Column::Quote), ENGLISH.get().copied());
+    let query = || PgFunc::plainto_tsquery(Expr::val(text), ENGLISH.get().copied());
+
+    quote::Entity::find()
+        .filter(Expr::col(quote::Column::Deleted).not())
+        .filter(Expr::expr(vector()).matches(query()))
+        .order_by(SimpleExpr::from(PgFunc::ts_rank(vector(), query())), Order::Desc)
+        .order_by_asc(quote::Column::Id)
+        .limit(1)
+}
+
+impl CommandHandler for Identify {
+    fn pattern(&self) -> &str {
+        // Don't steal `quote id < 1000` and friends from `Find`.
+        r"quote (?:identify|id) ([^\s<>=:].*)"
+    }
+
+    fn help(&self) -> Option<Help> {
+        Some(Help {
+            name: "quote id".into(),
+            usage: "quote id <TEXT>".into(),
+            summary: "Find the ID of a quote from its text".into(),
+            description: concat!(
+                "Find the quote that most closely matches the text and post it along with its ",
+                "ID. Only the text of the quote is searched, not the context or the attribution.",
+            )
+            .into(),
+            examples: Cow::Borrowed(&[
+                Cow::Borrowed("quote id I'm not a doctor"),
+                Cow::Borrowed("quote identify long pig"),
+            ]),
+        })
+    }
+
+    fn category(&self) -> &'static str {
+        "Quotes"
+    }
+
+    fn handle<'a>(
+        &'a self,
+        _: &'a Cache,
+        config: &'a Config,
+        discord: &'a DiscordClient,
+        _: Commands<'a>,
+        message: &'a Message,
+        args: &'a Args,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>> {
+        Box::pin(async move {
+            load_regconfig(&self.db).await.context("failed to load `english` regconfig")?;
+
+            let text = args.get(0).context("quote text missing")?;
+            let quote = identify_select(text)
+                .one(&self.db)
+                .await
+                .context("failed to search for the quote")?;
+
+            let content = match quote {
+                Some(quote) => {
+                    let show = quote
+                        .find_related(show::Entity)
+                        .one(&self.db)
+                        .await
+                        .context("failed to load the show")?;
+                    format!(
+                        "That's quote #{}: {}",
+                        quote.id,
+                        spoiler_escape(config, show.as_ref(), &quote.to_string())
+                    )
+                }
+                None => String::from("Could not find a quote like that."),
+            };
+
+            discord
+                .create_message(message.channel_id)
+                .reply(message.id)
+                .flags(MessageFlags::SUPPRESS_EMBEDS)
+                .content(&content)
+                .await
+                .context("failed to reply to command")?;
+
+            Ok(())
+        })
+    }
+}
+
+pub struct Link {
+    db: DatabaseConnection,
+}
+
+impl Link {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+}
+
+/// The reply to `!quote link`: a link to the quote if it exists.
+fn link_reply(quote_url: &Url, quote_id: i32, quote: Option<&quote::Model>) -> String {
+    match quote.filter(|quote| !quote.deleted) {
+        Some(quote) => match quote_url.join(&quote.id.to_string()) {
+            Ok(url) => format!("Quote #{}: {url}", quote.id),
+            Err(error) => format!("Failed to construct the link to quote #{}: {error}", quote.id),
+        },
+        None => format!("Could not find quote #{quote_id}"),
+    }
+}
+
+impl CommandHandler for Link {
+    fn pattern(&self) -> &str {
+        r"quote link (\d+)"
+    }
+
+    fn help(&self) -> Option<Help> {
+        Some(Help {
+            name: "quote link".into(),
+            usage: "quote link <ID>".into(),
+            summary: "Post a link to a quote on the website".into(),
+            description: "Post a link to a quote on the website.".into(),
+            examples: Cow::Borrowed(&[Cow::Borrowed("quote link 110")]),
+        })
+    }
+
+    fn category(&self) -> &'static str {
+        "Quotes"
+    }
+
+    fn handle<'a>(
+        &'a self,
+        _: &'a Cache,
+        config: &'a Config,
+        discord: &'a DiscordClient,
+        _: Commands<'a>,
+        message: &'a Message,
+        args: &'a Args,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>> {
+        Box::pin(async move {
+            let quote_id = match args.get(0).context("quote ID missing")?.parse::<i32>() {
+                Ok(id) => id,
+                Err(error) => {
+                    discord
+                        .create_message(message.channel_id)
+                        .reply(message.id)
+                        .flags(MessageFlags::SUPPRESS_EMBEDS)
+                        .content(&format!("Failed to parse the quote ID: {error}"))
+                        .await
+                        .context("failed to report the parse error")?;
+                    return Ok(());
+                }
+            };
+
+            let quote = quote::Entity::find_by_id(quote_id)
+                .filter(Expr::col(quote::Column::Deleted).not())
+                .one(&self.db)
+                .await
+                .context("failed to load the quote")?;
+
+            discord
+                .create_message(message.channel_id)
+                .reply(message.id)
+                .content(&link_reply(&config.quote_url, quote_id, quote.as_ref()))
+                .await
+                .context("failed to reply to command")?;
+
+            Ok(())
+        })
+    }
+}
+
+pub struct Cite {
+    db: DatabaseConnection,
+}
+
+impl Cite {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+}
+
+/// The reply to `!quote cite`: the quote and its link as plain text in a code block, so that
+/// Discord shows it exactly as it should be pasted elsewhere.
+fn cite_reply(
+    config: &Config,
+    quote_id: i32,
+    quote: Option<&(quote::Model, Option<show::Model>)>,
+) -> String {
+    let Some((quote, show)) = quote.filter(|(quote, _)| !quote.deleted) else {
+        return format!("Could not find quote #{quote_id}");
+    };
+    let mut citation = quote.to_string();
+    if let Ok(url) = config.quote_url.join(&quote.id.to_string()) {
+        citation.push('\n');
+        citation.push_str(url.as_str());
+    }
+    let block = crate::markdown::code_block(None, &citation);
+    match show {
+        // Markdown isn't parsed inside the code block, so the quote can't close the spoiler.
+        Some(show) if config.spoiler_shows.contains(&show.key) => format!("||{block}||"),
+        _ => block,
+    }
+}
+
+impl CommandHandler for Cite {
+    fn pattern(&self) -> &str {
+        r"quote cite (\d+)"
+    }
+
+    fn help(&self) -> Option<Help> {
+        Some(Help {
+            name: "quote cite".into(),
+            usage: "quote cite <ID>".into(),
+            summary: "Post a quote ready to be copied elsewhere".into(),
+            description: concat!(
+                "Post a quote and its link as plain text in a code block, so that it can be ",
+                "copied without any Discord formatting.",
+            )
+            .into(),
+            examples: Cow::Borrowed(&[Cow::Borrowed("quote cite 110")]),
+        })
+    }
+
+    fn category(&self) -> &'static str {
+        "Quotes"
+    }
+
+    fn handle<'a>(
+        &'a self,
+        _: &'a Cache,
+        config: &'a Config,
+        discord: &'a DiscordClient,
+        _: Commands<'a>,
+        message: &'a Message,
+        args: &'a Args,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>> {
+        Box::pin(async move {
+            let content = match args.get(0).context("quote ID missing")?.parse::<i32>() {
+                Ok(quote_id) => {
+                    let quote = quote::Entity::find_by_id(quote_id)
+                        .find_also_related(show::Entity)
+                        .filter(Expr::col((quote::Entity, quote::Column::Deleted)).not())
+                        .one(&self.db)
+                        .await
+                        .context("failed to load the quote")?;
+                    cite_reply(config, quote_id, quote.as_ref())
+                }
+                Err(error) => format!("Failed to parse the quote ID: {error}"),
+            };
+
+            discord
+                .create_message(message.channel_id)
+                .reply(message.id)
+                .flags(MessageFlags::SUPPRESS_EMBEDS)
+                .content(&content)
+                .await
+                .context("failed to reply to command")?;
+
+            Ok(())
+        })
+    }
+}
+
+pub struct Report {
+    db: DatabaseConnection,
+}
+
+impl Report {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+}
+
+/// Further reports of a quote within this long of the first one aren't passed on to the mods.
+const REPORT_COOLDOWN: chrono::TimeDelta = match chrono::TimeDelta::try_days(1) {
+    Some(delta) => delta,
+    None => panic!("REPORT_COOLDOWN is invalid"),
+};
+
+fn report_state_key(quote_id: i32) -> String {
+    format!("eris.quote.report.{quote_id}")
+}
+
+/// Has the quote already been reported recently enough that the mods don't need to hear again?
+fn is_duplicate_report(reported_at: Option<DateTime<Utc>>, now: DateTime<Utc>) -> bool {
+    reported_at.is_some_and(|reported_at| now - reported_at < REPORT_COOLDOWN)
+}
+
+/// The message to the mods about a reported quote.
+fn report_message(
+    config: &Config,
+    show: Option<&show::Model>,
+    quote: &quote::Model,
+    reporter: Id<UserMarker>,
+    reason: Option<&str>,
+) -> String {
+    let mut content = format!("{} reported quote #{}", reporter.mention(), quote.id);
+    match reason {
+        Some(reason) => {
+            content.push_str(": ");
+            content.push_str(&crate::markdown::escape(reason));
+        }
+        None => content.push('.'),
+    }
+    content.push_str("\n> ");
+    content.push_str(&spoiler_escape(config, show, &quote.to_string()));
+    if let Some(show) = show {
+        content.push_str(&format!(" (on {})", crate::markdown::escape(&show.name)));
+    }
+    content
+}
+
+impl CommandHandler for Report {
+    fn pattern(&self) -> &str {
+        r"quote report (\d+)(?: (.+))?"
+    }
+
+    fn help(&self) -> Option<Help> {
+        Some(Help {
+            name: "quote report".into(),
+            usage: "quote report <ID> [REASON]".into(),
+            summary: "Ask the moderators to review a quote".into(),
+            description: concat!(
+                "Ask the moderators to review a quote, for example if it's particularly awful. ",
+                "The quote is only passed on once a day no matter how many times it's reported.",
+            )
+            .into(),
+            examples: Cow::Borrowed(&[
+                Cow::Borrowed("quote report 110"),
+                Cow::Borrowed("quote report 110 out of context"),
+            ]),
+        })
+    }
+
+    fn category(&self) -> &'static str {
+        "Quotes"
+    }
+
+    fn handle<'a>(
+        &'a self,
+        _: &'a Cache,
+        config: &'a Config,
+        discord: &'a DiscordClient,
+        _: Commands<'a>,
+        message: &'a Message,
+        args: &'a Args,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>> {
+        Box::pin(async move {
+            let quote_id = match args.get(0).context("quote ID missing")?.parse::<i32>() {
+                Ok(id) => id,
+                Err(error) => {
+                    discord
+                        .create_message(message.channel_id)
+                        .reply(message.id)
+                        .flags(MessageFlags::SUPPRESS_EMBEDS)
+                        .content(&format!("Failed to parse the quote ID: {error}"))
+                        .await
+                        .context("failed to report the parse error")?;
+                    return Ok(());
+                }
+            };
+            let reason = args.get(1).map(str::trim).filter(|reason| !reason.is_empty());
+
+            let Some(quote) = quote::Entity::find_by_id(quote_id)
+                .filter(Expr::col(quote::Column::Deleted).not())
+                .one(&self.db)
+                .await
+                .context("failed to load the quote")?
+            else {
+                discord
+                    .create_message(message.channel_id)
+                    .reply(message.id)
+                    .flags(MessageFlags::SUPPRESS_EMBEDS)
+                    .content(&format!("Could not find quote #{quote_id}"))
+                    .await
+                    .context("failed to reply to command")?;
+                return Ok(());
+            };
+
+            let now = Utc::now();
+            let state_key = report_state_key(quote.id);
+            let reported_at = state::get::<DateTime<Utc>>(&state_key, &self.db)
+                .await
+                .context("failed to load the previous report")?;
+
+            if !is_duplicate_report(reported_at, now) {
+                let show = quote
+                    .find_related(show::Entity)
+                    .one(&self.db)
+                    .await
+                    .context("failed to load the show")?;
+                discord
+                    .create_message(config.mods_channel)
+                    .flags(MessageFlags::SUPPRESS_EMBEDS)
+                    .content(&report_message(
+                        config,
+                        show.as_ref(),
+                        &quote,
+                        message.author.id,
+                        reason,
+                    ))
+                    .await
+                    .context("failed to pass the report on to the mods")?;
+                state::set(state_key, now, &self.db)
+                    .await
+                    .context("failed to record the report")?;
+            }
+
+            discord
+                .create_message(message.channel_id)
+                .reply(message.id)
+                .flags(MessageFlags::SUPPRESS_EMBEDS)
+                .content(&format!(
+                    "Thank you, the moderators will take a look at quote #{}.",
+                    quote.id
+                ))
+                .await
+                .context("failed to reply to command")?;
+
+            Ok(())
+        })
+    }
+}
+
+pub struct SearchWeb;
+
+impl SearchWeb {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// A link to the website's quote search with `query` filled in.
+fn search_link(search_url: &Url, query: &str) -> Url {
+    let mut url = search_url.clone();
+    url.query_pairs_mut().append_pair("q", query);
+    url
+}
+
+impl CommandHandler for SearchWeb {
+    fn pattern(&self) -> &str {
+        "quote search-web (.+)"
+    }
+
+    fn help(&self) -> Option<Help> {
+        Some(Help {
+            name: "quote search-web".into(),
+            usage: "quote search-web <QUERY>".into(),
+            summary: "Post a link to the quote search on the website".into(),
+            description: concat!(
+                "Post a link to the quote search on the website with `QUERY` filled in. The query ",
+                "is checked the same way as with `quote`.",
+            )
+            .into(),
+            examples: Cow::Borrowed(&[Cow::Borrowed("quote search-web from:alex \"long pig\"")]),
+        })
+    }
+
+    fn category(&self) -> &'static str {
+        "Quotes"
+    }
+
+    fn handle<'a>(
+        &'a self,
+        _: &'a Cache,
+        config: &'a Config,
+        discord: &'a DiscordClient,
+        _: Commands<'a>,
+        message: &'a Message,
+        args: &'a Args,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>> {
+        Box::pin(async move {
+            let query = args.get(0).context("query missing")?;
+            if let Err(err) = parser::QueryParser::new().parse(query) {
+                return report_parse_error(discord, message, query, err).await;
+            }
+
+            discord
+                .create_message(message.channel_id)
+                .reply(message.id)
+                .content(search_link(&config.quote_search_url, query).as_str())
+                .await
+                .context("failed to reply to command")?;
+
+            Ok(())
+        })
+    }
+}
+
+pub struct OfTheDay {
+    db: DatabaseConnection,
+}
+
+impl OfTheDay {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+}
+
+const OF_THE_DAY_STATE_KEY: &str = "eris.quote.of_the_day";
+
+/// The quote picked as the quote of the day. Persisted so that everyone gets the same quote on the
+/// same day even if quotes are added or deleted in the meantime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct DailyQuote {
+    date: NaiveDate,
+    quote_id: i32,
+}
+
+/// The offset of the quote of the day among `count` quotes. Seeded by the date so that everyone
+/// gets the same quote on the same day.
+fn daily_offset(date: NaiveDate, count: u64) -> u64 {
+    let mut rng = StdRng::seed_from_u64(date.num_days_from_ce() as u64);
+    rng.gen_range(0..count)
+}
+
+/// The quote already picked for `today`, if any.
+fn picked_for(stored: Option<DailyQuote>, today: NaiveDate) -> Option<i32> {
+    stored.filter(|stored| stored.date == today).map(|stored| stored.quote_id)
+}
+
+impl CommandHandler for OfTheDay {
+    fn pattern(&self) -> &str {
+        "quote of-the-day"
+    }
+
+    fn help(&self) -> Option<Help> {
+        Some(Help {
+            name: "quote of-the-day".into(),
+            usage: "quote of-the-day".into(),
+            summary: "Post the quote of the day".into(),
+            description: "Post the quote of the day. Everyone gets the same quote until midnight."
+                .into(),
+            examples: Cow::Borrowed(&[Cow::Borrowed("quote of-the-day")]),
+        })
+    }
+
+    fn category(&self) -> &'static str {
+        "Quotes"
+    }
+
+    fn handle<'a>(
+        &'a self,
+        _: &'a Cache,
+        config: &'a Config,
+        discord: &'a DiscordClient,
+        _: Commands<'a>,
+        message: &'a Message,
+        _: &'a Args,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>> {
+        Box::pin(async move {
+            let today = Utc::now().with_timezone(&&config.timezone).date_naive();
+            let stored = state::get::<DailyQuote>(OF_THE_DAY_STATE_KEY, &self.db).await?;
+            let stored = match picked_for(stored, today) {
+                Some(quote_id) => quote::Entity::find_by_id(quote_id)
+                    .filter(Expr::col(quote::Column::Deleted).not())
+                    .one(&self.db)
+                    .await
+                    .context("failed to load the quote of the day")?,
+                None => None,
+            };
+
+            // Pick a new quote if there isn't one for today yet or if it has since been deleted.
+            let quote = match stored {
+                Some(quote) => Some(quote),
+                None => {
+                    let select = quote::Entity::find()
+                        .filter(Expr::col(quote::Column::Deleted).not())
+                        .order_by_asc(quote::Column::Id);
+                    let count = select
+                        .clone()
+                        .count(&self.db)
+                        .await
+                        .context("failed to count the quotes")?;
+                    let quote = if count == 0 {
+                        None
+                    } else {
+                        select
+                            .offset(daily_offset(today, count))
+                            .limit(1)
+                            .one(&self.db)
+                            .await
+                            .context("failed to load the quote of the day")?
+                    };
+                    if let Some(ref quote) = quote {
+                        state::set(
+                            String::from(OF_THE_DAY_STATE_KEY),
+                            DailyQuote { date: today, quote_id: quote.id },
+                            &self.db,
+                        )
+                        .await
+                        .context("failed to save the quote of the day")?;
+                    }
+                    quote
+                }
+            };
+
+            let content = match quote {
+                Some(quote) => {
+                    let show = quote
+                        .find_related(show::Entity)
+                        .one(&self.db)
+                        .await
+                        .context("failed to load the show")?;
+                    format!(
+                        "Quote of the day {}",
+                        spoiler_escape(config, show.as_ref(), &quote.to_string())
+                    )
+                }
+                None => String::from("There are no quotes yet."),
+            };
+            discord
+                .create_message(message.channel_id)
+                .reply(message.id)
+                .flags(MessageFlags::SUPPRESS_EMBEDS)
+                .content(&content)
+                .await
+                .context("failed to reply to command")?;
+
+            Ok(())
+        })
+    }
+}
+
+/// The `state` key the merges are recorded under, newest first.
+const MERGE_STATE_KEY: &str = "eris.quote.merges";
+/// How many merges are kept in the `state` table.
+const MAX_MERGE_RECORDS: u32 = 100;
+
+pub struct Merge {
+    db: DatabaseConnection,
+}
+
+impl Merge {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+}
+
+/// A merge, as recorded under `MERGE_STATE_KEY`.
+#[derive(Serialize)]
+struct MergeRecord {
+    duplicate: i32,
+    canonical: i32,
+    moderator: u64,
+    copied: Vec<&'static str>,
+    merged_at: DateTime<Utc>,
+}
+
+/// Fill in the fields `canonical` lacks from `duplicate`, and list the fields that were copied.
+///
+/// The context is copied if `canonical` has none. The game and the show go together: both are
+/// copied if `canonical` has neither, and otherwise only the missing one is copied if the other
+/// one agrees, so a quote never ends up with the game of one show and a different show.
+fn merge_fields(
+    duplicate: &quote::Model,
+    canonical: &quote::Model,
+) -> (quote::ActiveModel, Vec<&'static str>) {
+    let mut merged = quote::ActiveModel::from(canonical.clone());
+    let mut copied = vec![];
+
+    let is_empty =
+        |context: &Option<String>| context.as_deref().is_none_or(|c| c.trim().is_empty());
+    if is_empty(&canonical.context) && !is_empty(&duplicate.context) {
+        merged.context = Set(duplicate.context.clone());
+        copied.push("context");
+    }
+
+    match (canonical.game_id, canonical.show_id) {
+        (None, None) => {
+            if duplicate.game_id.is_some() {
+                merged.game_id = Set(duplicate.game_id);
+                copied.push("game");
+            }
+            if duplicate.show_id.is_some() {
+                merged.show_id = Set(duplicate.show_id);
+                copied.push("show");
+            }
+        }
+        (None, Some(show_id))
+            if duplicate.show_id == Some(show_id) && duplicate.game_id.is_some() =>
+        {
+            merged.game_id = Set(duplicate.game_id);
+            copied.push("game");
+        }
+        (Some(game_id), None)
+            if duplicate.game_id == Some(game_id) && duplicate.show_id.is_some() =>
+        {
+            merged.show_id = Set(duplicate.show_id);
+            copied.push("show");
+        }
+        _ => (),
+    }
+
+    (merged, copied)
+}
+
+/// Soft-delete `duplicate`.
+fn retire_duplicate(duplicate: &quote::Model) -> quote::ActiveModel {
+    quote::ActiveModel { deleted: Set(true), ..quote::ActiveModel::from(duplicate.clone()) }
+}
+
+fn merge_reply(duplicate_id: i32, canonical_id: i32, copied: &[&str]) -> String {
+    let mut reply = format!("Merged quote #{duplicate_id} into quote #{canonical_id}");
+    match copied {
+        [] => reply.push_str(". Nothing was copied over."),
+        [field] => reply.push_str(&format!(" and copied over the {field}.")),
+        [init @ .., last] => {
+            reply.push_str(&format!(" and copied over the {} and the {last}.", init.join(", the ")))
+        }
+    }
+    reply
+}
+
+impl CommandHandler for Merge {
+    fn pattern(&self) -> &str {
+        r"quote merge (\d+) (\d+)"
+    }
+
+    fn help(&self) -> Option<Help> {
+        Some(Help {
+            name: "quote merge".into(),
+            usage: "quote merge <DUPLICATE> <CANONICAL>".into(),
+            summary: "Merge a duplicate quote into another".into(),
+            description: concat!(
+                "Delete the duplicate quote and copy its context, game, and show over to the ",
+                "canonical quote if that one doesn't have them. The duplicate's tags are dropped. ",
+                "Find candidates with `quote dedupe`.",
+            )
+            .into(),
+            examples: Cow::Borrowed(&[Cow::Borrowed("quote merge 3850 3849")]),
+        })
+    }
+
+    fn access(&self) -> Access {
+        Access::ModOnly
+    }
+
+    fn category(&self) -> &'static str {
+        "Quotes"
+    }
+
+    fn handle<'a>(
+        &'a self,
+        _: &'a Cache,
+        _: &'a Config,
+        discord: &'a DiscordClient,
+        _: Commands<'a>,
+        message: &'a Message,
+        args: &'a Args,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>> {
+        Box::pin(async move {
+            let duplicate_id = args.get(0).context("duplicate ID missing")?.parse::<i32>();
+            let canonical_id = args.get(1).context("canonical ID missing")?.parse::<i32>();
+            let content = 'reply: {
+                let (duplicate_id, canonical_id) = match (duplicate_id, canonical_id) {
+                    (Ok(duplicate_id), Ok(canonical_id)) => (duplicate_id, canonical_id),
+                    (Err(error), _) | (_, Err(error)) => {
+                        break 'reply format!("Failed to parse the quote ID: {error}")
+                    }
+                };
+                if duplicate_id == canonical_id {
+                    break 'reply String::from("Can't merge a quote into itself.");
+                }
+
+                let txn = self.db.begin().await.context("failed to start a transaction")?;
+                let mut quotes = quote::Entity::find()
+                    .filter(quote::Column::Id.is_in([duplicate_id, canonical_id]))
+                    .filter(Expr::col(quote::Column::Deleted).not())
+                    .lock_exclusive()
+                    .all(&txn)
+                    .await
+                    .context("failed to load the quotes")?;
+                let Some(canonical) = quotes.iter().position(|quote| quote.id == canonical_id)
+                else {
+                    break 'reply format!("Could not find quote #{canonical_id}");
+                };
+                let canonical = quotes.swap_remove(canonical);
+                let Some(duplicate) = quotes.pop() else {
+                    break 'reply format!("Could not find quote #{duplicate_id}");
+                };
+
+                let (merged, copied) = merge_fields(&duplicate, &canonical);
+                if !copied.is_empty() {
+                    merged.update(&txn).await.context("failed to update the canonical quote")?;
+                }
+                retire_duplicate(&duplicate)
+                    .update(&txn)
+                    .await
+                    .context("failed to delete the duplicate")?;
+                let mut all_tags = state::get_for_update::<Tags>(TAGS_STATE_KEY, &txn)
+                    .await
+                    .context("failed to load the tags")?;
+                if drop_tags(&mut all_tags, duplicate_id) {
+                    state::set(String::from(TAGS_STATE_KEY), all_tags, &txn)
+                        .await
+                        .context("failed to untag the duplicate")?;
+                }
+                state::insert_fifo_cache(
+                    String::from(MERGE_STATE_KEY),
+                    MergeRecord {
+                        duplicate: duplicate_id,
+                        canonical: canonical_id,
+                        moderator: message.author.id.get(),
+                        copied: copied.clone(),
+                        merged_at: Utc::now(),
+                    },
+                    MAX_MERGE_RECORDS,
+                    &txn,
+                )
+                .await
+                .context("failed to record the merge")?;
+                txn.commit().await.context("failed to commit the merge")?;
+
+                info!(
+                    duplicate = duplicate_id,
+                    canonical = canonical_id,
+                    moderator = message.author.id.get(),
+                    ?copied,
+                    "Merged quotes"
+                );
+                merge_reply(duplicate_id, canonical_id, &copied)
+            };
+
+            discord
+                .create_message(message.channel_id)
+                .reply(message.id)
+                .flags(MessageFlags::SUPPRESS_EMBEDS)
+                .content(&content)
+                .await
+                .context("failed to reply to command")?;
+
+            Ok(())
+        })
+    }
+}
+
+pub struct Mine {
+    db: DatabaseConnection,
+}
+
+impl Mine {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+}
+
+const NOT_QUOTED: &str = "No quotes are attributed to you yet. Say something quotable!";
+
+/// The names a user might be credited under: their nickname, display name, and username.
+fn attribution_names(
+    cache: &InMemoryCache,
+    guild_id: Option<Id<GuildMarker>>,
+    user: &User,
+) -> Vec<String> {
+    let nick = guild_id
+        .and_then(|guild_id| cache.member(guild_id, user.id))
+        .and_then(|member| member.nick().map(String::from));
+
+    let mut names: Vec<String> = vec![];
+    for name in nick.into_iter().chain(user.global_name.clone()).chain(Some(user.name.clone())) {
+        if !names.iter().any(|known| known.eq_ignore_ascii_case(&name)) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+/// A query for the quotes attributed to any of the `names`, matched like `from:NAME`.
+fn attributed_to<S: AsRef<str>>(names: &[S]) -> Option<Ast<'_>> {
+    names
+        .iter()
+        .map(|name| Ast::Column {
+            column: Column::Name,
+            op: Op::Fuzzy,
+            term: Cow::Borrowed(name.as_ref()),
+        })
+        .reduce(Ast::or)
+}
+
+impl CommandHandler for Mine {
+    fn pattern(&self) -> &str {
+        "quote mine( count)?"
+    }
+
+    fn help(&self) -> Option<Help> {
+        Some(Help {
+            name: "quote mine".into(),
+            usage: "quote mine [count]".into(),
+            summary: "Find a quote attributed to you".into(),
+            description: concat!(
+                "Post a random quote attributed to your nickname, display name, or username. With ",
+                "`count`, post how many quotes are attributed to you instead.",
+            )
+            .into(),
+            examples: Cow::Borrowed(&[
+                Cow::Borrowed("quote mine"),
+                Cow::Borrowed("quote mine count"),
+            ]),
+        })
+    }
+
+    fn category(&self) -> &'static str {
+        "Quotes"
+    }
+
+    fn handle<'a>(
+        &'a self,
+        cache: &'a Cache,
+        config: &'a Config,
+        discord: &'a DiscordClient,
+        _: Commands<'a>,
+        message: &'a Message,
+        args: &'a Args,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>> {
+        Box::pin(async move {
+            let names =
+                cache.with(|cache| attribution_names(cache, message.guild_id, &message.author));
+            let query = attributed_to(&names).context("user has no names")?;
+            let select = quote::Entity::find()
+                .filter(Condition::all().add(query.to_condition()?))
+                .filter(Expr::col(quote::Column::Deleted).not());
+
+            let content = if args.get(0).is_some() {
+                let count = select.count(&self.db).await.context("failed to count the quotes")?;
+                match count {
+                    0 => String::from(NOT_QUOTED),
+                    1 => String::from("1 quote is attributed to you."),
+                    count => format!("{count} quotes are attributed to you."),
+                }
+            } else {
+                let quotes = select.all(&self.db).await.context("failed to load the quotes")?;
+                let quote = quotes.choose(&mut rand::thread_rng()).cloned();
+                match quote {
+                    Some(quote) => {
+                        let show = quote
+                            .find_related(show::Entity)
+                            .one(&self.db)
+                            .await
+                            .context("failed to load the show")?;
+                        let needs_context = context_flags(&self.db).await?.contains(&quote.id);
+                        quote_reply(config, &quote, show.as_ref(), needs_context)
+                    }
+                    None => String::from(NOT_QUOTED),
+                }
+            };
+
+            discord
+                .create_message(message.channel_id)
+                .reply(message.id)
+                .flags(MessageFlags::SUPPRESS_EMBEDS)
+                .content(&content)
+                .await
+                .context("failed to reply to command")?;
+
+            Ok(())
+        })
+    }
+}
+
+/// How many of the most quoted games `quote games` lists.
+const TOP_GAMES: u64 = 20;
+
+/// Join `items` into a reply under `heading`, leaving out the ones that don't fit in a message.
+fn list_reply(heading: &str, items: &[String]) -> String {
+    let reserved = format!(", and {} more.", items.len()).chars().count();
+    let mut reply = format!("{heading}: ");
+    let mut len = reply.chars().count();
+    for (i, item) in items.iter().enumerate() {
+        let item = crate::markdown::escape(item);
+        let separator = if i == 0 { "" } else { ", " };
+        let item_len = separator.len() + item.chars().count();
+        if len + item_len + reserved > MESSAGE_CONTENT_LENGTH_MAX {
+            reply.push_str(&format!(", and {} more.", items.len() - i));
+            return reply;
+        }
+        reply.push_str(separator);
+        reply.push_str(&item);
+        len += item_len;
+    }
+    reply.push('.');
+    reply
+}
+
+/// How many quotes one user can have pinned.
+const MAX_PINS: usize = 25;
+
+pub struct PinQuote {
+    db: DatabaseConnection,
+}
+
+impl PinQuote {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+}
+
+/// Check that `quote_id` can be added to the user's `pinned` quotes, or explain why not.
+fn check_pin(pinned: &[i32], quote_id: i32) -> Result<(), String> {
+    if pinned.contains(&quote_id) {
+        Err(format!("You've already pinned quote #{quote_id}."))
+    } else if pinned.len() >= MAX_PINS {
+        Err(format!(
+            "You can pin at most {MAX_PINS} quotes. Unpin some with `quote unpin` to make room."
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// The state key for the IDs of the quotes `user_id` has pinned, oldest pin first.
+fn pins_state_key(user_id: Id<UserMarker>) -> String {
+    format!("eris.quote.pins.{user_id}")
+}
+
+async fn pinned_quotes(
+    db: &DatabaseConnection,
+    user_id: Id<UserMarker>,
+) -> Result<Vec<i32>, Error> {
+    Ok(state::get::<Vec<i32>>(&pins_state_key(user_id), db)
+        .await
+        .context("failed to load the pinned quotes")?
+        .unwrap_or_default())
+}
+
+impl CommandHandler for PinQuote {
+    fn pattern(&self) -> &str {
+        r"quote (un)?pin (\d+)"
+    }
+
+    fn help(&self) -> Option<Help> {
+        Some(Help {
+            name: "quote pin".into(),
+            usage: "quote [un]pin <ID>".into(),
+            summary: "Pin a quote to your collection".into(),
+            description: format!(
+                "Add a quote to your personal collection, or remove it with `quote unpin`. You \
+                 can pin up to {MAX_PINS} quotes. List them with `quote pins`."
+            )
+            .into(),
+            examples: Cow::Borrowed(&[
+                Cow::Borrowed("quote pin 3849"),
+                Cow::Borrowed("quote unpin 3849"),
+            ]),
+        })
+    }
+
+    fn category(&self) -> &'static str {
+        "Quotes"
+    }
+
+    fn handle<'a>(
+        &'a self,
+        _: &'a Cache,
+        _: &'a Config,
+        discord: &'a DiscordClient,
+        _: Commands<'a>,
+        message: &'a Message,
+        args: &'a Args,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>> {
+        Box::pin(async move {
+            let quote_id = args.get(1).context("quote ID missing")?.parse::<i32>();
+            let content = match quote_id {
+                Err(error) => format!("Failed to parse the quote ID: {error}"),
+                Ok(quote_id) if args.get(0).is_some() => {
+                    let state_key = pins_state_key(message.author.id);
+                    let txn = self.db.begin().await.context("failed to start a transaction")?;
+                    let mut pinned = state::get_for_update::<Vec<i32>>(&state_key, &txn)
+                        .await
+                        .context("failed to load the pinned quotes")?;
+                    let content = if let Some(i) = pinned.iter().position(|&id| id == quote_id) {
+                        pinned.remove(i);
+                        state::set(state_key, pinned, &txn)
+                            .await
+                            .context("failed to unpin the quote")?;
+                        format!("Unpinned quote #{quote_id}.")
+                    } else {
+                        format!("You haven't pinned quote #{quote_id}.")
+                    };
+                    txn.commit().await.context("failed to commit the pins")?;
+                    content
+                }
+                Ok(quote_id) => {
+                    let quote = quote::Entity::find_by_id(quote_id)
+                        .filter(Expr::col(quote::Column::Deleted).not())
+                        .one(&self.db)
+                        .await
+                        .context("failed to load the quote")?;
+                    if quote.is_some() {
+                        // The check and the insert happen under the lock so that concurrent pins
+                        // can't go over `MAX_PINS`.
+                        let state_key = pins_state_key(message.author.id);
+                        let txn = self.db.begin().await.context("failed to start a transaction")?;
+                        let mut pinned = state::get_for_update::<Vec<i32>>(&state_key, &txn)
+                            .await
+                            .context("failed to load the pinned quotes")?;
+                        let content = match check_pin(&pinned, quote_id) {
+                            Err(reply) => reply,
+                            Ok(()) => {
+                                pinned.push(quote_id);
+                                state::set(state_key, pinned, &txn)
+                                    .await
+                                    .context("failed to pin the quote")?;
+                                format!("Pinned quote #{quote_id}.")
+                            }
+                        };
+                        txn.commit().await.context("failed to commit the pins")?;
+                        content
+                    } else {
+                        format!("Could not find quote #{quote_id}")
+                    }
+                }
+            };
+
+            discord
+                .create_message(message.channel_id)
+                .reply(message.id)
+                .flags(MessageFlags::SUPPRESS_EMBEDS)
+                .content(&content)
+                .await
+                .context("failed to reply to command")?;
+
+            Ok(())
+        })
+    }
+}
+
+pub struct Pins {
+    db: DatabaseConnection,
+}
+
+impl Pins {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+}
+
+/// `quotes` one per line under `heading`, leaving out the ones that don't fit in a message.
+fn quote_list(
+    config: &Config,
+    heading: &str,
+    quotes: &[(quote::Model, Option<show::Model>)],
+) -> String {
+    let mut reply = String::from(heading);
+    for (i, (quote, show)) in quotes.iter().enumerate() {
+        let line = format!("\nQuote {}", spoiler_escape(config, show.as_ref(), &quote.to_string()));
+        let more = format!("\n…and {} more.", quotes.len() - i);
+        if reply.chars().count() + line.chars().count() + more.chars().count()
+            > MESSAGE_CONTENT_LENGTH_MAX
+        {
+            reply.push_str(&more);
+            break;
+        }
+        reply.push_str(&line);
+    }
+    reply
+}
+
+/// The reply to `quote pins`: one pinned quote per line, as many as fit in a message.
+fn pins_reply(config: &Config, quotes: &[(quote::Model, Option<show::Model>)]) -> String {
+    if quotes.is_empty() {
+        return String::from("You haven't pinned any quotes yet. Pin one with `quote pin`.");
+    }
+
+    quote_list(config, "Your pinned quotes:", quotes)
+}
+
+impl CommandHandler for Pins {
+    fn pattern(&self) -> &str {
+        "quote pins"
+    }
+
+    fn help(&self) -> Option<Help> {
+        Some(Help {
+            name: "quote pins".into(),
+            usage: "quote pins".into(),
+            summary: "List the quotes you've pinned".into(),
+            description: "List the quotes you've pinned with `quote pin`, oldest pin first.".into(),
+            examples: Cow::Borrowed(&[Cow::Borrowed("quote pins")]),
+        })
+    }
+
+    fn category(&self) -> &'static str {
+        "Quotes"
+    }
+
+    fn handle<'a>(
+        &'a self,
+        _: &'a Cache,
+        config: &'a Config,
+        discord: &'a DiscordClient,
+        _: Commands<'a>,
+        message: &'a Message,
+        _: &'a Args,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>> {
+        Box::pin(async move {
+            let quote_ids = pinned_quotes(&self.db, message.author.id).await?;
+            let mut quotes = quote::Entity::find()
+                .find_also_related(show::Entity)
+                .filter(quote::Column::Id.is_in(quote_ids.iter().copied()))
+                .filter(Expr::col((quote::Entity, quote::Column::Deleted)).not())
+                .all(&self.db)
+                .await
+                .context("failed to load the quotes")?;
+            quotes.sort_by_key(|(quote, _)| quote_ids.iter().position(|&id| id == quote.id));
+
+            discord
+                .create_message(message.channel_id)
+                .reply(message.id)
+                .flags(MessageFlags::SUPPRESS_EMBEDS)
+                .content(&pins_reply(config, &quotes))
+                .await
+                .context("failed to reply to command")?;
+
+            Ok(())
+        })
+    }
+}
+
+/// How many quotes `quote recent-additions` lists.
+const RECENT_QUOTES: u64 = 5;
+
+pub struct Recent {
+    db: DatabaseConnection,
+}
+
+impl Recent {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+}
+
+/// The newest quotes. There's no creation timestamp, so the highest IDs stand in for it.
+fn recent_select() -> Select<quote::Entity> {
+    quote::Entity::find()
+        .filter(Expr::col((quote::Entity, quote::Column::Deleted)).not())
+        .order_by_desc(quote::Column::Id)
+        .limit(RECENT_QUOTES)
+}
+
+/// The reply to `quote recent-additions`, newest quote first.
+fn recent_reply(config: &Config, quotes: &[(quote::Model, Option<show::Model>)]) -> String {
+    if quotes.is_empty() {
+        return String::from("There are no quotes yet.");
+    }
+
+    quote_list(config, "Recently added quotes:", quotes)
+}
+
+impl CommandHandler for Recent {
+    fn pattern(&self) -> &str {
+        "quote recent-additions"
+    }
+
+    fn help(&self) -> Option<Help> {
+        Some(Help {
+            name: "quote recent-additions".into(),
+            usage: "quote recent-additions".into(),
+            summary: "List the newest quotes".into(),
+            description: format!(
+                "List the {RECENT_QUOTES} most recently added quotes, newest first."
+            )
+            .into(),
+            examples: Cow::Borrowed(&[Cow::Borrowed("quote recent-additions")]),
+        })
+    }
+
+    fn category(&self) -> &'static str {
+        "Quotes"
+    }
+
+    fn handle<'a>(
+        &'a self,
+        _: &'a Cache,
+        config: &'a Config,
+        discord: &'a DiscordClient,
+        _: Commands<'a>,
+        message: &'a Message,
+        _: &'a Args,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>> {
+        Box::pin(async move {
+            let quotes = recent_select()
+                .find_also_related(show::Entity)
+                .all(&self.db)
+                .await
+                .context("failed to load the recent quotes")?;
+
+            discord
+                .create_message(message.channel_id)
+                .reply(message.id)
+                .flags(MessageFlags::SUPPRESS_EMBEDS)
+                .content(&recent_reply(config, &quotes))
+                .await
+                .context("failed to reply to command")?;
+
+            Ok(())
+        })
+    }
+}
+
+/// How long a single tag can be.
+const MAX_TAG_LENGTH: usize = 32;
+
+pub struct TagQuote {
+    db: DatabaseConnection,
+}
+
+impl TagQuote {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+}
+
+/// Split the tags on whitespace and lowercase them, dropping duplicates.
+fn parse_tags(tags: &str) -> Result<Vec<String>, String> {
+    let mut parsed = Vec::<String>::new();
+    for tag in tags.split_whitespace() {
+        let tag = tag.to_lowercase();
+        if tag.chars().count() > MAX_TAG_LENGTH {
+            return Err(format!("Tags can be at most {MAX_TAG_LENGTH} characters long."));
+        }
+        if !parsed.contains(&tag) {
+            parsed.push(tag);
+        }
+    }
+    if parsed.is_empty() {
+        Err(String::from("No tags given."))
+    } else {
+        Ok(parsed)
+    }
+}
+
+/// The quote tags, as a map from the tag to the IDs of the quotes with that tag. LRRbot owns the
+/// quote tables, so the tags are kept in the state instead.
+const TAGS_STATE_KEY: &str = "eris.quote.tags";
+
+type Tags = BTreeMap<String, BTreeSet<i32>>;
+
+fn add_tags(all_tags: &mut Tags, quote_id: i32, tags: &[String]) {
+    for tag in tags {
+        all_tags.entry(tag.clone()).or_default().insert(quote_id);
+    }
+}
+
+/// Remove every tag from `quote_id`. Returns whether it had any.
+fn drop_tags(all_tags: &mut Tags, quote_id: i32) -> bool {
+    let mut dropped = false;
+    all_tags.retain(|_, quote_ids| {
+        dropped |= quote_ids.remove(&quote_id);
+        !quote_ids.is_empty()
+    });
+    dropped
+}
+
+/// Remove `tags` from `quote_id`. Returns how many of them the quote had.
+fn remove_tags(all_tags: &mut Tags, quote_id: i32, tags: &[String]) -> usize {
+    let mut removed = 0;
+    for tag in tags {
+        if let Some(quote_ids) = all_tags.get_mut(tag) {
+            if quote_ids.remove(&quote_id) {
+                removed += 1;
+            }
+            if quote_ids.is_empty() {
+                all_tags.remove(tag);
+            }
+        }
+    }
+    removed
+}
+
+impl CommandHandler for TagQuote {
+    fn pattern(&self) -> &str {
+        r"quote (un)?tag (\d+) (.+)"
+    }
+
+    fn help(&self) -> Option<Help> {
+        Some(Help {
+            name: "quote tag".into(),
+            usage: "quote [un]tag <ID> <TAG>...".into(),
+            summary: "Tag a quote".into(),
+            description: format!(
+                "Add tags to a quote, or remove them with `quote untag`. Tags are separated by \
+                 spaces, case-insensitive, and at most {MAX_TAG_LENGTH} characters long. Find \
+                 tagged quotes with `quote tag:TAG`."
+            )
+            .into(),
+            examples: Cow::Borrowed(&[
+                Cow::Borrowed("quote tag 3849 pun callback"),
+                Cow::Borrowed("quote untag 3849 callback"),
+            ]),
+        })
+    }
+
+    fn access(&self) -> Access {
+        Access::ModOnly
+    }
+
+    fn category(&self) -> &'static str {
+        "Quotes"
+    }
+
+    fn handle<'a>(
+        &'a self,
+        _: &'a Cache,
+        _: &'a Config,
+        discord: &'a DiscordClient,
+        _: Commands<'a>,
+        message: &'a Message,
+        args: &'a Args,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>> {
+        Box::pin(async move {
+            let quote_id = args.get(1).context("quote ID missing")?.parse::<i32>();
+            let tags = parse_tags(args.get(2).context("tags missing")?);
+            let content = match (quote_id, tags) {
+                (Err(error), _) => format!("Failed to parse the quote ID: {error}"),
+                (_, Err(reply)) => reply,
+                (Ok(quote_id), Ok(tags)) if args.get(0).is_some() => {
+                    let txn = self.db.begin().await.context("failed to start a transaction")?;
+                    let mut all_tags = state::get_for_update::<Tags>(TAGS_STATE_KEY, &txn)
+                        .await
+                        .context("failed to load the tags")?;
+                    let removed = remove_tags(&mut all_tags, quote_id, &tags);
+                    if removed > 0 {
+                        state::set(String::from(TAGS_STATE_KEY), all_tags, &txn)
+                            .await
+                            .context("failed to untag the quote")?;
+                    }
+                    txn.commit().await.context("failed to commit the tags")?;
+                    match removed {
+                        0 => format!("Quote #{quote_id} had none of those tags."),
+                        1 => format!("Removed 1 tag from quote #{quote_id}."),
+                        n => format!("Removed {n} tags from quote #{quote_id}."),
+                    }
+                }
+                (Ok(quote_id), Ok(tags)) => {
+                    let quote = quote::Entity::find_by_id(quote_id)
+                        .filter(Expr::col(quote::Column::Deleted).not())
+                        .one(&self.db)
+                        .await
+                        .context("failed to load the quote")?;
+                    if quote.is_some() {
+                        let txn = self.db.begin().await.context("failed to start a transaction")?;
+                        let mut all_tags = state::get_for_update::<Tags>(TAGS_STATE_KEY, &txn)
+                            .await
+                            .context("failed to load the tags")?;
+                        add_tags(&mut all_tags, quote_id, &tags);
+                        state::set(String::from(TAGS_STATE_KEY), all_tags, &txn)
+                            .await
+                            .context("failed to tag the quote")?;
+                        txn.commit().await.context("failed to commit the tags")?;
+                        format!("Tagged quote #{quote_id} with {}.", tags.join(", "))
+                    } else {
+                        format!("Could not find quote #{quote_id}")
+                    }
+                }
+            };
+
+            discord
+                .create_message(message.channel_id)
+                .reply(message.id)
+                .flags(MessageFlags::SUPPRESS_EMBEDS)
+                .content(&content)
+                .await
+                .context("failed to reply to command")?;
+
+            Ok(())
+        })
+    }
+}
+
+/// How many of the most recently shown quotes `quote random-unseen` avoids in each channel.
+const UNSEEN_WINDOW: u32 = 100;
+
+pub struct RandomUnseen {
+    db: DatabaseConnection,
+}
+
+impl RandomUnseen {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+}
+
+fn seen_state_key(channel_id: Id<ChannelMarker>) -> String {
+    format!("eris.quote.seen.{channel_id}")
+}
+
+/// Pick a random quote that isn't among the `seen` ones. If every quote has been seen, pick from
+/// all of them and signal that the seen quotes should be forgotten.
+fn pick_unseen<R: Rng>(ids: &[i32], seen: &[i32], rng: &mut R) -> Option<(i32, bool)> {
+    let unseen = ids.iter().copied().filter(|id| !seen.contains(id)).collect::<Vec<_>>();
+    match unseen.choose(rng) {
+        Some(&id) => Some((id, false)),
+        None => ids.choose(rng).map(|&id| (id, true)),
+    }
+}
+
+impl CommandHandler for RandomUnseen {
+    fn pattern(&self) -> &str {
+        "quote random-unseen"
+    }
+
+    fn help(&self) -> Option<Help> {
+        Some(Help {
+            name: "quote random-unseen".into(),
+            usage: "quote random-unseen".into(),
+            summary: "Post a random quote that hasn't been posted here recently".into(),
+            description: format!(
+                "Post a random quote, skipping the last {UNSEEN_WINDOW} quotes this command \
+                 posted in the channel. Once every quote has been posted, start over."
+            )
+            .into(),
+            examples: Cow::Borrowed(&[Cow::Borrowed("quote random-unseen")]),
+        })
+    }
+
+    fn category(&self) -> &'static str {
+        "Quotes"
+    }
+
+    fn handle<'a>(
+        &'a self,
+        _: &'a Cache,
+        config: &'a Config,
+        discord: &'a DiscordClient,
+        _: Commands<'a>,
+        message: &'a Message,
+        _: &'a Args,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>> {
+        Box::pin(async move {
+            let key = seen_state_key(message.channel_id);
+            let seen = state::get::<Vec<i32>>(&key, &self.db).await?.unwrap_or_default();
+            let ids = quote::Entity::find()
+                .select_only()
+                .column(quote::Column::Id)
+                .filter(Expr::col(quote::Column::Deleted).not())
+                .into_tuple::<i32>()
+                .all(&self.db)
+                .await
+                .context("failed to load the quote IDs")?;
+
+            let picked = pick_unseen(&ids, &seen, &mut rand::thread_rng());
+            let quote = match picked {
+                Some((id, reset)) => {
+                    if reset {
+                        state::set(key.clone(), Vec::<i32>::new(), &self.db).await?;
+                    }
+                    state::insert_fifo_cache(key, id, UNSEEN_WINDOW, &self.db)
+                        .await
+                        .context("failed to record the quote as seen")?;
+                    quote::Entity::find_by_id(id)
+                        .find_also_related(show::Entity)
+                        .one(&self.db)
+                        .await
+                        .context("failed to load the quote")?
+                }
+                None => None,
+            };
+
+            let content = match quote {
+                Some((quote, show)) => {
+                    let needs_context = context_flags(&self.db).await?.contains(&quote.id);
+                    quote_reply(config, &quote, show.as_ref(), needs_context)
+                }
+                None => String::from("There are no quotes yet."),
+            };
+            discord
+                .create_message(message.channel_id)
+                .reply(message.id)
+                .flags(MessageFlags::SUPPRESS_EMBEDS)
+                .content(&content)
+                .await
+                .context("failed to reply to command")?;
+
+            Ok(())
+        })
+    }
+}
+
+/// How many channels `quote blame-channel` lists.
+const BLAME_TOP: usize = 10;
+
+pub struct BlameChannel;
+
+impl BlameChannel {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+fn is_quote_command(command: &str) -> bool {
+    command == "quote" || command.starts_with("quote ")
+}
+
+/// How many quote commands were run in each channel.
+fn quote_uses_by_channel(uses: &[CommandUse]) -> HashMap<Id<ChannelMarker>, u64> {
+    let mut counts = HashMap::new();
+    for command_use in uses.iter().filter(|command_use| is_quote_command(&command_use.command)) {
+        *counts.entry(command_use.channel_id).or_default() += 1;
+    }
+    counts
+}
+
+fn blame_reply(counts: &HashMap<Id<ChannelMarker>, u64>, hours: i64) -> String {
+    if counts.is_empty() {
+        return format!("No quote commands in the last {hours} hours.");
+    }
+
+    let mut ranked = counts.iter().collect::<Vec<_>>();
+    ranked.sort_by(|(a_id, a_count), (b_id, b_count)| b_count.cmp(a_count).then(a_id.cmp(b_id)));
+
+    let mut reply = format!("Quote commands in the last {hours} hours:");
+    for (i, (channel_id, count)) in ranked.into_iter().take(BLAME_TOP).enumerate() {
+        reply.push_str(&format!("\n{}. {}: {count}", i + 1, channel_id.mention()));
+    }
+    if counts.len() > BLAME_TOP {
+        reply.push_str(&format!("\n…and {} more channels.", counts.len() - BLAME_TOP));
+    }
+    reply
+}
+
+impl CommandHandler for BlameChannel {
+    fn pattern(&self) -> &str {
+        r"quote blame-channel(?: (\d+))?"
+    }
+
+    fn help(&self) -> Option<Help> {
+        Some(Help {
+            name: "quote blame-channel".into(),
+            usage: "quote blame-channel [HOURS]".into(),
+            summary: "List the channels with the most quote commands".into(),
+            description: format!(
+                "List the channels where quote commands were used the most in the last `HOURS` \
+                 hours, 24 by default and at most {}. Only counts commands since the last \
+                 restart.",
+                MAX_USE_AGE.num_hours()
+            )
+            .into(),
+            examples: Cow::Borrowed(&[
+                Cow::Borrowed("quote blame-channel"),
+                Cow::Borrowed("quote blame-channel 168"),
+            ]),
+        })
+    }
+
+    fn access(&self) -> Access {
+        Access::ModOnly
+    }
+
+    fn category(&self) -> &'static str {
+        "Quotes"
+    }
+
+    fn handle<'a>(
+        &'a self,
+        _: &'a Cache,
+        _: &'a Config,
+        discord: &'a DiscordClient,
+        commands: Commands<'a>,
+        message: &'a Message,
+        args: &'a Args,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>> {
+        Box::pin(async move {
+            let hours = args.get(0).map_or(Ok(24), str::parse::<i64>);
+            let content = match hours {
+                Ok(hours) if (1..=MAX_USE_AGE.num_hours()).contains(&hours) => {
+                    let since = Utc::now() - chrono::TimeDelta::hours(hours);
+                    blame_reply(&quote_uses_by_channel(&commands.uses_since(since)), hours)
+                }
+                _ => {
+                    format!("The window has to be between 1 and {} hours.", MAX_USE_AGE.num_hours())
+                }
+            };
+
+            discord
+                .create_message(message.channel_id)
+                .reply(message.id)
+                .flags(MessageFlags::SUPPRESS_EMBEDS)
+                .content(&content)
+                .await
+                .context("failed to reply to command")?;
+
+            Ok(())
+        })
+    }
+}
+
+pub struct Shows {
+    db: DatabaseConnection,
+}
+
+impl Shows {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+}
+
+impl CommandHandler for Shows {
+    fn pattern(&self) -> &str {
+        "quote shows"
+    }
+
+    fn help(&self) -> Option<Help> {
+        Some(Help {
+            name: "quote shows".into(),
+            usage: "quote shows".into(),
+            summary: "List the shows that have quotes".into(),
+            description: "List the shows that have quotes, to use with `quote show:NAME`.".into(),
+            examples: Cow::Borrowed(&[Cow::Borrowed("quote shows")]),
+        })
+    }
+
+    fn category(&self) -> &'static str {
+        "Quotes"
+    }
+
+    fn handle<'a>(
+        &'a self,
+        _: &'a Cache,
+        _: &'a Config,
+        discord: &'a DiscordClient,
+        _: Commands<'a>,
+        message: &'a Message,
+        _: &'a Args,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>> {
+        Box::pin(async move {
+            let quoted = QuerySelect::query(
+                &mut quote::Entity::find()
+                    .filter(Expr::col(quote::Column::Deleted).not())
+                    .select_only()
+                    .column(quote::Column::ShowId),
+            )
+            .take();
+            let names = show::Entity::find()
+                .filter(show::Column::Id.in_subquery(quoted))
+                .order_by_asc(show::Column::Name)
+                .all(&self.db)
+                .await
+                .context("failed to load the shows")?
+                .into_iter()
+                .map(|show| show.name)
+                .collect::<Vec<_>>();
+
+            let content = if names.is_empty() {
+                String::from("There are no quotes yet.")
+            } else {
+                list_reply("Shows with quotes", &names)
+            };
+            discord
+                .create_message(message.channel_id)
+                .reply(message.id)
+                .flags(MessageFlags::SUPPRESS_EMBEDS)
+                .content(&content)
+                .await
+                .context("failed to reply to command")?;
+
+            Ok(())
+        })
+    }
+}
+
+pub struct Games {
+    db: DatabaseConnection,
+}
+
+impl Games {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+}
+
+/// The names of the games with the most quotes and their quote counts.
+fn top_games() -> Selector<SelectGetableTuple<(String, i64)>> {
+    quote::Entity::find()
+        .select_only()
+        .column(game::Column::Name)
+        .column_as(Expr::col((quote::Entity, quote::Column::Id)).count(), "count")
+        .inner_join(game::Entity)
+        .filter(Expr::col((quote::Entity, quote::Column::Deleted)).not())
+        .group_by(game::Column::Id)
+        .order_by(Expr::col(Alias::new("count")), Order::Desc)
+        .order_by_asc(game::Column::Name)
+        .limit(TOP_GAMES)
+        .into_tuple()
+}
+
+impl CommandHandler for Games {
+    fn pattern(&self) -> &str {
+        "quote games"
+    }
+
+    fn help(&self) -> Option<Help> {
+        Some(Help {
+            name: "quote games".into(),
+            usage: "quote games".into(),
+            summary: "List the most quoted games".into(),
+            description: concat!(
+                "List the games with the most quotes and how many quotes they have, to use with ",
+                "`quote game:NAME`.",
+            )
+            .into(),
+            examples: Cow::Borrowed(&[Cow::Borrowed("quote games")]),
+        })
+    }
+
+    fn category(&self) -> &'static str {
+        "Quotes"
+    }
+
+    fn handle<'a>(
+        &'a self,
+        _: &'a Cache,
+        _: &'a Config,
+        discord: &'a DiscordClient,
+        _: Commands<'a>,
+        message: &'a Message,
+        _: &'a Args,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>> {
+        Box::pin(async move {
+            let games = top_games()
+                .all(&self.db)
+                .await
+                .context("failed to load the games")?
+                .into_iter()
+                .map(|(name, count)| format!("{name} ({count})"))
+                .collect::<Vec<_>>();
+
+            let content = if games.is_empty() {
+                String::from("There are no quotes yet.")
+            } else {
+                list_reply("Most quoted games", &games)
+            };
+            discord
+                .create_message(message.channel_id)
+                .reply(message.id)
+                .flags(MessageFlags::SUPPRESS_EMBEDS)
+                .content(&content)
+                .await
+                .context("failed to reply to command")?;
+
+            Ok(())
+        })
+    }
+}
+
+pub struct RenameGame {
+    db: DatabaseConnection,
+}
+
+impl RenameGame {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+}
+
+/// The game to rename, and the show if only the name it's displayed under there is changed.
+struct RenameTarget {
+    game: game::Model,
+    show: Option<(show::Model, game_entry::Model)>,
+}
+
+/// Check that everything `quote rename-game` looked up was found, or explain what wasn't.
+fn rename_target(
+    game_id: i32,
+    show_key: Option<&str>,
+    game: Option<game::Model>,
+    show: Option<show::Model>,
+    entry: Option<game_entry::Model>,
+) -> Result<RenameTarget, String> {
+    let game = game.ok_or_else(|| format!("Could not find game #{game_id}."))?;
+    let Some(show_key) = show_key else { return Ok(RenameTarget { game, show: None }) };
+    let show = show
+        .ok_or_else(|| format!("Could not find the show {}.", crate::markdown::escape(show_key)))?;
+    let entry = entry.ok_or_else(|| {
+        format!("Game #{game_id} hasn't been played on {}.", crate::markdown::escape(&show.name))
+    })?;
+    Ok(RenameTarget { game, show: Some((show, entry)) })
+}
+
+/// The row `quote rename-game` changes.
+enum Rename {
+    Game(game::ActiveModel),
+    Entry(game_entry::ActiveModel),
+}
+
+/// The change that renames `target` to `name` and the reply with the old and new names, or why
+/// it can't be renamed.
+fn rename_update(target: &RenameTarget, name: &str) -> Result<(Rename, String), String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(String::from("The new name can't be empty."));
+    }
+
+    let RenameTarget { game, show } = target;
+    Ok(match show {
+        None => (
+            Rename::Game(game::ActiveModel {
+                name: Set(String::from(name)),
+                ..game::ActiveModel::from(game.clone())
+            }),
+            format!(
+                "Renamed game #{} from {} to {}.",
+                game.id,
+                crate::markdown::escape(&game.name),
+                crate::markdown::escape(name),
+            ),
+        ),
+        Some((show, entry)) => (
+            Rename::Entry(game_entry::ActiveModel {
+                display_name: Set(Some(String::from(name))),
+                ..game_entry::ActiveModel::from(entry.clone())
+            }),
+            format!(
+                "Renamed game #{} on {} from {} to {}.",
+                game.id,
+                crate::markdown::escape(&show.name),
+                crate::markdown::escape(game.effective_name(Some(entry))),
+                crate::markdown::escape(name),
+            ),
+        ),
+    })
+}
+
+impl CommandHandler for RenameGame {
+    fn pattern(&self) -> &str {
+        r"quote rename-game (\d+)(?: show:(\S+))? (.+)"
+    }
+
+    fn help(&self) -> Option<Help> {
+        Some(Help {
+            name: "quote rename-game".into(),
+            usage: "quote rename-game <ID> [show:SHOW] <NAME>".into(),
+            summary: "Correct the name of a game".into(),
+            description: concat!(
+                "Rename a game everywhere. With `show:` followed by the show's key, only change ",
+                "the name the game is displayed under on that show.",
+            )
+            .into(),
+            examples: Cow::Borrowed(&[
+                Cow::Borrowed("quote rename-game 110 Magic: The Gathering"),
+                Cow::Borrowed("quote rename-game 110 show:lrrmtg Magic"),
+            ]),
+        })
+    }
+
+    fn access(&self) -> Access {
+        Access::ModOnly
+    }
+
+    fn category(&self) -> &'static str {
+        "Quotes"
+    }
+
+    fn handle<'a>(
+        &'a self,
+        _: &'a Cache,
+        _: &'a Config,
+        discord: &'a DiscordClient,
+        _: Commands<'a>,
+        message: &'a Message,
+        args: &'a Args,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>> {
+        Box::pin(async move {
+            let show_key = args.get(1);
+            let name = args.get(2).context("name missing")?;
+
+            let target = match args.get(0).context("game ID missing")?.parse::<i32>() {
+                Ok(game_id) => {
+                    let game = game::Entity::find_by_id(game_id)
+                        .one(&self.db)
+                        .await
+                        .context("failed to load the game")?;
+                    let show = match show_key {
+                        Some(key) => show::Entity::find()
+                            .filter(show::Column::Key.eq(key))
+                            .one(&self.db)
+                            .await
+                            .context("failed to load the show")?,
+                        None => None,
+                    };
+                    let entry = match (&game, &show) {
+                        (Some(game), Some(show)) => {
+                            game_entry::Entity::find_by_id((game.id, show.id))
+                                .one(&self.db)
+                                .await
+                                .context("failed to load the game entry")?
+                        }
+                        _ => None,
+                    };
+                    rename_target(game_id, show_key, game, show, entry)
+                }
+                Err(error) => Err(format!("Failed to parse the game ID: {error}")),
+            };
+
+            let content = match target.and_then(|target| rename_update(&target, name)) {
+                Ok((Rename::Game(game), reply)) => {
+                    game.update(&self.db).await.context("failed to rename the game")?;
+                    reply
+                }
+                Ok((Rename::Entry(entry), reply)) => {
+                    entry
+                        .update(&self.db)
+                        .await
+                        .context("failed to rename the game on the show")?;
+                    reply
+                }
+                Err(reply) => reply,
+            };
+
+            discord
+                .create_message(message.channel_id)
+                .reply(message.id)
+                .flags(MessageFlags::SUPPRESS_EMBEDS)
+                .content(&content)
+                .await
+                .context("failed to reply to command")?;
+
+            Ok(())
+        })
+    }
+}
+
+/// How similar the text of two quotes has to be for `quote dedupe` to report them.
+const DEDUPE_THRESHOLD: f64 = 0.6;
+/// How many pairs of quotes `quote dedupe` reports at most.
+const DEDUPE_LIMIT: u64 = 20;
+
+pub struct Dedupe {
+    db: DatabaseConnection,
+}
+
+impl Dedupe {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+}
+
+/// Whether the `pg_trgm` extension that [`duplicate_pairs`] needs is installed. LRRbot's schema
+/// doesn't install it, so it has to be set up with `CREATE EXTENSION pg_trgm` first.
+async fn has_pg_trgm(conn: &DatabaseConnection) -> Result<bool, Error> {
+    let row = conn
+        .query_one(Statement::from_sql_and_values(
+            DatabaseBackend::Postgres,
+            "SELECT 1 FROM pg_extension WHERE extname = 'pg_trgm'",
+            [],
+        ))
+        .await
+        .context("failed to look up the `pg_trgm` extension")?;
+    Ok(row.is_some())
+}
+
+/// The `pg_trgm` trigram similarity of the text of the quotes `a` and `b`.
+fn similarity(a: &Alias, b: &Alias) -> SimpleExpr {
+    SimpleExpr::from(
+        Func::cust(Alias::new("similarity"))
+            .arg(Expr::col((a.clone(), quote::Column::Quote)))
+            .arg(Expr::col((b.clone(), quote::Column::Quote))),
+    )
+}
+
+/// Pairs of non-deleted quotes `a` and `b` that meet all of `conditions`, most similar first.
+fn quote_pairs(
+    a: Alias,
+    b: Alias,
+    conditions: impl IntoIterator<Item = SimpleExpr>,
+) -> SelectStatement {
+    let join = Condition::all().add(
+        Expr::col((a.clone(), quote::Column::Id)).lt(Expr::col((b.clone(), quote::Column::Id))),
+    );
+
+    Query::select()
+        .expr_as(Expr::col((a.clone(), quote::Column::Id)), Alias::new("first"))
+        .expr_as(Expr::col((b.clone(), quote::Column::Id)), Alias::new("second"))
+        .expr_as(similarity(&a, &b), Alias::new("similarity"))
+        .from_as(quote::Entity, a.clone())
+        .join_as(
+            JoinType::InnerJoin,
+            quote::Entity,
+            b.clone(),
+            conditions.into_iter().fold(join, Condition::add),
+        )
+        .and_where(Expr::col((a.clone(), quote::Column::Deleted)).not())
+        .and_where(Expr::col((b, quote::Column::Deleted)).not())
+        .order_by(Alias::new("similarity"), Order::Desc)
+        .order_by((a, quote::Column::Id), Order::Asc)
+        .limit(DEDUPE_LIMIT)
+        .to_owned()
+}
+
+/// Pairs of non-deleted quotes from the same game on the same show whose text is similar by
+/// `pg_trgm`'s trigram similarity, most similar first.
+///
+/// Only comparing quotes from the same game keeps the self-join tractable.
+fn duplicate_pairs() -> SelectStatement {
+    let (a, b) = (Alias::new("a"), Alias::new("b"));
+    let mut select = quote_pairs(
+        a.clone(),
+        b.clone(),
+        [
+            Expr::col((a.clone(), quote::Column::GameId))
+                .equals((b.clone(), quote::Column::GameId)),
+            Expr::col((a.clone(), quote::Column::ShowId))
+                .equals((b.clone(), quote::Column::ShowId)),
+        ],
+    );
+    select.and_where(Expr::expr(similarity(&a, &b)).gt(DEDUPE_THRESHOLD));
+    select
+}
+
+/// Pairs of non-deleted quotes with exactly the same text where either is missing a game or a
+/// show, which [`duplicate_pairs`] doesn't compare. Matching the text exactly keeps the self-join
+/// tractable without the game to narrow it down.
+fn unsorted_duplicate_pairs() -> SelectStatement {
+    let (a, b) = (Alias::new("a"), Alias::new("b"));
+    quote_pairs(
+        a.clone(),
+        b.clone(),
+        [
+            Expr::col((a.clone(), quote::Column::Quote)).equals((b.clone(), quote::Column::Quote)),
+            Expr::col((a.clone(), quote::Column::GameId))
+                .is_null()
+                .or(Expr::col((a, quote::Column::ShowId)).is_null())
+                .or(Expr::col((b.clone(), quote::Column::GameId)).is_null())
+                .or(Expr::col((b, quote::Column::ShowId)).is_null()),
+        ],
+    )
+}
+
+/// The pairs from both queries, most similar first and at most `DEDUPE_LIMIT` of them.
+fn merge_pairs(
+    mut pairs: Vec<(i32, i32, f32)>,
+    unsorted: Vec<(i32, i32, f32)>,
+) -> Vec<(i32, i32, f32)> {
+    pairs.extend(unsorted);
+    pairs.sort_by(|a, b| b.2.total_cmp(&a.2).then(a.0.cmp(&b.0)));
+    pairs.truncate(DEDUPE_LIMIT as usize);
+    pairs
+}
+
+/// Run a query from [`quote_pairs`].
+async fn query_pairs(
+    db: &DatabaseConnection,
+    select: &SelectStatement,
+) -> Result<Vec<(i32, i32, f32)>, Error> {
+    db.query_all(db.get_database_backend().build(select))
+        .await
+        .context("failed to search for duplicates")?
+        .iter()
+        .map(<(i32, i32, f32)>::try_get_many_by_index)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(DbErr::from)
+        .context("failed to parse the duplicates")
+}
+
+fn dedupe_reply(pairs: &[(i32, i32, f32)]) -> String {
+    if pairs.is_empty() {
+        return String::from("Could not find any likely duplicates.");
+    }
+
+    let pairs = pairs
+        .iter()
+        .map(|(first, second, similarity)| {
+            format!("#{first} and #{second} ({:.0}%)", similarity * 100.0)
+        })
+        .collect::<Vec<_>>();
+    list_reply("Likely duplicates", &pairs)
+}
+
+impl CommandHandler for Dedupe {
+    fn pattern(&self) -> &str {
+        "quote dedupe"
+    }
+
+    fn help(&self) -> Option<Help> {
+        Some(Help {
+            name: "quote dedupe".into(),
+            usage: "quote dedupe".into(),
+            summary: "Find quotes that are likely duplicates".into(),
+            description: concat!(
+                "List pairs of quotes from the same game on the same show with very similar text, ",
+                "and quotes without a game or a show with the same text as another, most similar ",
+                "first, for review. Nothing is changed.",
+            )
+            .into(),
+            examples: Cow::Borrowed(&[Cow::Borrowed("quote dedupe")]),
+        })
+    }
+
+    fn access(&self) -> Access {
+        Access::ModOnly
+    }
+
+    fn category(&self) -> &'static str {
+        "Quotes"
+    }
+
+    fn handle<'a>(
+        &'a self,
+        _: &'a Cache,
+        _: &'a Config,
+        discord: &'a DiscordClient,
+        _: Commands<'a>,
+        message: &'a Message,
+        _: &'a Args,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>> {
+        Box::pin(async move {
+            let content = if has_pg_trgm(&self.db).await? {
+                let pairs = query_pairs(&self.db, &duplicate_pairs()).await?;
+                let unsorted = query_pairs(&self.db, &unsorted_duplicate_pairs()).await?;
+                dedupe_reply(&merge_pairs(pairs, unsorted))
+            } else {
+                String::from("Finding duplicates needs the `pg_trgm` extension in the database.")
+            };
+
+            discord
+                .create_message(message.channel_id)
+                .reply(message.id)
+                .flags(MessageFlags::SUPPRESS_EMBEDS)
+                .content(&content)
+                .await
+                .context("failed to reply to command")?;
+
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::borrow::Cow;
+    use std::collections::{BTreeSet, HashMap};
+
+    use chrono::{NaiveDate, TimeDelta, TimeZone, Utc};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use sea_orm::sea_query::PostgresQueryBuilder;
+    use sea_orm::ActiveValue::{Set, Unchanged};
+    use sea_orm::{
+        Condition, DatabaseBackend, DatabaseConnection, EntityTrait, Order, QueryFilter, QueryTrait,
+    };
+    use twilight_model::id::Id;
+    use url::Url;
+
+    use super::parser::QueryParser;
+    use super::{
+        add_tags, around_reply, as_ilike, attributed_to, blame_reply, check_pin, cite_reply,
+        daily_offset, dedupe_reply, drop_tags, duplicate_pairs, expand_date_range, identify_select,
+        is_duplicate_report, link_reply, list_reply, merge_fields, merge_pairs, merge_reply,
+        neighbour, parse_tags, pick_unseen, picked_for, pins_reply, pins_state_key, push_csv_quote,
+        push_csv_record, quote_reply, quote_uses_by_channel, recent_reply, recent_select,
+        remove_tags, rename_target, rename_update, report_message, retire_duplicate, search_link,
+        seen_state_key, set_context_flag, split_order, spoiler_escape, top_games, unescape,
+        unsorted_duplicate_pairs, Ast, Column, DailyQuote, Identify, Op, QuoteOrder, Rename, Tags,
+        CSV_HEADER, MAX_PINS, MAX_TAG_LENGTH, MESSAGE_CONTENT_LENGTH_MAX,
+    };
+    use crate::command_parser::{Builder, CommandHandler, CommandUse};
+    use crate::config::Config;
+    use crate::models::{game, game_entry, quote, show};
+
+    #[test]
+    fn parsing() {
+        let parser = QueryParser::new();
+        assert_eq!(parser.parse("butts").unwrap(), Ast::Bare(Cow::Borrowed("butts")));
+        assert_eq!(
+            parser.parse("bare words get concatenated").unwrap(),
+            Ast::Bare(Cow::Borrowed("bare words get concatenated"))
+        );
+        assert_eq!(
+            parser.parse("quote:also quote:FTS quote:fields").unwrap(),
+            Ast::Column {
+                column: Column::Quote,
+                op: Op::Fuzzy,
+                term: Cow::Borrowed("also FTS fields"),
+            }
+        );
+    }
+
+    #[test]
+    fn game_parsing() {
+        let parser = QueryParser::new();
+        assert_eq!(
+            parser.parse(r#"game:"Magic: The Gathering""#).unwrap(),
+            Ast::Column {
+                column: Column::Game,
+                op: Op::Fuzzy,
+                term: Cow::Borrowed("Magic: The Gathering"),
+            }
+        );
+        assert_eq!(
+            parser.parse("game=Draft").unwrap(),
+            Ast::Column { column: Column::Game, op: Op::Equal, term: Cow::Borrowed("Draft") }
+        );
+    }
+
+    #[test]
+    fn game_display_name() {
+        let sql = |op| {
+            let query = Ast::Column { column: Column::Game, op, term: Cow::Borrowed("Draft") };
+            quote::Entity::find()
+                .filter(Condition::all().add(query.to_condition().unwrap()))
+                .build(DatabaseBackend::Postgres)
+                .to_string()
+        };
+
+        assert!(sql(Op::Fuzzy).ends_with(concat!(
+            r#"WHERE "game_id" IN (SELECT "games"."id" FROM "games" WHERE LOWER("name") LIKE '%draft%') "#,
+            r#"OR ("game_id", "show_id") IN (SELECT "game_per_show_data"."game_id", "game_per_show_data"."show_id" "#,
+            r#"FROM "game_per_show_data" WHERE LOWER("display_name") LIKE '%draft%')"#,
+        )), "{}", sql(Op::Fuzzy));
+        assert!(sql(Op::Equal).ends_with(concat!(
+            r#"WHERE "game_id" IN (SELECT "games"."id" FROM "games" WHERE "games"."name" = 'Draft') "#,
+            r#"OR ("game_id", "show_id") IN (SELECT "game_per_show_data"."game_id", "game_per_show_data"."show_id" "#,
+            r#"FROM "game_per_show_data" WHERE "game_per_show_data"."display_name" = 'Draft')"#,
+        )), "{}", sql(Op::Equal));
+    }
+
+    #[test]
+    fn unquote() {
+        assert_eq!(unescape("\"test\""), "test");
+        assert_eq!(unescape("\"quote: \\\" \\n\""), "quote: \" \n");
+    }
+
+    #[test]
+    fn ilike() {
+        assert_eq!(as_ilike("dark souls"), "%dark%souls%");
+        assert_eq!(as_ilike("%"), "%\\%%");
+    }
+
+    #[test]
+    fn link() {
+        let quote_url = Url::parse("https://lrrbot.com/quotes/").unwrap();
+        let mut quote = quote::Model {
+            id: 110,
+            quote: String::from("I'm not a bad person, I'm just bad at things."),
+            attrib_name: None,
+            attrib_date: None,
+            deleted: false,
+            context: None,
+            game_id: None,
+            show_id: None,
+        };
+
+        assert_eq!(
+            link_reply(&quote_url, 110, Some(&quote)),
+            "Quote #110: https://lrrbot.com/quotes/110"
+        );
+        assert_eq!(link_reply(&quote_url, 110, None), "Could not find quote #110");
+
+        quote.deleted = true;
+        assert_eq!(link_reply(&quote_url, 110, Some(&quote)), "Could not find quote #110");
+    }
+
+    #[test]
+    fn cite() {
+        let config = Config::for_tests("[eris]\nspoiler_shows=secret\n");
+        let mut quote = quote::Model {
+            id: 110,
+            quote: String::from("*Technically* it's a_b ~~test~~ | ```oops```"),
+            attrib_name: Some(String::from("Paul_S")),
+            attrib_date: None,
+            deleted: false,
+            context: None,
+            game_id: None,
+            show_id: None,
+        };
+        let show = show::Model { id: 1, key: String::from("secret"), name: String::from("Secret") };
+
+        let reply = cite_reply(&config, 110, Some(&(quote.clone(), None)));
+        assert_eq!(
+            reply,
+            concat!(
+                "```\n#110: \"*Technically* it's a_b ~~test~~ | `\\``oops`\\``\" —Paul_S\n",
+                "https://lrrbot.com/quotes/110\n```",
+            )
+        );
+        // The only escape is the one that keeps the code block from closing early.
+        let body = reply.strip_prefix("```\n").unwrap().strip_suffix("\n```").unwrap();
+        assert_eq!(body.replace("`\\``", "```").matches('\\').count(), 0);
+
+        assert_eq!(
+            cite_reply(&config, 110, Some(&(quote.clone(), Some(show)))),
+            format!("||{reply}||")
+        );
+        assert_eq!(cite_reply(&config, 110, None), "Could not find quote #110");
+        quote.deleted = true;
+        assert_eq!(cite_reply(&config, 110, Some(&(quote, None))), "Could not find quote #110");
+    }
+
+    #[test]
+    fn order_modifier() {
+        assert_eq!(split_order("butts"), Ok((Cow::Borrowed("butts"), QuoteOrder::Random)));
+        assert_eq!(
+            split_order("order:newest from:graham"),
+            Ok((Cow::Borrowed("from:graham"), QuoteOrder::Newest))
+        );
+        assert_eq!(
+            split_order("from:graham ORDER:Oldest butts"),
+            Ok((Cow::Borrowed("from:graham butts"), QuoteOrder::Oldest))
+        );
+        assert_eq!(split_order("order:random"), Ok((Cow::Borrowed(""), QuoteOrder::Random)));
+        assert_eq!(
+            split_order(r#""order:newest \" order:oldest""#),
+            Ok((Cow::Borrowed(r#""order:newest \" order:oldest""#), QuoteOrder::Random))
+        );
+        assert_eq!(
+            split_order("reorder:newest"),
+            Ok((Cow::Borrowed("reorder:newest"), QuoteOrder::Random))
+        );
+        assert!(split_order("order:best").is_err());
+        assert!(split_order("order:newest order:oldest").is_err());
+    }
+
+    #[test]
+    fn order_sql() {
+        let sql = |order: QuoteOrder| {
+            order.apply(quote::Entity::find()).build(DatabaseBackend::Postgres).to_string()
+        };
+
+        assert!(!sql(QuoteOrder::Random).contains("ORDER BY"));
+        assert!(sql(QuoteOrder::Newest)
+            .ends_with(r#"ORDER BY "quotes"."attrib_date" DESC NULLS LAST, "quotes"."id" DESC"#));
+        assert!(sql(QuoteOrder::Oldest)
+            .ends_with(r#"ORDER BY "quotes"."attrib_date" ASC NULLS LAST, "quotes"."id" ASC"#));
+    }
+
+    #[test]
+    fn csv() {
+        let quotes = [
+            quote::Model {
+                id: 1,
+                quote: String::from("I'm not going to say \"butts\", that's too easy."),
+                attrib_name: Some(String::from("Graham")),
+                attrib_date: chrono::NaiveDate::from_ymd_opt(2020, 1, 2),
+                deleted: false,
+                context: Some(String::from("Dominion, again")),
+                game_id: Some(3),
+                show_id: Some(4),
+            },
+            quote::Model {
+                id: 2,
+                quote: String::from("Line one\nline two"),
+                attrib_name: None,
+                attrib_date: None,
+                deleted: false,
+                context: None,
+                game_id: None,
+                show_id: None,
+            },
+        ];
+
+        let mut csv = String::new();
+        push_csv_record(&mut csv, CSV_HEADER);
+        for quote in &quotes {
+            push_csv_quote(&mut csv, quote);
+        }
+
+        assert_eq!(
+            csv,
+            concat!(
+                "id,quote,name,date,context,game_id,show_id\r\n",
+                "1,\"I'm not going to say \"\"butts\"\", that's too easy.\",Graham,2020-01-02,\"Dominion, again\",3,4\r\n",
+                "2,\"Line one\nline two\",,,,,\r\n",
+            )
+        );
+    }
+
+    #[test]
+    fn spoilers() {
+        let config = Config::for_tests("[eris]\nspoiler_shows = lrrmtg, spoilers");
+        let show = |key: &str| show::Model { id: 1, key: String::from(key), name: String::new() };
+
+        assert_eq!(
+            spoiler_escape(&config, Some(&show("spoilers")), "Bruce Willis was dead || alive"),
+            r"||Bruce Willis was dead \|\| alive||"
+        );
+        assert_eq!(spoiler_escape(&config, Some(&show("lrrmtg")), "*gasp*"), r"||\*gasp\*||");
+        assert_eq!(spoiler_escape(&config, Some(&show("qfc")), "*gasp*"), r"\*gasp\*");
+        assert_eq!(spoiler_escape(&config, None, "*gasp*"), r"\*gasp\*");
+    }
+
+    #[test]
+    fn rename() {
+        let game = || Some(game::Model { id: 110, name: String::from("Magic") });
+        let show = || {
+            Some(show::Model {
+                id: 3,
+                key: String::from("lrrmtg"),
+                name: String::from("Friday Nights"),
+            })
+        };
+        let entry = |display_name: Option<&str>| {
+            Some(game_entry::Model {
+                game_id: 110,
+                show_id: 3,
+                display_name: display_name.map(String::from),
+                verified: None,
+            })
+        };
+
+        let target = rename_target(110, None, game(), None, None).unwrap();
+        let (update, reply) = rename_update(&target, "Magic: The Gathering").unwrap();
+        assert_eq!(reply, "Renamed game #110 from Magic to Magic: The Gathering.");
+        let Rename::Game(update) = update else { panic!("renamed a game entry") };
+        assert_eq!(update.id, Unchanged(110));
+        assert_eq!(update.name, Set(String::from("Magic: The Gathering")));
+
+        let target =
+            rename_target(110, Some("lrrmtg"), game(), show(), entry(Some("MTG"))).unwrap();
+        let (update, reply) = rename_update(&target, " Magic ").unwrap();
+        assert_eq!(reply, "Renamed game #110 on Friday Nights from MTG to Magic.");
+        let Rename::Entry(update) = update else { panic!("renamed the game everywhere") };
+        assert_eq!((update.game_id, update.show_id), (Unchanged(110), Unchanged(3)));
+        assert_eq!(update.display_name, Set(Some(String::from("Magic"))));
+        assert_eq!(update.verified, Unchanged(None));
+
+        let target = rename_target(110, Some("lrrmtg"), game(), show(), entry(None)).unwrap();
+        let (_, reply) = rename_update(&target, "MTG").unwrap();
+        assert_eq!(reply, "Renamed game #110 on Friday Nights from Magic to MTG.");
+
+        let error = rename_update(&target, " \t ").err().unwrap();
+        assert_eq!(error, "The new name can't be empty.");
+    }
+
+    #[test]
+    fn rename_missing() {
+        let game = || Some(game::Model { id: 110, name: String::from("Magic") });
+        let show = || {
+            Some(show::Model {
+                id: 3,
+                key: String::from("lrrmtg"),
+                name: String::from("Friday Nights"),
+            })
+        };
+
+        let error = |result: Result<_, String>| result.err().unwrap();
+        assert_eq!(error(rename_target(110, None, None, None, None)), "Could not find game #110.");
+        assert_eq!(
+            error(rename_target(110, Some("lrrmtg"), None, show(), None)),
+            "Could not find game #110."
+        );
+        assert_eq!(
+            error(rename_target(110, Some("qfc"), game(), None, None)),
+            "Could not find the show qfc."
+        );
+        assert_eq!(
+            error(rename_target(110, Some("lrrmtg"), game(), show(), None)),
+            "Game #110 hasn't been played on Friday Nights."
+        );
+    }
+
+    #[test]
+    fn listing() {
+        let shows =
+            [String::from("Friday Nights"), String::from("Let's NOPE"), String::from("*Q*")];
+        assert_eq!(
+            list_reply("Shows with quotes", &shows),
+            r"Shows with quotes: Friday Nights, Let's NOPE, \*Q\*."
+        );
+
+        // 250 names of 9 characters and a separator don't fit in a message.
+        let shows = (0..250).map(|i| format!("Show {i:04}")).collect::<Vec<_>>();
+        let reply = list_reply("Shows with quotes", &shows);
+        assert!(reply.len() <= 2000, "{}", reply.len());
+        assert!(reply.starts_with("Shows with quotes: Show 0000, Show 0001, "));
+        assert!(reply.ends_with(", Show 0177, and 72 more."), "{reply}");
+
+        let sql = top_games().into_statement(DatabaseBackend::Postgres).to_string();
+        assert_eq!(
+            sql,
+            concat!(
+                r#"SELECT "games"."name", COUNT("quotes"."id") AS "count" FROM "quotes" "#,
+                r#"INNER JOIN "games" ON "quotes"."game_id" = "games"."id" "#,
+                r#"WHERE NOT "quotes"."deleted" GROUP BY "games"."id" "#,
+                r#"ORDER BY "count" DESC, "games"."name" ASC LIMIT 20"#,
+            )
+        );
+    }
+
+    #[test]
+    fn exact_name() {
+        let parser = QueryParser::new();
+        assert_eq!(
+            parser.parse("from==Alex").unwrap(),
+            Ast::Column { column: Column::Name, op: Op::Exact, term: Cow::Borrowed("Alex") }
+        );
+        assert_eq!(
+            parser.parse("from:Al").unwrap(),
+            Ast::Column { column: Column::Name, op: Op::Fuzzy, term: Cow::Borrowed("Al") }
+        );
+
+        let sql = |query: &str| {
+            let query = parser.parse(query).unwrap();
+            quote::Entity::find()
+                .filter(Condition::all().add(query.to_condition().unwrap()))
+                .build(DatabaseBackend::Postgres)
+                .to_string()
+        };
+        assert!(sql("from==Alex").ends_with(r#"WHERE LOWER("attrib_name") = 'alex'"#));
+        assert!(sql("name==\"Uncle Ben\"").ends_with(r#"WHERE LOWER("attrib_name") = 'uncle ben'"#));
+        assert!(sql("from:Al").ends_with(r#"WHERE LOWER("attrib_name") LIKE '%al%'"#));
+        // Other columns treat `==` as `=`.
+        assert_eq!(sql("id==12"), sql("id=12"));
+    }
+
+    #[test]
+    fn tags() {
+        let parser = QueryParser::new();
+        assert_eq!(
+            parser.parse("tag:Pun").unwrap(),
+            Ast::Column { column: Column::Tag, op: Op::Fuzzy, term: Cow::Borrowed("Pun") }
+        );
+        assert_eq!(parser.parse("tag").unwrap(), Ast::Bare(Cow::Borrowed("tag")));
+
+        let sql = |query: &str| {
+            let query = parser.parse(query).unwrap();
+            quote::Entity::find()
+                .filter(Condition::all().add(query.to_condition().unwrap()))
+                .build(DatabaseBackend::Postgres)
+                .to_string()
+        };
+        assert!(sql("tag:Pun").ends_with(concat!(
+            r#"WHERE "id" IN (SELECT JSONB_ARRAY_ELEMENTS_TEXT("tags"."value")::integer "#,
+            r#"FROM "state", JSONB_EACH("state"."value") AS "tags" "#,
+            r#"WHERE "state"."key" = 'eris.quote.tags' AND "tags"."key" = 'pun')"#
+        )));
+        assert_eq!(sql("tag:pun"), sql("tag==PUN"));
+        for query in ["tag<pun", "tag<=pun", "tag>pun", "tag>=pun"] {
+            assert!(parser.parse(query).unwrap().to_condition().is_err(), "{query}");
+        }
+
+        assert_eq!(
+            parse_tags("Pun callback pun").unwrap(),
+            vec![String::from("pun"), String::from("callback")]
+        );
+        assert_eq!(parse_tags(" ").unwrap_err(), "No tags given.");
+        assert_eq!(
+            parse_tags(&"a".repeat(MAX_TAG_LENGTH + 1)).unwrap_err(),
+            "Tags can be at most 32 characters long."
+        );
+
+        let tags = |tags: &[&str]| tags.iter().copied().map(String::from).collect::<Vec<_>>();
+        let mut all_tags = Tags::new();
+        add_tags(&mut all_tags, 3849, &tags(&["pun", "callback"]));
+        add_tags(&mut all_tags, 12, &tags(&["pun"]));
+        assert_eq!(
+            all_tags,
+            Tags::from([
+                (String::from("callback"), BTreeSet::from([3849])),
+                (String::from("pun"), BTreeSet::from([12, 3849])),
+            ])
+        );
+        assert_eq!(remove_tags(&mut all_tags, 3849, &tags(&["callback", "meta"])), 1);
+        assert_eq!(remove_tags(&mut all_tags, 3849, &tags(&["callback"])), 0);
+        assert_eq!(all_tags, Tags::from([(String::from("pun"), BTreeSet::from([12, 3849]))]));
+
+        add_tags(&mut all_tags, 3849, &tags(&["callback"]));
+        assert!(drop_tags(&mut all_tags, 3849));
+        assert!(!drop_tags(&mut all_tags, 3849));
+        assert_eq!(all_tags, Tags::from([(String::from("pun"), BTreeSet::from([12]))]));
+    }
+
+    #[test]
+    fn random_unseen() {
+        let mut rng = StdRng::seed_from_u64(1987);
+        let ids = (1..=10).collect::<Vec<_>>();
+
+        // Mimic the FIFO in the `state` table: newest first, at most `window` entries.
+        let mut pick = |seen: &mut Vec<i32>, window: usize| {
+            let (id, reset) = pick_unseen(&ids, seen, &mut rng).unwrap();
+            if reset {
+                seen.clear();
+            }
+            seen.insert(0, id);
+            seen.truncate(window);
+            (id, reset)
+        };
+
+        // Every quote is shown once before any repeats.
+        let mut seen = vec![];
+        let mut shown = (0..10).map(|_| pick(&mut seen, 100)).collect::<Vec<_>>();
+        assert!(shown.iter().all(|&(_, reset)| !reset));
+        shown.sort_unstable();
+        assert_eq!(shown.into_iter().map(|(id, _)| id).collect::<Vec<_>>(), ids);
+        // Then the pool is exhausted and starts over.
+        assert!(pick(&mut seen, 100).1);
+        assert_eq!(seen.len(), 1);
+
+        // With a smaller window, no quote repeats within the window.
+        let mut seen = vec![];
+        let shown = (0..100).map(|_| pick(&mut seen, 4).0).collect::<Vec<_>>();
+        for window in shown.windows(5) {
+            assert!(!window[1..].contains(&window[0]), "{window:?}");
+        }
+
+        assert_eq!(pick_unseen(&[], &[], &mut rng), None);
+        assert_eq!(seen_state_key(Id::new(42)), "eris.quote.seen.42");
+    }
+
+    #[test]
+    fn needs_context() {
+        let mut flagged = BTreeSet::from([12]);
+        assert!(set_context_flag(&mut flagged, 3849, true));
+        assert!(!set_context_flag(&mut flagged, 3849, true));
+        assert_eq!(flagged, BTreeSet::from([12, 3849]));
+        assert!(set_context_flag(&mut flagged, 12, false));
+        assert!(!set_context_flag(&mut flagged, 12, false));
+        assert_eq!(flagged, BTreeSet::from([3849]));
+
+        let config = Config::for_tests("[eris]\nspoiler_shows=secret\n");
+        let quote = quote::Model {
+            id: 3849,
+            quote: String::from("long pig"),
+            attrib_name: Some(String::from("Alex")),
+            attrib_date: NaiveDate::from_ymd_opt(2019, 6, 1),
+            deleted: false,
+            context: Some(String::from("on cannibalism")),
+            game_id: None,
+            show_id: None,
+        };
+        let show = show::Model { id: 1, key: String::from("secret"), name: String::from("Secret") };
+        assert_eq!(
+            quote_reply(&config, &quote, None, false),
+            "Quote #3849: \"long pig\" —Alex, on cannibalism [2019-06-01]"
+        );
+        assert_eq!(
+            quote_reply(&config, &quote, None, true),
+            "Quote #3849: \"long pig\" —Alex, on cannibalism [2019-06-01] (out of context)"
+        );
+        assert_eq!(
+            quote_reply(&config, &quote, Some(&show), true),
+            "Quote ||#3849: \"long pig\" —Alex, on cannibalism [2019-06-01] (out of context)||"
+        );
+    }
+
+    #[test]
+    fn blame_channel() {
+        let time = Utc.with_ymd_and_hms(2024, 1, 5, 12, 0, 0).unwrap();
+        let command_use = |command: &str, channel_id| CommandUse {
+            command: String::from(command),
+            channel_id: Id::new(channel_id),
+            time,
+        };
+        let uses = [
+            command_use("quote", 2),
+            command_use("quote details", 1),
+            command_use("quote", 1),
+            command_use("quotes", 3),
+            command_use("live", 3),
+            command_use("quote pin", 2),
+            command_use("quote", 1),
+        ];
+        let counts = quote_uses_by_channel(&uses);
+        assert_eq!(counts, HashMap::from([(Id::new(1), 3), (Id::new(2), 2)]));
+        assert_eq!(
+            blame_reply(&counts, 24),
+            "Quote commands in the last 24 hours:\n1. <#1>: 3\n2. <#2>: 2"
+        );
+
+        // Ties are broken by the channel ID.
+        let counts = (1..=12).map(|id| (Id::new(id), 13 - id.min(3))).collect::<HashMap<_, _>>();
+        let reply = blame_reply(&counts, 168);
+        assert!(
+            reply.starts_with(concat!(
+                "Quote commands in the last 168 hours:\n",
+                "1. <#1>: 12\n2. <#2>: 11\n3. <#3>: 10\n4. <#4>: 10\n",
+            )),
+            "{reply}"
+        );
+        assert!(reply.ends_with("\n10. <#10>: 10\n…and 2 more channels."), "{reply}");
+
+        assert_eq!(blame_reply(&HashMap::new(), 24), "No quote commands in the last 24 hours.");
+    }
+
+    #[test]
+    fn identify() {
+        assert_eq!(
+            identify_select("long pig").build(DatabaseBackend::Postgres).to_string(),
+            concat!(
+                r#"SELECT "quotes"."id", "quotes"."quote", "quotes"."attrib_name", "#,
+                r#""quotes"."attrib_date", "quotes"."deleted", "quotes"."context", "#,
+                r#""quotes"."game_id", "quotes"."show_id" FROM "quotes" "#,
+                r#"WHERE (NOT "deleted") AND TO_TSVECTOR("quote") @@ PLAINTO_TSQUERY('long pig') "#,
+                r#"ORDER BY TS_RANK(TO_TSVECTOR("quote"), PLAINTO_TSQUERY('long pig')) DESC, "#,
+                r#""quotes"."id" ASC LIMIT 1"#,
+            )
+        );
+    }
+
+    #[test]
+    fn identify_pattern() {
+        let pattern = Builder::expand_pattern(
+            Identify::new(DatabaseConnection::Disconnected).pattern(),
+            false,
+        )
+        .unwrap();
+        let text = |command| pattern.captures(command).map(|captures| captures[1].to_string());
+
+        assert_eq!(text("quote id long pig"), Some(String::from("long pig")));
+        assert_eq!(text("quote identify I'm not a doctor"), Some(String::from("I'm not a doctor")));
+        assert_eq!(text("quote id < 1000"), None);
+        assert_eq!(text("quote id >= 1000"), None);
+        assert_eq!(text("quote id:1000"), None);
+        assert_eq!(text("quote id"), None);
+    }
+
+    #[test]
+    fn dedupe() {
+        assert_eq!(
+            duplicate_pairs().to_string(PostgresQueryBuilder),
+            concat!(
+                r#"SELECT "a"."id" AS "first", "b"."id" AS "second", "#,
+                r#"similarity("a"."quote", "b"."quote") AS "similarity" "#,
+                r#"FROM "quotes" AS "a" "#,
+                r#"INNER JOIN "quotes" AS "b" ON "a"."id" < "b"."id" "#,
+                r#"AND "a"."game_id" = "b"."game_id" AND "a"."show_id" = "b"."show_id" "#,
+                r#"WHERE (NOT "a"."deleted") AND (NOT "b"."deleted") "#,
+                r#"AND similarity("a"."quote", "b"."quote") > 0.6 "#,
+                r#"ORDER BY "similarity" DESC, "a"."id" ASC LIMIT 20"#,
+            )
+        );
+        assert_eq!(
+            unsorted_duplicate_pairs().to_string(PostgresQueryBuilder),
+            concat!(
+                r#"SELECT "a"."id" AS "first", "b"."id" AS "second", "#,
+                r#"similarity("a"."quote", "b"."quote") AS "similarity" "#,
+                r#"FROM "quotes" AS "a" "#,
+                r#"INNER JOIN "quotes" AS "b" ON "a"."id" < "b"."id" "#,
+                r#"AND "a"."quote" = "b"."quote" "#,
+                r#"AND ("a"."game_id" IS NULL OR "a"."show_id" IS NULL "#,
+                r#"OR "b"."game_id" IS NULL OR "b"."show_id" IS NULL) "#,
+                r#"WHERE (NOT "a"."deleted") AND (NOT "b"."deleted") "#,
+                r#"ORDER BY "similarity" DESC, "a"."id" ASC LIMIT 20"#,
+            )
+        );
+
+        let pairs = (1..=20).map(|i| (i, i + 100, 0.7)).collect::<Vec<_>>();
+        let merged = merge_pairs(pairs, vec![(30, 31, 1.0), (25, 26, 1.0)]);
+        assert_eq!(merged.len(), 20);
+        assert_eq!(merged[..3], [(25, 26, 1.0), (30, 31, 1.0), (1, 101, 0.7)]);
+        assert_eq!(merged[19], (18, 118, 0.7));
+
+        assert_eq!(dedupe_reply(&[]), "Could not find any likely duplicates.");
+        assert_eq!(
+            dedupe_reply(&[(12, 345, 0.874), (3, 4, 0.6)]),
+            "Likely duplicates: #12 and #345 (87%), #3 and #4 (60%)."
+        );
+    }
+
+    #[test]
+    fn pins() {
+        assert_eq!(check_pin(&[], 110), Ok(()));
+        assert_eq!(check_pin(&[12, 34], 110), Ok(()));
+        assert_eq!(
+            check_pin(&[12, 110], 110),
+            Err(String::from("You've already pinned quote #110."))
+        );
+
+        let full = (1..=MAX_PINS as i32).collect::<Vec<_>>();
+        assert!(check_pin(&full[1..], 110).is_ok());
+        assert!(check_pin(&full, 110).unwrap_err().starts_with("You can pin at most 25 quotes."));
+        // Already pinned takes precedence over the cap.
+        assert_eq!(check_pin(&full, 1), Err(String::from("You've already pinned quote #1.")));
+
+        assert_eq!(
+            pins_state_key(Id::new(144128240389324800)),
+            "eris.quote.pins.144128240389324800"
+        );
+
+        let config = Config::for_tests("[eris]\nspoiler_shows=secret\n");
+        let quote = |id, text: &str| quote::Model {
+            id,
+            quote: String::from(text),
+            attrib_name: Some(String::from("Alex")),
+            attrib_date: None,
+            deleted: false,
+            context: None,
+            game_id: None,
+            show_id: None,
+        };
+        let show = show::Model { id: 1, key: String::from("secret"), name: String::from("Secret") };
+        assert_eq!(
+            pins_reply(&config, &[]),
+            "You haven't pinned any quotes yet. Pin one with `quote pin`."
+        );
+        assert_eq!(
+            pins_reply(&config, &[(quote(1, "butts"), None), (quote(2, "spoilers"), Some(show))]),
+            "Your pinned quotes:\nQuote #1: \"butts\" —Alex\nQuote ||#2: \"spoilers\" —Alex||"
+        );
+
+        let long =
+            (0..MAX_PINS as i32).map(|id| (quote(id, &"a".repeat(150)), None)).collect::<Vec<_>>();
+        let reply = pins_reply(&config, &long);
+        assert!(reply.chars().count() <= MESSAGE_CONTENT_LENGTH_MAX);
+        assert!(reply.ends_with(" more."), "{reply}");
+    }
+
+    #[test]
+    fn recent_additions() {
+        assert_eq!(
+            recent_select().build(DatabaseBackend::Postgres).to_string(),
+            concat!(
+                r#"SELECT "quotes"."id", "quotes"."quote", "quotes"."attrib_name", "#,
+                r#""quotes"."attrib_date", "quotes"."deleted", "quotes"."context", "#,
+                r#""quotes"."game_id", "quotes"."show_id" FROM "quotes" "#,
+                r#"WHERE NOT "quotes"."deleted" ORDER BY "quotes"."id" DESC LIMIT 5"#,
+            )
+        );
+
+        let config = Config::for_tests("[eris]\nspoiler_shows=secret\n");
+        let quote = |id| quote::Model {
+            id,
+            quote: String::from("butts"),
+            attrib_name: None,
+            attrib_date: None,
+            deleted: false,
+            context: None,
+            game_id: None,
+            show_id: None,
+        };
+        assert_eq!(recent_reply(&config, &[]), "There are no quotes yet.");
+        assert_eq!(
+            recent_reply(&config, &[(quote(12), None), (quote(11), None)]),
+            "Recently added quotes:\nQuote #12: \"butts\"\nQuote #11: \"butts\""
+        );
+    }
+
+    #[test]
+    fn merge() {
+        let quote = |id, context: Option<&str>, game_id, show_id| quote::Model {
+            id,
+            quote: String::from("butts"),
+            attrib_name: Some(String::from("Alex")),
+            attrib_date: None,
+            deleted: false,
+            context: context.map(String::from),
+            game_id,
+            show_id,
+        };
+        let copied = |duplicate: &quote::Model, canonical: &quote::Model| {
+            let (merged, copied) = merge_fields(duplicate, canonical);
+            (merged.context, merged.game_id, merged.show_id, copied)
+        };
+
+        // Everything the canonical quote lacks is copied.
+        let duplicate = quote(2, Some("on stream"), Some(10), Some(20));
+        assert_eq!(
+            copied(&duplicate, &quote(1, None, None, None)),
+            (
+                Set(Some(String::from("on stream"))),
+                Set(Some(10)),
+                Set(Some(20)),
+                vec!["context", "game", "show"]
+            )
+        );
+        assert_eq!(
+            copied(&duplicate, &quote(1, Some(" "), None, None)).3,
+            vec!["context", "game", "show"]
+        );
+
+        // The canonical quote's own fields win.
+        assert_eq!(
+            copied(&duplicate, &quote(1, Some("in the car"), Some(11), Some(21))),
+            (
+                Unchanged(Some(String::from("in the car"))),
+                Unchanged(Some(11)),
+                Unchanged(Some(21)),
+                vec![]
+            )
+        );
+        assert!(copied(&quote(2, Some(""), None, None), &quote(1, None, None, None)).3.is_empty());
+
+        // A missing game or show is only filled in if the other one agrees.
+        assert_eq!(copied(&duplicate, &quote(1, None, None, Some(20))).3, vec!["context", "game"]);
+        assert_eq!(copied(&duplicate, &quote(1, None, None, Some(21))).3, vec!["context"]);
+        assert_eq!(copied(&duplicate, &quote(1, None, Some(10), None)).3, vec!["context", "show"]);
+        assert_eq!(copied(&duplicate, &quote(1, None, Some(11), None)).3, vec!["context"]);
+
+        let retired = retire_duplicate(&duplicate);
+        assert_eq!(retired.id, Unchanged(2));
+        assert_eq!(retired.deleted, Set(true));
+        assert_eq!(retired.context, Unchanged(Some(String::from("on stream"))));
+
+        assert_eq!(
+            merge_reply(2, 1, &[]),
+            "Merged quote #2 into quote #1. Nothing was copied over."
+        );
+        assert_eq!(
+            merge_reply(2, 1, &["game"]),
+            "Merged quote #2 into quote #1 and copied over the game."
+        );
+        assert_eq!(
+            merge_reply(2, 1, &["context", "game", "show"]),
+            "Merged quote #2 into quote #1 and copied over the context, the game and the show."
+        );
+    }
+
+    #[test]
+    fn attributed() {
+        let sql = |names: &[&str]| {
+            let query = attributed_to(names).unwrap();
+            quote::Entity::find()
+                .filter(Condition::all().add(query.to_condition().unwrap()))
+                .build(DatabaseBackend::Postgres)
+                .to_string()
+        };
+
+        assert!(sql(&["Graham"]).ends_with(r#"WHERE LOWER("attrib_name") LIKE '%graham%'"#));
+        assert!(sql(&["Graham", "lrr_graham"]).ends_with(concat!(
+            r#"WHERE LOWER("attrib_name") LIKE '%graham%' "#,
+            r#"OR LOWER("attrib_name") LIKE E'%lrr\\_graham%'"#,
+        )));
+        assert!(attributed_to::<&str>(&[]).is_none());
+    }
+
+    #[test]
+    fn around() {
+        let config = Config::for_tests("");
+        let quote = |id, text: &str| quote::Model {
+            id,
+            quote: String::from(text),
+            attrib_name: Some(String::from("Paul")),
+            attrib_date: None,
+            deleted: false,
+            context: None,
+            game_id: Some(3),
+            show_id: None,
+        };
+        let quotes = [quote(10, "Hey!"), quote(12, "Hi."), quote(15, "Bye.")];
+
+        assert_eq!(
+            around_reply(&config, None, Some(&quotes[0]), &quotes[1], Some(&quotes[2])),
+            concat!(
+                "Quote #10: \"Hey!\" —Paul\n",
+                "**Quote #12: \"Hi.\" —Paul**\n",
+                "Quote #15: \"Bye.\" —Paul",
+            )
+        );
+        assert_eq!(
+            around_reply(&config, None, None, &quotes[0], Some(&quotes[1])),
+            "**Quote #10: \"Hey!\" —Paul**\nQuote #12: \"Hi.\" —Paul"
+        );
+        assert_eq!(
+            around_reply(&config, None, None, &quotes[2], None),
+            "**Quote #15: \"Bye.\" —Paul**\nThere are no other quotes from the same show and game."
+        );
+
+        let sql = |order| neighbour(&quotes[1], order).build(DatabaseBackend::Postgres).to_string();
+        assert!(sql(Order::Desc).ends_with(concat!(
+            r#"WHERE "quotes"."id" < 12 AND "quotes"."show_id" IS NULL AND "quotes"."game_id" = 3 "#,
+            r#"AND (NOT "deleted") ORDER BY "quotes"."id" DESC LIMIT 1"#,
+        )));
+        assert!(sql(Order::Asc).ends_with(concat!(
+            r#"WHERE "quotes"."id" > 12 AND "quotes"."show_id" IS NULL AND "quotes"."game_id" = 3 "#,
+            r#"AND (NOT "deleted") ORDER BY "quotes"."id" ASC LIMIT 1"#,
+        )));
+    }
+
+    #[test]
+    fn date_ranges() {
+        let parser = QueryParser::new();
+        let sql = |query: &str| {
+            let query = parser.parse(query).unwrap();
+            quote::Entity::find()
+                .filter(Condition::all().add(query.to_condition().unwrap()))
+                .build(DatabaseBackend::Postgres)
+                .to_string()
+        };
+        let expanded = |query| sql(&expand_date_range(query).unwrap());
+
+        assert_eq!(expanded("since 2020-01-01"), sql("date >= 2020-01-01"));
+        assert_eq!(expanded("SINCE  2020-01-01"), sql("date >= 2020-01-01"));
+        assert_eq!(
+            expanded("between 2019-01-01 2019-12-31"),
+            sql("date >= 2019-01-01 date <= 2019-12-31")
+        );
+        assert_eq!(
+            expanded("between 2019-01-01 2019-12-31 from:graham"),
+            sql("date >= 2019-01-01 date <= 2019-12-31 from:graham")
+        );
+
+        // Not followed by a date so they're just words.
+        assert_eq!(expand_date_range("since you asked"), Ok(Cow::Borrowed("since you asked")));
+        assert_eq!(expand_date_range("between"), Ok(Cow::Borrowed("between")));
+        assert_eq!(expand_date_range("from:graham"), Ok(Cow::Borrowed("from:graham")));
+
+        assert!(expand_date_range("since 2020-13-01").is_err());
+        assert!(expand_date_range("between 2019-01-01").is_err());
+        assert!(expand_date_range("between 2019-01-01 butts").is_err());
+    }
+
+    #[test]
+    fn duplicate_reports() {
+        let now = Utc.with_ymd_and_hms(2024, 1, 2, 12, 0, 0).unwrap();
+
+        assert!(!is_duplicate_report(None, now));
+        assert!(is_duplicate_report(Some(now), now));
+        assert!(is_duplicate_report(Some(now - TimeDelta::hours(23)), now));
+        assert!(!is_duplicate_report(Some(now - TimeDelta::days(1)), now));
+        assert!(!is_duplicate_report(Some(now - TimeDelta::days(30)), now));
+    }
+
+    #[test]
+    fn report() {
+        let config = Config::for_tests("[eris]\nspoiler_shows = spoilers");
+        let quote = quote::Model {
+            id: 110,
+            quote: String::from("*gasp*"),
+            attrib_name: Some(String::from("Paul")),
+            attrib_date: None,
+            deleted: false,
+            context: None,
+            game_id: None,
+            show_id: Some(1),
+        };
+        let show = |key: &str| show::Model {
+            id: 1,
+            key: String::from(key),
+            name: String::from("*Spoiler* Warning"),
+        };
+
+        assert_eq!(
+            report_message(&config, None, &quote, Id::new(10), Some("not_funny")),
+            "<@10> reported quote #110: not\\_funny\n> #110: \"\\*gasp\\*\" —Paul"
+        );
+        assert_eq!(
+            report_message(&config, Some(&show("qfc")), &quote, Id::new(10), None),
+            concat!(
+                "<@10> reported quote #110.\n",
+                "> #110: \"\\*gasp\\*\" —Paul (on \\*Spoiler\\* Warning)",
+            )
+        );
+        assert_eq!(
+            report_message(&config, Some(&show("spoilers")), &quote, Id::new(10), None),
+            concat!(
+                "<@10> reported quote #110.\n",
+                "> ||#110: \"\\*gasp\\*\" —Paul|| (on \\*Spoiler\\* Warning)",
+            )
+        );
+    }
+
+    #[test]
+    fn search() {
+        let search_url = Url::parse("https://lrrbot.com/quotes/search").unwrap();
+
+        assert_eq!(
+            search_link(&search_url, "butts").as_str(),
+            "https://lrrbot.com/quotes/search?q=butts"
+        );
+        assert_eq!(
+            search_link(&search_url, r#"(show:"Let's NOPE" | id >= 100) & from:alex"#).as_str(),
+            "https://lrrbot.com/quotes/search?q=%28show%3A%22Let%27s+NOPE%22+%7C+id+%3E%3D+100%29+%26+from%3Aalex"
+        );
+    }
+
+    #[test]
+    fn of_the_day() {
+        let day = NaiveDate::from_ymd_opt(2024, 1, 7).unwrap();
+        let next_day = day.succ_opt().unwrap();
+
+        assert_eq!(daily_offset(day, 1000), daily_offset(day, 1000));
+        assert_ne!(daily_offset(day, 1000), daily_offset(next_day, 1000));
+        assert_eq!(daily_offset(day, 1), 0);
+        assert!((0..365)
+            .filter_map(|days| day.checked_add_days(chrono::Days::new(days)))
+            .all(|date| daily_offset(date, 10) < 10));
+
+        let stored = DailyQuote { date: day, quote_id: 42 };
+
+        assert_eq!(picked_for(Some(stored), day), Some(42));
+        assert_eq!(picked_for(Some(stored), next_day), None);
+        assert_eq!(picked_for(None, day), None);
+        assert_eq!(
+            serde_json::from_value::<DailyQuote>(serde_json::to_value(stored).unwrap()).unwrap(),
+            stored
+        );
     }
 }
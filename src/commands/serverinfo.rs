@@ -0,0 +1,238 @@
+use std::borrow::Cow;
+use std::future::Future;
+use std::pin::Pin;
+
+use anyhow::{Context, Error};
+use twilight_cache_inmemory::InMemoryCache;
+use twilight_http::Client as DiscordClient;
+use twilight_mention::Mention;
+use twilight_model::channel::message::Embed;
+use twilight_model::channel::Message;
+use twilight_model::guild::PremiumTier;
+use twilight_model::id::marker::{GuildMarker, UserMarker};
+use twilight_model::id::Id;
+use twilight_model::util::ImageHash;
+use twilight_util::builder::embed::{EmbedBuilder, EmbedFieldBuilder, ImageSource};
+use twilight_util::snowflake::Snowflake;
+
+use crate::cache::Cache;
+use crate::command_parser::{Args, CommandHandler, Commands, Help};
+use crate::config::Config;
+
+pub struct ServerInfo;
+
+impl ServerInfo {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// The parts of the cached guild that are shown.
+struct Summary {
+    id: Id<GuildMarker>,
+    name: String,
+    icon: Option<ImageHash>,
+    owner_id: Id<UserMarker>,
+    member_count: Option<u64>,
+    channel_count: usize,
+    role_count: usize,
+    premium_tier: PremiumTier,
+    boost_count: Option<u64>,
+}
+
+impl Summary {
+    fn from_cache(cache: &InMemoryCache, guild_id: Id<GuildMarker>) -> Option<Self> {
+        let guild = cache.guild(guild_id)?;
+        Some(Self {
+            id: guild.id(),
+            name: guild.name().into(),
+            icon: guild.icon().copied(),
+            owner_id: guild.owner_id(),
+            member_count: guild.member_count(),
+            channel_count: cache.guild_channels(guild_id).map_or(0, |channels| channels.len()),
+            // The `@everyone` role has the same ID as the guild.
+            role_count: cache
+                .guild_roles(guild_id)
+                .map_or(0, |roles| roles.iter().filter(|&&id| id != guild_id.cast()).count()),
+            premium_tier: guild.premium_tier(),
+            boost_count: guild.premium_subscription_count(),
+        })
+    }
+
+    fn icon_url(&self) -> Option<String> {
+        self.icon.map(|icon| {
+            let ext = if icon.is_animated() { "gif" } else { "png" };
+            format!("https://cdn.discordapp.com/icons/{}/{icon}.{ext}", self.id)
+        })
+    }
+
+    fn embed(&self) -> Result<Embed, Error> {
+        let boost_level = match self.premium_tier {
+            PremiumTier::None => String::from("None"),
+            PremiumTier::Tier1 => String::from("Level 1"),
+            PremiumTier::Tier2 => String::from("Level 2"),
+            PremiumTier::Tier3 => String::from("Level 3"),
+            PremiumTier::Other(tier) => format!("Level {tier}"),
+            _ => String::from("Unknown"),
+        };
+        let boost_level = match self.boost_count {
+            Some(boosts) => format!("{boost_level} ({boosts} boosts)"),
+            None => boost_level,
+        };
+
+        let mut embed = EmbedBuilder::new()
+            .title(crate::markdown::escape(&self.name))
+            .field(
+                EmbedFieldBuilder::new("Created", format!("<t:{}:D>", self.id.timestamp() / 1000))
+                    .inline(),
+            )
+            .field(EmbedFieldBuilder::new("Owner", self.owner_id.mention().to_string()).inline())
+            .field(
+                EmbedFieldBuilder::new(
+                    "Members",
+                    self.member_count.map_or_else(|| String::from("Unknown"), |n| n.to_string()),
+                )
+                .inline(),
+            )
+            .field(EmbedFieldBuilder::new("Channels", self.channel_count.to_string()).inline())
+            .field(EmbedFieldBuilder::new("Roles", self.role_count.to_string()).inline())
+            .field(EmbedFieldBuilder::new("Boost level", boost_level).inline());
+        if let Some(url) = self.icon_url() {
+            embed =
+                embed.thumbnail(ImageSource::url(url).context("failed to create the icon source")?);
+        }
+
+        Ok(embed.validate().context("server info embed failed validation")?.build())
+    }
+}
+
+impl CommandHandler for ServerInfo {
+    fn pattern(&self) -> &str {
+        "serverinfo"
+    }
+
+    fn help(&self) -> Option<Help> {
+        Some(Help {
+            name: "serverinfo".into(),
+            usage: "serverinfo".into(),
+            summary: "Show information about the server".into(),
+            description: concat!(
+                "Show when the server was created, who owns it, how many members, channels, and ",
+                "roles it has, and its boost level.",
+            )
+            .into(),
+            examples: Cow::Borrowed(&[Cow::Borrowed("serverinfo")]),
+        })
+    }
+
+    fn handle<'a>(
+        &'a self,
+        cache: &'a Cache,
+        config: &'a Config,
+        discord: &'a DiscordClient,
+        _: Commands<'a>,
+        message: &'a Message,
+        _: &'a Args,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>> {
+        Box::pin(async move {
+            let guild_id = message.guild_id.unwrap_or(config.guild);
+            let summary = cache
+                .with(|cache| Summary::from_cache(cache, guild_id))
+                .context("server not in cache")?;
+
+            discord
+                .create_message(message.channel_id)
+                .reply(message.id)
+                .embeds(&[summary.embed()?])
+                .await
+                .context("failed to reply to command")?;
+
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use twilight_model::gateway::event::Event;
+    use twilight_model::gateway::payload::incoming::GuildCreate;
+    use twilight_model::id::Id;
+
+    use super::Summary;
+    use crate::cache::Cache;
+
+    fn role(id: &str) -> serde_json::Value {
+        json!({
+            "color": 0,
+            "hoist": false,
+            "id": id,
+            "managed": false,
+            "mentionable": false,
+            "name": "role",
+            "permissions": "0",
+            "position": 0,
+            "flags": 0,
+        })
+    }
+
+    fn channel(id: &str) -> serde_json::Value {
+        json!({ "id": id, "type": 0, "guild_id": "1", "name": "general" })
+    }
+
+    #[test]
+    fn embed() {
+        let guild = serde_json::from_value(json!({
+            "afk_timeout": 300,
+            "channels": [channel("10"), channel("11"), channel("12")],
+            "default_message_notifications": 1,
+            "explicit_content_filter": 0,
+            "features": [],
+            "icon": "a_0123456789abcdef0123456789abcdef",
+            "id": "288920509272555520",
+            "member_count": 1234,
+            "mfa_level": 0,
+            "name": "LoadingReadyRun*",
+            "nsfw_level": 0,
+            "owner_id": "2",
+            "preferred_locale": "en-US",
+            "premium_progress_bar_enabled": false,
+            "premium_subscription_count": 9,
+            "premium_tier": 2,
+            "roles": [role("288920509272555520"), role("20"), role("21")],
+            "system_channel_flags": 0,
+            "verification_level": 0,
+        }))
+        .unwrap();
+        let cache = Cache::new(Id::new(288920509272555520));
+        cache.update(&Event::GuildCreate(Box::new(GuildCreate::Available(guild))));
+
+        let summary =
+            cache.with(|cache| Summary::from_cache(cache, Id::new(288920509272555520))).unwrap();
+        let embed = summary.embed().unwrap();
+
+        assert_eq!(embed.title.as_deref(), Some(r"LoadingReadyRun\*"));
+        assert_eq!(
+            embed.thumbnail.unwrap().url,
+            "https://cdn.discordapp.com/icons/288920509272555520/a_0123456789abcdef0123456789abcdef.gif"
+        );
+        let fields = embed
+            .fields
+            .iter()
+            .map(|field| (field.name.as_str(), field.value.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fields,
+            [
+                ("Created", "<t:1488954417:D>"),
+                ("Owner", "<@2>"),
+                ("Members", "1234"),
+                ("Channels", "3"),
+                ("Roles", "2"),
+                ("Boost level", "Level 2 (9 boosts)"),
+            ]
+        );
+
+        assert!(cache.with(|cache| Summary::from_cache(cache, Id::new(3))).is_none());
+    }
+}
@@ -3,7 +3,7 @@ use std::future::Future;
 use std::pin::Pin;
 
 use anyhow::{Context, Error};
-use chrono::Utc;
+use chrono::{DateTime, TimeDelta, Utc};
 use twilight_http::Client;
 use twilight_model::channel::Message;
 
@@ -11,6 +11,12 @@ use crate::cache::Cache;
 use crate::command_parser::{Args, CommandHandler, Commands, Help};
 use crate::config::Config;
 
+/// Offsets further than this into the past or future are clamped.
+const MAX_OFFSET: TimeDelta = match TimeDelta::try_days(366) {
+    Some(delta) => delta,
+    None => panic!("MAX_OFFSET is invalid"),
+};
+
 pub struct Time {
     pattern: &'static str,
     help: Help,
@@ -80,3 +86,111 @@ impl CommandHandler for Time {
         })
     }
 }
+
+pub struct Timestamp;
+
+impl Timestamp {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Discord timestamp token for `time`, in the given style.
+fn timestamp_token(time: DateTime<Utc>, style: &str) -> String {
+    format!("<t:{}:{style}>", time.timestamp())
+}
+
+/// Parse a `now`, `+2h`, or `-30m` argument into an offset from the current time.
+fn parse_offset(arg: &str) -> Option<TimeDelta> {
+    if arg.eq_ignore_ascii_case("now") {
+        Some(TimeDelta::zero())
+    } else {
+        crate::time::parse_duration(arg).map(|offset| offset.clamp(-MAX_OFFSET, MAX_OFFSET))
+    }
+}
+
+impl CommandHandler for Timestamp {
+    fn pattern(&self) -> &str {
+        r"time (now|[+-]\S+)(?: ([tTdDfFR]))?"
+    }
+
+    fn help(&self) -> Option<Help> {
+        Some(Help {
+            name: "time now".into(),
+            usage: "time <now|+OFFSET|-OFFSET> [STYLE]".into(),
+            summary: "Post a Discord timestamp for the current time".into(),
+            description: concat!(
+                "Post a Discord timestamp for the current time, or `OFFSET` from now, ",
+                "that shows up in everyone's local time.\n\n",
+                "`OFFSET` is made up of days, hours, minutes, and seconds, like `1h30m`. ",
+                "`STYLE` is one of Discord's timestamp styles (`t`, `T`, `d`, `D`, `f`, `F`, `R`) ",
+                "and defaults to `F`.",
+            )
+            .into(),
+            examples: Cow::Borrowed(&[
+                Cow::Borrowed("time now"),
+                Cow::Borrowed("time +2h"),
+                Cow::Borrowed("time +1d12h R"),
+            ]),
+        })
+    }
+
+    fn handle<'a>(
+        &'a self,
+        _: &'a Cache,
+        _: &'a Config,
+        discord: &'a Client,
+        _: Commands<'a>,
+        message: &'a Message,
+        args: &'a Args,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>> {
+        Box::pin(async move {
+            let arg = args.get(0).context("offset missing")?;
+            let style = args.get(1).unwrap_or("F");
+
+            let content = match parse_offset(arg) {
+                Some(offset) => {
+                    let token = timestamp_token(Utc::now() + offset, style);
+                    format!("{token} (`{token}`)")
+                }
+                None => format!(
+                    "Couldn't understand {}. Try something like `+2h` or `-1h30m`.",
+                    crate::markdown::escape(arg)
+                ),
+            };
+
+            discord
+                .create_message(message.channel_id)
+                .reply(message.id)
+                .content(&content)
+                .await
+                .context("failed to reply to command")?;
+
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, TimeZone, Utc};
+
+    use super::{parse_offset, timestamp_token, MAX_OFFSET};
+
+    #[test]
+    fn token() {
+        let time = Utc.timestamp_opt(1700000000, 0).unwrap();
+        assert_eq!(timestamp_token(time, "F"), "<t:1700000000:F>");
+        assert_eq!(timestamp_token(time + parse_offset("+2h").unwrap(), "R"), "<t:1700007200:R>");
+    }
+
+    #[test]
+    fn offset() {
+        assert_eq!(parse_offset("now"), Some(TimeDelta::zero()));
+        assert_eq!(parse_offset("+2h"), TimeDelta::try_hours(2));
+        assert_eq!(parse_offset("-1h30m"), TimeDelta::try_minutes(-90));
+        assert_eq!(parse_offset("+1000d"), Some(MAX_OFFSET));
+        assert_eq!(parse_offset("-1000d"), Some(-MAX_OFFSET));
+        assert_eq!(parse_offset("+soon"), None);
+    }
+}
@@ -0,0 +1,253 @@
+use std::borrow::Cow;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+use anyhow::{Context, Error};
+use chrono::Utc;
+use twilight_http::request::AuditLogReason;
+use twilight_http::Client as DiscordClient;
+use twilight_mention::Mention;
+use twilight_model::channel::Message;
+use twilight_model::id::marker::UserMarker;
+use twilight_model::id::Id;
+use twilight_model::util::Timestamp;
+
+use crate::cache::Cache;
+use crate::command_parser::{Access, Args, CommandHandler, Commands, Help};
+use crate::config::Config;
+
+/// The longest timeout Discord allows.
+const MAX_TIMEOUT: Duration = Duration::from_secs(28 * 24 * 60 * 60);
+
+pub struct Timeout;
+
+impl Timeout {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// The user from either the mention or the bare ID.
+fn target_user(mention: Option<&str>, id: Option<&str>) -> Option<Id<UserMarker>> {
+    mention.or(id)?.parse().ok().and_then(Id::new_checked)
+}
+
+/// Parse a timeout duration like `90s`, `10m`, or `1d12h`. It has to be positive.
+fn timeout_duration(s: &str) -> Option<Duration> {
+    crate::time::parse_duration(s)
+        .and_then(|duration| duration.to_std().ok())
+        .filter(|duration| !duration.is_zero())
+}
+
+/// The timestamp `duration` from `now`, at most `MAX_TIMEOUT` away, and whether it was cut short.
+fn timeout_until(now: i64, duration: Duration) -> (i64, bool) {
+    let capped = duration.min(MAX_TIMEOUT);
+    (now.saturating_add(capped.as_secs() as i64), capped < duration)
+}
+
+fn log_message(
+    moderator: Id<UserMarker>,
+    user: Id<UserMarker>,
+    until: i64,
+    reason: Option<&str>,
+) -> String {
+    let mut content = format!(
+        "{} timed out {} until <t:{until}:f> (<t:{until}:R>)",
+        moderator.mention(),
+        user.mention()
+    );
+    match reason {
+        Some(reason) => {
+            content.push_str(": ");
+            content.push_str(&crate::markdown::escape(reason));
+        }
+        None => content.push('.'),
+    }
+    content
+}
+
+impl CommandHandler for Timeout {
+    fn pattern(&self) -> &str {
+        r"(?:timeout|mute) (?:<@!?(\d+)>|(\d+)) (\S+)(?: (.+))?"
+    }
+
+    fn help(&self) -> Option<Help> {
+        Some(Help {
+            name: "timeout".into(),
+            usage: "timeout <USER> <DURATION> [REASON]".into(),
+            summary: "Time out a user".into(),
+            description: concat!(
+                "Stop a user from chatting, reacting, and joining voice channels for a while. The ",
+                "timeout is logged in the moderators' channel.\n\n",
+                "`USER` is either a mention or a user ID. `DURATION` is made up of numbers ",
+                "followed by `s`, `m`, `h`, `d`, or `w` for seconds, minutes, hours, days, or ",
+                "weeks, like `1d12h`. Timeouts can be at most 28 days long.",
+            )
+            .into(),
+            examples: Cow::Borrowed(&[
+                Cow::Borrowed("timeout @LRRbot 10m"),
+                Cow::Borrowed("mute 144128240389324800 1d spamming"),
+            ]),
+        })
+    }
+
+    fn access(&self) -> Access {
+        Access::ModOnly
+    }
+
+    fn category(&self) -> &'static str {
+        "Moderation"
+    }
+
+    fn handle<'a>(
+        &'a self,
+        _: &'a Cache,
+        config: &'a Config,
+        discord: &'a DiscordClient,
+        _: Commands<'a>,
+        message: &'a Message,
+        args: &'a Args,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>> {
+        Box::pin(async move {
+            let user_id = target_user(args.get(0), args.get(1));
+            let duration = args.get(2).and_then(timeout_duration);
+            let (user_id, duration) = match (user_id, duration) {
+                (Some(user_id), Some(duration)) => (user_id, duration),
+                (None, _) => {
+                    discord
+                        .create_message(message.channel_id)
+                        .reply(message.id)
+                        .content("That's not a valid user.")
+                        .await
+                        .context("failed to reply to command")?;
+                    return Ok(());
+                }
+                (_, None) => {
+                    discord
+                        .create_message(message.channel_id)
+                        .reply(message.id)
+                        .content(
+                            "That's not a valid duration. Try something like `10m` or `1d12h`.",
+                        )
+                        .await
+                        .context("failed to reply to command")?;
+                    return Ok(());
+                }
+            };
+            let reason = args.get(3).map(str::trim).filter(|reason| !reason.is_empty());
+
+            let (until, capped) = timeout_until(Utc::now().timestamp(), duration);
+            let timestamp = Timestamp::from_secs(until).context("timeout end is out of range")?;
+
+            let guild_id = message.guild_id.unwrap_or(config.guild);
+            let request = discord
+                .update_guild_member(guild_id, user_id)
+                .communication_disabled_until(Some(timestamp));
+            match reason {
+                Some(reason) => request.reason(reason).await,
+                None => request.await,
+            }
+            .context("failed to time out the user")?;
+
+            discord
+                .create_message(config.mods_channel)
+                .content(&log_message(message.author.id, user_id, until, reason))
+                .await
+                .context("failed to log the timeout")?;
+
+            let mut content = format!("Timed out {} until <t:{until}:f>.", user_id.mention());
+            if capped {
+                content.push_str(" That's the longest Discord allows.");
+            }
+            discord
+                .create_message(message.channel_id)
+                .reply(message.id)
+                .content(&content)
+                .await
+                .context("failed to reply to command")?;
+
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use twilight_model::id::Id;
+
+    use super::{log_message, target_user, timeout_duration, timeout_until, Timeout, MAX_TIMEOUT};
+    use crate::command_parser::{Builder, CommandHandler};
+
+    #[test]
+    fn durations() {
+        assert_eq!(timeout_duration("90s"), Some(Duration::from_secs(90)));
+        assert_eq!(timeout_duration("10m"), Some(Duration::from_secs(600)));
+        assert_eq!(timeout_duration("2h"), Some(Duration::from_secs(7200)));
+        assert_eq!(timeout_duration("1d12h"), Some(Duration::from_secs(36 * 60 * 60)));
+        assert_eq!(timeout_duration("1w"), Some(Duration::from_secs(7 * 24 * 60 * 60)));
+        assert_eq!(timeout_duration("1h30m15s"), Some(Duration::from_secs(5415)));
+
+        for invalid in
+            ["", "10", "m", "10x", "1.5h", "-1h", "0s", "1h 30m", "99999999999999999999s"]
+        {
+            assert_eq!(timeout_duration(invalid), None, "{invalid}");
+        }
+    }
+
+    #[test]
+    fn cap() {
+        let now = 1_700_000_000;
+        assert_eq!(timeout_until(now, Duration::from_secs(600)), (now + 600, false));
+        assert_eq!(timeout_until(now, MAX_TIMEOUT), (now + MAX_TIMEOUT.as_secs() as i64, false));
+        assert_eq!(
+            timeout_until(now, timeout_duration("5w").unwrap()),
+            (now + MAX_TIMEOUT.as_secs() as i64, true)
+        );
+        assert_eq!(
+            timeout_until(now, Duration::from_secs(u64::MAX)),
+            (now + MAX_TIMEOUT.as_secs() as i64, true)
+        );
+    }
+
+    #[test]
+    fn mentions() {
+        let pattern = Builder::expand_pattern(Timeout::new().pattern(), false).unwrap();
+        let args = |command: &'static str| {
+            let captures = pattern.captures(command)?;
+            let arg = |i| captures.get(i).map(|m| m.as_str());
+            Some((target_user(arg(1), arg(2)), arg(3), arg(4)))
+        };
+
+        assert_eq!(
+            args("timeout <@144128240389324800> 10m"),
+            Some((Some(Id::new(144128240389324800)), Some("10m"), None))
+        );
+        assert_eq!(
+            args("mute <@!144128240389324800> 1d spamming links"),
+            Some((Some(Id::new(144128240389324800)), Some("1d"), Some("spamming links")))
+        );
+        assert_eq!(
+            args("timeout 144128240389324800 1h"),
+            Some((Some(Id::new(144128240389324800)), Some("1h"), None))
+        );
+        assert_eq!(args("timeout <@0> 1h"), Some((None, Some("1h"), None)));
+        assert_eq!(args("timeout <@&144128240389324800> 1h"), None);
+        assert_eq!(args("timeout @LRRbot 1h"), None);
+        assert_eq!(args("timeout <@144128240389324800>"), None);
+    }
+
+    #[test]
+    fn log() {
+        assert_eq!(
+            log_message(Id::new(1), Id::new(2), 1_700_000_000, Some("*spam*")),
+            "<@1> timed out <@2> until <t:1700000000:f> (<t:1700000000:R>): \\*spam\\*"
+        );
+        assert_eq!(
+            log_message(Id::new(1), Id::new(2), 1_700_000_000, None),
+            "<@1> timed out <@2> until <t:1700000000:f> (<t:1700000000:R>)."
+        );
+    }
+}
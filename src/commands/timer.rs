@@ -0,0 +1,177 @@
+use std::borrow::Cow;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+use anyhow::{Context, Error};
+use tokio::sync::watch::Receiver;
+use tokio::time::Instant;
+use tracing::error;
+use twilight_http::Client as DiscordClient;
+use twilight_model::channel::Message;
+
+use crate::cache::Cache;
+use crate::command_parser::{Args, CommandHandler, Commands, Help};
+use crate::config::Config;
+
+/// How often the countdown is updated. Well clear of Discord's rate limit on edits.
+const UPDATE_INTERVAL: Duration = Duration::from_secs(5);
+/// The longest timer that can be started.
+const MAX_DURATION: Duration = Duration::from_secs(12 * 60 * 60);
+
+pub struct Timer {
+    running: Receiver<bool>,
+}
+
+impl Timer {
+    pub fn new(running: Receiver<bool>) -> Self {
+        Self { running }
+    }
+}
+
+/// Parse a timer duration like `90s`, `5m`, or `2h`. It has to be between 1 second and
+/// `MAX_DURATION`.
+fn timer_duration(s: &str) -> Option<Duration> {
+    crate::time::parse_duration(s)
+        .and_then(|duration| duration.to_std().ok())
+        .filter(|duration| !duration.is_zero() && *duration <= MAX_DURATION)
+}
+
+/// The countdown message, with the remaining time rounded up to the next second.
+fn countdown(remaining: Duration) -> String {
+    let secs = remaining.as_secs() + u64::from(remaining.subsec_nanos() != 0);
+    let (hours, minutes, secs) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if hours > 0 {
+        format!("\u{23F3} {hours}:{minutes:02}:{secs:02} remaining")
+    } else {
+        format!("\u{23F3} {minutes}:{secs:02} remaining")
+    }
+}
+
+impl CommandHandler for Timer {
+    fn pattern(&self) -> &str {
+        r"timer (\d+[smh])"
+    }
+
+    fn help(&self) -> Option<Help> {
+        Some(Help {
+            name: "timer".into(),
+            usage: "timer <DURATION>".into(),
+            summary: "Start a countdown".into(),
+            description: concat!(
+                "Post a countdown that's kept up to date until the time is up.\n\n",
+                "`DURATION` is a number followed by `s`, `m`, or `h` for seconds, minutes, or ",
+                "hours. Timers can be at most 12 hours long.",
+            )
+            .into(),
+            examples: Cow::Borrowed(&[Cow::Borrowed("timer 90s"), Cow::Borrowed("timer 5m")]),
+        })
+    }
+
+    fn category(&self) -> &'static str {
+        "Fun"
+    }
+
+    fn handle<'a>(
+        &'a self,
+        _: &'a Cache,
+        _: &'a Config,
+        discord: &'a DiscordClient,
+        _: Commands<'a>,
+        message: &'a Message,
+        args: &'a Args,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>> {
+        Box::pin(async move {
+            let Some(duration) = args.get(0).and_then(timer_duration) else {
+                discord
+                    .create_message(message.channel_id)
+                    .reply(message.id)
+                    .content("Timers have to be between 1 second and 12 hours long.")
+                    .await
+                    .context("failed to reply to command")?;
+                return Ok(());
+            };
+
+            let deadline = Instant::now() + duration;
+            let countdown_message = discord
+                .create_message(message.channel_id)
+                .reply(message.id)
+                .content(&countdown(duration))
+                .await
+                .context("failed to post the countdown")?
+                .model()
+                .await
+                .context("failed to parse the countdown message")?;
+
+            let mut running = self.running.clone();
+            let finished = loop {
+                let now = Instant::now();
+                if now >= deadline {
+                    break true;
+                }
+
+                tokio::select! {
+                    _ = running.changed() => break false,
+                    _ = tokio::time::sleep_until(deadline.min(now + UPDATE_INTERVAL)) => {
+                        let remaining = deadline.saturating_duration_since(Instant::now());
+                        if remaining.is_zero() {
+                            continue;
+                        }
+                        // A missed update isn't worth abandoning the countdown for.
+                        if let Err(error) = discord
+                            .update_message(countdown_message.channel_id, countdown_message.id)
+                            .content(Some(&countdown(remaining)))
+                            .await
+                        {
+                            error!(?error, "Failed to update the countdown");
+                        }
+                    },
+                }
+            };
+
+            let content = if finished {
+                "\u{23F0} Time's up!"
+            } else {
+                "\u{23F9}\u{FE0F} Timer cancelled because the bot is shutting down."
+            };
+            discord
+                .update_message(countdown_message.channel_id, countdown_message.id)
+                .content(Some(content))
+                .await
+                .context("failed to finish the countdown")?;
+
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{countdown, timer_duration};
+
+    #[test]
+    fn durations() {
+        assert_eq!(timer_duration("90s"), Some(Duration::from_secs(90)));
+        assert_eq!(timer_duration("5m"), Some(Duration::from_secs(300)));
+        assert_eq!(timer_duration("12h"), Some(Duration::from_secs(12 * 60 * 60)));
+        assert_eq!(timer_duration("13h"), None);
+        assert_eq!(timer_duration("0s"), None);
+        assert_eq!(timer_duration("m"), None);
+        assert_eq!(timer_duration(""), None);
+        assert_eq!(timer_duration("5\u{00B5}"), None);
+        assert_eq!(timer_duration("99999999999999999999h"), None);
+    }
+
+    #[test]
+    fn formatting() {
+        assert_eq!(countdown(Duration::from_secs(83)), "\u{23F3} 1:23 remaining");
+        assert_eq!(countdown(Duration::from_millis(82_100)), "\u{23F3} 1:23 remaining");
+        assert_eq!(countdown(Duration::from_secs(5)), "\u{23F3} 0:05 remaining");
+        assert_eq!(countdown(Duration::from_millis(1)), "\u{23F3} 0:01 remaining");
+        assert_eq!(countdown(Duration::from_secs(600)), "\u{23F3} 10:00 remaining");
+        assert_eq!(countdown(Duration::from_secs(3600)), "\u{23F3} 1:00:00 remaining");
+        assert_eq!(countdown(Duration::from_secs(45_296)), "\u{23F3} 12:34:56 remaining");
+    }
+}
@@ -0,0 +1,253 @@
+use std::borrow::Cow;
+use std::future::Future;
+use std::pin::Pin;
+
+use anyhow::{Context, Error};
+use twilight_http::Client as DiscordClient;
+use twilight_mention::Mention;
+use twilight_model::channel::Message;
+use twilight_model::id::marker::{RoleMarker, UserMarker};
+use twilight_model::id::Id;
+use twilight_model::user::User;
+use twilight_model::util::Timestamp;
+use twilight_util::builder::embed::{EmbedBuilder, EmbedFieldBuilder};
+use twilight_util::snowflake::Snowflake;
+
+use crate::cache::Cache;
+use crate::command_parser::{Access, Args, CommandHandler, Commands, Help};
+use crate::config::Config;
+
+pub struct UserInfo;
+
+impl UserInfo {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// The user to show: the mentioned user, the user with the given ID, or the invoker.
+fn target_user(
+    mention: Option<&str>,
+    id: Option<&str>,
+    invoker: Id<UserMarker>,
+) -> Option<Id<UserMarker>> {
+    match mention.or(id) {
+        Some(id) => id.parse().ok().and_then(Id::new_checked),
+        None => Some(invoker),
+    }
+}
+
+/// A Discord timestamp token in the long date format.
+fn date_token(secs: i64) -> String {
+    format!("<t:{secs}:D>")
+}
+
+struct MemberInfo {
+    user: Option<User>,
+    joined_at: Option<Timestamp>,
+    roles: Vec<Id<RoleMarker>>,
+}
+
+async fn member_info(
+    cache: &Cache,
+    discord: &DiscordClient,
+    config: &Config,
+    message: &Message,
+    user_id: Id<UserMarker>,
+) -> Result<Option<MemberInfo>, Error> {
+    let guild_id = message.guild_id.unwrap_or(config.guild);
+
+    let cached = cache.with(|cache| {
+        cache.member(guild_id, user_id).map(|member| MemberInfo {
+            user: cache.user(user_id).map(|user| user.clone()),
+            joined_at: member.joined_at(),
+            roles: member.roles().to_vec(),
+        })
+    });
+    if let Some(info) = cached {
+        return Ok(Some(info));
+    }
+
+    let response = match discord.guild_member(guild_id, user_id).await {
+        Ok(response) => response,
+        Err(error) => {
+            if let twilight_http::error::ErrorType::Response { status, .. } = error.kind() {
+                if status.get() == 404 {
+                    return Ok(None);
+                }
+            }
+            return Err(Error::from(error).context("failed to fetch the member"));
+        }
+    };
+    let member = response.model().await.context("failed to parse the member")?;
+
+    Ok(Some(MemberInfo {
+        user: Some(member.user),
+        joined_at: member.joined_at,
+        roles: member.roles,
+    }))
+}
+
+impl CommandHandler for UserInfo {
+    fn pattern(&self) -> &str {
+        r"userinfo(?: (?:<@!?(\d+)>|(\d+)))?"
+    }
+
+    fn help(&self) -> Option<Help> {
+        Some(Help {
+            name: "userinfo".into(),
+            usage: "userinfo [USER]".into(),
+            summary: "Show information about a user".into(),
+            description: concat!(
+                "Show when a user joined the server, their roles, when their account was created, ",
+                "and whether they're a subscriber or a moderator.\n\n",
+                "`USER` is either a mention or a user ID. Defaults to yourself.",
+            )
+            .into(),
+            examples: Cow::Borrowed(&[
+                Cow::Borrowed("userinfo"),
+                Cow::Borrowed("userinfo @LRRbot"),
+                Cow::Borrowed("userinfo 144128240389324800"),
+            ]),
+        })
+    }
+
+    fn access(&self) -> Access {
+        Access::ModOnly
+    }
+
+    fn category(&self) -> &'static str {
+        "Moderation"
+    }
+
+    fn handle<'a>(
+        &'a self,
+        cache: &'a Cache,
+        config: &'a Config,
+        discord: &'a DiscordClient,
+        _: Commands<'a>,
+        message: &'a Message,
+        args: &'a Args,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>> {
+        Box::pin(async move {
+            let Some(user_id) = target_user(args.get(0), args.get(1), message.author.id) else {
+                discord
+                    .create_message(message.channel_id)
+                    .reply(message.id)
+                    .content("That's not a valid user.")
+                    .await
+                    .context("failed to reply to command")?;
+                return Ok(());
+            };
+
+            let Some(info) = member_info(cache, discord, config, message, user_id).await? else {
+                discord
+                    .create_message(message.channel_id)
+                    .reply(message.id)
+                    .content("That user is not a member of the server.")
+                    .await
+                    .context("failed to reply to command")?;
+                return Ok(());
+            };
+
+            let guild_id = message.guild_id.unwrap_or(config.guild);
+            let is_sub = Access::SubOnly
+                .user_has_access_or_fetch(user_id, guild_id, cache, discord)
+                .await
+                .context("failed to check whether the user is a subscriber")?;
+            let is_mod = Access::ModOnly
+                .user_has_access_or_fetch(user_id, guild_id, cache, discord)
+                .await
+                .context("failed to check whether the user is a moderator")?;
+
+            let title = match info.user {
+                Some(ref user) => {
+                    crate::markdown::escape(user.global_name.as_ref().unwrap_or(&user.name))
+                        .into_owned()
+                }
+                None => user_id.to_string(),
+            };
+            let roles = if info.roles.is_empty() {
+                String::from("None")
+            } else {
+                info.roles
+                    .iter()
+                    .map(|role_id| role_id.mention().to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            };
+            let yes_no = |value: bool| if value { "Yes" } else { "No" };
+
+            let mut embed =
+                EmbedBuilder::new().title(title).description(user_id.mention().to_string()).field(
+                    EmbedFieldBuilder::new(
+                        "Account created",
+                        date_token(user_id.timestamp() / 1000),
+                    )
+                    .inline(),
+                );
+            if let Some(joined_at) = info.joined_at {
+                embed = embed.field(
+                    EmbedFieldBuilder::new("Joined", date_token(joined_at.as_secs())).inline(),
+                );
+            }
+            let embed = embed
+                .field(EmbedFieldBuilder::new("Subscriber", yes_no(is_sub)).inline())
+                .field(EmbedFieldBuilder::new("Moderator", yes_no(is_mod)).inline())
+                .field(EmbedFieldBuilder::new("Roles", roles))
+                .validate()
+                .context("user info embed failed validation")?
+                .build();
+
+            discord
+                .create_message(message.channel_id)
+                .reply(message.id)
+                .embeds(&[embed])
+                .await
+                .context("failed to reply to command")?;
+
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use twilight_model::id::Id;
+
+    use super::{target_user, UserInfo};
+    use crate::command_parser::{Builder, CommandHandler};
+
+    #[test]
+    fn target() {
+        let invoker = Id::new(1);
+
+        assert_eq!(target_user(None, None, invoker), Some(invoker));
+        assert_eq!(
+            target_user(Some("144128240389324800"), None, invoker),
+            Some(Id::new(144128240389324800))
+        );
+        assert_eq!(
+            target_user(None, Some("144128240389324800"), invoker),
+            Some(Id::new(144128240389324800))
+        );
+        assert_eq!(target_user(None, Some("0"), invoker), None);
+        assert_eq!(target_user(None, Some("99999999999999999999999"), invoker), None);
+    }
+
+    #[test]
+    fn pattern() {
+        let pattern = Builder::expand_pattern(UserInfo::new().pattern(), false).unwrap();
+        let captures = |content: &'static str| {
+            pattern
+                .captures(content)
+                .map(|c| (c.get(1).map(|m| m.as_str()), c.get(2).map(|m| m.as_str())))
+        };
+
+        assert_eq!(captures("userinfo"), Some((None, None)));
+        assert_eq!(captures("userinfo <@123>"), Some((Some("123"), None)));
+        assert_eq!(captures("userinfo <@!123>"), Some((Some("123"), None)));
+        assert_eq!(captures("userinfo  123 "), Some((None, Some("123"))));
+        assert_eq!(captures("userinfo qrpth"), None);
+    }
+}
@@ -13,11 +13,29 @@ use twilight_model::channel::Message;
 use twilight_model::id::marker::ChannelMarker;
 use twilight_model::id::Id;
 
-use crate::announcements::youtube::Video;
+use crate::announcements::youtube::{playlist_video_ids, Video, MAX_PLAYLIST_VIDEOS};
 use crate::cache::Cache;
 use crate::command_parser::{Access, Args, CommandHandler, Commands, Help};
 use crate::config::Config;
 
+async fn report_invalid_video_id(
+    discord: &Client,
+    message: &Message,
+    input: &str,
+) -> Result<(), Error> {
+    discord
+        .create_message(message.channel_id)
+        .reply(message.id)
+        .flags(MessageFlags::SUPPRESS_EMBEDS)
+        .content(&format!(
+            "`{}` is not a YouTube video ID or a link to a YouTube video.",
+            input.replace('`', "")
+        ))
+        .await
+        .context("failed to report an error")?;
+    Ok(())
+}
+
 pub struct New {
     channel_id: Id<ChannelMarker>,
     youtube: YouTube<HttpsConnector<HttpConnector>>,
@@ -39,8 +57,11 @@ impl CommandHandler for New {
             name: "video new".into(),
             usage: "video new <VIDEO ID>".into(),
             summary: "Create a new video thread".into(),
-            description: "Create a new video thread.".into(),
-            examples: Cow::Borrowed(&[Cow::Borrowed("video new dQw4w9WgXcQ")]),
+            description: "Create a new video thread. The video can also be given as a link.".into(),
+            examples: Cow::Borrowed(&[
+                Cow::Borrowed("video new dQw4w9WgXcQ"),
+                Cow::Borrowed("video new https://youtu.be/dQw4w9WgXcQ"),
+            ]),
         })
     }
 
@@ -48,10 +69,14 @@ impl CommandHandler for New {
         Access::ModOnly
     }
 
+    fn category(&self) -> &'static str {
+        "Moderation"
+    }
+
     fn handle<'a>(
         &'a self,
         cache: &'a Cache,
-        _: &'a Config,
+        config: &'a Config,
         discord: &'a Client,
         _: Commands<'a>,
         message: &'a Message,
@@ -65,7 +90,12 @@ impl CommandHandler for New {
                 })
                 .context("channel not in cache")?;
 
-            let videos = Video::fetch(&self.youtube, &[args.get(0).context("video ID missing")?])
+            let input = args.get(0).context("video ID missing")?;
+            let Some(video_id) = Video::video_id_from_input(input) else {
+                return report_invalid_video_id(discord, message, input).await;
+            };
+
+            let videos = Video::fetch(&self.youtube, &[video_id], config.youtube_shorts_detection)
                 .await
                 .context("failed to get the video")?;
 
@@ -98,6 +128,125 @@ impl CommandHandler for New {
     }
 }
 
+pub struct NewPlaylist {
+    channel_id: Id<ChannelMarker>,
+    youtube: YouTube<HttpsConnector<HttpConnector>>,
+}
+
+impl NewPlaylist {
+    pub fn new(config: &Config, youtube: YouTube<HttpsConnector<HttpConnector>>) -> Option<Self> {
+        Some(Self { channel_id: config.lrr_videos_channel?, youtube })
+    }
+}
+
+impl CommandHandler for NewPlaylist {
+    fn pattern(&self) -> &str {
+        r"video new-playlist (\S+)"
+    }
+
+    fn help(&self) -> Option<Help> {
+        Some(Help {
+            name: "video new-playlist".into(),
+            usage: "video new-playlist <PLAYLIST ID>".into(),
+            summary: "Create video threads for a whole playlist".into(),
+            description: Cow::Owned(format!(
+                concat!(
+                    "Create video threads for every video in a playlist that doesn't have one yet. ",
+                    "Livestreams and shorts are skipped like they are for automatic announcements.\n\n",
+                    "At most the first {} videos of the playlist are checked."
+                ),
+                MAX_PLAYLIST_VIDEOS
+            )),
+            examples: Cow::Borrowed(&[Cow::Borrowed(
+                "video new-playlist PLh3VJeXMaLrI0EH7cMbYDSUqRrV0Xnnkw",
+            )]),
+        })
+    }
+
+    fn access(&self) -> Access {
+        Access::ModOnly
+    }
+
+    fn category(&self) -> &'static str {
+        "Moderation"
+    }
+
+    fn handle<'a>(
+        &'a self,
+        cache: &'a Cache,
+        config: &'a Config,
+        discord: &'a Client,
+        _: Commands<'a>,
+        message: &'a Message,
+        args: &'a Args,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>> {
+        Box::pin(async move {
+            let (channel_type, available_tags) = cache
+                .with(|cache| {
+                    let channel = cache.channel(self.channel_id)?;
+                    Some((channel.kind, channel.available_tags.clone()))
+                })
+                .context("channel not in cache")?;
+
+            let playlist_id = args.get(0).context("playlist ID missing")?;
+            let (video_ids, truncated) =
+                playlist_video_ids(&self.youtube, playlist_id, MAX_PLAYLIST_VIDEOS)
+                    .await
+                    .context("failed to get the playlist")?;
+
+            let mut videos =
+                Video::fetch(&self.youtube, &video_ids, config.youtube_shorts_detection)
+                    .await
+                    .context("failed to get the videos")?;
+            videos.sort_by_key(Video::published_at);
+            let unavailable = video_ids.len() - videos.len();
+
+            let mut created = 0;
+            let mut already_announced = 0;
+            let mut not_announced = 0;
+            for video in videos {
+                if video.is_already_announced(self.channel_id, cache, discord).await? {
+                    already_announced += 1;
+                } else if !video.should_announce() {
+                    not_announced += 1;
+                } else {
+                    video
+                        .announce(self.channel_id, channel_type, available_tags.as_deref(), discord)
+                        .await
+                        .context("failed to create the video thread")?;
+                    created += 1;
+                }
+            }
+
+            let mut content = format!("Created {created} video threads.");
+            for (count, reason) in [
+                (already_announced, "already had a thread"),
+                (not_announced, "were livestreams or shorts"),
+                (unavailable, "were private or deleted"),
+            ] {
+                if count > 0 {
+                    content.push_str(&format!(" Skipped {count} videos that {reason}."));
+                }
+            }
+            if truncated {
+                content.push_str(&format!(
+                    " Only the first {MAX_PLAYLIST_VIDEOS} videos of the playlist were checked."
+                ));
+            }
+
+            discord
+                .create_message(message.channel_id)
+                .reply(message.id)
+                .flags(MessageFlags::SUPPRESS_EMBEDS)
+                .content(&content)
+                .await
+                .context("failed to reply to command")?;
+
+            Ok(())
+        })
+    }
+}
+
 pub struct Refresh {
     channel_id: Id<ChannelMarker>,
     youtube: YouTube<HttpsConnector<HttpConnector>>,
@@ -122,7 +271,7 @@ impl CommandHandler for Refresh {
             description: Cow::Owned(format!(
                 concat!(
                 "Update the video thread to have up to date video information. Optionally pass a ",
-                "YouTube video ID to replace the current video\n\n",
+                "YouTube video ID or link to replace the current video\n\n",
                 "Must be used in a thread in {}."
             ),
                 self.channel_id.mention()
@@ -135,10 +284,14 @@ impl CommandHandler for Refresh {
         Access::ModOnly
     }
 
+    fn category(&self) -> &'static str {
+        "Moderation"
+    }
+
     fn handle<'a>(
         &'a self,
         cache: &'a Cache,
-        _: &'a Config,
+        config: &'a Config,
         discord: &'a Client,
         _: Commands<'a>,
         message: &'a Message,
@@ -197,7 +350,10 @@ impl CommandHandler for Refresh {
                 return Ok(());
             };
 
-            let video_id = if let Some(video_id) = args.get(0) {
+            let video_id = if let Some(input) = args.get(0) {
+                let Some(video_id) = Video::video_id_from_input(input) else {
+                    return report_invalid_video_id(discord, message, input).await;
+                };
                 video_id
             } else if let Some(video_id) = Video::video_id_from_message(&original_message.content) {
                 video_id
@@ -212,7 +368,7 @@ impl CommandHandler for Refresh {
                 return Ok(());
             };
 
-            let videos = Video::fetch(&self.youtube, &[video_id])
+            let videos = Video::fetch(&self.youtube, &[video_id], config.youtube_shorts_detection)
                 .await
                 .context("failed to get the video")?;
 
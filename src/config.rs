@@ -2,18 +2,27 @@
 
 use std::collections::HashMap;
 use std::error::Error as StdError;
-use std::path::Path;
-#[cfg(unix)]
-use std::path::PathBuf;
+use std::hash::Hash;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
 
 use anyhow::{anyhow, Context, Error};
 use ini::Ini;
+use regex::Regex;
+use separator::FixedPlaceSeparatable;
+use tokio::sync::watch::{Receiver, Sender};
+use tokio::sync::Semaphore;
+use tracing::{error, info};
+use twilight_model::gateway::presence::ActivityType;
 use twilight_model::id::marker::{ChannelMarker, GuildMarker};
 use twilight_model::id::Id;
 use twitch_api::twitch_oauth2::{ClientId, ClientSecret};
 use url::Url;
 
+use crate::aiomas::keepalive::KeepAlive;
 use crate::tz::Tz;
 
 #[derive(Debug)]
@@ -24,6 +33,8 @@ pub struct Config {
     pub database_url: String,
 
     pub command_prefix: String,
+    /// Guilds that use a different command prefix than `command_prefix`.
+    pub guild_command_prefixes: HashMap<Id<GuildMarker>, String>,
 
     pub timezone: Tz,
 
@@ -37,8 +48,18 @@ pub struct Config {
     #[cfg(not(unix))]
     pub eris_port: u16,
 
+    /// Log the Discord changes made by the background tasks instead of making them, for trying out
+    /// a deployment against production data.
+    pub dry_run: bool,
+
+    /// Pings on the RPC connections to and from LRRbot, if `rpc_keepalive_interval` is set.
+    pub rpc_keepalive: Option<KeepAlive>,
+    /// How many requests from LRRbot are handled at once on each connection, if limited.
+    pub rpc_max_in_flight: Option<usize>,
+
     pub twitch_client_id: ClientId,
     pub twitch_client_secret: ClientSecret,
+    pub twitch_eventsub: Option<EventSubWebhook>,
 
     pub discord_botsecret: String,
     pub temp_channel_prefix: String,
@@ -51,31 +72,269 @@ pub struct Config {
 
     pub mastodon_server: Url,
     pub mastodon_users: HashMap<String, Vec<Id<ChannelMarker>>>,
+    // Not announced anywhere yet, only validated.
+    #[allow(dead_code)]
+    pub bsky_users: HashMap<AtIdentifier, Vec<Id<ChannelMarker>>>,
 
     pub contact_spreadsheet: Option<String>,
 
+    /// Quote IDs are joined onto this URL to link to the quote on the website.
+    pub quote_url: Url,
+    /// The quote search page on the website. Takes the query in the `q` query parameter.
+    pub quote_search_url: Url,
+    /// Keys of the shows whose quotes are posted behind spoiler tags.
+    pub spoiler_shows: Vec<String>,
+
     pub influxdb: Option<(String, String)>,
+    /// How long to wait for the final write of the buffered measurements on shutdown.
+    pub influxdb_flush_timeout: Duration,
+    /// Where to serve the metrics for Prometheus to scrape, if anywhere.
+    pub prometheus_listen: Option<SocketAddr>,
+    /// The fraction of messages recorded in the metrics. Each recorded message stands in for the
+    /// ones that weren't.
+    pub message_metrics_sample_rate: f64,
+
+    /// The user the Google service account impersonates through domain-wide delegation.
+    pub google_subject: Option<String>,
 
     pub youtube_channels: Vec<String>,
+    /// More new videos than this at once are announced together in a single digest thread.
+    pub youtube_digest_threshold: Option<usize>,
+    pub youtube_shorts_detection: ShortsDetection,
+
+    /// How many consecutive offline readings it takes for the topic to switch away from live.
+    /// The default of 1 switches on the first offline reading.
+    pub autotopic_offline_threshold: usize,
+    /// How far ahead a fan stream can be to be mentioned in the topic next to the scheduled
+    /// streams.
+    pub autotopic_fan_stream_horizon: Option<Duration>,
+    /// What the topic says about the stream while it's live.
+    pub autotopic_live: LiveTopic,
+
+    /// Messages edited within this long of being sent are checked for commands again.
+    pub command_edit_window: Option<Duration>,
+
+    /// The text shown above the command listing in `!help`.
+    pub help_intro: String,
+
+    /// How amounts of money, like the Desert Bus total, are written.
+    pub money_format: MoneyFormat,
+
+    /// The `User-Agent` of the HTTP requests, if not the default one.
+    pub user_agent: Option<String>,
+    /// The activity shown as the bot's status.
+    pub presence: PresenceActivity,
+
+    /// Commands that reply with values from an HTTP API, from the `[eris.http.<name>]` sections.
+    pub http_commands: Vec<HttpCommand>,
+
+    /// Other channels the automatic topic is copied to.
+    pub topic_mirrors: Vec<TopicMirror>,
+
+    /// The names and URLs posted by `!links`, from the `[eris.links]` section.
+    pub links: Vec<(String, Url)>,
+}
+
+/// Where Twitch delivers EventSub notifications and the secret they're signed with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventSubWebhook {
+    pub callback: Url,
+    pub secret: String,
+    /// The local address the webhook is served on, behind whatever serves `callback`.
+    pub listen: SocketAddr,
+}
+
+/// A channel the automatic topic is copied to and the maximum topic length in it, if it isn't the
+/// default.
+pub type TopicMirror = (Id<ChannelMarker>, Option<usize>);
+
+/// A command that fetches JSON from `url` and replies with `response`.
+///
+/// Both are `strfmt` templates. `{args}` is whatever follows the command name (URL-encoded in
+/// `url`) and the response can also use the values picked out of the JSON by `fields`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpCommand {
+    pub name: String,
+    pub url: String,
+    /// Template variables and the JSON pointers to the values they're set to.
+    pub fields: Vec<(String, String)>,
+    pub response: String,
+    pub summary: Option<String>,
+}
+
+/// How YouTube Shorts are told apart from regular videos, which the API doesn't do by itself.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ShortsDetection {
+    /// Short videos with a square or vertical embed player. Needs the `player` part, which costs
+    /// quota.
+    #[default]
+    Player,
+    /// Short videos that `youtube.com/shorts/<ID>` serves instead of redirecting to the watch
+    /// page. If that can't be checked, short videos with a `#shorts` hashtag.
+    Probe,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MoneyFormat {
+    pub symbol: String,
+    pub thousands_separator: String,
+    pub decimal_separator: String,
+}
+
+impl Default for MoneyFormat {
+    fn default() -> Self {
+        Self {
+            symbol: String::from("$"),
+            thousands_separator: String::from(","),
+            decimal_separator: String::from("."),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PresenceActivity {
+    pub kind: ActivityType,
+    /// The text, a `strfmt` template where `{prefix}` is the command prefix and `{version}` is the
+    /// version of the bot.
+    pub text: String,
+}
+
+impl Default for PresenceActivity {
+    fn default() -> Self {
+        Self { kind: ActivityType::Listening, text: String::from("{prefix}help || v{version}") }
+    }
+}
+
+impl PresenceActivity {
+    /// Fill in the placeholders in the text. Fails on unknown placeholders and unmatched braces.
+    pub fn format(&self, prefix: &str, version: &str) -> Result<String, Error> {
+        let vars =
+            HashMap::from([(String::from("prefix"), prefix), (String::from("version"), version)]);
+        strfmt::strfmt(&self.text, &vars)
+            .with_context(|| format!("failed to format {:?}", self.text))
+    }
+}
+
+/// The line at the start of the automatic topic while the stream is live, depending on whether
+/// LRRbot knows the game and the show. These are `strfmt` templates where `{channel}` is the
+/// Twitch channel, `{game}` the game, and `{show}` the show, where they're known.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LiveTopic {
+    pub game_and_show: String,
+    pub game: String,
+    pub show: String,
+    pub neither: String,
+}
+
+impl Default for LiveTopic {
+    fn default() -> Self {
+        Self {
+            game_and_show: String::from("Now live: {game} on {show}."),
+            game: String::from("Now live: {game}."),
+            show: String::from("Now live: {show}."),
+            neither: String::from("Now live: something?"),
+        }
+    }
+}
+
+impl LiveTopic {
+    /// Fill in the template for the combination of `game` and `show`. Fails on unknown
+    /// placeholders, placeholders for something that isn't known, and unmatched braces.
+    pub fn format(
+        &self,
+        channel: &str,
+        game: Option<&str>,
+        show: Option<&str>,
+    ) -> Result<String, Error> {
+        let template = match (game, show) {
+            (Some(_), Some(_)) => &self.game_and_show,
+            (Some(_), None) => &self.game,
+            (None, Some(_)) => &self.show,
+            (None, None) => &self.neither,
+        };
+        let mut vars = HashMap::from([(String::from("channel"), channel)]);
+        vars.extend(game.map(|game| (String::from("game"), game)));
+        vars.extend(show.map(|show| (String::from("show"), show)));
+        strfmt::strfmt(template, &vars).with_context(|| format!("failed to format {template:?}"))
+    }
+
+    /// Check every template by formatting it with placeholder values.
+    fn validate(&self) -> Result<(), Error> {
+        for (game, show, option) in [
+            (Some(""), Some(""), "autotopic_live"),
+            (Some(""), None, "autotopic_live_game"),
+            (None, Some(""), "autotopic_live_show"),
+            (None, None, "autotopic_live_unknown"),
+        ] {
+            self.format("", game, show).with_context(|| format!("failed to parse {option:?}"))?;
+        }
+        Ok(())
+    }
+}
+
+impl MoneyFormat {
+    /// Format `amount` with two decimal places, like `$1,234.56`.
+    pub fn format(&self, amount: f64) -> String {
+        let mut formatted = self.symbol.clone();
+        for c in amount.separated_string_with_fixed_place(2).chars() {
+            match c {
+                ',' => formatted.push_str(&self.thousands_separator),
+                '.' => formatted.push_str(&self.decimal_separator),
+                c => formatted.push(c),
+            }
+        }
+        formatted
+    }
 }
 
 impl Config {
+    // `main` loads the files with `load_from_files`.
+    #[allow(dead_code)]
     pub fn load_from_file<P: AsRef<Path>>(filename: P) -> Result<Config, Error> {
-        let ini = Ini::load_from_file(filename)?;
+        Config::load_from_files(&[filename])
+    }
+
+    /// Load and merge several config files, for example a shared base and a local file with the
+    /// secrets. A key in a later file replaces the same key in the earlier files.
+    pub fn load_from_files<P: AsRef<Path>>(filenames: &[P]) -> Result<Config, Error> {
+        let mut merged = Ini::new();
+        for filename in filenames {
+            let filename = filename.as_ref();
+            let ini = Ini::load_from_file(filename)
+                .with_context(|| format!("failed to load {}", filename.display()))?;
+            merge_ini(&mut merged, ini);
+        }
+        Config::from_ini(&merged)
+    }
+
+    fn from_ini(ini: &Ini) -> Result<Config, Error> {
         Ok(Config {
             username: ini.get_from(Some("lrrbot"), "username").unwrap_or("lrrbot").into(),
             channel: ini.get_from(Some("lrrbot"), "channel").unwrap_or("loadingreadyrun").into(),
 
-            database_url: ini
-                .get_from(Some("lrrbot"), "postgres")
-                .unwrap_or("postgres:///lrrbot")
-                .into(),
+            database_url: Config::get_secret(ini, "postgres")?
+                .unwrap_or_else(|| String::from("postgres:///lrrbot")),
 
-            command_prefix: ini
-                .get_from(Some("lrrbot"), "commandprefix")
-                .unwrap_or("!")
-                .trim()
-                .into(),
+            command_prefix: command_prefix(
+                ini.get_from(Some("lrrbot"), "commandprefix").unwrap_or("!"),
+            )
+            .context("invalid commandprefix in [lrrbot]")?,
+            guild_command_prefixes: ini
+                .section(Some("eris.command_prefixes"))
+                .into_iter()
+                .flat_map(|section| section.iter())
+                .map(|(guild_id, prefix)| {
+                    let guild_id = parse_id(guild_id).with_context(|| {
+                        format!("failed to parse the guild {guild_id:?} in [eris.command_prefixes]")
+                    })?;
+                    let prefix = command_prefix(prefix).with_context(|| {
+                        format!(
+                            "invalid prefix for the guild {guild_id} in [eris.command_prefixes]"
+                        )
+                    })?;
+                    Ok((guild_id, prefix))
+                })
+                .collect::<Result<_, Error>>()?,
 
             timezone: {
                 let timezone =
@@ -92,72 +351,144 @@ impl Config {
             eris_socket: ini.get_from(Some("lrrbot"), "eris_socket").unwrap_or("eris.sock").into(),
 
             #[cfg(not(unix))]
-            lrrbot_port: Config::get_option_parsed(&ini, "socket_port")?.unwrap_or(49601),
+            lrrbot_port: Config::get_option_parsed(ini, "socket_port")?.unwrap_or(49601),
             #[cfg(not(unix))]
-            eris_port: Config::get_option_parsed(&ini, "eris_port")?.unwrap_or(49603),
+            eris_port: Config::get_option_parsed(ini, "eris_port")?.unwrap_or(49603),
+
+            dry_run: ini
+                .get_from(Some("eris"), "dry_run")
+                .map(|dry_run| {
+                    dry_run.trim().parse::<bool>().context("failed to parse \"dry_run\"")
+                })
+                .transpose()?
+                .unwrap_or(false),
+
+            rpc_keepalive: Config::get_duration(ini, "rpc_keepalive_interval")?
+                .filter(|interval| !interval.is_zero())
+                .map(|interval| {
+                    Ok::<_, Error>(KeepAlive {
+                        interval,
+                        timeout: Config::get_duration(ini, "rpc_keepalive_timeout")?
+                            .unwrap_or(Duration::from_secs(10)),
+                    })
+                })
+                .transpose()?,
+            rpc_max_in_flight: match ini
+                .get_from(Some("eris"), "rpc_max_in_flight")
+                .map(|max| {
+                    max.trim().parse::<usize>().context("failed to parse \"rpc_max_in_flight\"")
+                })
+                .transpose()?
+            {
+                Some(max) if max > Semaphore::MAX_PERMITS => anyhow::bail!(
+                    "\"rpc_max_in_flight\" can't be more than {}",
+                    Semaphore::MAX_PERMITS
+                ),
+                max => max.filter(|&max| max != 0),
+            },
 
-            twitch_client_id: ClientId::new(Config::get_option_required(&ini, "twitch_clientid")?),
+            twitch_client_id: ClientId::new(Config::get_option_required(ini, "twitch_clientid")?),
             twitch_client_secret: ClientSecret::new(Config::get_option_required(
-                &ini,
+                ini,
                 "twitch_clientsecret",
             )?),
+            twitch_eventsub: Config::get_eventsub_webhook(ini)?,
 
-            discord_botsecret: Config::get_option_required(&ini, "discord_botsecret")?,
+            discord_botsecret: Config::get_option_required(ini, "discord_botsecret")?,
 
             temp_channel_prefix: ini
                 .get_from(Some("lrrbot"), "discord_temp_channel_prefix")
                 .unwrap_or("[TEMP]")
                 .trim()
                 .into(),
-            announcements: Config::get_option_parsed(&ini, "discord_channel_announcements")?
+            announcements: Config::get_option_id(ini, "discord_channel_announcements")?
                 .unwrap_or(Id::new(322643668831961088)),
-            voice_category: Config::get_option_parsed(&ini, "discord_category_voice")?
+            voice_category: Config::get_option_id(ini, "discord_category_voice")?
                 .unwrap_or(Id::new(360796352357072896)),
-            mods_channel: Config::get_option_parsed(&ini, "discord_channel_mods")?
+            mods_channel: Config::get_option_id(ini, "discord_channel_mods")?
                 .unwrap_or(Id::new(289166968307712000u64)),
             general_channel: if let Some(channel_id) =
-                Config::get_option_parsed(&ini, "discord_channel_general")?
+                Config::get_option_id(ini, "discord_channel_general")?
             {
                 channel_id
             } else {
-                Config::get_option_parsed(&ini, "discord_serverid")?
+                Config::get_option_id(ini, "discord_serverid")?
                     .unwrap_or(Id::new(288920509272555520))
             },
-            lrr_videos_channel: Config::get_option_parsed(&ini, "discord_channel_lrr_videos")?,
-            guild: Config::get_option_parsed(&ini, "discord_serverid")?
+            lrr_videos_channel: Config::get_option_id(ini, "discord_channel_lrr_videos")?,
+            guild: Config::get_option_id(ini, "discord_serverid")?
                 .unwrap_or(Id::new(288920509272555520)),
 
-            mastodon_server: Self::get_option_parsed(&ini, "mastodon_server")?
+            mastodon_server: Self::get_option_parsed(ini, "mastodon_server")?
+                .map(base_url)
+                .transpose()
+                .context("invalid \"mastodon_server\"")?
                 .unwrap_or_else(|| Url::parse("https://mastodon.qrpth.eu/").unwrap()),
-            mastodon_users: ini
-                .section(Some("eris.mastodon"))
-                .map(|section| {
-                    section
-                        .iter()
-                        .map(|(name, channels)| {
-                            Ok((
-                                name.into(),
-                                channels
-                                    .split(',')
-                                    .map(|id| Ok(str::parse(id)?))
-                                    .collect::<Result<Vec<Id<ChannelMarker>>, Error>>()?,
-                            ))
-                        })
-                        .collect::<Result<HashMap<String, Vec<Id<ChannelMarker>>>, Error>>()
-                })
-                .transpose()?
-                .unwrap_or_default(),
+            mastodon_users: Config::get_user_channels(ini, "eris.mastodon", |name| {
+                Ok(String::from(name))
+            })?,
+            // The colons in DIDs have to be escaped (`did\:plc\:...`) because `:` also separates
+            // keys from values.
+            bsky_users: Config::get_user_channels(ini, "eris.bsky", str::parse)?,
 
             contact_spreadsheet: ini
                 .get_from(Some("lrrbot"), "discord_contact_spreadsheet")
                 .map(String::from),
 
+            quote_url: Self::get_option_parsed(ini, "quote_url")?
+                .unwrap_or_else(|| Url::parse("https://lrrbot.com/quotes/").unwrap()),
+            quote_search_url: Self::get_option_parsed(ini, "quote_search_url")?
+                .unwrap_or_else(|| Url::parse("https://lrrbot.com/quotes/search").unwrap()),
+
             influxdb: {
                 let url = ini.get_from(Some("eris"), "influxdb").map(String::from);
                 let db = ini.get_from(Some("eris"), "influxdb_database").map(String::from);
 
                 url.and_then(|url| db.map(|db| (url, db)))
             },
+            influxdb_flush_timeout: Config::get_duration(ini, "influxdb_flush_timeout")?
+                .unwrap_or(Duration::from_secs(5)),
+            prometheus_listen: ini
+                .get_from(Some("eris"), "prometheus_listen")
+                .map(|addr| {
+                    addr.trim()
+                        .parse::<SocketAddr>()
+                        .context("failed to parse \"prometheus_listen\"")
+                })
+                .transpose()?,
+            message_metrics_sample_rate: ini
+                .get_from(Some("eris"), "message_metrics_sample_rate")
+                .map(|rate| {
+                    rate.trim()
+                        .parse::<f64>()
+                        .map_err(Error::from)
+                        .and_then(|rate| {
+                            anyhow::ensure!(
+                                rate > 0.0 && rate <= 1.0,
+                                "sample rate must be greater than 0 and at most 1"
+                            );
+                            Ok(rate)
+                        })
+                        .context("failed to parse \"message_metrics_sample_rate\"")
+                })
+                .transpose()?
+                .unwrap_or(1.0),
+
+            google_subject: ini
+                .get_from(Some("eris"), "google_subject")
+                .map(str::trim)
+                .filter(|subject| !subject.is_empty())
+                .map(String::from),
+
+            spoiler_shows: ini
+                .get_from(Some("eris"), "spoiler_shows")
+                .map(str::trim)
+                .filter(|opt| !opt.is_empty())
+                .into_iter()
+                .flat_map(|opt| opt.split(','))
+                .map(str::trim)
+                .map(String::from)
+                .collect(),
 
             youtube_channels: ini
                 .get_from(Some("lrrbot"), "youtube_channels")
@@ -168,14 +499,334 @@ impl Config {
                 .map(str::trim)
                 .map(String::from)
                 .collect(),
+
+            youtube_digest_threshold: ini
+                .get_from(Some("eris"), "youtube_digest_threshold")
+                .map(|threshold| {
+                    threshold
+                        .trim()
+                        .parse::<usize>()
+                        .context("failed to parse \"youtube_digest_threshold\"")
+                })
+                .transpose()?,
+            youtube_shorts_detection: match ini
+                .get_from(Some("eris"), "youtube_shorts_detection")
+                .map(str::trim)
+            {
+                None => ShortsDetection::default(),
+                Some(detection) => match &detection.to_lowercase()[..] {
+                    "player" => ShortsDetection::Player,
+                    "probe" => ShortsDetection::Probe,
+                    _ => anyhow::bail!("unknown \"youtube_shorts_detection\" {detection:?}"),
+                },
+            },
+
+            autotopic_offline_threshold: ini
+                .get_from(Some("eris"), "autotopic_offline_threshold")
+                .map(|threshold| {
+                    threshold
+                        .trim()
+                        .parse::<usize>()
+                        .context("failed to parse \"autotopic_offline_threshold\"")
+                })
+                .transpose()?
+                .unwrap_or(1),
+            autotopic_fan_stream_horizon: match Config::get_duration(
+                ini,
+                "autotopic_fan_stream_horizon",
+            )? {
+                Some(horizon) if horizon.is_zero() => None,
+                Some(horizon) => Some(horizon),
+                None => Some(Duration::from_secs(24 * 60 * 60)),
+            },
+            autotopic_live: {
+                let default = LiveTopic::default();
+                let template = |option, default| {
+                    ini.get_from(Some("eris"), option)
+                        .map(str::trim)
+                        .filter(|template| !template.is_empty())
+                        .map_or(default, String::from)
+                };
+                let live = LiveTopic {
+                    game_and_show: template("autotopic_live", default.game_and_show),
+                    game: template("autotopic_live_game", default.game),
+                    show: template("autotopic_live_show", default.show),
+                    neither: template("autotopic_live_unknown", default.neither),
+                };
+                live.validate()?;
+                live
+            },
+
+            command_edit_window: Config::get_duration(ini, "command_edit_window")?
+                .filter(|window| !window.is_zero()),
+
+            help_intro: ini
+                .get_from(Some("eris"), "help_intro")
+                .map(str::trim)
+                .filter(|intro| !intro.is_empty())
+                .map(String::from)
+                .unwrap_or_else(|| {
+                    String::from(concat!(
+                        "To get help with an individual command, pass its name as an argument to ",
+                        "this command. Simple text response commands (like `!advice`) are not ",
+                        "listed here, for those see ",
+                        "[LRRbot's website](https://lrrbot.com/help#help-section-text).",
+                    ))
+                }),
+
+            money_format: {
+                let default = MoneyFormat::default();
+                // Values are trimmed, so a space has to be spelled out.
+                let option = |option: &str, default: String| match ini
+                    .get_from(Some("eris.money"), option)
+                {
+                    Some("space") => String::from(" "),
+                    Some(value) => String::from(value),
+                    None => default,
+                };
+                MoneyFormat {
+                    symbol: option("symbol", default.symbol),
+                    thousands_separator: option("thousands_separator", default.thousands_separator),
+                    decimal_separator: option("decimal_separator", default.decimal_separator),
+                }
+            },
+
+            user_agent: ini
+                .get_from(Some("eris"), "user_agent")
+                .map(str::trim)
+                .filter(|user_agent| !user_agent.is_empty())
+                .map(String::from),
+            presence: {
+                let default = PresenceActivity::default();
+                let kind = match ini.get_from(Some("eris"), "presence_type").map(str::trim) {
+                    None => default.kind,
+                    Some(kind) => match &kind.to_lowercase()[..] {
+                        "playing" => ActivityType::Playing,
+                        "listening" => ActivityType::Listening,
+                        "watching" => ActivityType::Watching,
+                        "competing" => ActivityType::Competing,
+                        _ => anyhow::bail!("unknown \"presence_type\" {kind:?}"),
+                    },
+                };
+                let presence = PresenceActivity {
+                    kind,
+                    text: ini
+                        .get_from(Some("eris"), "presence_text")
+                        .map(str::trim)
+                        .filter(|text| !text.is_empty())
+                        .map_or(default.text, String::from),
+                };
+                presence.format("", "").context("failed to parse \"presence_text\"")?;
+                presence
+            },
+
+            http_commands: Config::get_http_commands(ini)?,
+
+            topic_mirrors: Config::get_topic_mirrors(ini)?,
+
+            links: Config::get_links(ini)?,
         })
     }
 
+    /// The command prefix used in `guild_id`, or the global one for direct messages.
+    pub fn command_prefix_for(&self, guild_id: Option<Id<GuildMarker>>) -> &str {
+        guild_id
+            .and_then(|guild_id| self.guild_command_prefixes.get(&guild_id))
+            .unwrap_or(&self.command_prefix)
+    }
+
+    /// Parse a config from a string, filling in the required options that don't have defaults.
+    #[cfg(test)]
+    pub fn for_tests(extra: &str) -> Config {
+        Config::try_for_tests(extra).unwrap()
+    }
+
+    /// Like `for_tests` but for configs that might not be valid.
+    #[cfg(test)]
+    pub fn try_for_tests(extra: &str) -> Result<Config, Error> {
+        let ini = Ini::load_from_str(&format!(
+            "[lrrbot]\ntwitch_clientid=id\ntwitch_clientsecret=secret\ndiscord_botsecret=token\n{extra}"
+        ))?;
+        Config::from_ini(&ini)
+    }
+
     fn get_option_required(ini: &Ini, option: &str) -> Result<String, Error> {
-        Ok(ini
-            .get_from(Some("lrrbot"), option)
-            .ok_or_else(|| anyhow!("{:?} is missing", option))?
-            .into())
+        Config::get_secret(ini, option)?.ok_or_else(|| anyhow!("{:?} is missing", option))
+    }
+
+    /// Get a secret from `[lrrbot]`, either directly or from the file named by `<option>_file`,
+    /// like a mounted container secret. The file wins if both are set.
+    fn get_secret(ini: &Ini, option: &str) -> Result<Option<String>, Error> {
+        let file_option = format!("{option}_file");
+        match ini.get_from(Some("lrrbot"), &file_option) {
+            Some(path) => {
+                let path = path.trim();
+                let secret = std::fs::read_to_string(path)
+                    .with_context(|| format!("failed to read {file_option:?} ({path})"))?;
+                Ok(Some(secret.trim().into()))
+            }
+            None => Ok(ini.get_from(Some("lrrbot"), option).map(String::from)),
+        }
+    }
+
+    /// The EventSub webhook from `twitch_eventsub_callback` and `twitch_eventsub_listen` in
+    /// `[eris]` and the `twitch_eventsub_secret` secret. It's either all or none.
+    fn get_eventsub_webhook(ini: &Ini) -> Result<Option<EventSubWebhook>, Error> {
+        let callback = ini.get_from(Some("eris"), "twitch_eventsub_callback").map(str::trim);
+        let (callback, secret) =
+            match (callback, Config::get_secret(ini, "twitch_eventsub_secret")?) {
+                (Some(callback), Some(secret)) => (callback, secret),
+                (None, None) => return Ok(None),
+                (Some(_), None) => anyhow::bail!("\"twitch_eventsub_secret\" is missing"),
+                (None, Some(_)) => anyhow::bail!("\"twitch_eventsub_callback\" is missing"),
+            };
+
+        // Twitch only delivers to HTTPS on the default port and limits the length of the secret.
+        let callback =
+            Url::parse(callback).context("failed to parse \"twitch_eventsub_callback\"")?;
+        anyhow::ensure!(
+            callback.scheme() == "https" && callback.port().is_none(),
+            "the EventSub callback {callback} isn't an HTTPS URL on port 443"
+        );
+        anyhow::ensure!(
+            (10..=100).contains(&secret.len()) && secret.is_ascii(),
+            "\"twitch_eventsub_secret\" has to be 10 to 100 ASCII characters"
+        );
+
+        let listen = ini
+            .get_from(Some("eris"), "twitch_eventsub_listen")
+            .context("\"twitch_eventsub_listen\" is missing")?
+            .trim()
+            .parse::<SocketAddr>()
+            .context("failed to parse \"twitch_eventsub_listen\"")?;
+
+        Ok(Some(EventSubWebhook { callback, secret, listen }))
+    }
+
+    fn get_option_id<T>(ini: &Ini, option: &str) -> Result<Option<Id<T>>, Error> {
+        ini.get_from(Some("lrrbot"), option)
+            .map(|id| parse_id(id).with_context(|| format!("failed to parse {option:?}")))
+            .transpose()
+    }
+
+    /// Parse a section that maps accounts to comma-separated lists of channels.
+    fn get_user_channels<K: Eq + Hash>(
+        ini: &Ini,
+        section: &str,
+        parse_user: impl Fn(&str) -> Result<K, Error>,
+    ) -> Result<HashMap<K, Vec<Id<ChannelMarker>>>, Error> {
+        let Some(section_data) = ini.section(Some(section)) else {
+            return Ok(HashMap::new());
+        };
+
+        section_data
+            .iter()
+            .map(|(user, channels)| {
+                let user = parse_user(user)
+                    .with_context(|| format!("failed to parse the user {user:?} in [{section}]"))?;
+                let channels = channels
+                    .split(',')
+                    .map(parse_id)
+                    .collect::<Result<Vec<_>, Error>>()
+                    .with_context(|| format!("failed to parse the channels in [{section}]"))?;
+                Ok((user, channels))
+            })
+            .collect()
+    }
+
+    /// Parse the `[eris.http.<name>]` sections. The values are picked out of the JSON with
+    /// `field.<variable>=<JSON pointer>` options.
+    fn get_http_commands(ini: &Ini) -> Result<Vec<HttpCommand>, Error> {
+        ini.iter()
+            .filter_map(|(section, properties)| {
+                Some((section?.strip_prefix("eris.http.")?, section?, properties))
+            })
+            .map(|(name, section, properties)| {
+                let option = |option: &str| {
+                    properties
+                        .get(option)
+                        .map(String::from)
+                        .ok_or_else(|| anyhow!("{option:?} is missing from [{section}]"))
+                };
+                Ok(HttpCommand {
+                    name: name.into(),
+                    url: option("url")?,
+                    fields: properties
+                        .iter()
+                        .filter_map(|(key, pointer)| {
+                            Some((key.strip_prefix("field.")?.into(), pointer.into()))
+                        })
+                        .collect(),
+                    response: option("response")?,
+                    summary: properties.get("summary").map(String::from),
+                })
+            })
+            .collect()
+    }
+
+    /// Parse the `[eris.topic_mirrors]` section that maps channels to their maximum topic
+    /// lengths. An empty length means the default. Voice channels have no topic, so mirroring to
+    /// them fails.
+    fn get_topic_mirrors(ini: &Ini) -> Result<Vec<TopicMirror>, Error> {
+        let Some(section) = ini.section(Some("eris.topic_mirrors")) else {
+            return Ok(vec![]);
+        };
+
+        section
+            .iter()
+            .map(|(channel_id, max_len)| {
+                let channel_id = parse_id(channel_id).with_context(|| {
+                    format!("failed to parse the channel {channel_id:?} in [eris.topic_mirrors]")
+                })?;
+                let max_len = Some(max_len.trim())
+                    .filter(|max_len| !max_len.is_empty())
+                    .map(str::parse)
+                    .transpose()
+                    .with_context(|| format!("failed to parse the topic length of {channel_id}"))?;
+                if let Some(max_len) = max_len {
+                    // Topics are at most 1024 characters and have to fit at least the `[…]` marker.
+                    anyhow::ensure!(
+                        (3..=1024).contains(&max_len),
+                        "the topic length of {channel_id} must be between 3 and 1024"
+                    );
+                }
+                Ok((channel_id, max_len))
+            })
+            .collect()
+    }
+
+    /// Parse the `[eris.links]` section that maps names to HTTP(S) URLs, in the order they're in
+    /// the file.
+    fn get_links(ini: &Ini) -> Result<Vec<(String, Url)>, Error> {
+        let Some(section) = ini.section(Some("eris.links")) else {
+            return Ok(vec![]);
+        };
+
+        section
+            .iter()
+            .map(|(name, url)| {
+                let url = Url::parse(url.trim()).with_context(|| {
+                    format!("failed to parse the URL of {name:?} in [eris.links]")
+                })?;
+                if !matches!(url.scheme(), "http" | "https") {
+                    return Err(anyhow!("the link {name:?} in [eris.links] is not an HTTP(S) URL"));
+                }
+                Ok((name.into(), url))
+            })
+            .collect()
+    }
+
+    /// Read a number of seconds from the `[eris]` section.
+    fn get_duration(ini: &Ini, option: &str) -> Result<Option<Duration>, Error> {
+        ini.get_from(Some("eris"), option)
+            .map(|secs| {
+                secs.trim()
+                    .parse::<f64>()
+                    .map_err(Error::from)
+                    .and_then(|secs| Ok(Duration::try_from_secs_f64(secs)?))
+                    .with_context(|| format!("failed to parse {option:?}"))
+            })
+            .transpose()
     }
 
     fn get_option_parsed<T>(ini: &Ini, option: &str) -> Result<Option<T>, Error>
@@ -190,3 +841,670 @@ impl Config {
         }
     }
 }
+
+/// Copy the keys of `overrides` into `base`, replacing any that are already there.
+fn merge_ini(base: &mut Ini, overrides: Ini) {
+    for (section, properties) in overrides {
+        let merged = base.entry(section).or_insert_with(Default::default);
+        for (key, value) in properties {
+            merged.insert(key, value);
+        }
+    }
+}
+
+/// Reload the config files on `SIGHUP` and publish the config to the tasks that support reloading.
+///
+/// A config that fails to load is logged and ignored.
+pub async fn reload_on_sighup(
+    mut running: Receiver<bool>,
+    paths: Vec<PathBuf>,
+    config_tx: Sender<Arc<Config>>,
+) {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::hangup()) {
+            Ok(mut hangup) => loop {
+                tokio::select! {
+                    _ = running.changed() => return,
+                    _ = hangup.recv() => match Config::load_from_files(&paths) {
+                        Ok(config) => {
+                            info!(?paths, "config reloaded");
+                            config_tx.send_replace(Arc::new(config));
+                        }
+                        Err(error) => error!(?error, "failed to reload the config file"),
+                    },
+                }
+            },
+            Err(error) => error!(?error, "failed to listen for SIGHUP"),
+        }
+    }
+
+    #[cfg(not(unix))]
+    let _ = (paths, config_tx);
+
+    let _ = running.changed().await;
+}
+
+/// A Bluesky account, identified either by its handle or by its DID.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum AtIdentifier {
+    Handle(String),
+    Did(String),
+}
+
+impl FromStr for AtIdentifier {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        static DID: OnceLock<Regex> = OnceLock::new();
+        static HANDLE: OnceLock<Regex> = OnceLock::new();
+
+        let s = s.trim();
+        if s.starts_with("did:") {
+            let did = DID.get_or_init(|| {
+                Regex::new(r"^did:[a-z]+:[a-zA-Z0-9._:%-]*[a-zA-Z0-9._-]$").unwrap()
+            });
+            anyhow::ensure!(s.len() <= 2048 && did.is_match(s), "{s:?} is not a valid DID");
+            Ok(AtIdentifier::Did(s.into()))
+        } else {
+            let s = s.strip_prefix('@').unwrap_or(s);
+            let handle = HANDLE.get_or_init(|| {
+                Regex::new(concat!(
+                    r"^([a-zA-Z0-9]([a-zA-Z0-9-]{0,61}[a-zA-Z0-9])?\.)+",
+                    r"[a-zA-Z]([a-zA-Z0-9-]{0,61}[a-zA-Z0-9])?$",
+                ))
+                .unwrap()
+            });
+            anyhow::ensure!(s.len() <= 253 && handle.is_match(s), "{s:?} is not a valid handle");
+            Ok(AtIdentifier::Handle(s.to_ascii_lowercase()))
+        }
+    }
+}
+
+/// Parse a Discord ID that is either a bare snowflake or wrapped in a mention (`<#…>`, `<@…>`,
+/// `<@!…>`, `<@&…>`) as copied from the Discord client.
+fn parse_id<T>(s: &str) -> Result<Id<T>, Error> {
+    let s = s.trim();
+    let id = match s.strip_prefix('<').and_then(|s| s.strip_suffix('>')) {
+        Some(mention) => ["#", "@!", "@&", "@"]
+            .into_iter()
+            .find_map(|prefix| mention.strip_prefix(prefix))
+            .ok_or_else(|| anyhow!("{s:?} is not a channel, user, or role mention"))?,
+        None => s,
+    };
+    Ok(id.parse()?)
+}
+
+/// Trim a command prefix and make sure there's something left. Prefixes are stripped from the
+/// message as is rather than matched as part of the command regex, so any non-blank string works,
+/// but an empty one would turn every message into a command.
+fn command_prefix(s: &str) -> Result<String, Error> {
+    let s = s.trim();
+    anyhow::ensure!(!s.is_empty(), "the command prefix can't be empty or whitespace");
+    Ok(String::from(s))
+}
+
+/// Check that `url` is an HTTP(S) URL and make sure its path ends in a slash, so that
+/// [`Url::join`] appends to it instead of replacing the last path segment.
+fn base_url(mut url: Url) -> Result<Url, Error> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(anyhow!("{url} is not an HTTP(S) URL"));
+    }
+    if !url.path().ends_with('/') {
+        url.set_path(&format!("{}/", url.path()));
+    }
+    Ok(url)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use std::time::Duration;
+
+    use tokio::sync::Semaphore;
+    use twilight_model::gateway::presence::ActivityType;
+    use twilight_model::id::marker::ChannelMarker;
+    use twilight_model::id::Id;
+    use url::Url;
+
+    use super::{
+        parse_id, AtIdentifier, Config, EventSubWebhook, HttpCommand, LiveTopic, MoneyFormat,
+        PresenceActivity, ShortsDetection,
+    };
+    use crate::aiomas::keepalive::KeepAlive;
+
+    #[test]
+    fn bare_id() {
+        assert_eq!(
+            parse_id::<ChannelMarker>("322643668831961088").unwrap(),
+            Id::new(322643668831961088)
+        );
+        assert_eq!(
+            parse_id::<ChannelMarker>(" 322643668831961088 ").unwrap(),
+            Id::new(322643668831961088)
+        );
+    }
+
+    #[test]
+    fn mention() {
+        assert_eq!(
+            parse_id::<ChannelMarker>("<#322643668831961088>").unwrap(),
+            Id::new(322643668831961088)
+        );
+        assert_eq!(
+            parse_id::<ChannelMarker>("<@!322643668831961088>").unwrap(),
+            Id::new(322643668831961088)
+        );
+        assert_eq!(
+            parse_id::<ChannelMarker>("<@322643668831961088>").unwrap(),
+            Id::new(322643668831961088)
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(parse_id::<ChannelMarker>("<:emoji:322643668831961088>").is_err());
+        assert!(parse_id::<ChannelMarker>("<#general>").is_err());
+        assert!(parse_id::<ChannelMarker>("0").is_err());
+    }
+
+    #[test]
+    fn money_format() {
+        let config = Config::for_tests("");
+        assert_eq!(config.money_format, MoneyFormat::default());
+        assert_eq!(config.money_format.format(1234.5), "$1,234.50");
+        assert_eq!(config.money_format.format(1234567.891), "$1,234,567.89");
+
+        let config = Config::for_tests("[eris.money]\nsymbol=CA$\n");
+        assert_eq!(config.money_format.format(1234.56), "CA$1,234.56");
+
+        let config = Config::for_tests(concat!(
+            "[eris.money]\n",
+            "symbol=€\n",
+            "thousands_separator=space\n",
+            "decimal_separator=,\n",
+        ));
+        assert_eq!(config.money_format.format(1234567.89), "€1 234 567,89");
+    }
+
+    #[test]
+    fn http_commands() {
+        assert_eq!(Config::for_tests("").http_commands, vec![]);
+
+        let config = Config::for_tests(concat!(
+            "[eris.http.weather]\n",
+            "url=https://wttr.in/{args}?format=j1\n",
+            "field.temperature=/current_condition/0/temp_C\n",
+            "field.conditions=/current_condition/0/weatherDesc/0/value\n",
+            "response={args}: {conditions}, {temperature} °C\n",
+            "summary=Post the current weather\n",
+        ));
+        assert_eq!(
+            config.http_commands,
+            vec![HttpCommand {
+                name: "weather".into(),
+                url: "https://wttr.in/{args}?format=j1".into(),
+                fields: vec![
+                    ("temperature".into(), "/current_condition/0/temp_C".into()),
+                    ("conditions".into(), "/current_condition/0/weatherDesc/0/value".into()),
+                ],
+                response: "{args}: {conditions}, {temperature} °C".into(),
+                summary: Some("Post the current weather".into()),
+            }]
+        );
+
+        assert!(Config::try_for_tests(
+            "[eris.http.weather]\nurl=https://wttr.in/{args}?format=j1\n"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn topic_mirrors() {
+        assert_eq!(Config::for_tests("").topic_mirrors, vec![]);
+        assert_eq!(
+            Config::for_tests(
+                "[eris.topic_mirrors]\n322643668831961088=\n<#289166968307712000>=500\n"
+            )
+            .topic_mirrors,
+            vec![(Id::new(322643668831961088), None), (Id::new(289166968307712000), Some(500))]
+        );
+
+        for max_len in [3, 1024] {
+            let extra = format!("[eris.topic_mirrors]\n322643668831961088={max_len}\n");
+            assert_eq!(Config::for_tests(&extra).topic_mirrors[0].1, Some(max_len));
+        }
+        for max_len in [0, 2, 1025] {
+            let extra = format!("[eris.topic_mirrors]\n322643668831961088={max_len}\n");
+            assert!(Config::try_for_tests(&extra).is_err(), "{max_len}");
+        }
+    }
+
+    #[test]
+    fn override_file() {
+        let dir = std::env::temp_dir();
+        let base = dir.join(format!("eris-config-test-{}-base.conf", std::process::id()));
+        let local = dir.join(format!("eris-config-test-{}-local.conf", std::process::id()));
+        std::fs::write(
+            &base,
+            concat!(
+                "[lrrbot]\ntwitch_clientid=id\ntwitch_clientsecret=secret\n",
+                "discord_botsecret=placeholder\ncommandprefix=!\n",
+            ),
+        )
+        .unwrap();
+        std::fs::write(&local, "[lrrbot]\ndiscord_botsecret=token\n[eris]\ngoogle_subject=bot\n")
+            .unwrap();
+
+        let config = Config::load_from_files(&[&base, &local]);
+        let reversed = Config::load_from_files(&[&local, &base]);
+        let missing = Config::load_from_files(&[&base, &dir.join("eris-config-test-missing.conf")]);
+        let single = Config::load_from_file(&base);
+        std::fs::remove_file(&base).unwrap();
+        std::fs::remove_file(&local).unwrap();
+
+        let config = config.unwrap();
+        assert_eq!(config.discord_botsecret, "token");
+        assert_eq!(config.twitch_client_id.as_str(), "id");
+        assert_eq!(config.command_prefix, "!");
+        assert_eq!(config.google_subject.as_deref(), Some("bot"));
+        assert_eq!(reversed.unwrap().discord_botsecret, "placeholder");
+        assert!(missing.is_err());
+        assert_eq!(single.unwrap().discord_botsecret, "placeholder");
+    }
+
+    #[test]
+    fn secret_files() {
+        let dir = std::env::temp_dir();
+        let token = dir.join(format!("eris-config-test-{}-token", std::process::id()));
+        let postgres = dir.join(format!("eris-config-test-{}-postgres", std::process::id()));
+        std::fs::write(&token, "from-file\n").unwrap();
+        std::fs::write(&postgres, "postgres://eris:hunter2@db/lrrbot").unwrap();
+
+        let load = |extra: &str| {
+            ini::Ini::load_from_str(&format!(
+                "[lrrbot]\ntwitch_clientid=id\ntwitch_clientsecret=secret\n{extra}"
+            ))
+            .map_err(anyhow::Error::from)
+            .and_then(|ini| Config::from_ini(&ini))
+        };
+        let from_file = load(&format!(
+            "discord_botsecret_file={}\npostgres_file={}\n",
+            token.display(),
+            postgres.display()
+        ));
+        let both = load(&format!(
+            "discord_botsecret=inline\ndiscord_botsecret_file={}\n",
+            token.display()
+        ));
+        let missing = load(&format!(
+            "discord_botsecret_file={}\n",
+            dir.join("eris-config-test-missing-token").display()
+        ));
+        std::fs::remove_file(&token).unwrap();
+        std::fs::remove_file(&postgres).unwrap();
+
+        let from_file = from_file.unwrap();
+        assert_eq!(from_file.discord_botsecret, "from-file");
+        assert_eq!(from_file.database_url, "postgres://eris:hunter2@db/lrrbot");
+        assert_eq!(from_file.twitch_client_secret.as_str(), "secret");
+        assert_eq!(both.unwrap().discord_botsecret, "from-file");
+        assert!(missing.is_err());
+        assert_eq!(Config::for_tests("").database_url, "postgres:///lrrbot");
+    }
+
+    #[test]
+    fn links() {
+        assert_eq!(Config::for_tests("").links, vec![]);
+
+        let config = Config::for_tests(concat!(
+            "[eris.links]\n",
+            "Website=https://loadingreadyrun.com/\n",
+            "Schedule = https://lrr.cc/schedule\n",
+            "Store=https://store.loadingreadyrun.com\n",
+        ));
+        let links = config
+            .links
+            .iter()
+            .map(|(name, url)| (name.as_str(), url.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            links,
+            [
+                ("Website", "https://loadingreadyrun.com/"),
+                ("Schedule", "https://lrr.cc/schedule"),
+                ("Store", "https://store.loadingreadyrun.com/"),
+            ]
+        );
+
+        for link in ["Website=loadingreadyrun.com", "Email=mailto:contact@loadingreadyrun.com"] {
+            assert!(Config::try_for_tests(&format!("[eris.links]\n{link}\n")).is_err(), "{link}");
+        }
+    }
+
+    #[test]
+    fn google_subject() {
+        assert_eq!(Config::for_tests("").google_subject, None);
+        assert_eq!(Config::for_tests("[eris]\ngoogle_subject=\n").google_subject, None);
+        assert_eq!(
+            Config::for_tests("[eris]\ngoogle_subject=bot@loadingreadyrun.com\n")
+                .google_subject
+                .as_deref(),
+            Some("bot@loadingreadyrun.com")
+        );
+    }
+
+    #[test]
+    fn youtube_digest_threshold() {
+        assert_eq!(Config::for_tests("").youtube_digest_threshold, None);
+        assert_eq!(
+            Config::for_tests("[eris]\nyoutube_digest_threshold=5\n").youtube_digest_threshold,
+            Some(5)
+        );
+    }
+
+    #[test]
+    fn youtube_shorts_detection() {
+        assert_eq!(Config::for_tests("").youtube_shorts_detection, ShortsDetection::Player);
+        assert_eq!(
+            Config::for_tests("[eris]\nyoutube_shorts_detection=Probe\n").youtube_shorts_detection,
+            ShortsDetection::Probe
+        );
+        assert!(Config::try_for_tests("[eris]\nyoutube_shorts_detection=hashtag\n").is_err());
+    }
+
+    #[test]
+    fn twitch_eventsub() {
+        let load = Config::try_for_tests;
+        assert_eq!(load("").unwrap().twitch_eventsub, None);
+        assert_eq!(
+            load(concat!(
+                "twitch_eventsub_secret=s3cRe7-eris-tests\n",
+                "[eris]\ntwitch_eventsub_callback=https://eris.example.com:443/eventsub\n",
+                "twitch_eventsub_listen=127.0.0.1:8080\n",
+            ))
+            .unwrap()
+            .twitch_eventsub,
+            Some(EventSubWebhook {
+                callback: Url::parse("https://eris.example.com/eventsub").unwrap(),
+                secret: String::from("s3cRe7-eris-tests"),
+                listen: SocketAddr::from(([127, 0, 0, 1], 8080)),
+            })
+        );
+
+        for extra in [
+            "twitch_eventsub_secret=s3cRe7-eris-tests\n",
+            "[eris]\ntwitch_eventsub_callback=https://eris.example.com/eventsub\n",
+            "twitch_eventsub_secret=s3cRe7-eris-tests\n[eris]\ntwitch_eventsub_callback=eventsub\n",
+            concat!(
+                "twitch_eventsub_secret=s3cRe7-eris-tests\n",
+                "[eris]\ntwitch_eventsub_callback=http://eris.example.com/eventsub\n",
+            ),
+            concat!(
+                "twitch_eventsub_secret=s3cRe7-eris-tests\n",
+                "[eris]\ntwitch_eventsub_callback=https://eris.example.com:8443/eventsub\n",
+            ),
+            concat!(
+                "twitch_eventsub_secret=short\n",
+                "[eris]\ntwitch_eventsub_callback=https://eris.example.com/eventsub\n",
+                "twitch_eventsub_listen=127.0.0.1:8080\n",
+            ),
+            concat!(
+                "twitch_eventsub_secret=s3cRe7-eris-tests\n",
+                "[eris]\ntwitch_eventsub_callback=https://eris.example.com/eventsub\n",
+            ),
+            concat!(
+                "twitch_eventsub_secret=s3cRe7-eris-tests\n",
+                "[eris]\ntwitch_eventsub_callback=https://eris.example.com/eventsub\n",
+                "twitch_eventsub_listen=8080\n",
+            ),
+        ] {
+            assert!(load(extra).is_err(), "{extra:?}");
+        }
+    }
+
+    #[test]
+    fn autotopic_offline_threshold() {
+        assert_eq!(Config::for_tests("").autotopic_offline_threshold, 1);
+        assert_eq!(
+            Config::for_tests("[eris]\nautotopic_offline_threshold=3\n")
+                .autotopic_offline_threshold,
+            3
+        );
+    }
+
+    #[test]
+    fn autotopic_fan_stream_horizon() {
+        assert_eq!(
+            Config::for_tests("").autotopic_fan_stream_horizon,
+            Some(Duration::from_secs(24 * 60 * 60))
+        );
+        assert_eq!(
+            Config::for_tests("[eris]\nautotopic_fan_stream_horizon=3600\n")
+                .autotopic_fan_stream_horizon,
+            Some(Duration::from_secs(3600))
+        );
+        assert_eq!(
+            Config::for_tests("[eris]\nautotopic_fan_stream_horizon=0\n")
+                .autotopic_fan_stream_horizon,
+            None
+        );
+    }
+
+    #[test]
+    fn dry_run() {
+        assert!(!Config::for_tests("").dry_run);
+        assert!(Config::for_tests("[eris]\ndry_run=true\n").dry_run);
+        assert!(!Config::for_tests("[eris]\ndry_run=false\n").dry_run);
+
+        assert!(Config::try_for_tests("[eris]\ndry_run=yes\n").is_err());
+    }
+
+    #[test]
+    fn rpc_keepalive() {
+        assert_eq!(Config::for_tests("").rpc_keepalive, None);
+        assert_eq!(Config::for_tests("[eris]\nrpc_keepalive_interval=0\n").rpc_keepalive, None);
+        assert_eq!(
+            Config::for_tests("[eris]\nrpc_keepalive_interval=30\n").rpc_keepalive,
+            Some(KeepAlive { interval: Duration::from_secs(30), timeout: Duration::from_secs(10) })
+        );
+        assert_eq!(
+            Config::for_tests("[eris]\nrpc_keepalive_interval=30\nrpc_keepalive_timeout=2.5\n")
+                .rpc_keepalive,
+            Some(KeepAlive {
+                interval: Duration::from_secs(30),
+                timeout: Duration::from_millis(2500),
+            })
+        );
+    }
+
+    #[test]
+    fn rpc_max_in_flight() {
+        assert_eq!(Config::for_tests("").rpc_max_in_flight, None);
+        assert_eq!(Config::for_tests("[eris]\nrpc_max_in_flight=0\n").rpc_max_in_flight, None);
+        assert_eq!(Config::for_tests("[eris]\nrpc_max_in_flight=8\n").rpc_max_in_flight, Some(8));
+
+        let max = Semaphore::MAX_PERMITS;
+        assert_eq!(
+            Config::for_tests(&format!("[eris]\nrpc_max_in_flight={max}\n")).rpc_max_in_flight,
+            Some(max)
+        );
+        let too_many = max + 1;
+        assert!(Config::try_for_tests(&format!("[eris]\nrpc_max_in_flight={too_many}\n")).is_err());
+        assert!(Config::try_for_tests("[eris]\nrpc_max_in_flight=-1\n").is_err());
+    }
+
+    #[test]
+    fn presence() {
+        let config = Config::for_tests("");
+        assert_eq!(config.presence, PresenceActivity::default());
+        assert_eq!(config.presence.format("!", "1.2.3").unwrap(), "!help || v1.2.3");
+        assert_eq!(config.user_agent, None);
+
+        let config = Config::for_tests(concat!(
+            "[eris]\npresence_type=Watching\npresence_text=for {prefix}help\n",
+            "user_agent=eris-staging\n",
+        ));
+        assert_eq!(config.presence.kind, ActivityType::Watching);
+        assert_eq!(config.presence.format("?", "1.2.3").unwrap(), "for ?help");
+        assert_eq!(config.user_agent.as_deref(), Some("eris-staging"));
+
+        let braces =
+            PresenceActivity { kind: ActivityType::Playing, text: String::from("{{{prefix}}}") };
+        assert_eq!(braces.format("!", "1.2.3").unwrap(), "{!}");
+
+        for text in ["{prefix} {name}", "{prefix", "v{version}}", "{}"] {
+            let presence =
+                PresenceActivity { kind: ActivityType::Playing, text: String::from(text) };
+            assert!(presence.format("!", "1.2.3").is_err(), "{text}");
+        }
+
+        for extra in ["presence_text={verison}\n", "presence_type=streaming\n"] {
+            assert!(Config::try_for_tests(&format!("[eris]\n{extra}")).is_err(), "{extra}");
+        }
+    }
+
+    #[test]
+    fn command_prefix() {
+        let load = Config::try_for_tests;
+        assert_eq!(load("commandprefix = eris \n").unwrap().command_prefix, "eris");
+
+        for extra in [
+            "commandprefix=\n",
+            "commandprefix=   \n",
+            "[eris.command_prefixes]\n288920509272555520=\n",
+            "[eris.command_prefixes]\n288920509272555520=  \n",
+        ] {
+            assert!(load(extra).is_err(), "{extra:?}");
+        }
+    }
+
+    #[test]
+    fn live_topic() {
+        let live = Config::for_tests("").autotopic_live;
+        assert_eq!(live, LiveTopic::default());
+        let games = [(Some("Magic"), Some("Friday Nights")), (Some("Magic"), None)];
+        let shows = [(None, Some("Friday Nights")), (None, None)];
+        let formatted = games
+            .into_iter()
+            .chain(shows)
+            .map(|(game, show)| live.format("loadingreadyrun", game, show).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            formatted,
+            [
+                "Now live: Magic on Friday Nights.",
+                "Now live: Magic.",
+                "Now live: Friday Nights.",
+                "Now live: something?"
+            ]
+        );
+
+        let live = Config::for_tests(concat!(
+            "[eris]\nautotopic_live={show} is live with {game} on twitch.tv/{channel}\n",
+            "autotopic_live_game={game} on twitch.tv/{channel}\n",
+            "autotopic_live_show={show} on twitch.tv/{channel}\n",
+            "autotopic_live_unknown=twitch.tv/{channel}\n",
+        ))
+        .autotopic_live;
+        let formatted = games
+            .into_iter()
+            .chain(shows)
+            .map(|(game, show)| live.format("loadingreadyrun", game, show).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            formatted,
+            [
+                "Friday Nights is live with Magic on twitch.tv/loadingreadyrun",
+                "Magic on twitch.tv/loadingreadyrun",
+                "Friday Nights on twitch.tv/loadingreadyrun",
+                "twitch.tv/loadingreadyrun"
+            ]
+        );
+
+        for extra in [
+            "autotopic_live={game} on {shwo}\n",
+            "autotopic_live_game={game} on {show}\n",
+            "autotopic_live_show={game}\n",
+            "autotopic_live_unknown={channel\n",
+        ] {
+            assert!(Config::try_for_tests(&format!("[eris]\n{extra}")).is_err(), "{extra}");
+        }
+    }
+
+    #[test]
+    fn prometheus_listen() {
+        assert_eq!(Config::for_tests("").prometheus_listen, None);
+        assert_eq!(
+            Config::for_tests("[eris]\nprometheus_listen=127.0.0.1:9464\n").prometheus_listen,
+            Some(SocketAddr::from(([127, 0, 0, 1], 9464)))
+        );
+
+        assert!(Config::try_for_tests("[eris]\nprometheus_listen=9464\n").is_err());
+    }
+
+    #[test]
+    fn message_metrics_sample_rate() {
+        assert_eq!(Config::for_tests("").message_metrics_sample_rate, 1.0);
+        assert_eq!(
+            Config::for_tests("[eris]\nmessage_metrics_sample_rate=0.25\n")
+                .message_metrics_sample_rate,
+            0.25
+        );
+
+        for rate in ["0", "-0.5", "1.5", "half"] {
+            let extra = format!("[eris]\nmessage_metrics_sample_rate={rate}\n");
+            assert!(Config::try_for_tests(&extra).is_err(), "{rate}");
+        }
+    }
+
+    #[test]
+    fn bsky_users() {
+        let config = Config::for_tests(concat!(
+            "[eris.bsky]\n",
+            "@LoadingReadyRun.com=<#322643668831961088>\n",
+            "did\\:plc\\:z72i7hdynmk6r22z27h6tvur=322643668831961088,288920509272555520\n",
+        ));
+        assert_eq!(
+            config.bsky_users.get(&AtIdentifier::Handle(String::from("loadingreadyrun.com"))),
+            Some(&vec![Id::new(322643668831961088)])
+        );
+        assert_eq!(
+            config
+                .bsky_users
+                .get(&AtIdentifier::Did(String::from("did:plc:z72i7hdynmk6r22z27h6tvur"))),
+            Some(&vec![Id::new(322643668831961088), Id::new(288920509272555520)])
+        );
+    }
+
+    #[test]
+    fn malformed_at_identifier() {
+        assert!("loadingreadyrun".parse::<AtIdentifier>().is_err());
+        assert!("loading_ready_run.com".parse::<AtIdentifier>().is_err());
+        assert!("-lrr.com".parse::<AtIdentifier>().is_err());
+        assert!("lrr.123".parse::<AtIdentifier>().is_err());
+        assert!("did:PLC:z72i7hdynmk6r22z27h6tvur".parse::<AtIdentifier>().is_err());
+        assert!("did:plc:".parse::<AtIdentifier>().is_err());
+        assert!("did:plc:abc:".parse::<AtIdentifier>().is_err());
+    }
+
+    #[test]
+    fn mastodon_server() {
+        let config = Config::for_tests("mastodon_server=https://example.com/mastodon");
+        assert_eq!(config.mastodon_server.as_str(), "https://example.com/mastodon/");
+        assert_eq!(
+            config.mastodon_server.join("api/v2/search").unwrap().as_str(),
+            "https://example.com/mastodon/api/v2/search"
+        );
+
+        let config = Config::for_tests("mastodon_server=https://example.com");
+        assert_eq!(
+            config.mastodon_server.join("api/v2/search").unwrap().as_str(),
+            "https://example.com/api/v2/search"
+        );
+
+        assert!(Config::try_for_tests("mastodon_server=ftp://example.com/").is_err());
+    }
+}
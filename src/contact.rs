@@ -1,15 +1,19 @@
+use std::collections::HashSet;
 use std::sync::Arc;
 use std::time::Duration;
 
 use anyhow::{Context, Error};
-use chrono::NaiveDate;
+use chrono::{DateTime, NaiveDate, Utc};
 use google_sheets4::api::{
     BatchUpdateSpreadsheetRequest, CellData, CreateDeveloperMetadataRequest, DeveloperMetadata,
     DeveloperMetadataLocation, DimensionRange, Request, Spreadsheet,
 };
+use google_sheets4::common::{Response, Retry};
+use google_sheets4::hyper::header::RETRY_AFTER;
+use google_sheets4::hyper::StatusCode;
 use google_sheets4::hyper_rustls::HttpsConnector;
 use google_sheets4::hyper_util::client::legacy::connect::HttpConnector;
-use google_sheets4::Sheets;
+use google_sheets4::{Delegate, Sheets};
 use tokio::sync::watch::Receiver;
 use tracing::{error, info};
 use twilight_http::Client as DiscordClient;
@@ -18,11 +22,55 @@ use twilight_util::builder::embed::{EmbedAuthorBuilder, EmbedBuilder, EmbedFoote
 use twilight_validate::embed::{AUTHOR_NAME_LENGTH, DESCRIPTION_LENGTH};
 
 use crate::config::Config;
-use crate::shorten::{shorten, split_to_parts};
+use crate::shorten::{count_parts, shorten, split_to_parts};
 use crate::tz::Tz;
 
 const SENT_KEY: &str = "lrrbot.sent";
 
+/// How many times a rate limited or unavailable request is retried before giving up.
+const MAX_RETRIES: u32 = 5;
+/// The delay before the first retry. Doubled for each subsequent retry.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+/// The longest a single retry waits, even if `Retry-After` asks for longer.
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Retries requests that failed with 429 or 503 with exponential backoff, honoring `Retry-After`.
+#[derive(Default)]
+struct Backoff {
+    retries: u32,
+}
+
+impl Backoff {
+    /// The delay requested by the `Retry-After` header, either in seconds or as an HTTP date.
+    fn retry_after(response: &Response, now: DateTime<Utc>) -> Option<Duration> {
+        let value = response.headers().get(RETRY_AFTER)?.to_str().ok()?.trim();
+        if let Ok(secs) = value.parse::<u64>() {
+            return Some(Duration::from_secs(secs));
+        }
+        let date = DateTime::parse_from_rfc2822(value).ok()?;
+        Some((date.with_timezone(&Utc) - now).to_std().unwrap_or(Duration::ZERO))
+    }
+}
+
+impl Delegate for Backoff {
+    fn http_failure(&mut self, response: &Response, _: Option<&serde_json::Value>) -> Retry {
+        let retryable = matches!(
+            response.status(),
+            StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE
+        );
+        if !retryable || self.retries >= MAX_RETRIES {
+            return Retry::Abort;
+        }
+
+        let delay = Self::retry_after(response, Utc::now())
+            .unwrap_or(INITIAL_BACKOFF * 2u32.pow(self.retries))
+            .min(MAX_BACKOFF);
+        self.retries += 1;
+        info!(status = ?response.status(), ?delay, retries = self.retries, "Retrying the request");
+        Retry::After(delay)
+    }
+}
+
 pub async fn post_messages(
     mut running: Receiver<bool>,
     config: Arc<Config>,
@@ -35,12 +83,14 @@ pub async fn post_messages(
     };
 
     let mut timer = tokio::time::interval(Duration::from_secs(60));
+    // The rows a dry run has logged, so that they're only logged once.
+    let mut dry_run_rows = HashSet::new();
 
     loop {
         tokio::select! {
             _ = running.changed() => break,
             _ = timer.tick() => {
-                if let Err(error) = inner(&config, &discord, &sheets).await {
+                if let Err(error) = inner(&config, &discord, &sheets, &mut dry_run_rows).await {
                     error!(?error, "Failed to post new messages");
                 }
             },
@@ -72,13 +122,18 @@ fn extract_string(cell: &CellData) -> Option<&str> {
     cell.effective_value.as_ref()?.string_value.as_deref()
 }
 
-fn find_unsent_rows(spreadsheet: &Spreadsheet) -> Option<(i32, Vec<Entry>)> {
-    let tz = spreadsheet
+/// The time zone of the timestamps in `spreadsheet`, or UTC if it's missing or unknown.
+pub fn spreadsheet_timezone(spreadsheet: &Spreadsheet) -> Tz {
+    spreadsheet
         .properties
         .as_ref()
         .and_then(|prop| prop.time_zone.as_deref())
         .and_then(|tz| Tz::from_name(tz).ok())
-        .unwrap_or_else(Tz::utc);
+        .unwrap_or_else(Tz::utc)
+}
+
+fn find_unsent_rows(spreadsheet: &Spreadsheet) -> Option<(i32, Vec<Entry>)> {
+    let tz = spreadsheet_timezone(spreadsheet);
     let sheets = spreadsheet.sheets.as_ref()?;
     let sheet = sheets.get(0)?;
     let sheet_id = sheet.properties.as_ref()?.sheet_id?;
@@ -124,6 +179,7 @@ async fn inner(
     config: &Config,
     discord: &DiscordClient,
     sheets: &Sheets<HttpsConnector<HttpConnector>>,
+    dry_run_rows: &mut HashSet<i32>,
 ) -> Result<(), Error> {
     let spreadsheet_id = config
         .contact_spreadsheet
@@ -134,6 +190,7 @@ async fn inner(
         .spreadsheets()
         .get(spreadsheet_id)
         .param("fields", "properties.timeZone,sheets(properties.sheetId,data(startRow,startColumn,rowData.values.effectiveValue,rowMetadata.developerMetadata))")
+        .delegate(&mut Backoff::default())
         .doit()
         .await
         .context("failed to fetch the spreadsheet")?;
@@ -142,6 +199,17 @@ async fn inner(
         .ok_or_else(|| Error::msg("no sheets or required information missing"))?;
 
     for message in unsent {
+        if config.dry_run {
+            if dry_run_rows.insert(message.row) {
+                info!(
+                    row = message.row,
+                    parts = count_parts(message.message, DESCRIPTION_LENGTH),
+                    "Dry run: not forwarding a message from the contact form"
+                );
+            }
+            continue;
+        }
+
         let parts = split_to_parts(message.message, DESCRIPTION_LENGTH);
         let num_parts = parts.len();
         for (i, part) in parts.into_iter().enumerate() {
@@ -189,6 +257,7 @@ async fn inner(
         sheets
             .spreadsheets()
             .batch_update(req, spreadsheet_id)
+            .delegate(&mut Backoff::default())
             .doit()
             .await
             .context("failed to set the message as sent")?;
@@ -196,3 +265,93 @@ async fn inner(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::{TimeZone, Utc};
+    use google_sheets4::api::Spreadsheet;
+    use google_sheets4::common::{Client, NoToken};
+    use google_sheets4::hyper_rustls::HttpsConnectorBuilder;
+    use google_sheets4::hyper_util::client::legacy::Builder;
+    use google_sheets4::hyper_util::rt::TokioExecutor;
+    use google_sheets4::Sheets;
+    use serde_json::json;
+
+    use super::{spreadsheet_timezone, Backoff};
+    use crate::testing::{json, response, MockServer};
+
+    #[test]
+    fn retry_after() {
+        let now = Utc.with_ymd_and_hms(2015, 10, 21, 7, 28, 0).unwrap();
+        let response = |value: &str| {
+            google_sheets4::hyper::Response::builder()
+                .status(429)
+                .header("Retry-After", value)
+                .body(google_sheets4::common::to_body::<String>(None))
+                .unwrap()
+        };
+
+        assert_eq!(Backoff::retry_after(&response("120"), now), Some(Duration::from_secs(120)));
+        assert_eq!(
+            Backoff::retry_after(&response("Wed, 21 Oct 2015 07:28:30 GMT"), now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            Backoff::retry_after(&response("Wed, 21 Oct 2015 07:27:00 GMT"), now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(Backoff::retry_after(&response("soon"), now), None);
+    }
+
+    #[test]
+    fn timezone() {
+        let spreadsheet = |properties| {
+            serde_json::from_value::<Spreadsheet>(json!({ "properties": properties })).unwrap()
+        };
+        let noon = |spreadsheet| {
+            let tz = spreadsheet_timezone(&spreadsheet);
+            Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap().with_timezone(&&tz).naive_local()
+        };
+
+        let vancouver = spreadsheet(json!({ "timeZone": "America/Vancouver" }));
+        assert_eq!(noon(vancouver).to_string(), "2024-01-01 04:00:00");
+        let unknown = spreadsheet(json!({ "timeZone": "Mars/Olympus_Mons" }));
+        assert_eq!(noon(unknown).to_string(), "2024-01-01 12:00:00");
+        assert_eq!(noon(spreadsheet(json!({}))).to_string(), "2024-01-01 12:00:00");
+    }
+
+    #[tokio::test]
+    async fn rate_limited() {
+        let server = MockServer::bind().await;
+        let connector = HttpsConnectorBuilder::new()
+            .with_native_roots()
+            .unwrap()
+            .https_or_http()
+            .enable_http1()
+            .build();
+        let client: Client<_> = Builder::new(TokioExecutor::new()).build(connector);
+        let mut sheets = Sheets::new(client, NoToken);
+        sheets.base_url(server.url());
+
+        let server = tokio::spawn(async move {
+            let responses = [
+                response("429 Too Many Requests", &[("Retry-After", "0")], ""),
+                json(r#"{"spreadsheetId": "sheet"}"#),
+            ];
+            for canned in responses {
+                let request = server.respond(|_| canned).await;
+                assert!(request.request_line().starts_with("GET /v4/spreadsheets/sheet"));
+            }
+        });
+
+        let mut backoff = Backoff::default();
+        let (_, spreadsheet) =
+            sheets.spreadsheets().get("sheet").delegate(&mut backoff).doit().await.unwrap();
+        assert_eq!(spreadsheet.spreadsheet_id.as_deref(), Some("sheet"));
+        assert_eq!(backoff.retries, 1);
+
+        server.await.unwrap();
+    }
+}
@@ -1,4 +1,4 @@
-use tracing::error;
+use tracing::{error, info};
 use twilight_gateway::Event;
 use twilight_http::Client;
 use twilight_model::gateway::payload::incoming::{GuildCreate, VoiceStateUpdate};
@@ -6,21 +6,32 @@ use twilight_model::id::marker::{GuildMarker, UserMarker};
 use twilight_model::id::Id;
 
 use crate::cache::Cache;
+use crate::config::Config;
+
+async fn disconnect(
+    config: &Config,
+    discord: &Client,
+    guild: Id<GuildMarker>,
+    user: Id<UserMarker>,
+) {
+    if config.dry_run {
+        info!(guild.id = guild.get(), user.id = user.get(), "Dry run: not kicking a user");
+        return;
+    }
 
-async fn disconnect(discord: &Client, guild: Id<GuildMarker>, user: Id<UserMarker>) {
     if let Err(error) = discord.update_guild_member(guild, user).channel_id(None).await {
         error!(?error, "failed to kick user from the AFK channel");
     }
 }
 
-pub async fn on_event(cache: &Cache, discord: &Client, event: &Event) {
+pub async fn on_event(cache: &Cache, config: &Config, discord: &Client, event: &Event) {
     match event {
         Event::GuildCreate(event) => match &**event {
             GuildCreate::Available(guild) => {
                 let Some(afk_channel) = guild.afk_channel_id else { return };
                 for voice_state in &guild.voice_states {
                     if voice_state.channel_id == Some(afk_channel) {
-                        disconnect(discord, guild.id, voice_state.user_id).await;
+                        disconnect(config, discord, guild.id, voice_state.user_id).await;
                     }
                 }
             }
@@ -40,7 +51,7 @@ pub async fn on_event(cache: &Cache, discord: &Client, event: &Event) {
                 return;
             };
             if channel_id == afk_channel {
-                disconnect(discord, guild_id, voice_state.user_id).await;
+                disconnect(config, discord, guild_id, voice_state.user_id).await;
             }
         }
         _ => (),
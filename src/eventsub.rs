@@ -0,0 +1,313 @@
+//! Twitch EventSub webhooks, so that Twitch can tell us when the stream goes live instead of us
+//! having to ask LRRbot.
+
+use std::convert::Infallible;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::sync::Arc;
+
+use bytes::Bytes;
+use chrono::{DateTime, TimeDelta, Utc};
+use http_body_util::{BodyExt, Full};
+use hyper::body::Incoming;
+use hyper::header::CONTENT_TYPE;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use tokio::net::TcpListener;
+use tokio::sync::watch;
+use tracing::{error, info, warn};
+use twitch_api::eventsub::Event;
+
+use crate::config::EventSubWebhook;
+
+/// Older messages are rejected, so that a captured request can't be replayed later.
+const MAX_MESSAGE_AGE: TimeDelta = match TimeDelta::try_minutes(10) {
+    Some(delta) => delta,
+    None => panic!("MAX_MESSAGE_AGE is invalid"),
+};
+
+/// A webhook request that's known to come from Twitch.
+#[derive(Debug)]
+pub enum Verified {
+    /// Twitch is checking the callback of a new subscription. The challenge has to be sent back
+    /// as the body of the response.
+    Challenge(String),
+    /// A notification or a revocation.
+    Event(Box<Event>),
+}
+
+/// Why a webhook request was rejected.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Rejected {
+    /// The signature is missing or doesn't match the secret.
+    Signature,
+    /// The timestamp is missing or too far from the current time.
+    Timestamp,
+    /// The request was signed correctly but isn't an event we understand.
+    Malformed,
+}
+
+impl Rejected {
+    /// The status code to respond to the request with.
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Rejected::Signature | Rejected::Timestamp => StatusCode::FORBIDDEN,
+            Rejected::Malformed => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+impl Display for Rejected {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            Rejected::Signature => f.write_str("invalid EventSub signature"),
+            Rejected::Timestamp => f.write_str("stale EventSub message"),
+            Rejected::Malformed => f.write_str("malformed EventSub message"),
+        }
+    }
+}
+
+impl std::error::Error for Rejected {}
+
+/// Check that `request` was signed by Twitch with the webhook secret and is recent, then parse it.
+pub fn verify<B: AsRef<[u8]>>(
+    webhook: &EventSubWebhook,
+    request: &Request<B>,
+    now: DateTime<Utc>,
+) -> Result<Verified, Rejected> {
+    if !Event::verify_payload(request, webhook.secret.as_bytes()) {
+        return Err(Rejected::Signature);
+    }
+
+    // The timestamp is covered by the signature, so it can be trusted now.
+    let timestamp = request
+        .headers()
+        .get("Twitch-Eventsub-Message-Timestamp")
+        .and_then(|timestamp| timestamp.to_str().ok())
+        .and_then(|timestamp| DateTime::parse_from_rfc3339(timestamp).ok())
+        .ok_or(Rejected::Timestamp)?;
+    if (now - timestamp.to_utc()).abs() > MAX_MESSAGE_AGE {
+        return Err(Rejected::Timestamp);
+    }
+
+    let event = Event::parse_http(request).map_err(|_| Rejected::Malformed)?;
+    match event.get_verification_request() {
+        Some(verification) => Ok(Verified::Challenge(verification.challenge.clone())),
+        None => Ok(Verified::Event(Box::new(event))),
+    }
+}
+
+async fn respond(webhook: &EventSubWebhook, req: Request<Incoming>) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::default());
+    if req.uri().path() != webhook.callback.path() {
+        *response.status_mut() = StatusCode::NOT_FOUND;
+        return response;
+    }
+    if req.method() != Method::POST {
+        *response.status_mut() = StatusCode::METHOD_NOT_ALLOWED;
+        return response;
+    }
+
+    let (parts, body) = req.into_parts();
+    let body = match body.collect().await {
+        Ok(body) => body.to_bytes(),
+        Err(error) => {
+            error!(?error, "Failed to read an EventSub request");
+            *response.status_mut() = StatusCode::BAD_REQUEST;
+            return response;
+        }
+    };
+
+    match verify(webhook, &Request::from_parts(parts, body), Utc::now()) {
+        Ok(Verified::Challenge(challenge)) => {
+            info!("Answering an EventSub subscription challenge");
+            *response.body_mut() = Full::new(Bytes::from(challenge));
+            response.headers_mut().insert(CONTENT_TYPE, "text/plain".parse().unwrap());
+        }
+        Ok(Verified::Event(event)) => {
+            if event.is_notification() {
+                info!(?event, "Received an EventSub notification");
+            } else {
+                warn!(?event, "Received an EventSub message that isn't a notification");
+            }
+            *response.status_mut() = StatusCode::NO_CONTENT;
+        }
+        Err(rejected) => {
+            warn!(%rejected, "Rejected an EventSub request");
+            *response.status_mut() = rejected.status();
+        }
+    }
+    response
+}
+
+/// Serve the EventSub webhook on `listener` until shutdown.
+pub async fn serve(
+    mut running: watch::Receiver<bool>,
+    webhook: EventSubWebhook,
+    listener: TcpListener,
+) {
+    let webhook = Arc::new(webhook);
+    loop {
+        tokio::select! {
+            _ = running.changed() => break,
+            res = listener.accept() => match res {
+                Ok((stream, _)) => {
+                    let webhook = webhook.clone();
+                    let service = service_fn(move |req| {
+                        let webhook = webhook.clone();
+                        async move { Ok::<_, Infallible>(respond(&webhook, req).await) }
+                    });
+                    tokio::spawn(async move {
+                        let io = TokioIo::new(stream);
+                        let conn = http1::Builder::new().serve_connection(io, service);
+                        if let Err(error) = conn.await {
+                            error!(?error, "Failed to serve an EventSub request");
+                        }
+                    });
+                }
+                Err(error) => error!(?error, "Failed to accept an incoming connection"),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use chrono::{DateTime, TimeDelta, Utc};
+    use hyper::{Request, StatusCode};
+    use url::Url;
+
+    use tokio::sync::watch;
+
+    use super::{serve, verify, Rejected, Verified};
+    use crate::config::EventSubWebhook;
+    use crate::testing::listener;
+
+    const MESSAGE_ID: &str = "e76c6bd4-55c9-4987-8304-da1588d8988b";
+    const TIMESTAMP: &str = "2024-01-05T20:00:00.123456789Z";
+
+    const CHALLENGE: &str = concat!(
+        r#"{"challenge":"pogchamp-kappa-360noscope-vohiyo","subscription":{"#,
+        r#""id":"f1c2a387-161a-49f9-a165-0f21d7a4e1c4","#,
+        r#""status":"webhook_callback_verification_pending","type":"stream.online","#,
+        r#""version":"1","cost":1,"condition":{"broadcaster_user_id":"27132299"},"#,
+        r#""transport":{"method":"webhook","callback":"https://eris.example.com/eventsub"},"#,
+        r#""created_at":"2024-01-05T19:59:59.634234626Z"}}"#,
+    );
+    const CHALLENGE_SIGNATURE: &str =
+        "sha256=00c96642159dd6122dda3e5b1e5f5825cf65bc098f3f970a0c19ff4ea86bf4aa";
+
+    const ONLINE: &str = concat!(
+        r#"{"subscription":{"id":"f1c2a387-161a-49f9-a165-0f21d7a4e1c4","status":"enabled","#,
+        r#""type":"stream.online","version":"1","cost":0,"#,
+        r#""condition":{"broadcaster_user_id":"27132299"},"#,
+        r#""transport":{"method":"webhook","callback":"https://eris.example.com/eventsub"},"#,
+        r#""created_at":"2024-01-05T19:59:59.634234626Z"},"#,
+        r#""event":{"id":"9001","broadcaster_user_id":"27132299","#,
+        r#""broadcaster_user_login":"loadingreadyrun","broadcaster_user_name":"LoadingReadyRun","#,
+        r#""type":"live","started_at":"2024-01-05T19:59:58.123Z"}}"#,
+    );
+    const ONLINE_SIGNATURE: &str =
+        "sha256=61053280db5fb995923910c3dfb94731aa9b6cf769c60fdd67aee4adfd5cbe94";
+
+    fn webhook() -> EventSubWebhook {
+        EventSubWebhook {
+            callback: Url::parse("https://eris.example.com/eventsub").unwrap(),
+            secret: String::from("s3cRe7-eris-tests"),
+            listen: SocketAddr::from(([127, 0, 0, 1], 0)),
+        }
+    }
+
+    fn request(message_type: &str, body: &str, signature: Option<&str>) -> Request<String> {
+        let mut request = Request::post("https://eris.example.com/eventsub")
+            .header("Twitch-Eventsub-Message-Id", MESSAGE_ID)
+            .header("Twitch-Eventsub-Message-Timestamp", TIMESTAMP)
+            .header("Twitch-Eventsub-Message-Type", message_type)
+            .header("Twitch-Eventsub-Subscription-Type", "stream.online")
+            .header("Twitch-Eventsub-Subscription-Version", "1");
+        if let Some(signature) = signature {
+            request = request.header("Twitch-Eventsub-Message-Signature", signature);
+        }
+        request.body(String::from(body)).unwrap()
+    }
+
+    fn now() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(TIMESTAMP).unwrap().to_utc() + TimeDelta::seconds(1)
+    }
+
+    #[test]
+    fn challenge() {
+        let challenge =
+            request("webhook_callback_verification", CHALLENGE, Some(CHALLENGE_SIGNATURE));
+        match verify(&webhook(), &challenge, now()) {
+            Ok(Verified::Challenge(challenge)) => {
+                assert_eq!(challenge, "pogchamp-kappa-360noscope-vohiyo")
+            }
+            res => panic!("expected a challenge, got {res:?}"),
+        }
+    }
+
+    #[test]
+    fn notification() {
+        let online = request("notification", ONLINE, Some(ONLINE_SIGNATURE));
+        match verify(&webhook(), &online, now()) {
+            Ok(Verified::Event(event)) => assert!(event.is_notification()),
+            res => panic!("expected an event, got {res:?}"),
+        }
+
+        // Replays of old messages and messages from the future are rejected.
+        for now in [now() + TimeDelta::minutes(11), now() - TimeDelta::minutes(11)] {
+            assert_eq!(verify(&webhook(), &online, now).unwrap_err(), Rejected::Timestamp);
+        }
+    }
+
+    #[test]
+    fn invalid_signature() {
+        let wrong_secret = EventSubWebhook { secret: String::from("hunter2hunter2"), ..webhook() };
+        let online = request("notification", ONLINE, Some(ONLINE_SIGNATURE));
+        assert_eq!(verify(&wrong_secret, &online, now()).unwrap_err(), Rejected::Signature);
+
+        let tampered = ONLINE.replace("loadingreadyrun", "notloadingreadyrun");
+        for request in [
+            request("notification", &tampered, Some(ONLINE_SIGNATURE)),
+            request("notification", ONLINE, Some(CHALLENGE_SIGNATURE)),
+            request("notification", ONLINE, Some(&ONLINE_SIGNATURE.replace("sha256=", "md5="))),
+            request("notification", ONLINE, Some("sha256=butts")),
+            request("notification", ONLINE, None),
+        ] {
+            let rejected = verify(&webhook(), &request, now()).unwrap_err();
+            assert_eq!(rejected, Rejected::Signature);
+            assert_eq!(rejected.status(), StatusCode::FORBIDDEN);
+        }
+    }
+
+    #[tokio::test]
+    async fn serves_the_callback() {
+        let (listener, url) = listener().await;
+        let (running_tx, running_rx) = watch::channel(true);
+        tokio::spawn(serve(running_rx, webhook(), listener));
+
+        let client = reqwest::Client::new();
+        let response = client
+            .post(format!("{url}eventsub"))
+            .header("Twitch-Eventsub-Message-Id", MESSAGE_ID)
+            .header("Twitch-Eventsub-Message-Timestamp", TIMESTAMP)
+            .header("Twitch-Eventsub-Message-Type", "notification")
+            .header("Twitch-Eventsub-Message-Signature", "sha256=butts")
+            .body(ONLINE)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+
+        let response = client.get(format!("{url}eventsub")).send().await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::METHOD_NOT_ALLOWED);
+        let response = client.post(format!("{url}metrics")).send().await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+
+        let _ = running_tx.send(false);
+    }
+}
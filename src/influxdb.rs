@@ -1,14 +1,29 @@
-use std::sync::Arc;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use anyhow::{Context, Error};
+use flate2::write::GzEncoder;
+use flate2::Compression;
 use influxdb_line_protocol::LineProtocolBuilder;
-use reqwest::Client;
+use reqwest::header::CONTENT_ENCODING;
+use reqwest::{Client, StatusCode};
+use tokio::sync::watch::Receiver;
+use tracing::error;
 use url::Url;
 
+/// How often the buffered measurements are written to InfluxDB.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+/// Write requests with bodies at least this large are gzip compressed.
+const GZIP_MIN_SIZE: usize = 1024;
+/// Measurements that failed to be written are kept for the next flush, up to this many bytes.
+const MAX_BUFFER_SIZE: usize = 8 * 1024 * 1024;
+
 #[derive(Clone)]
 pub struct InfluxDb {
     http: Client,
     write_url: Arc<Url>,
+    buffer: Arc<Mutex<Vec<u8>>>,
 }
 
 impl InfluxDb {
@@ -17,22 +32,235 @@ impl InfluxDb {
         let mut write_url =
             base_url.join("write").context("failed to construct the /write endpoint URL")?;
         write_url.query_pairs_mut().append_pair("db", database);
-        Ok(Self { http, write_url: Arc::new(write_url) })
-    }
-
-    pub async fn write(&self, measurements: LineProtocolBuilder<Vec<u8>>) -> Result<(), Error> {
-        let body = measurements.build();
-        if !body.is_empty() {
-            self.http
-                .post((*self.write_url).clone())
-                .body(body)
-                .send()
-                .await
-                .context("failed to send the write request")?
-                .error_for_status()
-                .context("write request failed")?;
+        Ok(Self { http, write_url: Arc::new(write_url), buffer: Arc::new(Mutex::new(vec![])) })
+    }
+
+    /// Buffer the measurements until the next flush.
+    pub fn write(&self, measurements: LineProtocolBuilder<Vec<u8>>) {
+        self.buffer.lock().unwrap().extend(measurements.build());
+    }
+
+    /// The measurements buffered since the last flush.
+    #[cfg(test)]
+    pub fn buffered(&self) -> String {
+        String::from_utf8(self.buffer.lock().unwrap().clone()).unwrap()
+    }
+
+    /// Write all buffered measurements to InfluxDB. If that fails they are kept for the next flush,
+    /// unless InfluxDB rejected them.
+    pub async fn flush(&self) -> Result<(), Error> {
+        let body = std::mem::take(&mut *self.buffer.lock().unwrap());
+        if body.is_empty() {
+            return Ok(());
         }
 
+        match self.send(&body).await {
+            Ok(()) => Ok(()),
+            Err(error) if is_retryable(&error) => {
+                self.requeue(body);
+                Err(error)
+            }
+            Err(error) => Err(error.context("the measurements were rejected and dropped")),
+        }
+    }
+
+    async fn send(&self, body: &[u8]) -> Result<(), Error> {
+        let mut request = self.http.post((*self.write_url).clone());
+        let body = if body.len() >= GZIP_MIN_SIZE {
+            request = request.header(CONTENT_ENCODING, "gzip");
+            gzip(body).context("failed to compress the measurements")?
+        } else {
+            body.to_vec()
+        };
+        request
+            .body(body)
+            .send()
+            .await
+            .context("failed to send the write request")?
+            .error_for_status()
+            .context("write request failed")?;
+
         Ok(())
     }
+
+    /// Put measurements that failed to be written back in front of the ones buffered since. The
+    /// oldest lines are dropped if that's more than `MAX_BUFFER_SIZE`.
+    fn requeue(&self, mut body: Vec<u8>) {
+        let mut buffer = self.buffer.lock().unwrap();
+        body.extend_from_slice(&buffer);
+        if body.len() > MAX_BUFFER_SIZE {
+            let excess = body.len() - MAX_BUFFER_SIZE;
+            let start = body[excess - 1..]
+                .iter()
+                .position(|&byte| byte == b'\n')
+                .map_or(body.len(), |i| excess + i);
+            body.drain(..start);
+        }
+        *buffer = body;
+    }
+}
+
+/// Whether a failed write might succeed later. Other client errors mean that InfluxDB rejected the
+/// measurements, and retrying them would only hold up the ones buffered after them.
+fn is_retryable(error: &Error) -> bool {
+    match error.downcast_ref::<reqwest::Error>().and_then(reqwest::Error::status) {
+        Some(status) => status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS,
+        None => true,
+    }
+}
+
+fn gzip(body: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut encoder = GzEncoder::new(vec![], Compression::default());
+    encoder.write_all(body)?;
+    Ok(encoder.finish()?)
+}
+
+/// Periodically flush the buffered measurements, and flush one last time on shutdown so that the
+/// last window of data isn't lost. The final flush is abandoned after `shutdown_timeout`.
+pub async fn flush_periodically(
+    mut running: Receiver<bool>,
+    influxdb: InfluxDb,
+    shutdown_timeout: Duration,
+) {
+    let mut timer = tokio::time::interval(FLUSH_INTERVAL);
+
+    loop {
+        tokio::select! {
+            _ = running.changed() => break,
+            _ = timer.tick() => {
+                if let Err(error) = influxdb.flush().await {
+                    error!(?error, "Failed to write the measurements to InfluxDB");
+                }
+            },
+        }
+    }
+
+    match tokio::time::timeout(shutdown_timeout, influxdb.flush()).await {
+        Ok(Ok(())) => (),
+        Ok(Err(error)) => error!(?error, "Failed to write the final measurements to InfluxDB"),
+        Err(_) => error!("Timed out writing the final measurements to InfluxDB"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+    use std::time::Duration;
+
+    use flate2::read::GzDecoder;
+    use influxdb_line_protocol::LineProtocolBuilder;
+    use tokio::sync::watch;
+
+    use super::{flush_periodically, InfluxDb, GZIP_MIN_SIZE, MAX_BUFFER_SIZE};
+    use crate::testing::{listener, response, MockServer};
+
+    #[tokio::test]
+    async fn flush_on_shutdown() {
+        let server = MockServer::bind().await;
+        let influxdb = InfluxDb::new(reqwest::Client::new(), &server.url(), "lrrbot").unwrap();
+
+        let (running_tx, running_rx) = watch::channel(true);
+        let flusher =
+            tokio::spawn(flush_periodically(running_rx, influxdb.clone(), Duration::from_secs(5)));
+
+        // Let the first, immediate, tick go by with nothing buffered.
+        tokio::task::yield_now().await;
+
+        influxdb
+            .write(LineProtocolBuilder::new().measurement("test").field("count", 1.0).close_line());
+        running_tx.send_replace(false);
+
+        let request = server.respond(|_| response("204 No Content", &[], "")).await;
+        assert!(request.request_line().starts_with("POST /write?db=lrrbot "), "{}", request.head);
+        assert_eq!(request.body, b"test count=1\n");
+
+        flusher.await.unwrap();
+        assert!(influxdb.buffer.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn compressed_flush() {
+        let server = MockServer::bind().await;
+        let influxdb = InfluxDb::new(reqwest::Client::new(), &server.url(), "lrrbot").unwrap();
+
+        let mut expected = vec![];
+        let mut count = 0;
+        while expected.len() < GZIP_MIN_SIZE {
+            let line = LineProtocolBuilder::new()
+                .measurement("test")
+                .field("count", f64::from(count))
+                .close_line()
+                .build();
+            expected.extend_from_slice(&line);
+            influxdb.buffer.lock().unwrap().extend(line);
+            count += 1;
+        }
+
+        let flush = tokio::spawn({
+            let influxdb = influxdb.clone();
+            async move { influxdb.flush().await }
+        });
+
+        let request = server.respond(|_| response("204 No Content", &[], "")).await;
+        assert_eq!(request.header("Content-Encoding"), Some("gzip"), "{}", request.head);
+        flush.await.unwrap().unwrap();
+
+        let mut body = vec![];
+        GzDecoder::new(&request.body[..]).read_to_end(&mut body).unwrap();
+        assert_eq!(body, expected);
+    }
+
+    #[tokio::test]
+    async fn failed_flush() {
+        // Nothing is listening on the port once the listener is dropped.
+        let (listener, url) = listener().await;
+        drop(listener);
+        let influxdb = InfluxDb::new(reqwest::Client::new(), &url, "lrrbot").unwrap();
+
+        influxdb
+            .write(LineProtocolBuilder::new().measurement("test").field("count", 1.0).close_line());
+        assert!(influxdb.flush().await.is_err());
+        influxdb
+            .write(LineProtocolBuilder::new().measurement("test").field("count", 2.0).close_line());
+
+        assert_eq!(influxdb.buffered(), "test count=1\ntest count=2\n");
+    }
+
+    #[tokio::test]
+    async fn rejected_flush() {
+        let server = MockServer::bind().await;
+        let influxdb = InfluxDb::new(reqwest::Client::new(), &server.url(), "lrrbot").unwrap();
+
+        for (status, requeued) in [
+            ("400 Bad Request", false),
+            ("429 Too Many Requests", true),
+            ("503 Service Unavailable", true),
+        ] {
+            *influxdb.buffer.lock().unwrap() = b"test count=1\n".to_vec();
+            let flush = tokio::spawn({
+                let influxdb = influxdb.clone();
+                async move { influxdb.flush().await }
+            });
+            server.respond(|_| response(status, &[], "")).await;
+
+            assert!(flush.await.unwrap().is_err(), "{status}");
+            assert_eq!(!influxdb.buffered().is_empty(), requeued, "{status}");
+        }
+    }
+
+    #[test]
+    fn requeue_limit() {
+        let influxdb =
+            InfluxDb::new(reqwest::Client::new(), "http://localhost:8086/", "lrrbot").unwrap();
+        influxdb
+            .write(LineProtocolBuilder::new().measurement("new").field("count", 1.0).close_line());
+
+        let line = b"old count=1\n";
+        influxdb.requeue(line.repeat(MAX_BUFFER_SIZE / line.len() + 1));
+
+        let buffered = influxdb.buffered();
+        assert!(buffered.len() <= MAX_BUFFER_SIZE);
+        assert!(buffered.starts_with("old count=1\n"));
+        assert!(buffered.ends_with("old count=1\nnew count=1\n"));
+    }
 }
@@ -0,0 +1,206 @@
+//! Slash commands, handled next to the text commands in [`crate::command_parser`].
+//!
+//! Only `/quote` so far, which replies the same way `!quote` does.
+
+use std::sync::Arc;
+
+use anyhow::{Context, Error};
+use sea_orm::DatabaseConnection;
+use tokio::sync::mpsc::Sender;
+use tracing::error;
+use twilight_gateway::Event;
+use twilight_http::Client as DiscordClient;
+use twilight_model::application::command::{Command, CommandType};
+use twilight_model::application::interaction::application_command::{
+    CommandDataOption, CommandOptionValue,
+};
+use twilight_model::application::interaction::{Interaction, InteractionData};
+use twilight_model::channel::message::MessageFlags;
+use twilight_model::http::interaction::{InteractionResponse, InteractionResponseType};
+use twilight_model::id::marker::ApplicationMarker;
+use twilight_model::id::Id;
+use twilight_util::builder::command::{CommandBuilder, StringBuilder};
+use twilight_util::builder::InteractionResponseDataBuilder;
+
+use crate::config::Config;
+use crate::shutdown::Task;
+
+const QUOTE: &str = "quote";
+
+#[derive(Clone)]
+pub struct Interactions {
+    application_id: Id<ApplicationMarker>,
+    config: Arc<Config>,
+    db: DatabaseConnection,
+    discord: Arc<DiscordClient>,
+}
+
+impl Interactions {
+    /// Register the slash commands with Discord, replacing any left over from earlier versions.
+    pub async fn new(
+        config: Arc<Config>,
+        db: DatabaseConnection,
+        discord: Arc<DiscordClient>,
+    ) -> Result<Self, Error> {
+        let application_id = discord
+            .current_user_application()
+            .await
+            .context("failed to get the application")?
+            .model()
+            .await
+            .context("failed to parse the application")?
+            .id;
+
+        discord
+            .interaction(application_id)
+            .set_global_commands(&commands()?)
+            .await
+            .context("failed to register the slash commands")?;
+
+        Ok(Self { application_id, config, db, discord })
+    }
+
+    pub async fn on_event(&self, handler_tx: &Sender<Task>, event: &Event) {
+        let Event::InteractionCreate(interaction) = event else { return };
+        let Some(InteractionData::ApplicationCommand(ref data)) = interaction.data else { return };
+        if data.name != QUOTE {
+            return;
+        }
+
+        let _ = handler_tx
+            .send((
+                "/quote".into(),
+                tokio::spawn({
+                    let this = self.clone();
+                    let interaction = interaction.0.clone();
+                    let query = quote_query(&data.options);
+
+                    async move {
+                        if let Err(error) = this.quote(&interaction, &query).await {
+                            error!(?error, "Failed to handle /quote");
+                        }
+                    }
+                }),
+            ))
+            .await;
+    }
+
+    async fn quote(&self, interaction: &Interaction, query: &str) -> Result<(), Error> {
+        let client = self.discord.interaction(self.application_id);
+
+        // The search can take longer than the three seconds Discord waits for the response.
+        client
+            .create_response(
+                interaction.id,
+                &interaction.token,
+                &InteractionResponse {
+                    kind: InteractionResponseType::DeferredChannelMessageWithSource,
+                    data: Some(
+                        InteractionResponseDataBuilder::new()
+                            .flags(MessageFlags::SUPPRESS_EMBEDS)
+                            .build(),
+                    ),
+                },
+            )
+            .await
+            .context("failed to acknowledge the interaction")?;
+
+        // Answer even if the search fails so that the deferred response doesn't hang around.
+        let reply = crate::commands::quote::find_reply(&self.db, &self.config, query).await;
+        client
+            .create_followup(&interaction.token)
+            .flags(MessageFlags::SUPPRESS_EMBEDS)
+            .content(reply.as_deref().unwrap_or("Failed to look up the quote."))
+            .await
+            .context("failed to reply to the interaction")?;
+
+        reply?;
+        Ok(())
+    }
+}
+
+fn commands() -> Result<Vec<Command>, Error> {
+    let quote = CommandBuilder::new(
+        QUOTE,
+        "Search for a quote in the quote database",
+        CommandType::ChatInput,
+    )
+    .option(StringBuilder::new("query", "A quote ID or a search query, like in !quote"))
+    .option(StringBuilder::new("order", "Which of the matching quotes to pick").choices([
+        ("Random", "random"),
+        ("Newest", "newest"),
+        ("Oldest", "oldest"),
+    ]))
+    .validate()
+    .context("/quote failed validation")?
+    .build();
+
+    Ok(vec![quote])
+}
+
+/// The `!quote` query the options of `/quote` amount to.
+fn quote_query(options: &[CommandDataOption]) -> String {
+    let option = |name| {
+        options.iter().find(|option| option.name == name).and_then(|option| match option.value {
+            CommandOptionValue::String(ref value) => Some(value.trim()),
+            _ => None,
+        })
+    };
+
+    let mut query = String::from(option("query").unwrap_or(""));
+    if let Some(order @ ("newest" | "oldest")) = option("order") {
+        if !query.is_empty() {
+            query.push(' ');
+        }
+        query.push_str("order:");
+        query.push_str(order);
+    }
+    query
+}
+
+#[cfg(test)]
+mod tests {
+    use twilight_model::application::interaction::application_command::{
+        CommandDataOption, CommandOptionValue,
+    };
+
+    use super::{commands, quote_query};
+
+    fn option(name: &str, value: &str) -> CommandDataOption {
+        CommandDataOption {
+            name: String::from(name),
+            value: CommandOptionValue::String(String::from(value)),
+        }
+    }
+
+    #[test]
+    fn query() {
+        assert_eq!(quote_query(&[]), "");
+        assert_eq!(quote_query(&[option("query", "3849")]), "3849");
+        assert_eq!(quote_query(&[option("query", " from:alex butts ")]), "from:alex butts");
+        assert_eq!(
+            quote_query(&[option("order", "newest"), option("query", "from:graham")]),
+            "from:graham order:newest"
+        );
+        assert_eq!(quote_query(&[option("order", "oldest")]), "order:oldest");
+        assert_eq!(quote_query(&[option("query", "butts"), option("order", "random")]), "butts");
+        assert_eq!(
+            quote_query(&[
+                option("query", "butts"),
+                CommandDataOption {
+                    name: String::from("order"),
+                    value: CommandOptionValue::Integer(1),
+                },
+            ]),
+            "butts"
+        );
+    }
+
+    #[test]
+    fn registration() {
+        let commands = commands().unwrap();
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].name, "quote");
+        assert!(commands[0].options.iter().all(|option| option.required != Some(true)));
+    }
+}
@@ -11,6 +11,7 @@ use google_calendar3::hyper_util::client::legacy::Builder as HyperClientBuilder;
 use google_calendar3::hyper_util::rt::TokioExecutor;
 use google_calendar3::yup_oauth2::authenticator::{Authenticator, ServiceAccountAuthenticator};
 use google_calendar3::CalendarHub;
+use google_sheets4::common::GetToken;
 use google_sheets4::Sheets;
 use google_youtube3::YouTube;
 use tokio::sync::RwLock;
@@ -19,7 +20,7 @@ use twilight_gateway::{EventTypeFlags, Intents, StreamExt as _};
 use twilight_http::Client as DiscordClient;
 use twilight_model::channel::message::AllowedMentions;
 use twilight_model::gateway::payload::outgoing::update_presence::UpdatePresencePayload;
-use twilight_model::gateway::presence::{ActivityType, MinimalActivity, Status as PresenceStatus};
+use twilight_model::gateway::presence::{MinimalActivity, Status as PresenceStatus};
 
 mod aiomas;
 mod announcements;
@@ -33,15 +34,21 @@ mod config;
 mod contact;
 mod desertbus;
 mod disconnect_afk;
+mod eventsub;
 mod influxdb;
+mod interactions;
 mod markdown;
 mod metrics;
 mod models;
+mod prometheus;
 mod rpc;
+mod schedule_reminders;
 mod shorten;
 mod shutdown;
 #[cfg(target_os = "linux")]
 mod systemd;
+#[cfg(test)]
+mod testing;
 mod time;
 mod token_renewal;
 mod tz;
@@ -57,6 +64,7 @@ const USER_AGENT: &str = concat!(
 
 async fn create_google_client(
     service_account_path: impl AsRef<Path>,
+    subject: Option<&str>,
 ) -> Result<
     (HyperClient<HttpsConnector<HttpConnector>>, Authenticator<HttpsConnector<HttpConnector>>),
     Error,
@@ -74,14 +82,42 @@ async fn create_google_client(
         google_calendar3::yup_oauth2::read_service_account_key(service_account_path)
             .await
             .context("failed to read the Google service account key")?;
-    let auth = ServiceAccountAuthenticator::with_client(auth, builder.build(connector.clone()))
-        .build()
-        .await
-        .context("failed to create the Google service account authenticator")?;
+    let mut auth = ServiceAccountAuthenticator::with_client(auth, builder.build(connector.clone()));
+    if let Some(subject) = subject {
+        auth = auth.subject(subject);
+    }
+    let auth =
+        auth.build().await.context("failed to create the Google service account authenticator")?;
 
     Ok((builder.build(connector), auth))
 }
 
+/// Scopes the service account is used with, by the API that needs them.
+fn required_google_scopes() -> [(&'static str, &'static str); 4] {
+    [
+        ("Google Calendar", google_calendar3::api::Scope::EventReadonly.as_ref()),
+        ("Google Sheets", google_sheets4::api::Scope::DriveReadonly.as_ref()),
+        ("Google Sheets", google_sheets4::api::Scope::Drive.as_ref()),
+        ("YouTube", google_youtube3::api::Scope::Readonly.as_ref()),
+    ]
+}
+
+/// Request a token for every required scope so that a service account that lacks a permission
+/// fails at startup instead of on the first API call that needs it.
+async fn check_google_scopes(auth: &impl GetToken) -> Result<(), Error> {
+    for (service, scope) in required_google_scopes() {
+        let token =
+            auth.get_token(&[scope]).await.map_err(|error| anyhow::anyhow!(error)).with_context(
+                || format!("failed to get a token for the {service} scope {scope}"),
+            )?;
+        if token.is_none() {
+            anyhow::bail!("no token was issued for the {service} scope {scope}");
+        }
+    }
+
+    Ok(())
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Error> {
     let builder = tracing_subscriber::fmt::fmt()
@@ -112,8 +148,9 @@ async fn main() -> Result<(), Error> {
             clap::Arg::new("conf")
                 .short('c')
                 .value_name("FILE")
-                .help("Config file")
+                .help("Config file. Repeat to merge several, later files override earlier ones")
                 .value_parser(clap::value_parser!(PathBuf))
+                .action(clap::ArgAction::Append)
                 .default_value("lrrbot.conf"),
         )
         .arg(
@@ -132,16 +169,25 @@ async fn main() -> Result<(), Error> {
     let (handle, handler_tx) = crate::shutdown::wait_for_outstanding(running_rx.clone());
     tasks.push(handle);
 
-    let config = crate::config::Config::load_from_file(matches.get_one::<PathBuf>("conf").unwrap())
+    let config_files = matches.get_many::<PathBuf>("conf").unwrap().cloned().collect::<Vec<_>>();
+    let config = crate::config::Config::load_from_files(&config_files)
         .context("failed to load the config file")?;
     let config = Arc::new(config);
+    let (config_tx, config_rx) = tokio::sync::watch::channel(config.clone());
+    tasks.push(tokio::spawn(crate::config::reload_on_sighup(
+        running_rx.clone(),
+        config_files,
+        config_tx,
+    )));
 
     let db = sea_orm::Database::connect(&config.database_url)
         .await
         .context("failed to create the database pool")?;
 
+    let user_agent = config.user_agent.clone().unwrap_or_else(|| String::from(USER_AGENT));
+
     let http_client = reqwest::ClientBuilder::new()
-        .user_agent(USER_AGENT)
+        .user_agent(&user_agent)
         .build()
         .context("failed to create the HTTP client")?;
 
@@ -156,17 +202,22 @@ async fn main() -> Result<(), Error> {
     .context("failed to request the Twitch app access token")?;
     let helix_token = Arc::new(RwLock::new(helix_token));
 
-    let (google_client, google_auth) =
-        create_google_client(matches.get_one::<PathBuf>("google-service-account").unwrap())
-            .await
-            .context("failed to create the Google API client")?;
+    let (google_client, google_auth) = create_google_client(
+        matches.get_one::<PathBuf>("google-service-account").unwrap(),
+        config.google_subject.as_deref(),
+    )
+    .await
+    .context("failed to create the Google API client")?;
+    check_google_scopes(&google_auth)
+        .await
+        .context("the Google service account is missing a required permission")?;
 
     let mut calendar = CalendarHub::new(google_client.clone(), google_auth.clone());
-    calendar.user_agent(USER_AGENT.into());
+    calendar.user_agent(user_agent.clone());
     let mut sheets = Sheets::new(google_client.clone(), google_auth.clone());
-    sheets.user_agent(USER_AGENT.into());
+    sheets.user_agent(user_agent.clone());
     let mut youtube = YouTube::new(google_client.clone(), google_auth.clone());
-    youtube.user_agent(USER_AGENT.into());
+    youtube.user_agent(user_agent.clone());
 
     let influxdb = config
         .influxdb
@@ -174,6 +225,13 @@ async fn main() -> Result<(), Error> {
         .map(|(url, database)| crate::influxdb::InfluxDb::new(http_client.clone(), url, database))
         .transpose()
         .context("failed to create the InfluxDB client")?;
+    if let Some(ref influxdb) = influxdb {
+        tasks.push(tokio::spawn(crate::influxdb::flush_periodically(
+            running_rx.clone(),
+            influxdb.clone(),
+            config.influxdb_flush_timeout,
+        )));
+    }
 
     let desertbus = crate::desertbus::DesertBus::new(http_client.clone());
 
@@ -185,6 +243,29 @@ async fn main() -> Result<(), Error> {
     let discord = Arc::new(discord);
 
     let cache = Arc::new(crate::cache::Cache::new(config.guild));
+
+    let prometheus = match config.prometheus_listen {
+        Some(addr) => {
+            let listener = tokio::net::TcpListener::bind(addr)
+                .await
+                .context("failed to bind the Prometheus listener")?;
+            let prometheus = crate::prometheus::Prometheus::new();
+            tasks.push(tokio::spawn(crate::prometheus::serve(
+                running_rx.clone(),
+                prometheus.clone(),
+                cache.clone(),
+                listener,
+            )));
+            Some(prometheus)
+        }
+        None => None,
+    };
+    if let Some(webhook) = config.twitch_eventsub.clone() {
+        let listener = tokio::net::TcpListener::bind(webhook.listen)
+            .await
+            .context("failed to bind the EventSub listener")?;
+        tasks.push(tokio::spawn(crate::eventsub::serve(running_rx.clone(), webhook, listener)));
+    }
     let lrrbot = Arc::new(crate::rpc::LRRbot::new(running_rx.clone(), handler_tx.clone(), &config));
 
     let mut rpc_server = {
@@ -205,6 +286,12 @@ async fn main() -> Result<(), Error> {
         server
     }
     .context("failed to create the RPC server")?;
+    rpc_server.set_keepalive(config.rpc_keepalive);
+    rpc_server.set_max_in_flight(config.rpc_max_in_flight);
+    rpc_server.set_fallback(|method, _, _| async move {
+        tracing::warn!(method, "LRRbot called an unknown RPC method");
+        Err(crate::aiomas::codec::Exception::NoSuchMethod(method))
+    });
 
     rpc_server.register(
         "announcements/stream_up",
@@ -221,21 +308,23 @@ async fn main() -> Result<(), Error> {
     tasks.push(tokio::spawn(rpc_server.serve(running_rx.clone(), handler_tx.clone())));
     tasks.push(tokio::spawn(crate::announcements::post_toots(
         running_rx.clone(),
-        config.clone(),
+        config_rx.clone(),
         db.clone(),
         discord.clone(),
         http_client.clone(),
     )));
     tasks.push(tokio::spawn(crate::announcements::post_videos(
         running_rx.clone(),
+        config_rx.clone(),
         db.clone(),
         cache.clone(),
-        config.clone(),
         discord.clone(),
         youtube.clone(),
     )));
+    let topic_override = crate::autotopic::TopicOverride::new();
     tasks.push(tokio::spawn(crate::autotopic::autotopic(
         running_rx.clone(),
+        topic_override.clone(),
         cache.clone(),
         calendar.clone(),
         config.clone(),
@@ -252,6 +341,13 @@ async fn main() -> Result<(), Error> {
         config.clone(),
         discord.clone(),
     )));
+    tasks.push(tokio::spawn(crate::schedule_reminders::schedule_reminders(
+        running_rx.clone(),
+        calendar.clone(),
+        config.clone(),
+        db.clone(),
+        discord.clone(),
+    )));
     tasks.push(tokio::spawn(crate::contact::post_messages(
         running_rx.clone(),
         config.clone(),
@@ -264,26 +360,82 @@ async fn main() -> Result<(), Error> {
         http_client.clone(),
     )));
 
+    let http_commands = config
+        .http_commands
+        .iter()
+        .map(|command| crate::commands::http::Http::new(http_client.clone(), command.clone()));
+    let afk = crate::commands::afk::Afk::new();
     let command_parser = crate::command_parser::CommandParser::builder()
+        .command(afk.clone())
+        .command(crate::commands::autotopic::Pause::new(topic_override.clone()))
+        .command(crate::commands::autotopic::Resume::new(topic_override.clone()))
         .command(crate::commands::calendar::Next::fan(calendar.clone()))
         .command(crate::commands::calendar::Next::lrr(calendar.clone()))
+        .command(crate::commands::calendar::Subscribe::new(calendar.clone(), db.clone()))
+        .command(crate::commands::errors::Errors::new())
+        .command_opt(crate::commands::feedback::Feedback::new(&config, sheets.clone()))
         .command(crate::commands::help::Help::new())
+        .command(crate::commands::lastpost::LastPost::new(config.clone(), http_client.clone()))
+        .command_opt(crate::commands::links::Links::new(&config))
         .command(crate::commands::live::Live::new(db.clone(), helix.clone()))
+        .command(crate::commands::poll::Poll::new())
+        .command(crate::commands::quote::Around::new(db.clone()))
+        .command(crate::commands::quote::BlameChannel::new())
+        .command(crate::commands::quote::Cite::new(db.clone()))
+        .command(crate::commands::quote::Dedupe::new(db.clone()))
         .command(crate::commands::quote::Details::new(db.clone()))
+        .command(crate::commands::quote::Export::new(db.clone()))
+        .command(crate::commands::quote::FlagContext::new(db.clone()))
+        .command(crate::commands::quote::Games::new(db.clone()))
+        .command(crate::commands::quote::Identify::new(db.clone()))
+        .command(crate::commands::quote::Link::new(db.clone()))
+        .command(crate::commands::quote::Merge::new(db.clone()))
+        .command(crate::commands::quote::Mine::new(db.clone()))
+        .command(crate::commands::quote::OfTheDay::new(db.clone()))
+        .command(crate::commands::quote::PinQuote::new(db.clone()))
+        .command(crate::commands::quote::Pins::new(db.clone()))
         .command(crate::commands::quote::QueryDebugger::new())
+        .command(crate::commands::quote::RandomUnseen::new(db.clone()))
+        .command(crate::commands::quote::Recent::new(db.clone()))
+        .command(crate::commands::quote::RenameGame::new(db.clone()))
+        .command(crate::commands::quote::Report::new(db.clone()))
+        .command(crate::commands::quote::SearchWeb::new())
+        .command(crate::commands::quote::Shows::new(db.clone()))
+        .command(crate::commands::quote::TagQuote::new(db.clone()))
+        .command(crate::commands::serverinfo::ServerInfo::new())
         .command(crate::commands::time::Time::new_12())
         .command(crate::commands::time::Time::new_24())
+        .command(crate::commands::time::Timestamp::new())
+        .command(crate::commands::timeout::Timeout::new())
+        .command(crate::commands::timer::Timer::new(running_rx.clone()))
         .command(crate::commands::tracing::TracingFilter::new(reload_handle.clone()))
+        .command(crate::commands::userinfo::UserInfo::new())
         .command_opt(crate::commands::video::New::new(&config, youtube.clone()))
+        .command_opt(crate::commands::video::NewPlaylist::new(&config, youtube.clone()))
         .command_opt(crate::commands::video::Refresh::new(&config, youtube.clone()))
         .command(crate::commands::voice::Voice::new())
+        .commands(http_commands)
         // this command is after all other quote commands to avoid conflicts
         .command(crate::commands::quote::Find::new(db.clone()))
         // this is the last command on purpose to avoid conflicts
         .command(crate::commands::static_response::Static::new(db.clone()))
+        .observer(afk)
+        .metrics(influxdb.clone())
+        .prometheus(prometheus.clone())
         .build(cache.clone(), config.clone(), discord.clone())
         .context("failed to build the command parser")?;
 
+    let interactions =
+        match crate::interactions::Interactions::new(config.clone(), db.clone(), discord.clone())
+            .await
+        {
+            Ok(interactions) => Some(interactions),
+            Err(error) => {
+                tracing::warn!(?error, "failed to set up the slash commands");
+                None
+            }
+        };
+
     #[cfg(target_os = "linux")]
     let sd_notify = match crate::systemd::Notify::new() {
         Ok(notify) => Some(Arc::new(notify)),
@@ -292,20 +444,39 @@ async fn main() -> Result<(), Error> {
             None
         }
     };
+    #[cfg(target_os = "linux")]
+    if let Some(ref sd_notify) = sd_notify {
+        match crate::systemd::watchdog_feed_interval() {
+            Ok(Some(interval)) => {
+                tasks.push(tokio::spawn(crate::systemd::feed_watchdog_periodically(
+                    running_rx.clone(),
+                    sd_notify.clone(),
+                    interval,
+                )))
+            }
+            Ok(None) => (),
+            Err(error) => tracing::warn!(?error, "failed to read the systemd watchdog timeout"),
+        }
+    }
 
     let intents = Intents::GUILDS
         | Intents::GUILD_MEMBERS
+        | Intents::GUILD_PRESENCES
         | Intents::GUILD_EMOJIS_AND_STICKERS
         | Intents::GUILD_VOICE_STATES
         | Intents::GUILD_MESSAGES
+        | Intents::GUILD_MESSAGE_REACTIONS
         | Intents::DIRECT_MESSAGES
         | Intents::MESSAGE_CONTENT;
 
     let shard_config = twilight_gateway::Config::new(config.discord_botsecret.clone(), intents);
     let presence = UpdatePresencePayload::new(
         vec![MinimalActivity {
-            kind: ActivityType::Listening,
-            name: format!("{}help || v{}", config.command_prefix, env!("CARGO_PKG_VERSION")),
+            kind: config.presence.kind,
+            name: config
+                .presence
+                .format(&config.command_prefix, env!("CARGO_PKG_VERSION"))
+                .context("failed to format the presence")?,
             url: Some("https://lrrbot.com/".into()),
         }
         .into()],
@@ -323,12 +494,13 @@ async fn main() -> Result<(), Error> {
     for mut shard in shards {
         let cache = cache.clone();
         let command_parser = command_parser.clone();
+        let interactions = interactions.clone();
         let discord = discord.clone();
         let influxdb = influxdb.clone();
+        let message_sample_rate = config.message_metrics_sample_rate;
+        let config_rx = config_rx.clone();
         let mut running_rx = running_rx.clone();
         let handler_tx = handler_tx.clone();
-        #[cfg(target_os = "linux")]
-        let sd_notify = sd_notify.clone();
 
         tasks.push(tokio::spawn(async move {
             let shard_id = shard.id();
@@ -338,16 +510,15 @@ async fn main() -> Result<(), Error> {
                     _ = running_rx.changed() => break,
                     res = shard.next_event(EventTypeFlags::all()) => match res {
                         Some(Ok(event)) => {
-                            #[cfg(target_os = "linux")]
-                            if let Some(sd_notify) = sd_notify.as_ref() {
-                                if let Err(error) = sd_notify.feed_watchdog().await {
-                                    tracing::warn!(?error, "failed to feed the systemd watchdog");
-                                }
-                            }
-
                             if let Some(ref influxdb) = influxdb {
                                 if let Err(error) =
-                                    crate::metrics::on_event(&cache, influxdb, &event).await
+                                    crate::metrics::on_event(
+                                        &cache,
+                                        influxdb,
+                                        message_sample_rate,
+                                        &event,
+                                    )
+                                    .await
                                 {
                                     tracing::error!(?error, "failed to collect metrics");
                                 }
@@ -355,9 +526,14 @@ async fn main() -> Result<(), Error> {
 
                             cache.update(&event);
 
-                            crate::disconnect_afk::on_event(&cache, &discord, &event).await;
+                            let config = config_rx.borrow().clone();
+                            crate::disconnect_afk::on_event(&cache, &config, &discord, &event)
+                                .await;
 
                             command_parser.on_event(&handler_tx, &event).await;
+                            if let Some(ref interactions) = interactions {
+                                interactions.on_event(&handler_tx, &event).await;
+                            }
                         }
                         Some(Err(error)) => {
                             tracing::error!(
@@ -401,3 +577,52 @@ async fn main() -> Result<(), Error> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error as StdError;
+    use std::future::Future;
+    use std::pin::Pin;
+
+    use google_sheets4::common::GetToken;
+
+    use super::{check_google_scopes, required_google_scopes};
+
+    #[derive(Clone)]
+    struct DenyingAuth {
+        denied: &'static str,
+    }
+
+    impl GetToken for DenyingAuth {
+        fn get_token<'a>(
+            &'a self,
+            scopes: &'a [&str],
+        ) -> Pin<
+            Box<
+                dyn Future<Output = Result<Option<String>, Box<dyn StdError + Send + Sync>>>
+                    + Send
+                    + 'a,
+            >,
+        > {
+            Box::pin(async move {
+                if scopes.contains(&self.denied) {
+                    Err("unauthorized_client: Client is unauthorized to retrieve access tokens using this method".into())
+                } else {
+                    Ok(Some("token".into()))
+                }
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn google_scopes() {
+        for (_, scope) in required_google_scopes() {
+            assert!(check_google_scopes(&DenyingAuth { denied: "none" }).await.is_ok());
+
+            let error = check_google_scopes(&DenyingAuth { denied: scope }).await.unwrap_err();
+            let message = format!("{error:#}");
+            assert!(message.contains(scope), "{message}");
+            assert!(message.contains("unauthorized_client"), "{message}");
+        }
+    }
+}
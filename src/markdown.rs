@@ -22,9 +22,53 @@ pub fn escape_code_block(text: &str) -> String {
     text.replace("```", "`\\``")
 }
 
+/// Wrap `body` in a fenced code block, highlighted as `lang` if given.
+pub fn code_block(lang: Option<&str>, body: &str) -> String {
+    format!("```{}\n{}\n```", lang.unwrap_or(""), escape_code_block(body.trim_end_matches('\n')))
+}
+
+/// Wrap `body` in spoiler tags. Any unescaped `|` in `body` is escaped so that it can't close the
+/// spoiler early.
+pub fn spoiler(body: &str) -> String {
+    let mut spoiler = String::with_capacity(body.len() + 4);
+    spoiler.push_str("||");
+    let mut escaped = false;
+    for c in body.chars() {
+        if c == '|' && !escaped {
+            spoiler.push('\\');
+        }
+        escaped = c == '\\' && !escaped;
+        spoiler.push(c);
+    }
+    spoiler.push_str("||");
+    spoiler
+}
+
 pub fn suppress_embeds(text: &str) -> Cow<str> {
     static RE_URL: OnceLock<Regex> = OnceLock::new();
     let re_url = RE_URL.get_or_init(|| Regex::new(r"(https?://\S+)").unwrap());
 
     re_url.replace_all(text, "<$1>")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{code_block, spoiler};
+
+    #[test]
+    fn code_blocks() {
+        assert_eq!(code_block(None, "let x = 1;"), "```\nlet x = 1;\n```");
+        assert_eq!(code_block(Some("rust"), "let x = 1;\n"), "```rust\nlet x = 1;\n```");
+        assert_eq!(code_block(Some("md"), "```\ncode\n```"), "```md\n`\\``\ncode\n`\\``\n```");
+    }
+
+    #[test]
+    fn spoilers() {
+        assert_eq!(spoiler("Snape kills Dumbledore"), "||Snape kills Dumbledore||");
+        assert_eq!(spoiler("dead || alive"), r"||dead \|\| alive||");
+        assert_eq!(spoiler("trailing|"), r"||trailing\|||");
+        // Already escaped pipes are left alone.
+        assert_eq!(spoiler(r"dead \|\| alive"), r"||dead \|\| alive||");
+        assert_eq!(spoiler(r"backslash\\|"), r"||backslash\\\|||");
+    }
+}
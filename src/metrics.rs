@@ -1,17 +1,22 @@
 use std::fmt::Write;
+use std::time::Duration;
 
-use anyhow::{Context, Error};
+use anyhow::Error;
 use bytes::BufMut;
 use chrono::{DateTime, Utc};
 use influxdb_line_protocol::LineProtocolBuilder;
 use tracing::{error, warn};
 use twilight_gateway::Event;
+use twilight_model::channel::message::EmojiReactionType;
 use twilight_model::channel::{Channel, ChannelType};
 use twilight_model::gateway::payload::incoming::{
-    ChannelCreate, ChannelDelete, ChannelUpdate, GuildCreate, MessageCreate, ThreadCreate,
-    ThreadDelete, ThreadUpdate, VoiceStateUpdate,
+    ChannelCreate, ChannelDelete, ChannelUpdate, GuildCreate, MemberAdd, MemberRemove,
+    MessageCreate, PresenceUpdate, ReactionAdd, ReactionRemove, ThreadCreate, ThreadDelete,
+    ThreadUpdate, VoiceStateUpdate,
 };
-use twilight_model::id::marker::UserMarker;
+use twilight_model::gateway::presence::Status;
+use twilight_model::gateway::GatewayReaction;
+use twilight_model::id::marker::{GuildMarker, UserMarker};
 use twilight_model::id::Id;
 
 use crate::cache::Cache;
@@ -19,19 +24,31 @@ use crate::influxdb::InfluxDb;
 
 const TEXT_CHANNELS_MEASUREMENT: &str = "text_channels";
 const VOICE_CHANNELS_MEASUREMENT: &str = "voice_channels";
+const REACTIONS_MEASUREMENT: &str = "reactions";
+const PRESENCES_MEASUREMENT: &str = "presences";
+const MEMBERS_MEASUREMENT: &str = "members";
+const COMMAND_LATENCY_MEASUREMENT: &str = "command_latency";
 
 struct Measurement<'a> {
     time: DateTime<Utc>,
 
     event: &'a str,
+    guild_id: Option<u64>,
     channel_id: Option<u64>,
     channel_name: Option<&'a str>,
     category_id: Option<u64>,
     thread_id: Option<u64>,
     thread_name: Option<&'a str>,
     user_id: Option<u64>,
+    emoji_id: Option<u64>,
+    emoji_name: Option<&'a str>,
+    status: Option<&'a str>,
+    previous_status: Option<&'a str>,
+    command: Option<&'a str>,
 
     count: f64,
+    member_count: Option<u64>,
+    latency: Option<f64>,
     users: Option<String>,
 }
 
@@ -46,14 +63,87 @@ impl<'a> Measurement<'a> {
         Self {
             time,
             event,
+            guild_id: None,
             channel_id: channel.map(|c| c.id.get()),
             channel_name: channel.and_then(|c| c.name.as_deref()),
             category_id: channel.and_then(|c| c.parent_id.map(Id::get)),
             thread_id: thread.map(|t| t.id.get()),
             thread_name: thread.and_then(|t| t.name.as_deref()),
             count: count as f64,
+            member_count: None,
+            latency: None,
             users: None,
             user_id: None,
+            emoji_id: None,
+            emoji_name: None,
+            status: None,
+            previous_status: None,
+            command: None,
+        }
+    }
+
+    /// A user's status changed from `previous` to `status`.
+    ///
+    /// Users without a cached presence are offline. Presence updates that don't change the
+    /// status, like activity changes, make up most of the presence updates and aren't recorded.
+    fn presence(time: DateTime<Utc>, previous: Option<Status>, status: Status) -> Option<Self> {
+        let previous = previous.unwrap_or(Status::Offline);
+        if status_name(previous) == status_name(status) {
+            return None;
+        }
+
+        Some(Self {
+            status: Some(status_name(status)),
+            previous_status: Some(status_name(previous)),
+            ..Self::new(time, "presence_update", None, None, 1)
+        })
+    }
+
+    /// A member joined (`count` of 1) or left (`count` of -1) the guild.
+    ///
+    /// `member_count` is the member count after the event, if it's known.
+    fn member(
+        time: DateTime<Utc>,
+        event: &'a str,
+        guild_id: Id<GuildMarker>,
+        count: f64,
+        member_count: Option<u64>,
+    ) -> Self {
+        Self {
+            guild_id: Some(guild_id.get()),
+            count,
+            member_count,
+            ..Self::new(time, event, None, None, 0)
+        }
+    }
+
+    /// A command handler took `latency` to run.
+    fn command(time: DateTime<Utc>, command: &'a str, latency: Duration) -> Self {
+        Self {
+            command: Some(command),
+            latency: Some(latency.as_secs_f64()),
+            ..Self::new(time, "command", None, None, 1)
+        }
+    }
+
+    fn reaction(
+        time: DateTime<Utc>,
+        event: &'a str,
+        channel: Option<&'a Channel>,
+        thread: Option<&'a Channel>,
+        reaction: &'a GatewayReaction,
+        count: f64,
+    ) -> Self {
+        let (emoji_id, emoji_name) = match reaction.emoji {
+            EmojiReactionType::Custom { id, ref name, .. } => (Some(id.get()), name.as_deref()),
+            EmojiReactionType::Unicode { ref name } => (None, Some(name.as_str())),
+        };
+
+        Self {
+            count,
+            emoji_id,
+            emoji_name,
+            ..Self::new(time, event, channel, thread, 0).user_id(reaction.user_id)
         }
     }
 
@@ -75,6 +165,14 @@ impl<'a> Measurement<'a> {
     fn user_id(self, user_id: Id<UserMarker>) -> Self {
         Self { user_id: Some(user_id.get()), ..self }
     }
+
+    /// The first float field that's NaN or infinite. Line protocol can't represent those, and
+    /// InfluxDB rejects the whole batch if it contains one.
+    fn non_finite_field(&self) -> Option<(&'static str, f64)> {
+        [("count", Some(self.count)), ("latency", self.latency)]
+            .into_iter()
+            .find_map(|(field, value)| Some((field, value.filter(|value| !value.is_finite())?)))
+    }
 }
 
 trait LineProtocolBuilderExt {
@@ -83,7 +181,24 @@ trait LineProtocolBuilderExt {
 
 impl<B: BufMut + Default> LineProtocolBuilderExt for LineProtocolBuilder<B> {
     fn append(&mut self, name: &str, measurement: Measurement) {
+        if let Some((field, value)) = measurement.non_finite_field() {
+            warn!(
+                measurement = name,
+                event = measurement.event,
+                field,
+                value,
+                timestamp = measurement.time.to_rfc3339(),
+                "Dropping a measurement with a non-finite field"
+            );
+            return;
+        }
+
         let builder = std::mem::take(self).measurement(name).tag("event", measurement.event);
+        let builder = if let Some(guild_id) = measurement.guild_id {
+            builder.tag("guild_id", &guild_id.to_string())
+        } else {
+            builder
+        };
         let builder = if let Some(channel_id) = measurement.channel_id {
             builder.tag("channel_id", &channel_id.to_string())
         } else {
@@ -114,7 +229,43 @@ impl<B: BufMut + Default> LineProtocolBuilderExt for LineProtocolBuilder<B> {
         } else {
             builder
         };
+        let builder = if let Some(emoji_id) = measurement.emoji_id {
+            builder.tag("emoji_id", &emoji_id.to_string())
+        } else {
+            builder
+        };
+        let builder = if let Some(emoji_name) = measurement.emoji_name {
+            builder.tag("emoji_name", emoji_name)
+        } else {
+            builder
+        };
+        let builder = if let Some(status) = measurement.status {
+            builder.tag("status", status)
+        } else {
+            builder
+        };
+        let builder = if let Some(previous_status) = measurement.previous_status {
+            builder.tag("previous_status", previous_status)
+        } else {
+            builder
+        };
+        let builder = if let Some(command) = measurement.command {
+            builder.tag("command", command)
+        } else {
+            builder
+        };
         let builder = builder.field("count", measurement.count);
+        let builder = if let Some(member_count) = measurement.member_count {
+            // InfluxDB 1.x rejects the whole write if it has an unsigned integer in it.
+            builder.field("member_count", member_count as i64)
+        } else {
+            builder
+        };
+        let builder = if let Some(latency) = measurement.latency {
+            builder.field("latency", latency)
+        } else {
+            builder
+        };
         let builder = if let Some(users) = measurement.users.as_deref() {
             builder.field("users", users)
         } else {
@@ -129,7 +280,17 @@ impl<B: BufMut + Default> LineProtocolBuilderExt for LineProtocolBuilder<B> {
     }
 }
 
-fn is_guild_text_channel(kind: ChannelType) -> bool {
+fn status_name(status: Status) -> &'static str {
+    match status {
+        Status::DoNotDisturb => "dnd",
+        Status::Idle => "idle",
+        // Invisible users appear offline to everyone else.
+        Status::Invisible | Status::Offline => "offline",
+        Status::Online => "online",
+    }
+}
+
+pub fn is_guild_text_channel(kind: ChannelType) -> bool {
     match kind {
         ChannelType::GuildText
         | ChannelType::GuildVoice
@@ -152,7 +313,7 @@ fn is_guild_text_channel(kind: ChannelType) -> bool {
     }
 }
 
-fn is_guild_voice_channel(kind: ChannelType) -> bool {
+pub fn is_guild_voice_channel(kind: ChannelType) -> bool {
     match kind {
         ChannelType::GuildVoice | ChannelType::GuildStageVoice => true,
 
@@ -174,7 +335,26 @@ fn is_guild_voice_channel(kind: ChannelType) -> bool {
     }
 }
 
-pub async fn on_event(cache: &Cache, influxdb: &InfluxDb, event: &Event) -> Result<(), Error> {
+/// Record how long the handler of `command` took to run, in seconds.
+pub fn on_command(influxdb: &InfluxDb, command: &str, latency: Duration) {
+    let mut measurements = LineProtocolBuilder::new();
+    measurements
+        .append(COMMAND_LATENCY_MEASUREMENT, Measurement::command(Utc::now(), command, latency));
+    influxdb.write(measurements);
+}
+
+/// How much a message counts for when only `rate` of the messages are recorded, or `None` if the
+/// message isn't sampled. `roll` is uniformly distributed in `[0, 1)`.
+fn message_weight(rate: f64, roll: f64) -> Option<f64> {
+    (roll < rate).then(|| 1.0 / rate)
+}
+
+pub async fn on_event(
+    cache: &Cache,
+    influxdb: &InfluxDb,
+    message_sample_rate: f64,
+    event: &Event,
+) -> Result<(), Error> {
     let mut measurements = LineProtocolBuilder::new();
     let time = Utc::now();
 
@@ -442,6 +622,9 @@ pub async fn on_event(cache: &Cache, influxdb: &InfluxDb, event: &Event) -> Resu
 
         Event::MessageCreate(event) => {
             let MessageCreate(ref message) = **event;
+            let Some(weight) = message_weight(message_sample_rate, rand::random()) else {
+                return Ok(());
+            };
 
             cache.with(|cache| {
                 let (channel, thread) = if let Some(channel) = cache.channel(message.channel_id) {
@@ -461,16 +644,327 @@ pub async fn on_event(cache: &Cache, influxdb: &InfluxDb, event: &Event) -> Resu
 
                 measurements.append(
                     TEXT_CHANNELS_MEASUREMENT,
-                    Measurement::new(time, "message", channel.as_deref(), thread.as_deref(), 1)
-                        .user_id(message.author.id),
+                    Measurement {
+                        count: weight,
+                        ..Measurement::new(
+                            time,
+                            "message",
+                            channel.as_deref(),
+                            thread.as_deref(),
+                            0,
+                        )
+                        .user_id(message.author.id)
+                    },
                 );
             });
         }
 
+        Event::ReactionAdd(event) => {
+            let ReactionAdd(ref reaction) = **event;
+            append_reaction(cache, &mut measurements, time, "reaction_add", reaction, 1.0);
+        }
+        Event::ReactionRemove(event) => {
+            let ReactionRemove(ref reaction) = **event;
+            append_reaction(cache, &mut measurements, time, "reaction_remove", reaction, -1.0);
+        }
+
+        Event::PresenceUpdate(event) => {
+            let PresenceUpdate(ref presence) = **event;
+
+            let previous = cache.with(|cache| {
+                cache
+                    .presence(presence.guild_id, presence.user.id())
+                    .map(|presence| presence.status())
+            });
+            if let Some(measurement) = Measurement::presence(time, previous, presence.status) {
+                measurements.append(PRESENCES_MEASUREMENT, measurement);
+            }
+        }
+
+        Event::MemberAdd(event) => {
+            let MemberAdd { guild_id, ref member } = **event;
+
+            // NOTE: the cache hasn't seen this event yet, so the cached count doesn't include the
+            // new member.
+            let member_count = cache.with(|cache| {
+                cache.guild(guild_id).and_then(|guild| guild.member_count()).map(|count| count + 1)
+            });
+            measurements.append(
+                MEMBERS_MEASUREMENT,
+                Measurement::member(time, "member_add", guild_id, 1.0, member_count)
+                    .user_id(member.user.id),
+            );
+        }
+        Event::MemberRemove(event) => {
+            let MemberRemove { guild_id, ref user } = *event;
+
+            // NOTE: the cache hasn't seen this event yet, so the cached count still includes the
+            // member that left.
+            let member_count = cache.with(|cache| {
+                cache
+                    .guild(guild_id)
+                    .and_then(|guild| guild.member_count())
+                    .map(|count| count.saturating_sub(1))
+            });
+            measurements.append(
+                MEMBERS_MEASUREMENT,
+                Measurement::member(time, "member_remove", guild_id, -1.0, member_count)
+                    .user_id(user.id),
+            );
+        }
+
         _ => (),
     }
 
-    influxdb.write(measurements).await.context("failed to write the user count to InfluxDB")?;
+    influxdb.write(measurements);
 
     Ok(())
 }
+
+fn append_reaction(
+    cache: &Cache,
+    measurements: &mut LineProtocolBuilder<Vec<u8>>,
+    time: DateTime<Utc>,
+    event: &str,
+    reaction: &GatewayReaction,
+    count: f64,
+) {
+    if reaction.guild_id.is_none() {
+        // don't collect stats on direct messages
+        return;
+    }
+
+    cache.with(|cache| {
+        let (channel, thread) = match cache.channel(reaction.channel_id) {
+            Some(channel) if channel.kind.is_thread() => {
+                (channel.parent_id.and_then(|id| cache.channel(id)), Some(channel))
+            }
+            channel => (channel, None),
+        };
+
+        measurements.append(
+            REACTIONS_MEASUREMENT,
+            Measurement::reaction(
+                time,
+                event,
+                channel.as_deref(),
+                thread.as_deref(),
+                reaction,
+                count,
+            ),
+        );
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::{TimeZone, Utc};
+    use influxdb_line_protocol::LineProtocolBuilder;
+    use twilight_model::channel::message::EmojiReactionType;
+    use twilight_model::gateway::presence::Status;
+    use twilight_model::gateway::GatewayReaction;
+    use twilight_model::id::Id;
+
+    use super::{
+        message_weight, LineProtocolBuilderExt, Measurement, COMMAND_LATENCY_MEASUREMENT,
+        MEMBERS_MEASUREMENT, PRESENCES_MEASUREMENT, REACTIONS_MEASUREMENT,
+        TEXT_CHANNELS_MEASUREMENT,
+    };
+
+    fn reaction(emoji: EmojiReactionType) -> GatewayReaction {
+        GatewayReaction {
+            burst: false,
+            burst_colors: vec![],
+            channel_id: Id::new(2),
+            emoji,
+            guild_id: Some(Id::new(1)),
+            member: None,
+            message_author_id: None,
+            message_id: Id::new(3),
+            user_id: Id::new(4),
+        }
+    }
+
+    #[test]
+    fn reaction_add() {
+        let time = Utc.timestamp_opt(1, 0).unwrap();
+        let reaction = reaction(EmojiReactionType::Custom {
+            animated: false,
+            id: Id::new(5),
+            name: Some(String::from("lrrSPOOP")),
+        });
+
+        let mut measurements = LineProtocolBuilder::new();
+        measurements.append(
+            REACTIONS_MEASUREMENT,
+            Measurement::reaction(time, "reaction_add", None, None, &reaction, 1.0),
+        );
+
+        assert_eq!(
+            String::from_utf8(measurements.build()).unwrap(),
+            "reactions,event=reaction_add,user_id=4,emoji_id=5,emoji_name=lrrSPOOP count=1 1000000000\n"
+        );
+    }
+
+    #[test]
+    fn message_sampling() {
+        assert_eq!(message_weight(1.0, 0.0), Some(1.0));
+        assert_eq!(message_weight(1.0, 0.999), Some(1.0));
+        assert_eq!(message_weight(0.25, 0.1), Some(4.0));
+        assert_eq!(message_weight(0.25, 0.25), None);
+        assert_eq!(message_weight(0.25, 0.9), None);
+
+        let time = Utc.timestamp_opt(1, 0).unwrap();
+        let mut measurements = LineProtocolBuilder::new();
+        measurements.append(
+            TEXT_CHANNELS_MEASUREMENT,
+            Measurement {
+                count: message_weight(0.25, 0.1).unwrap(),
+                ..Measurement::new(time, "message", None, None, 0).user_id(Id::new(4))
+            },
+        );
+
+        assert_eq!(
+            String::from_utf8(measurements.build()).unwrap(),
+            "text_channels,event=message,user_id=4 count=4 1000000000\n"
+        );
+    }
+
+    #[test]
+    fn reaction_remove() {
+        let time = Utc.timestamp_opt(1, 0).unwrap();
+        let reaction = reaction(EmojiReactionType::Unicode { name: String::from("\u{1F44D}") });
+
+        let mut measurements = LineProtocolBuilder::new();
+        measurements.append(
+            REACTIONS_MEASUREMENT,
+            Measurement::reaction(time, "reaction_remove", None, None, &reaction, -1.0),
+        );
+
+        assert_eq!(
+            String::from_utf8(measurements.build()).unwrap(),
+            "reactions,event=reaction_remove,user_id=4,emoji_name=\u{1F44D} count=-1 1000000000\n"
+        );
+    }
+
+    #[test]
+    fn presence_transitions() {
+        let time = Utc.timestamp_opt(1, 0).unwrap();
+
+        let mut measurements = LineProtocolBuilder::new();
+        for (previous, status) in [
+            (None, Status::Online),
+            (Some(Status::Online), Status::Idle),
+            (Some(Status::DoNotDisturb), Status::Invisible),
+        ] {
+            measurements.append(
+                PRESENCES_MEASUREMENT,
+                Measurement::presence(time, previous, status).unwrap(),
+            );
+        }
+
+        assert_eq!(
+            String::from_utf8(measurements.build()).unwrap(),
+            concat!(
+                "presences,event=presence_update,status=online,previous_status=offline count=1 1000000000\n",
+                "presences,event=presence_update,status=idle,previous_status=online count=1 1000000000\n",
+                "presences,event=presence_update,status=offline,previous_status=dnd count=1 1000000000\n",
+            )
+        );
+    }
+
+    #[test]
+    fn presence_unchanged() {
+        let time = Utc.timestamp_opt(1, 0).unwrap();
+
+        assert!(Measurement::presence(time, Some(Status::Online), Status::Online).is_none());
+        assert!(Measurement::presence(time, None, Status::Offline).is_none());
+        assert!(Measurement::presence(time, Some(Status::Invisible), Status::Offline).is_none());
+    }
+
+    #[test]
+    fn member_add() {
+        let time = Utc.timestamp_opt(1, 0).unwrap();
+
+        let mut measurements = LineProtocolBuilder::new();
+        measurements.append(
+            MEMBERS_MEASUREMENT,
+            Measurement::member(time, "member_add", Id::new(1), 1.0, Some(1235))
+                .user_id(Id::new(4)),
+        );
+
+        assert_eq!(
+            String::from_utf8(measurements.build()).unwrap(),
+            "members,event=member_add,guild_id=1,user_id=4 count=1,member_count=1235i 1000000000\n"
+        );
+    }
+
+    #[test]
+    fn member_remove() {
+        let time = Utc.timestamp_opt(1, 0).unwrap();
+
+        let mut measurements = LineProtocolBuilder::new();
+        measurements.append(
+            MEMBERS_MEASUREMENT,
+            Measurement::member(time, "member_remove", Id::new(1), -1.0, Some(1233))
+                .user_id(Id::new(4)),
+        );
+        measurements.append(
+            MEMBERS_MEASUREMENT,
+            Measurement::member(time, "member_remove", Id::new(1), -1.0, None).user_id(Id::new(5)),
+        );
+
+        assert_eq!(
+            String::from_utf8(measurements.build()).unwrap(),
+            concat!(
+                "members,event=member_remove,guild_id=1,user_id=4 count=-1,member_count=1233i 1000000000\n",
+                "members,event=member_remove,guild_id=1,user_id=5 count=-1 1000000000\n",
+            )
+        );
+    }
+
+    #[test]
+    fn command_latency() {
+        let time = Utc.timestamp_opt(1, 0).unwrap();
+
+        let mut measurements = LineProtocolBuilder::new();
+        measurements.append(
+            COMMAND_LATENCY_MEASUREMENT,
+            Measurement::command(time, "quote", Duration::from_millis(250)),
+        );
+
+        assert_eq!(
+            String::from_utf8(measurements.build()).unwrap(),
+            "command_latency,event=command,command=quote count=1,latency=0.25 1000000000\n"
+        );
+    }
+
+    #[test]
+    fn non_finite_fields() {
+        let time = Utc.timestamp_opt(1, 0).unwrap();
+
+        let mut measurements = LineProtocolBuilder::new();
+        measurements.append(
+            MEMBERS_MEASUREMENT,
+            Measurement::member(time, "member_add", Id::new(1), f64::NAN, None),
+        );
+        measurements.append(
+            MEMBERS_MEASUREMENT,
+            Measurement::member(time, "member_add", Id::new(1), 1.0, Some(1235)),
+        );
+        measurements.append(
+            COMMAND_LATENCY_MEASUREMENT,
+            Measurement {
+                latency: Some(f64::INFINITY),
+                ..Measurement::command(time, "quote", Duration::from_millis(250))
+            },
+        );
+
+        assert_eq!(
+            String::from_utf8(measurements.build()).unwrap(),
+            "members,event=member_add,guild_id=1 count=1,member_count=1235i 1000000000\n"
+        );
+    }
+}
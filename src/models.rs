@@ -156,6 +156,17 @@ pub mod game {
         pub name: String,
     }
 
+    impl Model {
+        /// The name of the game as shown on a particular show: the show-specific display name if
+        /// there is one, otherwise the game's own name.
+        pub fn effective_name<'a>(
+            &'a self,
+            entry: Option<&'a super::game_entry::Model>,
+        ) -> &'a str {
+            entry.and_then(|entry| entry.display_name.as_deref()).unwrap_or(&self.name)
+        }
+    }
+
     #[derive(Debug, EnumIter, DeriveRelation)]
     pub enum Relation {
         #[sea_orm(has_many = "super::game_entry::Entity")]
@@ -295,7 +306,7 @@ pub mod state {
     use anyhow::{Context, Error};
     use sea_orm::entity::prelude::*;
     use sea_orm::sea_query::OnConflict;
-    use sea_orm::{DbBackend, Insert, Statement};
+    use sea_orm::{DatabaseTransaction, DbBackend, Insert, QuerySelect, Statement};
     use serde::de::DeserializeOwned;
     use serde::Serialize;
 
@@ -314,7 +325,7 @@ pub mod state {
 
     pub async fn get<T: DeserializeOwned>(
         key: &str,
-        conn: &DatabaseConnection,
+        conn: &impl ConnectionTrait,
     ) -> Result<Option<T>, Error> {
         let state = Entity::find()
             .filter(Column::Key.eq(key))
@@ -333,7 +344,7 @@ pub mod state {
     pub async fn set<T: Serialize>(
         key: String,
         value: T,
-        conn: &DatabaseConnection,
+        conn: &impl ConnectionTrait,
     ) -> Result<(), Error> {
         Insert::one(Model {
             key,
@@ -347,11 +358,46 @@ pub mod state {
         Ok(())
     }
 
+    pub async fn delete(key: &str, conn: &impl ConnectionTrait) -> Result<(), Error> {
+        Entity::delete_by_id(key)
+            .exec(conn)
+            .await
+            .with_context(|| format!("failed to delete state key {key:?}"))?;
+
+        Ok(())
+    }
+
+    /// Load the value of `key` and lock it until the end of the transaction, so that it can be
+    /// updated without losing concurrent updates. A missing key is created with the default value
+    /// first as there has to be a row to lock.
+    pub async fn get_for_update<T: DeserializeOwned + Serialize + Default>(
+        key: &str,
+        txn: &DatabaseTransaction,
+    ) -> Result<T, Error> {
+        Insert::one(Model {
+            key: String::from(key),
+            value: serde_json::to_value(T::default()).context("failed to serialize value")?,
+        })
+        .on_conflict(OnConflict::column(Column::Key).do_nothing().to_owned())
+        .exec_without_returning(txn)
+        .await
+        .with_context(|| format!("failed to create state key {key:?}"))?;
+
+        let state = Entity::find_by_id(key)
+            .lock_exclusive()
+            .one(txn)
+            .await
+            .with_context(|| format!("failed to lock state key {key:?}"))?
+            .with_context(|| format!("state key {key:?} is missing"))?;
+
+        serde_json::from_value(state.value).context("failed to parse the value")
+    }
+
     pub async fn insert_fifo_cache<T: Serialize>(
         key: String,
         value: T,
         max_entries: u32,
-        conn: &DatabaseConnection,
+        conn: &impl ConnectionTrait,
     ) -> Result<(), Error> {
         // TODO: do this with sea-orm. Currently there is no way to reference `EXCLUDED.value`.
         conn.execute(Statement::from_sql_and_values(
@@ -403,3 +449,23 @@ pub mod user {
 
     impl ActiveModelBehavior for ActiveModel {}
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{game, game_entry};
+
+    #[test]
+    fn effective_name() {
+        let game = game::Model { id: 1, name: String::from("Magic: The Gathering") };
+        let entry = |display_name: Option<&str>| game_entry::Model {
+            game_id: 1,
+            show_id: 2,
+            display_name: display_name.map(String::from),
+            verified: None,
+        };
+
+        assert_eq!(game.effective_name(Some(&entry(Some("Draft")))), "Draft");
+        assert_eq!(game.effective_name(Some(&entry(None))), "Magic: The Gathering");
+        assert_eq!(game.effective_name(None), "Magic: The Gathering");
+    }
+}
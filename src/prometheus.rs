@@ -0,0 +1,231 @@
+//! The metrics in the Prometheus text format, for scraping instead of pushing them to InfluxDB.
+//!
+//! Served at `/metrics` on the address in `prometheus_listen`.
+
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use bytes::Bytes;
+use http_body_util::Full;
+use hyper::body::Incoming;
+use hyper::header::CONTENT_TYPE;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use tokio::net::TcpListener;
+use tokio::sync::watch;
+use tracing::error;
+
+use crate::cache::Cache;
+use crate::metrics::{is_guild_text_channel, is_guild_voice_channel};
+
+const CONTENT_TYPE_TEXT: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+#[derive(Default)]
+struct CommandStats {
+    count: u64,
+    latency: f64,
+}
+
+/// The counters that can't be read off the cache at scrape time.
+#[derive(Clone, Default)]
+pub struct Prometheus {
+    commands: Arc<Mutex<BTreeMap<String, CommandStats>>>,
+}
+
+impl Prometheus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a run of `command` that took `latency`.
+    pub fn on_command(&self, command: &str, latency: Duration) {
+        let mut commands = self.commands.lock().unwrap();
+        let stats = commands.entry(String::from(command)).or_default();
+        stats.count += 1;
+        stats.latency += latency.as_secs_f64();
+    }
+
+    /// Render the metrics in the text exposition format.
+    fn render(&self, cache: &Cache) -> String {
+        let mut out = String::new();
+
+        {
+            let commands = self.commands.lock().unwrap();
+            header(&mut out, "eris_commands_total", "counter", "Commands handled.");
+            for (command, stats) in commands.iter() {
+                sample(&mut out, "eris_commands_total", ("command", command), stats.count as f64);
+            }
+            header(
+                &mut out,
+                "eris_command_latency_seconds_total",
+                "counter",
+                "Time spent handling commands.",
+            );
+            for (command, stats) in commands.iter() {
+                sample(
+                    &mut out,
+                    "eris_command_latency_seconds_total",
+                    ("command", command),
+                    stats.latency,
+                );
+            }
+        }
+
+        cache.with(|cache| {
+            let (text, voice) = cache.iter().channels().fold((0, 0), |(text, voice), channel| {
+                (
+                    text + usize::from(is_guild_text_channel(channel.kind)),
+                    voice + usize::from(is_guild_voice_channel(channel.kind)),
+                )
+            });
+            header(&mut out, "eris_channels", "gauge", "Guild channels by kind.");
+            sample(&mut out, "eris_channels", ("kind", "text"), text as f64);
+            sample(&mut out, "eris_channels", ("kind", "voice"), voice as f64);
+
+            let stats = cache.stats();
+            header(&mut out, "eris_cache_entries", "gauge", "Entries in the Discord cache.");
+            for (kind, entries) in [
+                ("channels", stats.channels()),
+                ("emojis", stats.emojis()),
+                ("guilds", stats.guilds()),
+                ("members", stats.members()),
+                ("presences", stats.presences()),
+                ("roles", stats.roles()),
+                ("users", stats.users()),
+                ("voice_states", stats.voice_states()),
+            ] {
+                sample(&mut out, "eris_cache_entries", ("kind", kind), entries as f64);
+            }
+        });
+
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+fn sample(out: &mut String, name: &str, (label, value): (&str, &str), sample: f64) {
+    let _ = write!(out, "{name}{{{label}=\"");
+    for c in value.chars() {
+        match c {
+            '\\' => out.push_str(r"\\"),
+            '"' => out.push_str(r#"\""#),
+            '\n' => out.push_str(r"\n"),
+            c => out.push(c),
+        }
+    }
+    let _ = writeln!(out, "\"}} {sample}");
+}
+
+fn respond(
+    prometheus: &Prometheus,
+    cache: &Cache,
+    req: &Request<Incoming>,
+) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::default());
+    match (req.method(), req.uri().path()) {
+        (&Method::GET, "/metrics") => {
+            *response.body_mut() = Full::new(Bytes::from(prometheus.render(cache)));
+            response.headers_mut().insert(CONTENT_TYPE, CONTENT_TYPE_TEXT.parse().unwrap());
+        }
+        (_, "/metrics") => *response.status_mut() = StatusCode::METHOD_NOT_ALLOWED,
+        _ => *response.status_mut() = StatusCode::NOT_FOUND,
+    }
+    response
+}
+
+pub async fn serve(
+    mut running: watch::Receiver<bool>,
+    prometheus: Prometheus,
+    cache: Arc<Cache>,
+    listener: TcpListener,
+) {
+    loop {
+        tokio::select! {
+            _ = running.changed() => break,
+            res = listener.accept() => match res {
+                Ok((stream, _)) => {
+                    let prometheus = prometheus.clone();
+                    let cache = cache.clone();
+                    let service = service_fn(move |req| {
+                        let response = respond(&prometheus, &cache, &req);
+                        async move { Ok::<_, Infallible>(response) }
+                    });
+                    tokio::spawn(async move {
+                        let io = TokioIo::new(stream);
+                        let conn = http1::Builder::new().serve_connection(io, service);
+                        if let Err(error) = conn.await {
+                            error!(?error, "Failed to serve a metrics request");
+                        }
+                    });
+                }
+                Err(error) => error!(?error, "Failed to accept an incoming connection"),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use tokio::sync::watch;
+    use twilight_model::id::Id;
+
+    use super::{serve, Prometheus};
+    use crate::cache::Cache;
+    use crate::testing::listener;
+
+    /// Parse the samples out of the text exposition format, keyed by the name and the labels.
+    fn parse(text: &str) -> HashMap<String, f64> {
+        let mut samples = HashMap::new();
+        for line in text.lines() {
+            if let Some(comment) = line.strip_prefix("# ") {
+                assert!(comment.starts_with("HELP ") || comment.starts_with("TYPE "), "{line}");
+                continue;
+            }
+            let (series, value) = line.rsplit_once(' ').unwrap();
+            samples.insert(String::from(series), value.parse().unwrap());
+        }
+        samples
+    }
+
+    #[tokio::test]
+    async fn scrape() {
+        let prometheus = Prometheus::new();
+        prometheus.on_command("quote", Duration::from_millis(250));
+        prometheus.on_command("quote", Duration::from_millis(500));
+        prometheus.on_command("say \"hi\"", Duration::from_secs(1));
+
+        let (listener, url) = listener().await;
+        let (running_tx, running_rx) = watch::channel(true);
+        tokio::spawn(serve(running_rx, prometheus, Arc::new(Cache::new(Id::new(1))), listener));
+
+        let client = reqwest::Client::new();
+        let response = client.get(format!("{url}metrics")).send().await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        assert!(response.headers()["content-type"].to_str().unwrap().starts_with("text/plain"));
+        let samples = parse(&response.text().await.unwrap());
+
+        assert_eq!(samples[r#"eris_commands_total{command="quote"}"#], 2.0);
+        assert_eq!(samples[r#"eris_command_latency_seconds_total{command="quote"}"#], 0.75);
+        assert_eq!(samples[r#"eris_commands_total{command="say \"hi\""}"#], 1.0);
+        assert_eq!(samples[r#"eris_channels{kind="text"}"#], 0.0);
+        assert_eq!(samples[r#"eris_cache_entries{kind="guilds"}"#], 0.0);
+
+        let response = client.get(&url).send().await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+
+        let _ = running_tx.send(false);
+    }
+}
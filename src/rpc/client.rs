@@ -8,12 +8,13 @@ use serde_json::Value;
 use tokio::sync::mpsc::Sender;
 use tokio::sync::watch::Receiver;
 use tokio::sync::Mutex;
-use tokio::task::JoinHandle;
 use tower::reconnect::Reconnect;
 use tower::Service;
 
-use crate::aiomas::client::MakeClient;
+use crate::aiomas::client::{call_batch, MakeClient};
+use crate::aiomas::codec::Request;
 use crate::config::Config;
+use crate::shutdown::Task;
 
 #[derive(Copy, Clone, Debug, Deserialize)]
 pub struct GameId {
@@ -50,12 +51,8 @@ pub struct LRRbot {
 }
 
 impl LRRbot {
-    pub fn new(
-        running: Receiver<bool>,
-        handler_tx: Sender<JoinHandle<()>>,
-        config: &Config,
-    ) -> LRRbot {
-        let make_client = MakeClient::new(running, handler_tx);
+    pub fn new(running: Receiver<bool>, handler_tx: Sender<Task>, config: &Config) -> LRRbot {
+        let make_client = MakeClient::new(running, handler_tx, config.rpc_keepalive);
 
         #[cfg(unix)]
         let addr = config.lrrbot_socket.clone();
@@ -71,27 +68,35 @@ impl LRRbot {
         args: Vec<Value>,
         kwargs: HashMap<String, Value>,
     ) -> Result<Value, Error> {
+        let mut values = self.call_many(vec![(name, args, kwargs)]).await?;
+        values.pop().context("no response to the request")
+    }
+
+    /// Like `call` but for several requests at once. They're all sent before waiting for any of
+    /// the responses.
+    async fn call_many(&self, requests: Vec<Request>) -> Result<Vec<Value>, Error> {
         // Implement retry logic here because `tower::retry::Retry` requires the service to be `Clone` which
         // `Reconnect<...>` never is.
         let mut last_error = None;
 
         for _ in 0..3 {
-            let future = {
+            let replies = {
                 let mut service = self.service.lock().await;
-                if let Err(error) = std::future::poll_fn(|cx| service.poll_ready(cx)).await {
-                    last_error = Some(
-                        anyhow::anyhow!(error)
-                            .context("failed to wait for the service to be ready"),
-                    );
-                    continue;
+                match call_batch(&mut *service, requests.clone()).await {
+                    Ok(replies) => replies,
+                    Err(error) => {
+                        last_error = Some(error);
+                        continue;
+                    }
                 }
-                service.call((name.clone(), args.clone(), kwargs.clone()))
             };
-            match future.await {
-                Ok(Ok(value)) => return Ok(value),
-                Ok(Err(exc)) => return Err(Error::msg(exc)),
+            match replies.await {
+                Ok(results) => {
+                    return results.into_iter().map(|result| result.map_err(Error::from)).collect()
+                }
                 Err(error) => {
-                    last_error = Some(anyhow::anyhow!(error).context("failed to send the request"));
+                    last_error =
+                        Some(anyhow::anyhow!(error).context("failed to send the requests"));
                     continue;
                 }
             }
@@ -105,13 +110,19 @@ impl LRRbot {
         serde_json::from_value(value).context("failed to deserialize the response")
     }
 
-    pub async fn get_game_id(&self) -> Result<Option<i32>, Error> {
-        let value = self.call("get_game_id".into(), vec![], HashMap::new()).await?;
-        serde_json::from_value(value).context("failed to deserialize the response")
-    }
-
-    pub async fn get_show_id(&self) -> Result<i32, Error> {
-        let value = self.call("get_show_id".into(), vec![], HashMap::new()).await?;
-        serde_json::from_value(value).context("failed to deserialize the response")
+    /// The current game and show IDs.
+    pub async fn get_game_and_show_id(&self) -> Result<(Option<i32>, i32), Error> {
+        let values = self
+            .call_many(vec![
+                ("get_game_id".into(), vec![], HashMap::new()),
+                ("get_show_id".into(), vec![], HashMap::new()),
+            ])
+            .await?;
+        let [game_id, show_id] = <[Value; 2]>::try_from(values)
+            .map_err(|values| anyhow::anyhow!("expected 2 responses, got {}", values.len()))?;
+        Ok((
+            serde_json::from_value(game_id).context("failed to deserialize the game ID")?,
+            serde_json::from_value(show_id).context("failed to deserialize the show ID")?,
+        ))
     }
 }
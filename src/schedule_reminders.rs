@@ -0,0 +1,365 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Error};
+use chrono::{DateTime, TimeDelta, Utc};
+use sea_orm::{DatabaseConnection, TransactionTrait};
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch::Receiver;
+use tracing::{error, info};
+use twilight_http::Client as DiscordClient;
+use twilight_model::id::marker::UserMarker;
+use twilight_model::id::Id;
+
+use crate::calendar::{CalendarHub, Event, LRR};
+use crate::config::Config;
+use crate::models::state;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// How long before the start of a stream the subscribers get their reminder.
+pub const REMINDER_LEAD: TimeDelta = match TimeDelta::try_minutes(15) {
+    Some(delta) => delta,
+    None => panic!("REMINDER_LEAD is invalid"),
+};
+
+#[derive(Debug, PartialEq, Eq)]
+enum Action {
+    /// Nothing to do yet.
+    Wait,
+    /// The event was moved but the reminder isn't due yet.
+    Reschedule(DateTime<Utc>),
+    /// Send the reminders.
+    Remind,
+    /// The event was cancelled. Let the subscribers know.
+    Cancelled,
+    /// The event is already over, probably because the bot was down. Drop the subscriptions.
+    Missed,
+}
+
+/// What to do about the subscriptions to an event that was last known to start at `start` now
+/// that the calendar says it looks like `event`.
+fn next_action(start: DateTime<Utc>, event: Option<&Event>, now: DateTime<Utc>) -> Action {
+    let Some(event) = event else { return Action::Cancelled };
+    if event.end <= now {
+        Action::Missed
+    } else if event.start - REMINDER_LEAD <= now {
+        Action::Remind
+    } else if event.start != start {
+        Action::Reschedule(event.start)
+    } else {
+        Action::Wait
+    }
+}
+
+/// The subscriptions are kept in the state as there's no table for them in the LRRbot schema.
+const STATE_KEY: &str = "eris.schedule.subscriptions";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Subscription {
+    pub user_id: Id<UserMarker>,
+    /// The Google Calendar ID of the event.
+    pub event_id: String,
+    pub summary: String,
+    /// When the event was last known to start.
+    pub start: DateTime<Utc>,
+}
+
+pub async fn load_subscriptions(db: &DatabaseConnection) -> Result<Vec<Subscription>, Error> {
+    Ok(state::get::<Vec<Subscription>>(STATE_KEY, db)
+        .await
+        .context("failed to load the subscriptions")?
+        .unwrap_or_default())
+}
+
+/// Load the subscriptions, apply `f` to them, and save them if `f` returns `true`. The
+/// subscriptions stay locked in between so concurrent updates aren't lost.
+///
+/// Returns what `f` returned.
+pub async fn update_subscriptions(
+    db: &DatabaseConnection,
+    f: impl FnOnce(&mut Vec<Subscription>) -> bool,
+) -> Result<bool, Error> {
+    let txn = db.begin().await.context("failed to start a transaction")?;
+    let mut subscriptions = state::get_for_update::<Vec<Subscription>>(STATE_KEY, &txn)
+        .await
+        .context("failed to load the subscriptions")?;
+    let changed = f(&mut subscriptions);
+    if changed {
+        state::set(String::from(STATE_KEY), subscriptions, &txn)
+            .await
+            .context("failed to save the subscriptions")?;
+    }
+    txn.commit().await.context("failed to commit the subscriptions")?;
+    Ok(changed)
+}
+
+/// Subscribe `user_id` to the event. Returns `false` if they already were.
+pub fn subscribe(
+    subscriptions: &mut Vec<Subscription>,
+    user_id: Id<UserMarker>,
+    event_id: &str,
+    event: &Event,
+) -> bool {
+    if subscriptions.iter().any(|sub| sub.user_id == user_id && sub.event_id == event_id) {
+        return false;
+    }
+    subscriptions.push(Subscription {
+        user_id,
+        event_id: String::from(event_id),
+        summary: event.summary.clone(),
+        start: event.start,
+    });
+    true
+}
+
+/// Unsubscribe `user_id` from the event. Returns `false` if they weren't subscribed.
+pub fn unsubscribe(
+    subscriptions: &mut Vec<Subscription>,
+    user_id: Id<UserMarker>,
+    event_id: &str,
+) -> bool {
+    let len = subscriptions.len();
+    subscriptions.retain(|sub| !(sub.user_id == user_id && sub.event_id == event_id));
+    subscriptions.len() != len
+}
+
+/// Remove exactly the `handled` subscriptions, leaving any that were added or changed since they
+/// were loaded. Returns `false` if none of them were left.
+fn remove_handled(subscriptions: &mut Vec<Subscription>, handled: &[&Subscription]) -> bool {
+    let len = subscriptions.len();
+    subscriptions.retain(|sub| !handled.contains(&sub));
+    subscriptions.len() != len
+}
+
+fn reschedule(subscriptions: &mut [Subscription], event_id: &str, start: DateTime<Utc>) -> bool {
+    let mut changed = false;
+    for subscription in subscriptions.iter_mut().filter(|sub| sub.event_id == event_id) {
+        changed |= subscription.start != start;
+        subscription.start = start;
+    }
+    changed
+}
+
+fn reminder_message(event: &Event, now: DateTime<Utc>) -> String {
+    let summary = crate::markdown::escape(&event.summary);
+    if event.start > now {
+        format!("Reminder: {summary} starts <t:{}:R>.", event.start.timestamp())
+    } else {
+        format!("Reminder: {summary} has started.")
+    }
+}
+
+fn cancellation_message(subscription: &Subscription) -> String {
+    format!(
+        "{} on <t:{}:F> has been cancelled, so there won't be a reminder for it.",
+        crate::markdown::escape(&subscription.summary),
+        subscription.start.timestamp()
+    )
+}
+
+pub async fn schedule_reminders(
+    mut running: Receiver<bool>,
+    calendar: CalendarHub,
+    config: Arc<Config>,
+    db: DatabaseConnection,
+    discord: Arc<DiscordClient>,
+) {
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+
+    loop {
+        tokio::select! {
+            _ = running.changed() => break,
+            _ = interval.tick() => {
+                if let Err(error) = check_subscriptions(&calendar, &config, &db, &discord).await {
+                    error!(?error, "Failed to check the schedule subscriptions");
+                }
+            },
+        }
+    }
+}
+
+async fn check_subscriptions(
+    calendar: &CalendarHub,
+    config: &Config,
+    db: &DatabaseConnection,
+    discord: &DiscordClient,
+) -> Result<(), Error> {
+    let subscriptions = load_subscriptions(db).await?;
+    let mut events = BTreeMap::<&str, Vec<&Subscription>>::new();
+    for subscription in &subscriptions {
+        events.entry(&subscription.event_id).or_default().push(subscription);
+    }
+
+    for (event_id, subscriptions) in events {
+        let event = match crate::calendar::get_event(calendar, LRR, event_id).await {
+            Ok(event) => event,
+            Err(error) => {
+                error!(?error, event.id = event_id, "Failed to look up a subscribed event");
+                continue;
+            }
+        };
+
+        // All the subscriptions to an event are rescheduled together so they agree on the start.
+        let now = Utc::now();
+        let message = match next_action(subscriptions[0].start, event.as_ref(), now) {
+            Action::Wait => continue,
+            Action::Reschedule(start) => {
+                update_subscriptions(db, |subscriptions| {
+                    reschedule(subscriptions, event_id, start)
+                })
+                .await
+                .context("failed to reschedule the subscriptions")?;
+                continue;
+            }
+            Action::Remind => event.as_ref().map(|event| reminder_message(event, now)),
+            Action::Cancelled => None,
+            Action::Missed => {
+                info!(event.id = event_id, "Dropping the subscriptions to an event that's over");
+                update_subscriptions(db, |all| remove_handled(all, &subscriptions))
+                    .await
+                    .context("failed to delete the subscriptions")?;
+                continue;
+            }
+        };
+
+        for subscription in subscriptions {
+            let content = message.clone().unwrap_or_else(|| cancellation_message(subscription));
+            if let Err(error) = send_dm(config, discord, subscription.user_id, &content).await {
+                error!(?error, user.id = subscription.user_id.get(), "Failed to send a reminder");
+            }
+            update_subscriptions(db, |all| remove_handled(all, &[subscription]))
+                .await
+                .context("failed to delete the subscription")?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn send_dm(
+    config: &Config,
+    discord: &DiscordClient,
+    user_id: Id<UserMarker>,
+    content: &str,
+) -> Result<(), Error> {
+    if config.dry_run {
+        info!(user.id = user_id.get(), content, "Dry run: not sending a reminder");
+        return Ok(());
+    }
+
+    let dm = discord
+        .create_private_channel(user_id)
+        .await
+        .context("failed to open a DM channel")?
+        .model()
+        .await
+        .context("failed to parse the DM channel")?;
+    discord.create_message(dm.id).content(content).await.context("failed to send the DM")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, TimeDelta, TimeZone, Utc};
+    use twilight_model::id::Id;
+
+    use super::{
+        next_action, reminder_message, remove_handled, reschedule, subscribe, unsubscribe, Action,
+        Subscription,
+    };
+    use crate::calendar::Event;
+
+    fn event(start: DateTime<Utc>) -> Event {
+        Event {
+            id: Some(String::from("abc123")),
+            start,
+            summary: String::from("Friday Nights"),
+            end: start + TimeDelta::hours(3),
+            location: None,
+            description: None,
+        }
+    }
+
+    #[test]
+    fn storage() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 5, 20, 0, 0).unwrap();
+        let mut subscriptions = vec![];
+        assert!(subscribe(&mut subscriptions, Id::new(42), "abc123", &event(start)));
+        assert!(!subscribe(&mut subscriptions, Id::new(42), "abc123", &event(start)));
+        assert!(subscribe(&mut subscriptions, Id::new(43), "abc123", &event(start)));
+        assert_eq!(
+            subscriptions[0],
+            Subscription {
+                user_id: Id::new(42),
+                event_id: String::from("abc123"),
+                summary: String::from("Friday Nights"),
+                start,
+            }
+        );
+
+        let later = start + TimeDelta::days(1);
+        assert!(reschedule(&mut subscriptions, "abc123", later));
+        assert!(!reschedule(&mut subscriptions, "abc123", later));
+        assert!(subscriptions.iter().all(|sub| sub.start == later));
+
+        assert!(unsubscribe(&mut subscriptions, Id::new(42), "abc123"));
+        assert!(!unsubscribe(&mut subscriptions, Id::new(42), "abc123"));
+        assert_eq!(subscriptions.len(), 1);
+        assert_eq!(subscriptions[0].user_id, Id::new(43));
+
+        // Only the subscriptions that were handled are removed, not ones that changed since.
+        let handled = subscriptions[0].clone();
+        assert!(subscribe(&mut subscriptions, Id::new(44), "abc123", &event(later)));
+        subscriptions[0].start = start;
+        assert!(!remove_handled(&mut subscriptions, &[&handled]));
+        subscriptions[0].start = later;
+        assert!(remove_handled(&mut subscriptions, &[&handled]));
+        assert_eq!(subscriptions.len(), 1);
+        assert_eq!(subscriptions[0].user_id, Id::new(44));
+
+        // Survives the round trip through the state table.
+        let value = serde_json::to_value(&subscriptions).unwrap();
+        assert_eq!(serde_json::from_value::<Vec<Subscription>>(value).unwrap(), subscriptions);
+    }
+
+    #[test]
+    fn due_notifications() {
+        let now = Utc.with_ymd_and_hms(2024, 1, 5, 19, 0, 0).unwrap();
+        let start = now + TimeDelta::hours(1);
+
+        assert_eq!(next_action(start, Some(&event(start)), now), Action::Wait);
+        assert_eq!(
+            next_action(start, Some(&event(start)), start - TimeDelta::minutes(16)),
+            Action::Wait
+        );
+        assert_eq!(
+            next_action(start, Some(&event(start)), start - TimeDelta::minutes(15)),
+            Action::Remind
+        );
+        // Late reminders are still sent while the stream is on.
+        assert_eq!(
+            next_action(start, Some(&event(start)), start + TimeDelta::hours(1)),
+            Action::Remind
+        );
+        assert_eq!(
+            next_action(start, Some(&event(start)), start + TimeDelta::hours(3)),
+            Action::Missed
+        );
+
+        // Rescheduled streams.
+        let later = start + TimeDelta::days(1);
+        assert_eq!(next_action(start, Some(&event(later)), now), Action::Reschedule(later));
+        let sooner = now + TimeDelta::minutes(10);
+        assert_eq!(next_action(start, Some(&event(sooner)), now), Action::Remind);
+
+        assert_eq!(next_action(start, None, now), Action::Cancelled);
+
+        assert_eq!(
+            reminder_message(&event(start), now),
+            format!("Reminder: Friday Nights starts <t:{}:R>.", start.timestamp())
+        );
+        assert_eq!(reminder_message(&event(start), start), "Reminder: Friday Nights has started.");
+    }
+}
@@ -20,12 +20,89 @@ pub fn shorten(s: &str, max_codepoints: usize) -> Cow<str> {
     }
 }
 
+/// Like [`shorten`] but cuts at the last word boundary that fits instead of mid-word.
+///
+/// Falls back to an exact cut if the first word alone doesn't fit.
+pub fn shorten_words(s: &str, max_codepoints: usize) -> Cow<'_, str> {
+    let exact = shorten(s, max_codepoints);
+    if let Cow::Borrowed(_) = exact {
+        return exact;
+    }
+
+    let remaining_len = max_codepoints - MARKER_LEN;
+    let mut split_point = 0;
+    let mut split_len = 0;
+    for segment in s.split_word_bounds() {
+        let segment_len = segment.chars().count();
+        if split_len + segment_len <= remaining_len {
+            split_len += segment_len;
+            split_point += segment.len();
+        } else {
+            break;
+        }
+    }
+
+    let head = s[..split_point].trim_end();
+    if head.is_empty() {
+        exact
+    } else {
+        Cow::Owned(String::from(head) + MARKER)
+    }
+}
+
+/// Receives the parts of a message as [`split`] builds them.
+trait Parts {
+    /// Append `s` to the current part.
+    fn push_str(&mut self, s: &str);
+    /// Finish the current part and start a new one.
+    fn end_part(&mut self);
+}
+
+#[derive(Default)]
+struct Collect {
+    parts: Vec<String>,
+    next: String,
+}
+
+impl Parts for Collect {
+    fn push_str(&mut self, s: &str) {
+        self.next.push_str(s);
+    }
+
+    fn end_part(&mut self) {
+        self.parts.push(std::mem::take(&mut self.next));
+    }
+}
+
+struct Count(usize);
+
+impl Parts for Count {
+    fn push_str(&mut self, _: &str) {}
+
+    fn end_part(&mut self) {
+        self.0 += 1;
+    }
+}
+
 pub fn split_to_parts(msg: &str, max_codepoints: usize) -> Vec<String> {
+    let mut parts = Collect::default();
+    split(msg, max_codepoints, &mut parts);
+    parts.parts
+}
+
+/// The number of parts [`split_to_parts`] would split `msg` into.
+pub fn count_parts(msg: &str, max_codepoints: usize) -> usize {
+    let mut parts = Count(0);
+    split(msg, max_codepoints, &mut parts);
+    parts.0
+}
+
+fn split(msg: &str, max_codepoints: usize, parts: &mut impl Parts) {
     assert!(max_codepoints > 2 * MARKER_LEN);
 
-    let mut ret = vec![];
-    let mut next = String::new();
     let mut next_len = 0;
+    // Whether the current part is empty or only has the leading marker.
+    let mut next_is_blank = true;
 
     let mut iter = msg.split_sentence_bounds().peekable();
 
@@ -36,14 +113,17 @@ pub fn split_to_parts(msg: &str, max_codepoints: usize) -> Vec<String> {
 
         if next_len + sentence_len + trailing_marker_len <= max_codepoints {
             // Sentence fits in this part.
-            next.push_str(sentence);
+            parts.push_str(sentence);
+            next_is_blank = next_len == 0 && sentence == MARKER;
             next_len += sentence_len;
         } else if MARKER_LEN + sentence_len + trailing_marker_len < max_codepoints {
             // Sentence fits in its own part.
-            next.push_str(MARKER);
-            ret.push(next);
+            parts.push_str(MARKER);
+            parts.end_part();
 
-            next = String::from(MARKER) + sentence;
+            parts.push_str(MARKER);
+            parts.push_str(sentence);
+            next_is_blank = false;
             next_len = MARKER_LEN + sentence_len;
         } else {
             // Sentence needs to be split to fit in a part.
@@ -51,7 +131,8 @@ pub fn split_to_parts(msg: &str, max_codepoints: usize) -> Vec<String> {
                 let remaining_len = max_codepoints - next_len - MARKER_LEN;
 
                 if sentence_len <= remaining_len {
-                    next.push_str(sentence);
+                    parts.push_str(sentence);
+                    next_is_blank = next_len == 0 && sentence == MARKER;
                     next_len += sentence_len;
                     break;
                 }
@@ -68,18 +149,19 @@ pub fn split_to_parts(msg: &str, max_codepoints: usize) -> Vec<String> {
                     }
                 }
 
-                if split_point == 0 && (next.is_empty() || next == MARKER) {
+                if split_point == 0 && next_is_blank {
                     // Empty part but the first word is too long to fit.
                     split_point =
                         sentence.char_indices().nth(remaining_len).map(|(i, _)| i).unwrap();
                     first_split_len = remaining_len;
                 }
 
-                next.push_str(&sentence[..split_point]);
-                next.push_str(MARKER);
-                ret.push(next);
+                parts.push_str(&sentence[..split_point]);
+                parts.push_str(MARKER);
+                parts.end_part();
 
-                next = String::from(MARKER);
+                parts.push_str(MARKER);
+                next_is_blank = true;
                 next_len = MARKER_LEN;
                 sentence = &sentence[split_point..];
                 sentence_len -= first_split_len;
@@ -88,9 +170,8 @@ pub fn split_to_parts(msg: &str, max_codepoints: usize) -> Vec<String> {
     }
 
     if next_len > 0 {
-        ret.push(next);
+        parts.end_part();
     }
-    ret
 }
 
 #[test]
@@ -116,9 +197,39 @@ fn shorten_proptest() {
     }
 }
 
+#[cfg(test)]
+mod shorten_words {
+    use super::{shorten, shorten_words};
+
+    const MSG: &str = "According to all known laws of aviation";
+
+    #[test]
+    fn fits() {
+        assert_eq!(shorten_words(MSG, 64), MSG);
+    }
+
+    #[test]
+    fn word_boundary() {
+        assert_eq!(shorten(MSG, 12), "According[…]");
+        assert_eq!(shorten_words(MSG, 12), "According[…]");
+
+        assert_eq!(shorten(MSG, 16), "According to […]");
+        assert_eq!(shorten_words(MSG, 16), "According to[…]");
+
+        assert_eq!(shorten(MSG, 24), "According to all know[…]");
+        assert_eq!(shorten_words(MSG, 24), "According to all[…]");
+    }
+
+    #[test]
+    fn too_long_word() {
+        assert_eq!(shorten_words(MSG, 8), "Accor[…]");
+        assert_eq!(shorten_words("Accordingtoallknownlaws", 16), "Accordingtoal[…]");
+    }
+}
+
 #[cfg(test)]
 mod split_to_parts {
-    use super::split_to_parts;
+    use super::{count_parts, split_to_parts};
 
     #[test]
     fn single() {
@@ -211,4 +322,49 @@ mod split_to_parts {
             ],
         );
     }
+
+    #[test]
+    fn count() {
+        let inputs = [
+            ("According to all known laws of aviation, there is no way that a bee should be able to fly.", 128),
+            (
+                concat!(
+                    "According to all known laws of aviation, there is no way that a bee should be able to fly. ",
+                    "Its wings are too small to get its fat little body off the ground. ",
+                    "The bee, of course, flies anyway because bees don't care what humans think is impossible.",
+                ),
+                128,
+            ),
+            (
+                concat!(
+                    "according to all known laws of aviation there is no way that a bee should be able to fly ",
+                    "its wings are too small to get its fat little body off the ground ",
+                    "the bee of course flies anyway because bees don't care what humans think is impossible",
+                ),
+                64,
+            ),
+            (
+                concat!(
+                    "According to all known laws of aviation, there is no way that a bee should be able to fly: ",
+                    "its wings are too small to get its fat little body off the ground. ",
+                    "The bee, of course, flies anyway because bees don't care what humans think is impossible.",
+                ),
+                95,
+            ),
+            ("Accordingtoallknownlawsofaviationthereisnowaythatabeeshould be able to fly.", 32),
+            ("According to all known lawsofaviationthereisnowaythat a bee should be able to fly.", 32),
+            ("[…]Accordingtoallknownlawsofaviationthereisnowaythatabeeshould be able to fly.", 32),
+            ("", 32),
+        ];
+
+        for (msg, max_codepoints) in inputs {
+            for max_codepoints in [max_codepoints, 7, 8, 16] {
+                assert_eq!(
+                    count_parts(msg, max_codepoints),
+                    split_to_parts(msg, max_codepoints).len(),
+                    "{msg:?} split into {max_codepoints} code point parts",
+                );
+            }
+        }
+    }
 }
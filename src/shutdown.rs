@@ -1,39 +1,107 @@
+use std::borrow::Cow;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
 use futures_util::stream::{FuturesUnordered, StreamExt};
 use tokio::sync::{mpsc, watch};
-use tokio::task::JoinHandle;
-use tracing::error;
+use tokio::task::{JoinError, JoinHandle};
+use tokio::time::Instant;
+use tracing::{error, warn};
+
+/// How long to wait between listing the tasks that are still running during shutdown.
+const SLOW_DRAIN: Duration = Duration::from_secs(5);
+
+/// A task to wait for before shutting down, labelled with what it's doing.
+pub type Task = (Cow<'static, str>, JoinHandle<()>);
 
 pub fn wait_for_outstanding(
     running: watch::Receiver<bool>,
-) -> (JoinHandle<()>, mpsc::Sender<JoinHandle<()>>) {
+) -> (JoinHandle<()>, mpsc::Sender<Task>) {
     let (tx, rx) = mpsc::channel(8);
 
     (tokio::spawn(waiter(running, rx)), tx)
 }
 
-async fn waiter(mut running: watch::Receiver<bool>, mut rx: mpsc::Receiver<JoinHandle<()>>) {
+struct Labelled {
+    label: Cow<'static, str>,
+    handle: JoinHandle<()>,
+}
+
+impl Future for Labelled {
+    type Output = (Cow<'static, str>, Result<(), JoinError>);
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.handle).poll(cx).map(|res| (self.label.clone(), res))
+    }
+}
+
+/// The labels of the tasks that haven't finished yet, sorted.
+fn still_running(tasks: &FuturesUnordered<Labelled>) -> Vec<&str> {
+    let mut labels = tasks.iter().map(|task| &task.label[..]).collect::<Vec<_>>();
+    labels.sort_unstable();
+    labels
+}
+
+fn log_failure((label, res): (Cow<'static, str>, Result<(), JoinError>)) {
+    if let Err(error) = res {
+        error!(?error, task = &label[..], "handler failed");
+    }
+}
+
+async fn waiter(mut running: watch::Receiver<bool>, mut rx: mpsc::Receiver<Task>) {
     let mut tasks = FuturesUnordered::new();
     loop {
         tokio::select! {
             _ = running.changed() => break,
             task = rx.recv() => {
-                if let Some(task) = task {
-                    tasks.push(task);
+                if let Some((label, handle)) = task {
+                    tasks.push(Labelled { label, handle });
                 } else {
                     break
                 }
             },
-            Some(res) = tasks.next() => {
-                if let Err(error) = res {
-                    error!(?error, "handler failed");
-                }
-            },
+            Some(res) = tasks.next() => log_failure(res),
         }
     }
 
-    while let Some(res) = tasks.next().await {
-        if let Err(error) = res {
-            error!(?error, "handler failed");
+    let mut report = tokio::time::interval_at(Instant::now() + SLOW_DRAIN, SLOW_DRAIN);
+    loop {
+        tokio::select! {
+            res = tasks.next() => match res {
+                Some(res) => log_failure(res),
+                None => break,
+            },
+            _ = report.tick() => {
+                warn!(tasks = ?still_running(&tasks), "Still waiting for tasks to finish");
+            },
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use futures_util::stream::{FuturesUnordered, StreamExt};
+
+    use super::{still_running, Labelled};
+
+    #[tokio::test]
+    async fn incomplete_tasks() {
+        let tasks = [
+            ("rpc connection", tokio::spawn(std::future::pending())),
+            ("command", tokio::spawn(async {})),
+            ("/quote", tokio::spawn(std::future::pending())),
+        ];
+        let mut tasks = tasks
+            .into_iter()
+            .map(|(label, handle)| Labelled { label: label.into(), handle })
+            .collect::<FuturesUnordered<_>>();
+        assert_eq!(still_running(&tasks), ["/quote", "command", "rpc connection"]);
+
+        let (label, res) = tasks.next().await.unwrap();
+        assert_eq!(label, "command");
+        assert!(res.is_ok());
+        assert_eq!(still_running(&tasks), ["/quote", "rpc connection"]);
+    }
+}
@@ -1,9 +1,14 @@
+use std::ffi::OsStr;
 use std::os::linux::net::SocketAddrExt;
 use std::os::unix::ffi::OsStrExt;
 use std::os::unix::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::{Context, Error};
 use tokio::net::UnixDatagram;
+use tokio::sync::watch::Receiver;
+use tracing::warn;
 
 pub struct Notify {
     socket: UnixDatagram,
@@ -48,3 +53,89 @@ impl Notify {
         self.notify("WATCHDOG=1").await
     }
 }
+
+/// How often to feed the watchdog: half of the timeout systemd set in `$WATCHDOG_USEC`, or
+/// `None` if the watchdog isn't enabled for this process.
+pub fn watchdog_feed_interval() -> Result<Option<Duration>, Error> {
+    feed_interval(
+        std::env::var_os("WATCHDOG_USEC").as_deref(),
+        std::env::var_os("WATCHDOG_PID").as_deref(),
+        std::process::id(),
+    )
+}
+
+fn feed_interval(
+    usec: Option<&OsStr>,
+    pid: Option<&OsStr>,
+    own_pid: u32,
+) -> Result<Option<Duration>, Error> {
+    let Some(usec) = usec else { return Ok(None) };
+
+    // The watchdog is meant for another process.
+    if let Some(pid) = pid {
+        let pid = pid
+            .to_str()
+            .and_then(|pid| pid.parse::<u32>().ok())
+            .with_context(|| format!("failed to parse $WATCHDOG_PID={pid:?}"))?;
+        if pid != own_pid {
+            return Ok(None);
+        }
+    }
+
+    let usec = usec
+        .to_str()
+        .and_then(|usec| usec.parse::<u64>().ok())
+        .filter(|&usec| usec != 0)
+        .with_context(|| format!("failed to parse $WATCHDOG_USEC={usec:?}"))?;
+
+    Ok(Some(Duration::from_micros(usec) / 2))
+}
+
+/// Feed the watchdog every `interval` until the bot shuts down.
+pub async fn feed_watchdog_periodically(
+    mut running: Receiver<bool>,
+    notify: Arc<Notify>,
+    interval: Duration,
+) {
+    let mut timer = tokio::time::interval(interval);
+
+    loop {
+        tokio::select! {
+            _ = running.changed() => break,
+            _ = timer.tick() => {
+                if let Err(error) = notify.feed_watchdog().await {
+                    warn!(?error, "failed to feed the systemd watchdog");
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsStr;
+    use std::time::Duration;
+
+    use super::feed_interval;
+
+    #[test]
+    fn watchdog_feed_interval() {
+        let usec = Some(OsStr::new("30000000"));
+        assert_eq!(feed_interval(usec, None, 42).unwrap(), Some(Duration::from_secs(15)));
+        assert_eq!(
+            feed_interval(usec, Some(OsStr::new("42")), 42).unwrap(),
+            Some(Duration::from_secs(15))
+        );
+        assert_eq!(
+            feed_interval(Some(OsStr::new("1001")), None, 42).unwrap(),
+            Some(Duration::from_nanos(500_500))
+        );
+
+        assert_eq!(feed_interval(None, None, 42).unwrap(), None);
+        assert_eq!(feed_interval(usec, Some(OsStr::new("41")), 42).unwrap(), None);
+
+        assert!(feed_interval(Some(OsStr::new("0")), None, 42).is_err());
+        assert!(feed_interval(Some(OsStr::new("30s")), None, 42).is_err());
+        assert!(feed_interval(usec, Some(OsStr::new("self")), 42).is_err());
+    }
+}
@@ -0,0 +1,171 @@
+use std::sync::{Arc, Mutex};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::subscriber::DefaultGuard;
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::Layer;
+
+/// A listener on a free local port and the base URL it's reachable at.
+pub async fn listener() -> (TcpListener, String) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    (listener, url)
+}
+
+/// An HTTP request received by a [`MockServer`].
+pub struct Request {
+    /// The request line and the headers.
+    pub head: String,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    pub fn request_line(&self) -> &str {
+        self.head.lines().next().unwrap_or("")
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.head.split("\r\n").skip(1).find_map(|header| {
+            let (key, value) = header.split_once(':')?;
+            key.eq_ignore_ascii_case(name).then(|| value.trim())
+        })
+    }
+}
+
+/// A response to a single request, closing the connection after it.
+pub fn response(status: &str, headers: &[(&str, &str)], body: &str) -> String {
+    let mut response = format!("HTTP/1.1 {status}\r\n");
+    for (key, value) in headers {
+        response.push_str(&format!("{key}: {value}\r\n"));
+    }
+    response.push_str(&format!("Content-Length: {}\r\nConnection: close\r\n\r\n", body.len()));
+    response.push_str(body);
+    response
+}
+
+/// A `200 OK` response with a JSON body.
+pub fn json(body: &str) -> String {
+    response("200 OK", &[("Content-Type", "application/json")], body)
+}
+
+/// A local HTTP server with canned responses, for testing the HTTP clients.
+pub struct MockServer {
+    listener: TcpListener,
+    url: String,
+}
+
+impl MockServer {
+    pub async fn bind() -> MockServer {
+        let (listener, url) = listener().await;
+        MockServer { listener, url }
+    }
+
+    /// The base URL of the server, with a trailing slash.
+    pub fn url(&self) -> String {
+        self.url.clone()
+    }
+
+    /// Answer the next request with `respond`, and return the request.
+    pub async fn respond(&self, respond: impl FnOnce(&Request) -> String) -> Request {
+        let (mut stream, _) = self.listener.accept().await.unwrap();
+
+        let mut request = vec![];
+        let mut buf = [0; 4096];
+        let head_len = loop {
+            if let Some(i) = request.windows(4).position(|window| window == b"\r\n\r\n") {
+                break i + 4;
+            }
+            let n = stream.read(&mut buf).await.unwrap();
+            assert_ne!(n, 0, "connection closed before the headers were received");
+            request.extend_from_slice(&buf[..n]);
+        };
+        let head = String::from_utf8(request[..head_len - 4].to_vec()).unwrap();
+        let mut request = Request { head, body: request.split_off(head_len) };
+
+        let content_length =
+            request.header("Content-Length").map_or(0, |length| length.parse().unwrap());
+        while request.body.len() < content_length {
+            let n = stream.read(&mut buf).await.unwrap();
+            assert_ne!(n, 0, "connection closed before the body was received");
+            request.body.extend_from_slice(&buf[..n]);
+        }
+
+        stream.write_all(respond(&request).as_bytes()).await.unwrap();
+        request
+    }
+
+    /// Answer every request with `respond` in the background, and return the base URL.
+    pub fn serve(self, respond: impl Fn(&Request) -> String + Send + Sync + 'static) -> String {
+        let url = self.url();
+        tokio::spawn(async move {
+            loop {
+                self.respond(&respond).await;
+            }
+        });
+        url
+    }
+}
+
+type Fields = Vec<(&'static str, String)>;
+
+/// Records the fields of every span and event.
+#[derive(Clone, Default)]
+pub struct TraceCapture {
+    spans: Arc<Mutex<Vec<(Id, &'static str, Fields)>>>,
+    events: Arc<Mutex<Vec<(Level, Fields)>>>,
+}
+
+impl TraceCapture {
+    /// Capture the traces of the current thread until the guard is dropped.
+    pub fn set_default(&self) -> DefaultGuard {
+        tracing::subscriber::set_default(tracing_subscriber::registry().with(self.clone()))
+    }
+
+    /// The name and the fields of every span, including the fields recorded after it was created.
+    pub fn spans(&self) -> Vec<(&'static str, Fields)> {
+        let spans = self.spans.lock().unwrap();
+        spans.iter().map(|(_, name, fields)| (*name, fields.clone())).collect()
+    }
+
+    /// The level and the fields of every event.
+    pub fn events(&self) -> Vec<(Level, Fields)> {
+        self.events.lock().unwrap().clone()
+    }
+}
+
+struct FieldVisitor<'a>(&'a mut Fields);
+
+impl Visit for FieldVisitor<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0.push((field.name(), format!("{value:?}")));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.push((field.name(), String::from(value)));
+    }
+}
+
+impl<S: Subscriber> Layer<S> for TraceCapture {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, _: Context<'_, S>) {
+        let mut fields = vec![];
+        attrs.record(&mut FieldVisitor(&mut fields));
+        self.spans.lock().unwrap().push((id.clone(), attrs.metadata().name(), fields));
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, _: Context<'_, S>) {
+        let mut spans = self.spans.lock().unwrap();
+        if let Some((_, _, fields)) = spans.iter_mut().rev().find(|(span, _, _)| span == id) {
+            values.record(&mut FieldVisitor(fields));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, _: Context<'_, S>) {
+        let mut fields = vec![];
+        event.record(&mut FieldVisitor(&mut fields));
+        self.events.lock().unwrap().push((*event.metadata().level(), fields));
+    }
+}
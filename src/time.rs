@@ -46,3 +46,69 @@ impl Display for HumanReadable {
         Ok(())
     }
 }
+
+/// Parse a duration in the format produced by [`HumanReadable`], like `1h30m` or `-2d`. Weeks
+/// (`w`) are accepted too.
+pub fn parse_duration(s: &str) -> Option<TimeDelta> {
+    let (negative, mut s) = match s.strip_prefix('-') {
+        Some(s) => (true, s),
+        None => (false, s.strip_prefix('+').unwrap_or(s)),
+    };
+
+    let mut duration = TimeDelta::zero();
+    let mut last_unit = None;
+    while !s.is_empty() {
+        let digits = s.find(|c: char| !c.is_ascii_digit())?;
+        let value = s[..digits].parse::<i64>().ok()?;
+        let unit = s[digits..].chars().next()?;
+        let (order, delta) = match unit {
+            'w' => (0, TimeDelta::try_weeks(value)?),
+            'd' => (1, TimeDelta::try_days(value)?),
+            'h' => (2, TimeDelta::try_hours(value)?),
+            'm' => (3, TimeDelta::try_minutes(value)?),
+            's' => (4, TimeDelta::try_seconds(value)?),
+            _ => return None,
+        };
+        // Units have to be in decreasing order and can't repeat.
+        if last_unit.is_some_and(|last| last >= order) {
+            return None;
+        }
+        last_unit = Some(order);
+        duration = duration.checked_add(&delta)?;
+        s = &s[digits + unit.len_utf8()..];
+    }
+
+    // At least one unit is required.
+    last_unit?;
+
+    Some(if negative { -duration } else { duration })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+
+    use super::{parse_duration, HumanReadable};
+
+    #[test]
+    fn parse() {
+        assert_eq!(parse_duration("2h"), TimeDelta::try_hours(2));
+        assert_eq!(parse_duration("+90m"), TimeDelta::try_minutes(90));
+        assert_eq!(parse_duration("-1d12h"), TimeDelta::try_hours(-36));
+        assert_eq!(parse_duration("1h30m15s"), TimeDelta::try_seconds(5415));
+        assert_eq!(parse_duration(""), None);
+        assert_eq!(parse_duration("+"), None);
+        assert_eq!(parse_duration("2"), None);
+        assert_eq!(parse_duration("2w3d"), TimeDelta::try_days(17));
+        assert_eq!(parse_duration("2y"), None);
+        assert_eq!(parse_duration("30m1h"), None);
+        assert_eq!(parse_duration("1h1h"), None);
+    }
+
+    #[test]
+    fn round_trip() {
+        let duration = TimeDelta::try_minutes(3 * 24 * 60 + 4 * 60 + 5).unwrap();
+        assert_eq!(parse_duration(&HumanReadable::new(duration).to_string()), Some(duration));
+        assert_eq!(parse_duration(&HumanReadable::new(-duration).to_string()), Some(-duration));
+    }
+}